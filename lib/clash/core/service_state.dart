@@ -18,6 +18,9 @@ enum ServiceState {
   // 正在卸载服务
   uninstalling,
 
+  // 服务版本过旧，需要重新安装
+  needsUpgrade,
+
   // 状态未知（检测失败）
   unknown,
 }
@@ -28,7 +31,8 @@ extension ServiceStateExtension on ServiceState {
   bool get isServiceModeInstalled =>
       this == ServiceState.installed ||
       this == ServiceState.running ||
-      this == ServiceState.uninstalling;
+      this == ServiceState.uninstalling ||
+      this == ServiceState.needsUpgrade;
 
   // 服务模式是否正在运行
   bool get isServiceModeRunning => this == ServiceState.running;
@@ -146,6 +150,9 @@ class ServiceStateManager extends ChangeNotifier {
       case 'not_installed':
         newState = ServiceState.notInstalled;
         break;
+      case 'needs_upgrade':
+        newState = ServiceState.needsUpgrade;
+        break;
       default:
         newState = ServiceState.unknown;
     }
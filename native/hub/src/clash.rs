@@ -4,9 +4,10 @@
 // 1. 直接进程管理模式 - 直接启动 Clash 核心进程
 // 2. 服务模式 - 通过系统服务以管理员权限运行 Clash 核心，支持 TUN 模式
 
-use rinf::{DartSignal, RustSignal};
+use rinf::DartSignal;
 use tokio::spawn;
 
+pub mod bandwidth;
 pub mod config;
 pub mod core_update;
 pub mod network;
@@ -15,11 +16,16 @@ pub mod process;
 pub mod service;
 pub mod signals;
 pub mod subscription;
+pub mod tun;
 
 pub use service::{
-    GetServiceStatus, InstallService, SendServiceHeartbeat, StartClash, StopClash, UninstallService,
+    GetServiceClashStats, GetServiceLogs, GetServiceStatus, InstallService, SendServiceHeartbeat,
+    SetServiceClashLogLevel, StartClash, StopClash, UninstallService, UpdateServiceGeoDatabases,
+};
+pub use signals::{
+    GetClashProcessStatus, GetCoreOutputTail, RestartClashProcess, SetProcessRestartPolicy,
+    StartClashProcess, StartProcessStatsStream, StopClashProcess, StopProcessStatsStream,
 };
-pub use signals::{StartClashProcess, StopClashProcess};
 
 /// 初始化 Clash 模块
 ///
@@ -31,48 +37,71 @@ pub fn init() {
     network::init_rest_api_listeners();
 
     // 直接进程管理模式
+    process::init();
 
     // 启动 Clash 进程
     spawn(async {
         let receiver = StartClashProcess::get_dart_signal_receiver();
         while let Some(dart_signal) = receiver.recv().await {
-            let message = dart_signal.message;
-            if let Err(e) = tokio::task::spawn_blocking(move || {
-                message.handle();
-            })
-            .await
-            {
-                log::error!("启动进程的任务执行失败（可能线程池耗尽）：{}", e);
-                // 向 Dart 发送错误响应
-                signals::ClashProcessResult {
-                    success: false,
-                    error_message: Some(format!("任务执行失败：{}", e)),
-                    pid: None,
-                }
-                .send_signal_to_dart();
-            }
+            dart_signal.message.handle().await;
         }
     });
 
     // 停止 Clash 进程
     spawn(async {
         let receiver = StopClashProcess::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle().await;
+        }
+    });
+
+    // 重启 Clash 进程
+    spawn(async {
+        let receiver = RestartClashProcess::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle().await;
+        }
+    });
+
+    // 查询 Clash 进程状态
+    spawn(async {
+        let receiver = GetClashProcessStatus::get_dart_signal_receiver();
         while let Some(dart_signal) = receiver.recv().await {
             let message = dart_signal.message;
-            if let Err(e) = tokio::task::spawn_blocking(move || {
-                message.handle();
-            })
-            .await
-            {
-                log::error!("停止进程的任务执行失败（可能线程池耗尽）：{}", e);
-                // 向 Dart 发送错误响应
-                signals::ClashProcessResult {
-                    success: false,
-                    error_message: Some(format!("任务执行失败：{}", e)),
-                    pid: None,
-                }
-                .send_signal_to_dart();
-            }
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+    });
+
+    // 设置自动重启策略
+    spawn(async {
+        let receiver = SetProcessRestartPolicy::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
+
+    // 获取核心最近输出
+    spawn(async {
+        let receiver = GetCoreOutputTail::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
+
+    // 核心资源占用采样
+    spawn(async {
+        let receiver = StartProcessStatsStream::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle().await;
+        }
+    });
+
+    spawn(async {
+        let receiver = StopProcessStatsStream::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
         }
     });
 
@@ -144,6 +173,50 @@ pub fn init() {
         }
     });
 
+    // 获取服务日志
+    spawn(async {
+        let receiver = GetServiceLogs::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+    });
+
+    // 获取服务模式下的核心统计
+    spawn(async {
+        let receiver = GetServiceClashStats::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+    });
+
+    // 通过服务修改核心日志级别
+    spawn(async {
+        let receiver = SetServiceClashLogLevel::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+    });
+
+    // 通过服务更新 Geo 数据库
+    spawn(async {
+        let receiver = UpdateServiceGeoDatabases::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+    });
+
     // 启动配置覆写监听器
     overrides::init_message_listeners();
 
@@ -155,4 +228,10 @@ pub fn init() {
 
     // 启动核心更新监听器
     core_update::init_message_listeners();
+
+    // 启动带宽限制监听器
+    bandwidth::init_message_listeners();
+
+    // 启动 TUN 残留检测监听器
+    tun::init_message_listeners();
 }
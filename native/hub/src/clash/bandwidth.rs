@@ -0,0 +1,445 @@
+// 带宽限制
+//
+// 目的：为按流量计费的网络提供一键限速，通过覆写引擎生成限速后的配置副本并重新加载
+//
+// mihomo 没有全局限速配置，也不能通过 API 修改带宽，只能为 hysteria/hysteria2 代理设置
+// up/down（拥塞控制参数，不是全局限速），因此结果总是标记为部分生效，配置中没有这类代理时
+// 直接报告不支持。
+// 副本与原配置位于同一目录（<名称>.bandwidth.yaml），用户的配置文件不会被修改；
+// 副本保存在磁盘上，应用重启后清除限制时仍能据此恢复原配置
+
+use super::network::handlers::send_ipc_request;
+use super::overrides::processor::OverrideProcessor;
+use super::overrides::signals::{OverrideConfig, OverrideFormat};
+use super::subscription::storage;
+use rinf::{DartSignal, RustSignal, SignalPiece};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use tokio::spawn;
+use tokio::sync::Mutex;
+
+// 单向限速上限（Mbps），超出视为无效输入
+const MAX_BANDWIDTH_MBPS: f64 = 100_000.0;
+
+// 覆写时可设置 up/down 的代理类型
+const LIMITABLE_PROXY_TYPES: [&str; 2] = ["hysteria", "hysteria2"];
+
+// ============================================================================
+// 消息协议
+// ============================================================================
+
+// 限速生效方式
+#[derive(Serialize, SignalPiece, Clone, Copy, Debug, PartialEq)]
+pub enum BandwidthLimitMechanism {
+    OverrideRewrite = 0,
+}
+
+// Dart → Rust：设置带宽限制
+#[derive(Deserialize, DartSignal)]
+pub struct SetBandwidthLimit {
+    pub up_mbps: f64,
+    pub down_mbps: f64,
+    // 当前运行配置路径（用于生成限速副本并重载）
+    pub config_path: String,
+}
+
+// Dart → Rust：清除带宽限制
+#[derive(Deserialize, DartSignal)]
+pub struct ClearBandwidthLimit {
+    pub config_path: String,
+}
+
+// Rust → Dart：带宽限制操作结果
+#[derive(Serialize, RustSignal)]
+pub struct BandwidthLimitResult {
+    pub success: bool,
+    pub mechanism: Option<BandwidthLimitMechanism>,
+    // 只对部分代理生效（设置成功时总是为 true）
+    pub partial: bool,
+    // 设置了 up/down 的代理数量与配置中的代理总数
+    pub limited_proxies: Option<u32>,
+    pub total_proxies: Option<u32>,
+    pub error_message: Option<String>,
+}
+
+impl BandwidthLimitResult {
+    fn failure(mechanism: Option<BandwidthLimitMechanism>, error: String) -> Self {
+        Self {
+            success: false,
+            mechanism,
+            partial: false,
+            limited_proxies: None,
+            total_proxies: None,
+            error_message: Some(error),
+        }
+    }
+}
+
+// 设置与清除持锁执行，不会交错
+static LIMIT_LOCK: Mutex<()> = Mutex::const_new(());
+
+// 覆写改写的代理数量
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RewriteCount {
+    limited: u32,
+    total: u32,
+}
+
+// ============================================================================
+// 消息处理器
+// ============================================================================
+
+impl SetBandwidthLimit {
+    pub async fn handle(self) {
+        log::info!(
+            "设置带宽限制：上行 {} Mbps，下行 {} Mbps",
+            self.up_mbps,
+            self.down_mbps
+        );
+
+        let _guard = LIMIT_LOCK.lock().await;
+        let result = match validate_mbps(self.up_mbps).and(validate_mbps(self.down_mbps)) {
+            Ok(()) => {
+                rewrite_config(
+                    self.up_mbps,
+                    self.down_mbps,
+                    &self.config_path,
+                    reload_config,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(count) => {
+                log::info!("带宽限制已生效：{}/{} 个代理", count.limited, count.total);
+                BandwidthLimitResult {
+                    success: true,
+                    mechanism: Some(BandwidthLimitMechanism::OverrideRewrite),
+                    partial: true,
+                    limited_proxies: Some(count.limited),
+                    total_proxies: Some(count.total),
+                    error_message: None,
+                }
+                .send_signal_to_dart();
+            }
+            Err(e) => {
+                log::error!("设置带宽限制失败：{}", e);
+                BandwidthLimitResult::failure(None, e).send_signal_to_dart();
+            }
+        }
+    }
+}
+
+impl ClearBandwidthLimit {
+    pub async fn handle(self) {
+        log::info!("清除带宽限制");
+
+        // 副本在磁盘上，应用重启后也能据此恢复
+        let _guard = LIMIT_LOCK.lock().await;
+        let result = restore_rewritten_config(&self.config_path, reload_config).await;
+
+        match result {
+            Ok(restored) => {
+                BandwidthLimitResult {
+                    success: true,
+                    mechanism: restored.then_some(BandwidthLimitMechanism::OverrideRewrite),
+                    partial: false,
+                    limited_proxies: None,
+                    total_proxies: None,
+                    error_message: None,
+                }
+                .send_signal_to_dart();
+            }
+            Err(e) => {
+                // 恢复失败时保留原状态，允许重试
+                log::error!("清除带宽限制失败：{}", e);
+                BandwidthLimitResult::failure(Some(BandwidthLimitMechanism::OverrideRewrite), e)
+                    .send_signal_to_dart();
+            }
+        }
+    }
+}
+
+// ============================================================================
+// 内部实现
+// ============================================================================
+
+// 校验限速值
+fn validate_mbps(value: f64) -> Result<(), String> {
+    if !value.is_finite() || value <= 0.0 {
+        return Err(format!("无效的带宽值：{}（必须大于 0）", value));
+    }
+    if value > MAX_BANDWIDTH_MBPS {
+        return Err(format!(
+            "无效的带宽值：{}（不能超过 {} Mbps）",
+            value, MAX_BANDWIDTH_MBPS
+        ));
+    }
+    Ok(())
+}
+
+fn format_mbps(value: f64) -> String {
+    format!("{} Mbps", value)
+}
+
+// 限速副本的路径：与原配置同目录的 <名称>.bandwidth.yaml
+fn limited_config_path(config_path: &str) -> Option<PathBuf> {
+    let path = Path::new(config_path);
+    let stem = path.file_stem()?.to_string_lossy();
+    Some(path.with_file_name(format!("{}.bandwidth.yaml", stem)))
+}
+
+// 通过覆写引擎生成限速副本并让核心加载副本
+//
+// 重新加载失败时删除副本，核心仍使用原配置
+async fn rewrite_config<F, Fut>(
+    up_mbps: f64,
+    down_mbps: f64,
+    config_path: &str,
+    reload: F,
+) -> Result<RewriteCount, String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let limited_path =
+        limited_config_path(config_path).ok_or_else(|| "未提供配置路径".to_string())?;
+
+    let original = tokio::fs::read_to_string(config_path)
+        .await
+        .map_err(|e| format!("读取配置文件失败：{}", e))?;
+    let (rewritten, count) =
+        tokio::task::spawn_blocking(move || rewrite_content(&original, up_mbps, down_mbps))
+            .await
+            .map_err(|e| format!("任务执行失败：{}", e))??;
+
+    let limited_path = limited_path.to_string_lossy().into_owned();
+    storage::save_atomic(&limited_path, &rewritten).await?;
+    if let Err(e) = reload(limited_path.clone()).await {
+        remove_limited_copy(Path::new(&limited_path)).await;
+        return Err(e);
+    }
+    Ok(count)
+}
+
+// 为支持限速的代理写入 up/down，配置中没有这类代理时报告不支持
+fn rewrite_content(
+    original: &str,
+    up_mbps: f64,
+    down_mbps: f64,
+) -> Result<(String, RewriteCount), String> {
+    let count = count_limitable_proxies(original)?;
+    if count.limited == 0 {
+        return Err(format!(
+            "不支持限速：配置中没有可限速的代理（{}）",
+            LIMITABLE_PROXY_TYPES.join("、")
+        ));
+    }
+
+    let script = format!(
+        r#"function main(config) {{
+    (config.proxies || []).forEach(function (proxy) {{
+        if ({}.indexOf(proxy.type) !== -1) {{
+            proxy.up = '{}';
+            proxy.down = '{}';
+        }}
+    }});
+    return config;
+}}"#,
+        json!(LIMITABLE_PROXY_TYPES),
+        format_mbps(up_mbps),
+        format_mbps(down_mbps)
+    );
+
+    let mut processor = OverrideProcessor::new()?;
+    let rewritten = processor.apply_overrides(
+        original,
+        vec![OverrideConfig {
+            id: "bandwidth-limit".to_string(),
+            name: "带宽限制".to_string(),
+            format: OverrideFormat::Javascript,
+            content: script,
+        }],
+    )?;
+    Ok((rewritten, count))
+}
+
+fn count_limitable_proxies(content: &str) -> Result<RewriteCount, String> {
+    let config: serde_yaml_ng::Value =
+        serde_yaml_ng::from_str(content).map_err(|e| format!("解析配置文件失败：{}", e))?;
+    let proxies = config
+        .get("proxies")
+        .and_then(|proxies| proxies.as_sequence())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let limited = proxies
+        .iter()
+        .filter(|proxy| {
+            proxy
+                .get("type")
+                .and_then(|kind| kind.as_str())
+                .is_some_and(|kind| LIMITABLE_PROXY_TYPES.contains(&kind))
+        })
+        .count();
+    Ok(RewriteCount {
+        limited: limited as u32,
+        total: proxies.len() as u32,
+    })
+}
+
+// 重新加载原配置并删除限速副本，没有副本时返回 false
+async fn restore_rewritten_config<F, Fut>(config_path: &str, reload: F) -> Result<bool, String>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let Some(limited_path) = limited_config_path(config_path) else {
+        return Ok(false);
+    };
+    if !tokio::fs::try_exists(&limited_path).await.unwrap_or(false) {
+        return Ok(false);
+    }
+
+    reload(config_path.to_string())
+        .await
+        .map_err(|e| format!("恢复原配置失败：{}", e))?;
+    remove_limited_copy(&limited_path).await;
+    Ok(true)
+}
+
+async fn remove_limited_copy(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        log::warn!("删除限速配置副本失败：{} - {}", path.display(), e);
+    }
+}
+
+async fn reload_config(config_path: String) -> Result<(), String> {
+    let body = json!({ "path": config_path }).to_string();
+    let response = send_ipc_request("PUT", "/configs?force=true", Some(&body)).await?;
+    if is_success_status(response.status_code) {
+        Ok(())
+    } else {
+        Err(format!(
+            "重新加载配置失败：HTTP {}：{}",
+            response.status_code, response.body
+        ))
+    }
+}
+
+fn is_success_status(status_code: u16) -> bool {
+    (200..300).contains(&status_code)
+}
+
+pub fn init_message_listeners() {
+    spawn(async {
+        let receiver = SetBandwidthLimit::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("设置带宽限制消息通道已关闭，退出监听器");
+    });
+
+    spawn(async {
+        let receiver = ClearBandwidthLimit::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("清除带宽限制消息通道已关闭，退出监听器");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "proxies:\n  - {name: hy, type: hysteria2, server: a.example, port: 443}\n  - {name: ss, type: ss, server: b.example, port: 8388}\n";
+
+    #[test]
+    fn test_validate_mbps() {
+        for value in [0.0, -1.0, f64::NAN, f64::INFINITY, MAX_BANDWIDTH_MBPS + 1.0] {
+            assert!(validate_mbps(value).is_err(), "{value}");
+        }
+        for value in [0.5, 10.0, MAX_BANDWIDTH_MBPS] {
+            assert!(validate_mbps(value).is_ok(), "{value}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_and_restore_round_trip() -> Result<(), String> {
+        let dir =
+            std::env::temp_dir().join(format!("stelliberty-bandwidth-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let config_path = dir.join("config.yaml");
+        std::fs::write(&config_path, CONFIG).map_err(|e| e.to_string())?;
+        let config_path = config_path.to_string_lossy().into_owned();
+        let limited_path = dir.join("config.bandwidth.yaml");
+
+        let reloaded = std::sync::Mutex::new(Vec::new());
+        let reload = |path: String| {
+            if let Ok(mut reloaded) = reloaded.lock() {
+                reloaded.push(path);
+            }
+            async { Ok(()) }
+        };
+
+        // 核心加载限速副本，用户的配置不变
+        let count = rewrite_config(10.0, 20.0, &config_path, reload).await?;
+        assert_eq!(
+            count,
+            RewriteCount {
+                limited: 1,
+                total: 2
+            }
+        );
+        let limited = std::fs::read_to_string(&limited_path).map_err(|e| e.to_string())?;
+        let limited: serde_yaml_ng::Value =
+            serde_yaml_ng::from_str(&limited).map_err(|e| e.to_string())?;
+        assert_eq!(limited["proxies"][0]["up"].as_str(), Some("10 Mbps"));
+        assert_eq!(limited["proxies"][0]["down"].as_str(), Some("20 Mbps"));
+        assert!(limited["proxies"][1].get("up").is_none());
+        assert_eq!(
+            std::fs::read_to_string(&config_path).map_err(|e| e.to_string())?,
+            CONFIG
+        );
+
+        // 恢复时重新加载原配置并删除副本
+        assert!(restore_rewritten_config(&config_path, reload).await?);
+        assert!(!limited_path.exists());
+        assert!(!restore_rewritten_config(&config_path, reload).await?);
+        let reloaded = reloaded.lock().map_err(|e| e.to_string())?.clone();
+        assert_eq!(
+            reloaded,
+            [
+                limited_path.to_string_lossy().into_owned(),
+                config_path.clone()
+            ]
+        );
+
+        // 重新加载失败时不保留副本
+        let failing = |_: String| async { Err("HTTP 400".to_string()) };
+        assert!(
+            rewrite_config(10.0, 20.0, &config_path, failing)
+                .await
+                .is_err()
+        );
+        assert!(!limited_path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_without_limitable_proxies_is_unsupported() {
+        let config = "proxies:\n  - {name: ss, type: ss, server: b.example, port: 8388}\n";
+        assert!(rewrite_content(config, 10.0, 10.0).is_err());
+    }
+}
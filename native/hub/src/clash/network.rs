@@ -1,16 +1,37 @@
 #![allow(unused_imports)]
 
 pub mod connection;
+pub mod connections;
+pub mod core_info;
+pub mod delay_test;
+pub mod dns;
+pub mod error;
 pub mod handlers;
 pub mod ipc_client;
+pub mod latency;
+pub mod path;
+pub mod proxies;
+pub mod rules;
 pub mod signals;
+pub mod stream_buffer;
 pub mod ws_client;
 
 pub use handlers::init_rest_api_listeners;
 pub use ipc_client::IpcClient;
 pub use signals::{
-    IpcDeleteRequest, IpcGetRequest, IpcLogData, IpcPatchRequest, IpcPostRequest, IpcPutRequest,
-    IpcResponse, IpcTrafficData, StartLogStream, StartTrafficStream, StopLogStream,
-    StopTrafficStream, StreamResult,
+    CloseAllConnections, CloseConnection, CloseConnectionsResult, ConfigureIpcPool, ConnectionInfo,
+    ConnectionsSnapshot, ControllerEndpointResult, ControllerSecretResult, CoreInfo, DnsQuery,
+    DnsQueryResult, DnsRecord, GetConnectionsTyped, GetCoreInfo, GetIpcLatencyStats,
+    GetProxiesTyped, GetRuleProvidersTyped, GetRulesTyped, GroupDelayComplete, IpcBatchItem,
+    IpcBatchItemResult, IpcBatchRequest, IpcBatchResponse, IpcCancelRequest, IpcConnectionsData,
+    IpcDeleteRequest, IpcErrorCode, IpcGetRequest, IpcLatencyEntry, IpcLatencyStats, IpcLogData,
+    IpcMemoryData, IpcPatchRequest, IpcPoolConfigured, IpcPostRequest, IpcPriority, IpcPutRequest,
+    IpcRequest, IpcResponse, IpcResponseChunk, IpcTrafficData, ProxiesSnapshot, ProxyDelay,
+    ProxyDelayResult, ProxyGroupInfo, ProxyNodeInfo, RuleInfo, RuleProviderInfo,
+    RuleProviderUpdateResult, RuleProvidersSnapshot, RulesSnapshot, SetControllerEndpoint,
+    SetControllerSecret, SetIpcSlowRequestThreshold, StartConnectionsStream, StartLogStream,
+    StartMemoryStream, StartTrafficStream, StopConnectionsStream, StopLogStream, StopMemoryStream,
+    StopTrafficStream, StreamResult, TestGroupDelay, UpdateRuleProvider, WsConnectionState,
+    WsStreamState,
 };
 pub use ws_client::WebSocketClient;
@@ -1,6 +1,15 @@
 // IPC 连接工具
 //
-// 提供 Named Pipe(Windows)和 Unix Socket(Unix)的统一连接接口
+// 提供 Named Pipe(Windows)、Unix Socket(Unix)与 TCP 的统一连接接口；
+// 核心只开放 external-controller TCP 端口时（远程或容器中的 mihomo）改用 TCP
+
+use super::error::IpcClientError;
+use super::ipc_client::IpcClient;
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::sync::RwLock;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
 
 #[cfg(unix)]
 use tokio::net::UnixStream;
@@ -8,18 +17,22 @@ use tokio::net::UnixStream;
 #[cfg(windows)]
 use tokio::net::windows::named_pipe::ClientOptions;
 
-// Named Pipe 连接最大重试次数（避免无限等待）
+// 管道繁忙（所有实例都在服务其他客户端）时的重试间隔与最长等待时间
+//
+// 实例通常在一毫秒内释放，短间隔轮询避免每次繁忙都多等几十毫秒
+#[cfg(windows)]
+const PIPE_BUSY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
 #[cfg(windows)]
-const MAX_PIPE_BUSY_RETRIES: u32 = 10;
+const PIPE_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
 
 // Windows：连接到 Named Pipe（带重试机制和超时保护）
 #[cfg(windows)]
 pub async fn connect_named_pipe(
     pipe_path: &str,
-) -> Result<tokio::net::windows::named_pipe::NamedPipeClient, String> {
+) -> Result<tokio::net::windows::named_pipe::NamedPipeClient, IpcClientError> {
     use windows_sys::Win32::Foundation::ERROR_PIPE_BUSY;
 
-    let mut retry_count = 0;
+    let deadline = tokio::time::Instant::now() + PIPE_BUSY_TIMEOUT;
 
     loop {
         match ClientOptions::new().open(pipe_path) {
@@ -28,24 +41,23 @@ pub async fn connect_named_pipe(
                 return Ok(client);
             }
             Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32) => {
-                retry_count += 1;
-
-                if retry_count >= MAX_PIPE_BUSY_RETRIES {
-                    return Err(format!(
-                        "Named Pipe 连接超时：管道繁忙，重试 {} 次后仍无法连接（{}）",
-                        MAX_PIPE_BUSY_RETRIES, pipe_path
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(IpcClientError::Io(
+                        std::io::ErrorKind::TimedOut,
+                        format!(
+                            "Named Pipe 连接超时：管道繁忙，{} ms 内仍无法连接（{}）",
+                            PIPE_BUSY_TIMEOUT.as_millis(),
+                            pipe_path
+                        ),
                     ));
                 }
 
-                log::trace!(
-                    "Named Pipe 繁忙，50 ms 后重试（{}/{}）",
-                    retry_count,
-                    MAX_PIPE_BUSY_RETRIES
-                );
-                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                log::trace!("Named Pipe 繁忙，稍后重试：{}", pipe_path);
+                tokio::time::sleep(PIPE_BUSY_POLL_INTERVAL).await;
             }
             Err(e) => {
-                return Err(format!("连接 Named Pipe 失败：{}", e));
+                // 管道不存在时为 ERROR_FILE_NOT_FOUND（映射为 NotFound）
+                return Err(IpcClientError::from_connect(e, "连接 Named Pipe 失败"));
             }
         }
     }
@@ -53,8 +65,177 @@ pub async fn connect_named_pipe(
 
 // Unix：连接到 Unix Socket
 #[cfg(unix)]
-pub async fn connect_unix_socket(socket_path: &str) -> Result<UnixStream, String> {
+pub async fn connect_unix_socket(socket_path: &str) -> Result<UnixStream, IpcClientError> {
     UnixStream::connect(socket_path)
         .await
-        .map_err(|e| format!("连接 Unix Socket 失败：{}", e))
+        .map_err(|e| IpcClientError::from_connect(e, "连接 Unix Socket 失败"))
+}
+
+// 控制器连接（对 Named Pipe/Unix Socket/TCP 统一抽象）
+pub trait ControllerStream: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> ControllerStream for T {}
+
+pub type ControllerConn = Box<dyn ControllerStream>;
+
+// 控制器端点
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControllerEndpoint {
+    // Named Pipe 或 Unix Socket 路径
+    Ipc(String),
+    // host:port
+    Tcp(String),
+}
+
+impl ControllerEndpoint {
+    // 解析端点：为空时使用默认 IPC 路径，tcp://host:port 为 TCP 端点
+    pub fn parse(endpoint: &str) -> Result<Self, String> {
+        let endpoint = endpoint.trim();
+        if endpoint.is_empty() {
+            return Ok(Self::Ipc(IpcClient::default_ipc_path()));
+        }
+
+        let Some(address) = endpoint.strip_prefix("tcp://") else {
+            return Err(format!("不支持的控制器端点：{}", endpoint));
+        };
+        let address = address.trim_end_matches('/');
+        let valid = address.rsplit_once(':').is_some_and(|(host, port)| {
+            !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p != 0)
+        });
+        if !valid {
+            return Err(format!("控制器地址格式应为 tcp://host:port：{}", endpoint));
+        }
+
+        Ok(Self::Tcp(address.to_string()))
+    }
+}
+
+impl fmt::Display for ControllerEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ipc(path) => write!(f, "{}", path),
+            Self::Tcp(address) => write!(f, "tcp://{}", address),
+        }
+    }
+}
+
+// 当前使用的控制器端点（默认为 IPC）
+static CONTROLLER_ENDPOINT: Lazy<RwLock<ControllerEndpoint>> =
+    Lazy::new(|| RwLock::new(ControllerEndpoint::Ipc(IpcClient::default_ipc_path())));
+
+pub fn current_endpoint() -> ControllerEndpoint {
+    CONTROLLER_ENDPOINT
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+// 切换控制器端点，返回端点是否发生了变化
+pub fn set_endpoint(endpoint: ControllerEndpoint) -> bool {
+    let mut current = CONTROLLER_ENDPOINT
+        .write()
+        .unwrap_or_else(|e| e.into_inner());
+    if *current == endpoint {
+        return false;
+    }
+    *current = endpoint;
+    true
+}
+
+// 连接到指定端点
+pub async fn connect(endpoint: &ControllerEndpoint) -> Result<ControllerConn, IpcClientError> {
+    match endpoint {
+        #[cfg(windows)]
+        ControllerEndpoint::Ipc(path) => Ok(Box::new(connect_named_pipe(path).await?)),
+        #[cfg(unix)]
+        ControllerEndpoint::Ipc(path) => Ok(Box::new(connect_unix_socket(path).await?)),
+        ControllerEndpoint::Tcp(address) => {
+            let stream = TcpStream::connect(address.as_str())
+                .await
+                .map_err(|e| IpcClientError::from_connect(e, "连接控制器 TCP 端口失败"))?;
+            // 请求与响应都很小，关闭 Nagle 以降低延迟
+            if let Err(e) = stream.set_nodelay(true) {
+                log::debug!("设置 TCP_NODELAY 失败：{}", e);
+            }
+            log::trace!("已连接到控制器：tcp://{}", address);
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            ControllerEndpoint::parse(""),
+            Ok(ControllerEndpoint::Ipc(IpcClient::default_ipc_path()))
+        );
+        assert_eq!(
+            ControllerEndpoint::parse("tcp://127.0.0.1:9090"),
+            Ok(ControllerEndpoint::Tcp("127.0.0.1:9090".to_string()))
+        );
+        assert_eq!(
+            ControllerEndpoint::parse(" tcp://[::1]:9090/ "),
+            Ok(ControllerEndpoint::Tcp("[::1]:9090".to_string()))
+        );
+
+        assert!(ControllerEndpoint::parse("http://127.0.0.1:9090").is_err());
+        assert!(ControllerEndpoint::parse("tcp://127.0.0.1").is_err());
+        assert!(ControllerEndpoint::parse("tcp://:9090").is_err());
+        assert!(ControllerEndpoint::parse("tcp://host:0").is_err());
+    }
+
+    // 单实例管道同一时刻只能服务一个客户端，其余客户端连接时收到 ERROR_PIPE_BUSY
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_concurrent_clients_wait_for_busy_pipe() -> Result<(), String> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        const CLIENTS: usize = 4;
+        let pipe_path = format!(r"\\.\pipe\stelliberty_test_busy_{}", std::process::id());
+
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .max_instances(1)
+            .create(&pipe_path)
+            .map_err(|e| e.to_string())?;
+        let server_task = tokio::spawn(async move {
+            for _ in 0..CLIENTS {
+                server.connect().await?;
+                let mut byte = [0u8; 1];
+                server.read_exact(&mut byte).await?;
+                server.write_all(&byte).await?;
+                server.flush().await?;
+                server.disconnect()?;
+            }
+            Ok::<(), std::io::Error>(())
+        });
+
+        let clients = (0..CLIENTS as u8).map(|id| {
+            let pipe_path = pipe_path.clone();
+            async move {
+                let mut client = connect_named_pipe(&pipe_path)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                client.write_all(&[id]).await.map_err(|e| e.to_string())?;
+                let mut byte = [0u8; 1];
+                client
+                    .read_exact(&mut byte)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok::<u8, String>(byte[0])
+            }
+        });
+        let echoed = futures_util::future::try_join_all(clients).await?;
+        assert_eq!(echoed, (0..CLIENTS as u8).collect::<Vec<_>>());
+
+        server_task
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())
+    }
 }
@@ -0,0 +1,208 @@
+// 连接列表解析
+//
+// 目的：繁忙时 /connections 可能有数千条连接，全部传给 Dart 再过滤是主要瓶颈。
+// 在 Rust 侧解析并按主机名或代理链过滤，只把需要显示的连接发送给 Dart
+
+use super::signals::ConnectionInfo;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawConnections {
+    #[serde(default)]
+    download_total: u64,
+    #[serde(default)]
+    upload_total: u64,
+    // 没有连接时核心返回 null
+    #[serde(default)]
+    connections: Option<Vec<serde_json::Value>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawConnection {
+    id: String,
+    #[serde(default)]
+    metadata: RawMetadata,
+    #[serde(default)]
+    upload: u64,
+    #[serde(default)]
+    download: u64,
+    #[serde(default)]
+    start: String,
+    #[serde(default)]
+    chains: Vec<String>,
+    #[serde(default)]
+    rule: String,
+    #[serde(default)]
+    rule_payload: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RawMetadata {
+    network: String,
+    #[serde(rename = "type")]
+    conn_type: String,
+    #[serde(rename = "sourceIP")]
+    source_ip: String,
+    source_port: String,
+    #[serde(rename = "destinationIP")]
+    destination_ip: String,
+    destination_port: String,
+    host: String,
+    process: String,
+}
+
+// 过滤条件：host 为主机名（或目标 IP）子串，不区分大小写；chain 为代理链中的名称，
+// 两者为空时不过滤
+pub struct ConnectionFilter {
+    host: String,
+    chain: String,
+}
+
+impl ConnectionFilter {
+    pub fn new(host: &str, chain: &str) -> Self {
+        Self {
+            host: host.trim().to_lowercase(),
+            chain: chain.trim().to_string(),
+        }
+    }
+
+    fn matches(&self, connection: &RawConnection) -> bool {
+        let metadata = &connection.metadata;
+        let host_matches = self.host.is_empty()
+            || [&metadata.host, &metadata.destination_ip]
+                .iter()
+                .any(|value| value.to_lowercase().contains(&self.host));
+        let chain_matches = self.chain.is_empty() || connection.chains.contains(&self.chain);
+        host_matches && chain_matches
+    }
+}
+
+// 解析结果（total 为过滤前的连接数）
+pub struct ParsedConnections {
+    pub download_total: u64,
+    pub upload_total: u64,
+    pub total: u64,
+    pub connections: Vec<ConnectionInfo>,
+}
+
+// 解析 GET /connections 的响应体，无法解析的单条连接会被跳过
+pub fn parse_connections(
+    body: &str,
+    filter: &ConnectionFilter,
+) -> Result<ParsedConnections, String> {
+    let raw: RawConnections =
+        serde_json::from_str(body).map_err(|e| format!("解析连接列表失败：{}", e))?;
+    let entries = raw.connections.unwrap_or_default();
+    let total = entries.len() as u64;
+
+    let connections = entries
+        .into_iter()
+        .filter_map(
+            |value| match serde_json::from_value::<RawConnection>(value) {
+                Ok(connection) => Some(connection),
+                Err(e) => {
+                    log::debug!("跳过无法解析的连接：{}", e);
+                    None
+                }
+            },
+        )
+        .filter(|connection| filter.matches(connection))
+        .map(to_info)
+        .collect();
+
+    Ok(ParsedConnections {
+        download_total: raw.download_total,
+        upload_total: raw.upload_total,
+        total,
+        connections,
+    })
+}
+
+fn to_info(connection: RawConnection) -> ConnectionInfo {
+    let metadata = connection.metadata;
+    ConnectionInfo {
+        id: connection.id,
+        network: metadata.network,
+        conn_type: metadata.conn_type,
+        host: metadata.host,
+        source: join_address(&metadata.source_ip, &metadata.source_port),
+        destination: join_address(&metadata.destination_ip, &metadata.destination_port),
+        process: metadata.process,
+        chains: connection.chains,
+        rule: connection.rule,
+        rule_payload: connection.rule_payload,
+        upload: connection.upload,
+        download: connection.download,
+        start: connection.start,
+    }
+}
+
+// 拼接 IP 与端口（IPv6 地址加方括号）
+fn join_address(ip: &str, port: &str) -> String {
+    match (ip.contains(':'), port.is_empty()) {
+        (_, true) => ip.to_string(),
+        (true, false) => format!("[{}]:{}", ip, port),
+        (false, false) => format!("{}:{}", ip, port),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{"downloadTotal":100,"uploadTotal":50,"connections":[
+        {"id":"a","metadata":{"network":"tcp","type":"HTTP","sourceIP":"127.0.0.1",
+            "sourcePort":"5000","destinationIP":"93.184.216.34","destinationPort":"443",
+            "host":"www.Example.com","process":"curl"},
+            "upload":1,"download":2,"start":"2025-01-01T00:00:00Z",
+            "chains":["HK 01","Proxy"],"rule":"Match","rulePayload":""},
+        {"id":"b","metadata":{"network":"udp","destinationIP":"2001:db8::1",
+            "destinationPort":"53","host":""},"chains":["DIRECT"],"rule":"GeoIP","rulePayload":"CN"},
+        {"metadata":{}}
+    ]}"#;
+
+    #[test]
+    fn test_parse_connections() -> Result<(), String> {
+        let parsed = parse_connections(FIXTURE, &ConnectionFilter::new("", ""))?;
+        assert_eq!(parsed.total, 3);
+        assert_eq!(parsed.download_total, 100);
+        assert_eq!(parsed.connections.len(), 2);
+
+        let first = &parsed.connections[0];
+        assert_eq!(first.source, "127.0.0.1:5000");
+        assert_eq!(first.destination, "93.184.216.34:443");
+        assert_eq!(first.chains, ["HK 01", "Proxy"]);
+        assert_eq!(parsed.connections[1].destination, "[2001:db8::1]:53");
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_connections() -> Result<(), String> {
+        let ids = |host: &str, chain: &str| -> Result<Vec<String>, String> {
+            let parsed = parse_connections(FIXTURE, &ConnectionFilter::new(host, chain))?;
+            Ok(parsed.connections.into_iter().map(|c| c.id).collect())
+        };
+
+        assert_eq!(ids("example", "")?, ["a"]);
+        assert_eq!(ids("2001:db8", "")?, ["b"]);
+        assert_eq!(ids("", "DIRECT")?, ["b"]);
+        assert_eq!(ids("example", "DIRECT")?, Vec::<String>::new());
+        // 代理链按完整名称匹配
+        assert_eq!(ids("", "HK")?, Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_connections() -> Result<(), String> {
+        let parsed = parse_connections(
+            r#"{"downloadTotal":0,"uploadTotal":0,"connections":null}"#,
+            &ConnectionFilter::new("", ""),
+        )?;
+        assert_eq!(parsed.total, 0);
+        assert!(parsed.connections.is_empty());
+        Ok(())
+    }
+}
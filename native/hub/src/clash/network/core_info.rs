@@ -0,0 +1,120 @@
+// 核心版本与功能探测
+//
+// 目的：不同 mihomo 构建支持的端点不同（/memory、/dns/query 等），界面此前只能在运行时
+// 通过 404 发现。解析 /version 并以 HEAD 探测可选端点，结果缓存到核心停止或切换端点为止
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::future::Future;
+use tokio::sync::Mutex;
+
+// 可选端点（功能名，探测路径）
+//
+// 使用 HEAD 探测：端点存在时核心返回 405 等非 404 状态，流式端点也不会开始推送数据
+pub const OPTIONAL_ENDPOINTS: [(&str, &str); 6] = [
+    ("memory", "/memory"),
+    ("dns_query", "/dns/query"),
+    ("group_delay", "/group"),
+    ("rule_providers", "/providers/rules"),
+    ("geo_update", "/configs/geo"),
+    ("upgrade", "/upgrade"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoreFeatures {
+    pub version: String,
+    pub features: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RawVersion {
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    meta: bool,
+    #[serde(default)]
+    premium: bool,
+}
+
+// 探测结果缓存（持锁探测，并发请求等待同一次探测）
+static CORE_FEATURES: Lazy<Mutex<Option<CoreFeatures>>> = Lazy::new(|| Mutex::new(None));
+
+// 解析 GET /version 的响应体，meta/premium 标记作为功能返回
+pub fn parse_version(body: &str) -> Result<CoreFeatures, String> {
+    let raw: RawVersion =
+        serde_json::from_str(body).map_err(|e| format!("解析核心版本失败：{}", e))?;
+    let features = [("meta", raw.meta), ("premium", raw.premium)]
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| feature.to_string())
+        .collect();
+    Ok(CoreFeatures {
+        version: raw.version,
+        features,
+    })
+}
+
+// 端点探测的状态码是否表示端点存在
+pub fn endpoint_supported(status_code: u16) -> bool {
+    status_code != 404
+}
+
+// 返回缓存的结果，没有缓存或 refresh 为 true 时执行 probe（失败不缓存）
+pub async fn get_or_probe<F>(refresh: bool, probe: F) -> Result<CoreFeatures, String>
+where
+    F: Future<Output = Result<CoreFeatures, String>>,
+{
+    let mut cached = CORE_FEATURES.lock().await;
+    if !refresh && let Some(features) = cached.as_ref() {
+        return Ok(features.clone());
+    }
+    let features = probe.await?;
+    *cached = Some(features.clone());
+    Ok(features)
+}
+
+// 清除缓存（核心停止或切换控制器端点时调用）
+pub async fn invalidate() {
+    CORE_FEATURES.lock().await.take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() -> Result<(), String> {
+        let info = parse_version(r#"{"meta":true,"version":"v1.19.2"}"#)?;
+        assert_eq!(info.version, "v1.19.2");
+        assert_eq!(info.features, ["meta"]);
+
+        let info = parse_version(r#"{"premium":true,"version":"2023.08.17"}"#)?;
+        assert_eq!(info.features, ["premium"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_until_invalidated() -> Result<(), String> {
+        let probe = |version: &str| {
+            let features = CoreFeatures {
+                version: version.to_string(),
+                features: Vec::new(),
+            };
+            async move { Ok(features) }
+        };
+
+        assert_eq!(get_or_probe(false, probe("v1")).await?.version, "v1");
+        assert_eq!(get_or_probe(false, probe("v2")).await?.version, "v1");
+        assert_eq!(get_or_probe(true, probe("v3")).await?.version, "v3");
+
+        // 探测失败不覆盖缓存
+        let failed = get_or_probe(true, async { Err("unreachable".to_string()) }).await;
+        assert!(failed.is_err());
+        assert_eq!(get_or_probe(false, probe("v4")).await?.version, "v3");
+
+        invalidate().await;
+        assert_eq!(get_or_probe(false, probe("v5")).await?.version, "v5");
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+// 代理组延迟测试
+//
+// 目的：由 Rust 获取代理组成员并以有限并发逐个测试延迟，每完成一个就发送结果，
+// 避免 Dart 同时发出大量 /delay 请求占满管道、结果乱序到达
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+// 未指定时的默认值
+pub const DEFAULT_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_CONCURRENCY: usize = 8;
+// 并发上限，避免单次测试占满连接
+const MAX_CONCURRENCY: usize = 32;
+
+// 正在测试的代理组
+static RUNNING_GROUPS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// 代理组的测试资格，释放时（含任务被中止）允许再次测试该组
+pub struct RunningTest {
+    group: String,
+}
+
+impl Drop for RunningTest {
+    fn drop(&mut self) {
+        RUNNING_GROUPS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.group);
+    }
+}
+
+// 登记对代理组的测试，该组已在测试中时返回 None
+pub fn try_start(group: &str) -> Option<RunningTest> {
+    let mut running = RUNNING_GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+    running.insert(group.to_string()).then(|| RunningTest {
+        group: group.to_string(),
+    })
+}
+
+// 实际使用的并发数（0 表示默认值）
+pub fn concurrency(requested: u32) -> usize {
+    match requested as usize {
+        0 => DEFAULT_CONCURRENCY,
+        n => n.min(MAX_CONCURRENCY),
+    }
+}
+
+#[derive(Deserialize)]
+struct RawGroup {
+    all: Option<Vec<String>>,
+}
+
+// 解析 GET /proxies/{group} 的响应，返回组成员
+pub fn parse_members(body: &str) -> Result<Vec<String>, String> {
+    let group: RawGroup =
+        serde_json::from_str(body).map_err(|e| format!("解析代理组失败：{}", e))?;
+    group.all.ok_or_else(|| "目标不是代理组".to_string())
+}
+
+#[derive(Deserialize)]
+struct RawDelay {
+    delay: Option<u32>,
+    message: Option<String>,
+}
+
+// 解析 GET /proxies/{name}/delay 的响应：成功时返回延迟（毫秒），
+// 超时（408）或测试失败（503）时返回核心给出的原因
+pub fn parse_delay(status_code: u16, body: &str) -> Result<u32, String> {
+    let raw = serde_json::from_str::<RawDelay>(body).ok();
+    match (status_code, raw) {
+        (
+            200,
+            Some(RawDelay {
+                delay: Some(delay), ..
+            }),
+        ) => Ok(delay),
+        (
+            _,
+            Some(RawDelay {
+                message: Some(message),
+                ..
+            }),
+        ) => Err(message),
+        (status, _) => Err(format!("核心返回 HTTP {}", status)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delay() {
+        assert_eq!(parse_delay(200, r#"{"delay":123}"#), Ok(123));
+        assert_eq!(
+            parse_delay(408, r#"{"message":"Timeout"}"#),
+            Err("Timeout".to_string())
+        );
+        assert_eq!(parse_delay(500, ""), Err("核心返回 HTTP 500".to_string()));
+    }
+
+    #[test]
+    fn test_parse_members() {
+        assert_eq!(
+            parse_members(r#"{"type":"Selector","now":"A","all":["A","B"]}"#),
+            Ok(vec!["A".to_string(), "B".to_string()])
+        );
+        assert!(parse_members(r#"{"type":"Shadowsocks"}"#).is_err());
+    }
+
+    #[test]
+    fn test_same_group_runs_once() {
+        let first = try_start("test-group");
+        assert!(first.is_some());
+        assert!(try_start("test-group").is_none());
+        assert!(try_start("other-group").is_some());
+
+        drop(first);
+        assert!(try_start("test-group").is_some());
+    }
+}
@@ -0,0 +1,136 @@
+// DNS 查询结果解析
+//
+// 目的：解析 GET /dns/query 返回的 DNS-over-JSON 格式（字段名大写、类型为数字），
+// 转换为带类型名的记录供应用内 DNS 测试工具使用
+
+use super::signals::DnsRecord;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawDnsResponse {
+    #[serde(default)]
+    status: u16,
+    #[serde(rename = "TC", default)]
+    truncated: bool,
+    #[serde(rename = "RA", default)]
+    recursion_available: bool,
+    #[serde(rename = "AD", default)]
+    authenticated_data: bool,
+    // 没有应答时字段缺失或为 null
+    #[serde(default)]
+    answer: Option<Vec<RawDnsRecord>>,
+}
+
+#[derive(Deserialize)]
+struct RawDnsRecord {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    record_type: u16,
+    #[serde(rename = "TTL", default)]
+    ttl: u32,
+    #[serde(default)]
+    data: String,
+}
+
+// 解析后的查询结果
+pub struct DnsAnswer {
+    pub rcode: String,
+    pub truncated: bool,
+    pub recursion_available: bool,
+    pub authenticated_data: bool,
+    pub records: Vec<DnsRecord>,
+}
+
+pub fn parse_dns_response(body: &str) -> Result<DnsAnswer, String> {
+    let raw: RawDnsResponse =
+        serde_json::from_str(body).map_err(|e| format!("解析 DNS 查询结果失败：{}", e))?;
+    Ok(DnsAnswer {
+        rcode: rcode_name(raw.status),
+        truncated: raw.truncated,
+        recursion_available: raw.recursion_available,
+        authenticated_data: raw.authenticated_data,
+        records: raw
+            .answer
+            .unwrap_or_default()
+            .into_iter()
+            .map(|record| DnsRecord {
+                name: record.name,
+                record_type: type_name(record.record_type),
+                ttl: record.ttl,
+                data: record.data,
+            })
+            .collect(),
+    })
+}
+
+// 记录类型编号 → 名称（未列出的类型为 TYPEn，同 RFC 3597）
+fn type_name(record_type: u16) -> String {
+    let name = match record_type {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        64 => "SVCB",
+        65 => "HTTPS",
+        other => return format!("TYPE{}", other),
+    };
+    name.to_string()
+}
+
+// 响应码 → 名称
+fn rcode_name(rcode: u16) -> String {
+    let name = match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        other => return format!("RCODE{}", other),
+    };
+    name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dns_response() -> Result<(), String> {
+        let answer = parse_dns_response(
+            r#"{"Status":0,"TC":false,"RD":true,"RA":true,"AD":false,"CD":false,
+                "Question":[{"Name":"example.com.","Qtype":28,"Qclass":1}],
+                "Answer":[
+                    {"name":"example.com.","type":5,"TTL":300,"data":"edge.example.net."},
+                    {"name":"edge.example.net.","type":28,"TTL":60,"data":"2001:db8::1"},
+                    {"name":"edge.example.net.","type":99,"TTL":60,"data":"x"}
+                ]}"#,
+        )?;
+
+        assert_eq!(answer.rcode, "NOERROR");
+        assert!(answer.recursion_available);
+        let types: Vec<&str> = answer
+            .records
+            .iter()
+            .map(|r| r.record_type.as_str())
+            .collect();
+        assert_eq!(types, ["CNAME", "AAAA", "TYPE99"]);
+        assert_eq!(answer.records[1].ttl, 60);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nxdomain_without_answer() -> Result<(), String> {
+        let answer = parse_dns_response(r#"{"Status":3,"Question":[],"Answer":null}"#)?;
+        assert_eq!(answer.rcode, "NXDOMAIN");
+        assert!(answer.records.is_empty());
+        Ok(())
+    }
+}
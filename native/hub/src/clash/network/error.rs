@@ -0,0 +1,70 @@
+// IPC 客户端错误类型定义
+//
+// 按错误的结构分类，避免依赖系统语言相关的错误文本判断 IPC 是否就绪
+
+use std::io::{self, ErrorKind};
+use thiserror::Error;
+
+// IPC 客户端错误类型
+#[derive(Error, Debug)]
+pub enum IpcClientError {
+    // 管道/套接字不存在（核心尚未启动或尚未创建 IPC 端点）
+    #[error("IPC 尚未就绪：{0}")]
+    NotReady(String),
+
+    // 端点存在但拒绝连接（核心正在启动或已退出）
+    #[error("IPC 拒绝连接：{0}")]
+    Refused(String),
+
+    // 外部控制器要求密钥，但未设置或密钥错误（HTTP 401）
+    #[error("控制器拒绝访问，密钥无效或未设置：{0}")]
+    Unauthorized(String),
+
+    // 其他 IO 错误（含上下文描述）
+    #[error("{1}")]
+    Io(ErrorKind, String),
+
+    // 请求本身不合法（方法不支持、路径或请求头含控制字符等），未发送给核心
+    #[error("请求无效：{0}")]
+    InvalidRequest(String),
+
+    // HTTP/WebSocket 协议错误
+    #[error("{0}")]
+    Protocol(String),
+}
+
+impl IpcClientError {
+    // 连接阶段的 IO 错误，按错误类型归类
+    pub fn from_connect(error: io::Error, context: &str) -> Self {
+        let message = format!("{}：{}", context, error);
+        match error.kind() {
+            ErrorKind::NotFound => Self::NotReady(message),
+            ErrorKind::ConnectionRefused => Self::Refused(message),
+            kind => Self::Io(kind, message),
+        }
+    }
+
+    // 读写阶段的 IO 错误
+    pub fn io(error: io::Error, context: &str) -> Self {
+        Self::Io(error.kind(), format!("{}：{}", context, error))
+    }
+
+    // IPC 尚未就绪（启动时的正常情况，不应按错误记录）
+    pub fn is_not_ready(&self) -> bool {
+        matches!(self, Self::NotReady(_) | Self::Refused(_))
+    }
+
+    // 复用的连接已被对端关闭（EOF、管道断开、连接重置）
+    pub fn is_stale_connection(&self) -> bool {
+        matches!(
+            self,
+            Self::Io(
+                ErrorKind::UnexpectedEof
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted,
+                _
+            )
+        )
+    }
+}
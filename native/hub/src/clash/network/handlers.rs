@@ -2,78 +2,191 @@
 //
 // 处理 Dart 层发送的 IPC 请求，通过 IpcClient 转发给 Clash 核心
 
-use super::ipc_client::IpcClient;
-
-// 检查错误是否为 IPC 尚未就绪（启动时的正常情况）
-fn is_ipc_not_ready_error(error_msg: &str) -> bool {
-    // Windows: os error 2 (系统找不到指定的文件)
-    // Linux: os error 111 (ECONNREFUSED，拒绝连接)
-    // macOS: os error 61 (ECONNREFUSED，拒绝连接)
-    error_msg.contains("系统找不到指定的文件")
-        || error_msg.contains("os error 2")
-        || error_msg.contains("拒绝连接")
-        || error_msg.contains("os error 111")
-        || error_msg.contains("os error 61")
-        || error_msg.contains("Connection refused")
-}
+use super::connection::{self, ControllerConn, ControllerEndpoint, current_endpoint};
+use super::connections::{self, ConnectionFilter};
+use super::core_info::{self, CoreFeatures};
+use super::delay_test;
+use super::dns;
+use super::error::IpcClientError;
+use super::ipc_client::{
+    ChunkSink, HttpResponse, IpcClient, normalize_controller_secret, set_controller_secret,
+};
+use super::latency;
+use super::path;
+use super::proxies;
+use super::rules;
+
 use super::signals::{
-    IpcDeleteRequest, IpcGetRequest, IpcLogData, IpcPatchRequest, IpcPostRequest, IpcPutRequest,
-    IpcResponse, IpcTrafficData, StartLogStream, StartTrafficStream, StopLogStream,
-    StopTrafficStream, StreamResult,
+    CloseAllConnections, CloseConnection, CloseConnectionsResult, ConfigureIpcPool,
+    ConnectionsSnapshot, ControllerEndpointResult, ControllerSecretResult, CoreInfo, DnsQuery,
+    DnsQueryResult, GetConnectionsTyped, GetCoreInfo, GetIpcLatencyStats, GetProxiesTyped,
+    GetRuleProvidersTyped, GetRulesTyped, GroupDelayComplete, IpcBatchItemResult, IpcBatchRequest,
+    IpcBatchResponse, IpcCancelRequest, IpcConnectionsData, IpcDeleteRequest, IpcErrorCode,
+    IpcGetRequest, IpcLatencyStats, IpcLogData, IpcMemoryData, IpcPatchRequest, IpcPoolConfigured,
+    IpcPostRequest, IpcPriority, IpcPutRequest, IpcRequest, IpcResponse, IpcResponseChunk,
+    IpcTrafficData, ProxiesSnapshot, ProxyDelayResult, RuleProviderUpdateResult,
+    RuleProvidersSnapshot, RulesSnapshot, SetControllerEndpoint, SetControllerSecret,
+    SetIpcSlowRequestThreshold, StartConnectionsStream, StartLogStream, StartMemoryStream,
+    StartTrafficStream, StopConnectionsStream, StopLogStream, StopMemoryStream, StopTrafficStream,
+    StreamResult, TestGroupDelay, UpdateRuleProvider,
 };
+use super::stream_buffer::{self, StreamBatch, StreamReceiver};
 use super::ws_client::WebSocketClient;
+use crate::settings::{self, keys};
 use once_cell::sync::Lazy;
 use rinf::{DartSignal, RustSignal};
-use std::collections::VecDeque;
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::sync::{RwLock, Semaphore};
+use tokio::task::AbortHandle;
+
+// 连接池配置（默认值，可通过 ConfigureIpcPool 在运行时调整）
+const DEFAULT_MAX_POOL_SIZE: usize = 300; // 匹配 Dart 层最大并发（CPU核心数*15，最高300）
+const DEFAULT_IDLE_TIMEOUT_MS: u64 = 500;
+const DEFAULT_HEALTH_CHECK_INTERVAL_S: u64 = 30;
+
+// 运行时调整的合法范围
+const POOL_SIZE_RANGE: (u64, u64) = (1, 1000);
+const IDLE_TIMEOUT_RANGE_MS: (u64, u64) = (50, 5 * 60 * 1000);
+const HEALTH_CHECK_INTERVAL_RANGE_S: (u64, u64) = (1, 3600);
+
+static MAX_POOL_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_POOL_SIZE);
+static IDLE_TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_IDLE_TIMEOUT_MS);
+static HEALTH_CHECK_INTERVAL_S: AtomicU64 = AtomicU64::new(DEFAULT_HEALTH_CHECK_INTERVAL_S);
+// 健康检查时是否主动探测空闲连接（发送 GET /version），获取连接时从不探测
+static ACTIVE_PROBE: AtomicBool = AtomicBool::new(false);
+
+// 连接池中为交互请求预留的空闲连接数（批量请求不会取用）
+const RESERVED_INTERACTIVE_CONNECTIONS: usize = 2;
+// 批量请求的最大并发数，超出后排队等待而不是继续新建连接
+const MAX_BULK_REQUESTS: usize = 8;
+
+// 主动探测的超时时间
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn store_pool_settings(
+    max_size: u64,
+    idle_timeout_ms: u64,
+    health_check_interval_s: u64,
+    active_probe: bool,
+) {
+    MAX_POOL_SIZE.store(max_size as usize, Ordering::Relaxed);
+    IDLE_TIMEOUT_MS.store(idle_timeout_ms, Ordering::Relaxed);
+    HEALTH_CHECK_INTERVAL_S.store(health_check_interval_s, Ordering::Relaxed);
+    ACTIVE_PROBE.store(active_probe, Ordering::Relaxed);
+}
+
+// 保存在 Hub 设置中的连接池参数
+const POOL_SETTING_KEYS: [&str; 4] = [
+    keys::IPC_POOL_MAX_SIZE,
+    keys::IPC_POOL_IDLE_TIMEOUT_MS,
+    keys::IPC_POOL_HEALTH_CHECK_INTERVAL_S,
+    keys::IPC_POOL_ACTIVE_PROBE,
+];
+
+// 恢复上次保存的连接池参数（超出范围的值按当前范围调整）
+fn restore_pool_settings() {
+    let stored = |key, default, range| {
+        settings::get_u64(key).map_or(default, |value| clamp_setting(value, range).0)
+    };
+    store_pool_settings(
+        stored(
+            keys::IPC_POOL_MAX_SIZE,
+            DEFAULT_MAX_POOL_SIZE as u64,
+            POOL_SIZE_RANGE,
+        ),
+        stored(
+            keys::IPC_POOL_IDLE_TIMEOUT_MS,
+            DEFAULT_IDLE_TIMEOUT_MS,
+            IDLE_TIMEOUT_RANGE_MS,
+        ),
+        stored(
+            keys::IPC_POOL_HEALTH_CHECK_INTERVAL_S,
+            DEFAULT_HEALTH_CHECK_INTERVAL_S,
+            HEALTH_CHECK_INTERVAL_RANGE_S,
+        ),
+        settings::get_bool(keys::IPC_POOL_ACTIVE_PROBE).unwrap_or(false),
+    );
+}
+
+fn restore_controller_secret() {
+    let secret = settings::get_secret(keys::CONTROLLER_SECRET).and_then(|secret| {
+        match normalize_controller_secret(&secret) {
+            Ok(secret) => secret,
+            Err(e) => {
+                log::warn!("忽略保存的控制器密钥：{}", e);
+                None
+            }
+        }
+    });
+    if secret.is_some() {
+        log::info!("已恢复控制器密钥");
+    }
+    set_controller_secret(secret);
+}
 
-#[cfg(unix)]
-use tokio::net::UnixStream;
+// 监听 Hub 设置变更，控制器密钥与连接池参数更新时重新加载
+fn watch_settings() {
+    let mut changes = settings::subscribe();
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(key) if key == keys::CONTROLLER_SECRET => restore_controller_secret(),
+                Ok(key) if POOL_SETTING_KEYS.contains(&key.as_str()) => restore_pool_settings(),
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    restore_controller_secret();
+                    restore_pool_settings();
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
 
-#[cfg(windows)]
-use tokio::net::windows::named_pipe::NamedPipeClient;
+fn max_pool_size() -> usize {
+    MAX_POOL_SIZE.load(Ordering::Relaxed)
+}
 
-// 连接池配置
-const MAX_POOL_SIZE: usize = 300; // 匹配 Dart 层最大并发（CPU核心数*15，最高300）
-const IDLE_TIMEOUT_MS: u64 = 500;
+fn idle_timeout() -> Duration {
+    Duration::from_millis(IDLE_TIMEOUT_MS.load(Ordering::Relaxed))
+}
 
-// 连接包装器
+// 连接包装器（记录所属端点，切换端点后旧连接不再复用）
+//
+// 只有上一个请求完整读完响应、核心同意保持连接且没有多余数据时才会归还到池中
 struct PooledConnection {
-    #[cfg(windows)]
-    conn: NamedPipeClient,
-    #[cfg(unix)]
-    conn: UnixStream,
+    conn: ControllerConn,
+    endpoint: ControllerEndpoint,
     last_used: Instant,
 }
 
 impl PooledConnection {
-    // 检查连接是否有效（主动探测）
-    fn is_valid(&self) -> bool {
-        use std::io::ErrorKind;
-
-        #[cfg(windows)]
-        {
-            let mut buf = [0u8; 1];
-            match self.conn.try_read(&mut buf) {
-                Ok(0) => false,                                      // 连接已关闭
-                Ok(_) => true, // 有数据可读（不应发生，但连接有效）
-                Err(e) if e.kind() == ErrorKind::WouldBlock => true, // 无数据但连接正常
-                Err(_) => false, // 其他错误表示连接失效
-            }
-        }
+    // 检查连接能否复用：未过期且属于当前端点
+    //
+    // 不从连接读取数据：试读既可能误判，也可能吞掉核心发送的数据，破坏下一个响应的解析。
+    // 已被核心关闭的连接在请求时失败，由幂等请求的重试兜底
+    fn is_reusable(&self, endpoint: &ControllerEndpoint) -> bool {
+        self.last_used.elapsed() < idle_timeout() && self.endpoint == *endpoint
+    }
+}
 
-        #[cfg(unix)]
-        {
-            let mut buf = [0u8; 1];
-            match self.conn.try_read(&mut buf) {
-                Ok(0) => false,                                      // 连接已关闭
-                Ok(_) => true, // 有数据可读（不应发生，但连接有效）
-                Err(e) if e.kind() == ErrorKind::WouldBlock => true, // 无数据但连接正常
-                Err(_) => false, // 其他错误表示连接失效
-            }
+// 主动探测：在连接上发送 GET /version，响应正常且连接可继续复用时保留
+async fn probe_connection(pooled: PooledConnection) -> Option<PooledConnection> {
+    let PooledConnection { conn, endpoint, .. } = pooled;
+    let request = IpcClient::request_with_connection("GET", "/version", &[], None, conn, None);
+    match tokio::time::timeout(PROBE_TIMEOUT, request).await {
+        Ok(Ok((response, conn))) if response.status_code == 200 && response.keep_alive => {
+            Some(PooledConnection {
+                conn,
+                endpoint,
+                last_used: Instant::now(),
+            })
         }
+        _ => None,
     }
 }
 
@@ -81,67 +194,119 @@ impl PooledConnection {
 static IPC_CONNECTION_POOL: Lazy<Arc<RwLock<VecDeque<PooledConnection>>>> =
     Lazy::new(|| Arc::new(RwLock::new(VecDeque::new())));
 
+// 进行中的 IPC 请求（request_id → 任务中止句柄），供 Dart 取消不再需要的请求
+static IN_FLIGHT_REQUESTS: Lazy<Mutex<HashMap<i64, AbortHandle>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 批量请求信号量
+static BULK_REQUEST_SEMAPHORE: Lazy<Arc<Semaphore>> =
+    Lazy::new(|| Arc::new(Semaphore::new(MAX_BULK_REQUESTS)));
+
 // 配置更新信号量（限制并发为 1，防止竞态条件）
 static CONFIG_UPDATE_SEMAPHORE: Lazy<Arc<Semaphore>> = Lazy::new(|| Arc::new(Semaphore::new(1)));
 
-// 启动连接池健康检查（30 秒间隔）
+// 启动连接池健康检查（默认 30 秒间隔，每轮重新读取间隔以便运行时调整）
 pub fn start_connection_pool_health_check() {
     tokio::spawn(async {
-        let mut interval = tokio::time::interval(Duration::from_secs(30));
-        interval.tick().await; // 跳过首次立即触发
-
         loop {
-            interval.tick().await;
+            let interval = HEALTH_CHECK_INTERVAL_S.load(Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_secs(interval)).await;
 
-            // 健康检查（使用 try_write 避免阻塞）
-            if let Ok(mut pool) = IPC_CONNECTION_POOL.try_write() {
-                let initial_count = pool.len();
+            check_pool_health().await;
+        }
+    });
 
-                if initial_count == 0 {
-                    continue; // 连接池为空，跳过
-                }
+    log::info!(
+        "连接池健康检查已启动（{}秒间隔）",
+        HEALTH_CHECK_INTERVAL_S.load(Ordering::Relaxed)
+    );
+}
 
-                log::trace!("开始连接池健康检查（当前 {} 个连接）", initial_count);
+// 连接池健康检查：移除过期连接，开启主动探测时再逐个探测剩余连接
+async fn check_pool_health() {
+    // 使用 try_write 避免阻塞
+    let Ok(mut pool) = IPC_CONNECTION_POOL.try_write() else {
+        log::trace!("健康检查：连接池繁忙，跳过本轮");
+        return;
+    };
+
+    let initial_count = pool.len();
+    if initial_count == 0 {
+        return; // 连接池为空，跳过
+    }
 
-                // 检查并移除失效连接（时间过期 + 连接状态检查）
-                pool.retain(|pooled_conn| {
-                    pooled_conn.last_used.elapsed() < Duration::from_millis(IDLE_TIMEOUT_MS)
-                        && pooled_conn.is_valid()
-                });
+    log::trace!("开始连接池健康检查（当前 {} 个连接）", initial_count);
 
-                let removed = initial_count - pool.len();
-                if removed > 0 {
-                    log::info!(
-                        "健康检查：移除{}个过期连接（剩余{}个）",
-                        removed,
-                        pool.len()
-                    );
-                } else {
-                    log::trace!("健康检查完成：所有连接正常（{}个）", pool.len());
-                }
-            } else {
-                log::trace!("健康检查：连接池繁忙，跳过本轮");
+    // 检查并移除失效连接（时间过期 + 端点切换）
+    let endpoint = current_endpoint();
+    pool.retain(|pooled_conn| pooled_conn.is_reusable(&endpoint));
+
+    let removed = initial_count - pool.len();
+    if removed > 0 {
+        log::info!(
+            "健康检查：移除{}个过期连接（剩余{}个）",
+            removed,
+            pool.len()
+        );
+    }
+
+    if !ACTIVE_PROBE.load(Ordering::Relaxed) || pool.is_empty() {
+        log::trace!("健康检查完成：剩余{}个连接", pool.len());
+        return;
+    }
+
+    // 探测期间不持有锁，请求可照常新建连接
+    let candidates: Vec<PooledConnection> = pool.drain(..).collect();
+    drop(pool);
+    let probed = futures_util::future::join_all(candidates.into_iter().map(probe_connection)).await;
+
+    let mut pool = IPC_CONNECTION_POOL.write().await;
+    let endpoint = current_endpoint();
+    let mut failed = 0;
+    for pooled in probed {
+        match pooled {
+            Some(pooled) if pooled.endpoint == endpoint && pool.len() < max_pool_size() => {
+                pool.push_back(pooled);
             }
+            Some(_) => {}
+            None => failed += 1,
         }
-    });
-
-    log::info!("连接池健康检查已启动（30秒间隔）");
+    }
+    if failed > 0 {
+        log::info!(
+            "健康检查：探测失败，移除{}个连接（剩余{}个）",
+            failed,
+            pool.len()
+        );
+    } else {
+        log::trace!("健康检查完成：探测通过（{}个）", pool.len());
+    }
 }
 
-// 从连接池获取连接（如果没有则创建新的）
-#[cfg(windows)]
-async fn acquire_connection() -> Result<NamedPipeClient, String> {
+// 从连接池获取连接（如果没有则创建新的），同时返回连接所属端点与连接是否来自连接池
+//
+// 批量请求不取用为交互请求预留的最后几个空闲连接，改为新建连接
+async fn acquire_connection(
+    priority: Option<IpcPriority>,
+) -> Result<(ControllerConn, ControllerEndpoint, bool), IpcClientError> {
+    let endpoint = current_endpoint();
+    let reserved = if priority == Some(IpcPriority::Bulk) {
+        RESERVED_INTERACTIVE_CONNECTIONS
+    } else {
+        0
+    };
+
     // 1. 尝试从池中获取（FIFO + 有效性检查）
     loop {
         let mut pool = IPC_CONNECTION_POOL.write().await;
 
-        if let Some(pooled) = pool.pop_front() {
+        if pool.len() > reserved
+            && let Some(pooled) = pool.pop_front()
+        {
             // 检查连接是否过期或失效
-            if pooled.last_used.elapsed() < Duration::from_millis(IDLE_TIMEOUT_MS)
-                && pooled.is_valid()
-            {
+            if pooled.is_reusable(&endpoint) {
                 log::trace!("从连接池获取连接（剩余{}）", pool.len());
-                return Ok(pooled.conn);
+                return Ok((pooled.conn, pooled.endpoint, true));
             }
             // 连接已过期或失效，丢弃并继续尝试下一个
             log::trace!("连接失效，丢弃并尝试下一个");
@@ -155,61 +320,86 @@ async fn acquire_connection() -> Result<NamedPipeClient, String> {
 
     // 2. 创建新连接
     log::trace!("连接池为空，创建新连接");
-    super::connection::connect_named_pipe(&IpcClient::default_ipc_path()).await
+    let conn = connection::connect(&endpoint).await?;
+    Ok((conn, endpoint, false))
 }
 
-#[cfg(unix)]
-async fn acquire_connection() -> Result<UnixStream, String> {
-    // 1. 尝试从池中获取（FIFO + 有效性检查）
-    loop {
-        let mut pool = IPC_CONNECTION_POOL.write().await;
+// 默认预热的连接数
+pub const DEFAULT_PREWARM_CONNECTIONS: usize = 4;
 
-        if let Some(pooled) = pool.pop_front() {
-            // 检查连接是否过期或失效
-            if pooled.last_used.elapsed() < Duration::from_millis(IDLE_TIMEOUT_MS)
-                && pooled.is_valid()
-            {
-                log::trace!("从连接池获取连接（剩余{}）", pool.len());
-                return Ok(pooled.conn);
-            }
-            // 连接已过期或失效，丢弃并继续尝试下一个
-            log::trace!("连接失效，丢弃并尝试下一个");
-            continue;
+// 预热探测核心可达的轮询间隔
+const PREWARM_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// 预先建立连接放入连接池，避免启动后首批请求同时新建连接（尽力而为）
+//
+// 先以 GET /version 确认核心可达，不可达时立即返回；预热后连接池不超过容量上限
+pub async fn prewarm_ipc_pool(n: usize) {
+    match send_ipc_request("GET", "/version", None).await {
+        Ok(response) if response.status_code == 200 => {}
+        _ => {
+            log::debug!("核心尚不可达，跳过连接池预热");
+            return;
         }
+    }
 
-        // 连接池为空，释放锁后创建新连接
-        drop(pool);
-        break;
+    let available = max_pool_size().saturating_sub(IPC_CONNECTION_POOL.read().await.len());
+    let count = n.min(available);
+    if count == 0 {
+        return;
     }
 
-    // 2. 创建新连接
-    log::trace!("连接池为空，创建新连接");
-    super::connection::connect_unix_socket(&IpcClient::default_ipc_path()).await
-}
+    let endpoint = current_endpoint();
+    let connections =
+        futures_util::future::join_all((0..count).map(|_| connection::connect(&endpoint))).await;
 
-// 归还连接到池中（FIFO：从尾部加入）
-#[cfg(windows)]
-async fn release_connection(conn: NamedPipeClient) {
     let mut pool = IPC_CONNECTION_POOL.write().await;
-
-    if pool.len() < MAX_POOL_SIZE {
+    let mut added = 0;
+    for conn in connections.into_iter().flatten() {
+        // 建立连接期间其他请求可能已归还连接
+        if pool.len() >= max_pool_size() {
+            break;
+        }
         pool.push_back(PooledConnection {
             conn,
+            endpoint: endpoint.clone(),
             last_used: Instant::now(),
         });
-        log::trace!("归还连接到池（当前{}）", pool.len());
-    } else {
-        log::trace!("连接池已满，丢弃连接");
+        added += 1;
+    }
+    log::debug!("连接池预热完成：新增{}个连接（当前{}）", added, pool.len());
+}
+
+// 等待核心可达（最长 timeout）后预热连接池，用于没有就绪等待流程的启动路径
+pub async fn prewarm_ipc_pool_when_ready(n: usize, timeout: Duration) {
+    let started = Instant::now();
+    loop {
+        if let Ok(response) = send_ipc_request("GET", "/version", None).await
+            && response.status_code == 200
+        {
+            prewarm_ipc_pool(n).await;
+            return;
+        }
+        if started.elapsed() >= timeout {
+            log::debug!("等待核心可达超时，跳过连接池预热");
+            return;
+        }
+        tokio::time::sleep(PREWARM_POLL_INTERVAL).await;
     }
 }
 
-#[cfg(unix)]
-async fn release_connection(conn: UnixStream) {
+// 归还连接到池中（FIFO：从尾部加入），端点已切换时直接丢弃
+async fn release_connection(conn: ControllerConn, endpoint: ControllerEndpoint) {
+    if endpoint != current_endpoint() {
+        log::trace!("控制器端点已切换，丢弃旧连接");
+        return;
+    }
+
     let mut pool = IPC_CONNECTION_POOL.write().await;
 
-    if pool.len() < MAX_POOL_SIZE {
+    if pool.len() < max_pool_size() {
         pool.push_back(PooledConnection {
             conn,
+            endpoint,
             last_used: Instant::now(),
         });
         log::trace!("归还连接到池（当前{}）", pool.len());
@@ -230,12 +420,34 @@ static TRAFFIC_CONNECTION_ID: Lazy<Arc<RwLock<Option<u32>>>> =
 static LOG_CONNECTION_ID: Lazy<Arc<RwLock<Option<u32>>>> =
     Lazy::new(|| Arc::new(RwLock::new(None)));
 
+// 当前日志流的级别（仅在 LOG_CONNECTION_ID 有值时有效）
+static LOG_STREAM_LEVEL: Lazy<Arc<RwLock<String>>> =
+    Lazy::new(|| Arc::new(RwLock::new(String::new())));
+
+// 日志流支持的级别
+const LOG_LEVELS: [&str; 5] = ["silent", "error", "warning", "info", "debug"];
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+// 各流的缓冲容量：流量只保留最新一帧，日志保留最近的若干行
+const TRAFFIC_BUFFER_SIZE: usize = 1;
+const LOG_BUFFER_SIZE: usize = 500;
+// 转发任务每轮发送后的间隔，限制发往 Dart 的信号速率
+const TRAFFIC_FORWARD_INTERVAL: Duration = Duration::from_millis(100);
+const LOG_FORWARD_INTERVAL: Duration = Duration::from_millis(50);
+
+// 存储当前的连接监控连接 ID
+static CONNECTIONS_CONNECTION_ID: Lazy<Arc<RwLock<Option<u32>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(None)));
+
+// 存储当前的内存监控连接 ID
+static MEMORY_CONNECTION_ID: Lazy<Arc<RwLock<Option<u32>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(None)));
+
 // 确保 WebSocket 客户端已初始化（统一入口）
 async fn ensure_ws_client_initialized() {
     let mut client_guard = WS_CLIENT.write().await;
     if client_guard.is_none() {
-        let ipc_path = IpcClient::default_ipc_path();
-        *client_guard = Some(WebSocketClient::new(ipc_path));
+        *client_guard = Some(WebSocketClient::new());
         log::debug!("WebSocket 客户端已初始化");
     }
 }
@@ -257,6 +469,17 @@ pub async fn cleanup_ws_client() {
         ws_client.disconnect_all().await;
         log::info!("WebSocket 客户端已清理");
     }
+    drop(client_guard);
+
+    // 连接 ID 属于已销毁的客户端，新客户端会重新从 1 分配
+    for connection_id in [
+        &TRAFFIC_CONNECTION_ID,
+        &LOG_CONNECTION_ID,
+        &CONNECTIONS_CONNECTION_ID,
+        &MEMORY_CONNECTION_ID,
+    ] {
+        connection_id.write().await.take();
+    }
 }
 
 // 清理所有网络资源（在 Clash 停止时调用的统一入口）
@@ -269,378 +492,1283 @@ pub async fn cleanup_all_network_resources() {
     // 2. 清理 IPC 连接池
     cleanup_ipc_connection_pool().await;
 
+    // 3. 核心功能需在下次启动后重新探测（控制器密钥已持久化，重启核心后继续使用）
+    core_info::invalidate().await;
+
     log::info!("所有网络资源已清理");
 }
 
-impl IpcGetRequest {
-    pub fn handle(self) {
-        let request_id = self.request_id;
-        tokio::spawn(async move {
-            // 从连接池获取连接
-            let ipc_conn = match acquire_connection().await {
-                Ok(c) => c,
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC GET 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC GET 获取连接失败：{}，error：{}", self.path, e);
-                    }
+// 供 Rust 侧模块直接发起 IPC 请求（复用连接池，PUT 同样串行执行）
+pub async fn send_ipc_request(
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+) -> Result<HttpResponse, String> {
+    let _permit = if method == "PUT" {
+        Some(
+            CONFIG_UPDATE_SEMAPHORE
+                .acquire()
+                .await
+                .map_err(|e| format!("获取配置锁失败：{}", e))?,
+        )
+    } else {
+        None
+    };
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("获取连接失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                    return;
-                }
-            };
+    let (ipc_conn, endpoint, _) = acquire_connection(None)
+        .await
+        .map_err(|e| format!("获取连接失败：{}", e))?;
 
-            // 使用连接发送请求
-            match IpcClient::request_with_connection("GET", &self.path, None, ipc_conn).await {
-                Ok((response, ipc_conn)) => {
-                    // 归还连接
-                    release_connection(ipc_conn).await;
-
-                    // 日志处理（成功）
-                    if response.body.len() > 200 {
-                        let preview = response.body.chars().take(100).collect::<String>();
-                        log::trace!(
-                            "响应体内容（截断）：{}…[总长度：{}字节]",
-                            preview,
-                            response.body.len()
-                        );
-                    } else {
-                        log::trace!("响应体内容：{}", response.body);
-                    }
+    let (response, ipc_conn) =
+        IpcClient::request_with_connection(method, path, &[], body, ipc_conn, None)
+            .await
+            .map_err(|e| format!("IPC 请求失败：{}", e))?;
+    if response.keep_alive {
+        release_connection(ipc_conn, endpoint).await;
+    }
 
-                    IpcResponse {
-                        request_id,
-                        status_code: response.status_code,
-                        body: response.body,
-                        success: true,
-                        error_message: None,
-                    }
-                    .send_signal_to_dart();
-                }
-                Err(e) => {
-                    // 连接已失效，不归还
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC GET 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC GET 请求失败：{}，error：{}", self.path, e);
-                    }
+    Ok(response)
+}
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("IPC 请求失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                }
-            }
-        });
+// 类型化 API：将非 2xx 响应转换为错误信息
+fn expect_success(response: HttpResponse) -> Result<HttpResponse, String> {
+    match response.status_code {
+        200..=299 => Ok(response),
+        401 => Err("控制器拒绝访问，密钥无效或未设置".to_string()),
+        status => Err(format!("核心返回 HTTP {}：{}", status, response.body)),
     }
 }
 
-impl IpcPostRequest {
-    pub fn handle(self) {
-        let request_id = self.request_id;
-        tokio::spawn(async move {
-            let ipc_conn = match acquire_connection().await {
-                Ok(c) => c,
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC POST 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC POST 获取连接失败：{}，error：{}", self.path, e);
-                    }
+// 类型化 API：GET 指定路径并用 parse 解析响应体
+async fn get_typed<T>(
+    path: &str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<T, String> {
+    let response = expect_success(send_ipc_request("GET", path, None).await?)?;
+    parse(&response.body)
+}
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("获取连接失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                    return;
-                }
-            };
+// 启动请求任务并登记中止句柄，任务结束后自动注销
+//
+// 持锁期间完成登记，任务即使立即结束也会在登记之后才注销
+fn spawn_request<F>(request_id: i64, request: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let mut in_flight = IN_FLIGHT_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    let handle = tokio::spawn(async move {
+        request.await;
+
+        // 仅注销自己的条目（Dart 可能复用了请求 ID）
+        let task_id = tokio::task::id();
+        let mut in_flight = IN_FLIGHT_REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+        if in_flight
+            .get(&request_id)
+            .is_some_and(|handle| handle.id() == task_id)
+        {
+            in_flight.remove(&request_id);
+        }
+    });
+    in_flight.insert(request_id, handle.abort_handle());
+}
 
-            match IpcClient::request_with_connection(
-                "POST",
-                &self.path,
-                self.body.as_deref(),
-                ipc_conn,
-            )
-            .await
-            {
-                Ok((response, ipc_conn)) => {
-                    release_connection(ipc_conn).await;
+// 失效连接重试计数（仅用于日志观察）
+static STALE_CONNECTION_RETRIES: AtomicU64 = AtomicU64::new(0);
 
-                    IpcResponse {
-                        request_id,
-                        status_code: response.status_code,
-                        body: response.body,
-                        success: true,
-                        error_message: None,
-                    }
-                    .send_signal_to_dart();
-                }
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC POST 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC POST 请求失败：{}，error：{}", self.path, e);
-                    }
+// 幂等请求可在复用连接失效时安全重试
+fn is_idempotent(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "OPTIONS" | "DELETE")
+}
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("IPC 请求失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                }
+// 使用新建连接重试一次（仅用于幂等请求）
+async fn retry_with_new_connection(
+    endpoint: &ControllerEndpoint,
+    method: &str,
+    path: &str,
+    headers: &[(String, String)],
+    body: Option<&str>,
+    sink: Option<&mut dyn ChunkSink>,
+    error: &IpcClientError,
+) -> Result<(HttpResponse, ControllerConn), IpcClientError> {
+    let retries = STALE_CONNECTION_RETRIES.fetch_add(1, Ordering::Relaxed) + 1;
+    log::trace!(
+        "复用连接已失效，新建连接重试 {} {}（累计 {} 次）：{}",
+        method,
+        path,
+        retries,
+        error
+    );
+    let conn = connection::connect(endpoint).await?;
+    IpcClient::request_with_connection(method, path, headers, body, conn, sink).await
+}
+
+// 将值限制在范围内，返回限制后的值与是否发生了限制
+fn clamp_setting(value: u64, (min, max): (u64, u64)) -> (u64, bool) {
+    let clamped = value.clamp(min, max);
+    (clamped, clamped != value)
+}
+
+impl ConfigureIpcPool {
+    pub async fn handle(self) {
+        let (max_size, size_clamped) = clamp_setting(self.max_size, POOL_SIZE_RANGE);
+        let (idle_timeout_ms, timeout_clamped) =
+            clamp_setting(self.idle_timeout_ms, IDLE_TIMEOUT_RANGE_MS);
+        let (health_check_interval_s, interval_clamped) =
+            clamp_setting(self.health_check_interval_s, HEALTH_CHECK_INTERVAL_RANGE_S);
+        let clamped = size_clamped || timeout_clamped || interval_clamped;
+        if clamped {
+            log::warn!(
+                "连接池参数超出范围，已调整：容量 {} → {}，空闲超时 {} → {} 毫秒，健康检查间隔 {} → {} 秒",
+                self.max_size,
+                max_size,
+                self.idle_timeout_ms,
+                idle_timeout_ms,
+                self.health_check_interval_s,
+                health_check_interval_s
+            );
+        }
+
+        // 新间隔在当前这轮等待结束后生效
+        store_pool_settings(
+            max_size,
+            idle_timeout_ms,
+            health_check_interval_s,
+            self.active_probe,
+        );
+
+        let result = settings::set_many([
+            (keys::IPC_POOL_MAX_SIZE, Some(max_size.into())),
+            (keys::IPC_POOL_IDLE_TIMEOUT_MS, Some(idle_timeout_ms.into())),
+            (
+                keys::IPC_POOL_HEALTH_CHECK_INTERVAL_S,
+                Some(health_check_interval_s.into()),
+            ),
+            (keys::IPC_POOL_ACTIVE_PROBE, Some(self.active_probe.into())),
+        ]);
+        if let Err(e) = result {
+            log::warn!("保存连接池参数失败：{}", e);
+        }
+
+        // 缩小容量时立即丢弃多余的连接（优先丢弃最久未用的）
+        let mut pool = IPC_CONNECTION_POOL.write().await;
+        let surplus = pool.len().saturating_sub(max_size as usize);
+        if surplus > 0 {
+            pool.drain(..surplus);
+            log::info!(
+                "连接池容量缩小，丢弃{}个连接（剩余{}个）",
+                surplus,
+                pool.len()
+            );
+        }
+        drop(pool);
+
+        log::info!(
+            "连接池参数已更新：容量 {}，空闲超时 {} 毫秒，健康检查间隔 {} 秒，主动探测 {}",
+            max_size,
+            idle_timeout_ms,
+            health_check_interval_s,
+            if self.active_probe {
+                "开启"
+            } else {
+                "关闭"
             }
-        });
+        );
+        IpcPoolConfigured {
+            max_size,
+            idle_timeout_ms,
+            health_check_interval_s,
+            active_probe: self.active_probe,
+            clamped,
+        }
+        .send_signal_to_dart();
     }
 }
 
-impl IpcPutRequest {
-    pub fn handle(self) {
-        let request_id = self.request_id;
-        tokio::spawn(async move {
-            // 获取配置更新锁（确保串行执行）
-            let _permit = match CONFIG_UPDATE_SEMAPHORE.acquire().await {
-                Ok(permit) => permit,
-                Err(e) => {
-                    log::error!("获取配置更新锁失败：{}", e);
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("获取配置锁失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                    return;
+impl SetControllerEndpoint {
+    pub async fn handle(self) {
+        let endpoint = match ControllerEndpoint::parse(&self.endpoint) {
+            Ok(endpoint) => endpoint,
+            Err(message) => {
+                log::error!("切换控制器端点失败：{}", message);
+                ControllerEndpointResult {
+                    success: false,
+                    error_message: Some(message),
+                    endpoint: current_endpoint().to_string(),
                 }
-            };
-            log::trace!("获取配置更新锁，开始处理 PUT 请求：{}", self.path);
+                .send_signal_to_dart();
+                return;
+            }
+        };
 
-            let ipc_conn = match acquire_connection().await {
-                Ok(c) => c,
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC PUT 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC PUT 获取连接失败：{}，error：{}", self.path, e);
-                    }
+        if connection::set_endpoint(endpoint.clone()) {
+            log::info!("控制器端点已切换：{}", endpoint);
+            // 旧端点的连接不再复用，WebSocket 流随客户端一并断开
+            cleanup_ipc_connection_pool().await;
+            cleanup_ws_client().await;
+            core_info::invalidate().await;
+        }
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("获取连接失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                    return;
+        ControllerEndpointResult {
+            success: true,
+            error_message: None,
+            endpoint: endpoint.to_string(),
+        }
+        .send_signal_to_dart();
+    }
+}
+
+impl SetControllerSecret {
+    pub fn handle(self) {
+        let secret = match normalize_controller_secret(&self.secret) {
+            Ok(secret) => secret,
+            Err(message) => {
+                log::error!("设置控制器密钥失败：{}", message);
+                ControllerSecretResult {
+                    success: false,
+                    error_message: Some(message),
                 }
-            };
+                .send_signal_to_dart();
+                return;
+            }
+        };
 
-            match IpcClient::request_with_connection(
-                "PUT",
-                &self.path,
-                self.body.as_deref(),
-                ipc_conn,
-            )
-            .await
-            {
-                Ok((response, ipc_conn)) => {
-                    release_connection(ipc_conn).await;
+        // 加密保存，下次启动时无需 Dart 重新下发
+        let saved = match &secret {
+            Some(secret) => {
+                log::info!("已设置控制器密钥");
+                settings::set_secret(keys::CONTROLLER_SECRET, secret)
+            }
+            None => {
+                log::info!("已清除控制器密钥");
+                settings::set(keys::CONTROLLER_SECRET, None)
+            }
+        };
+        if let Err(e) = saved {
+            log::warn!("保存控制器密钥失败：{}", e);
+        }
 
-                    IpcResponse {
-                        request_id,
-                        status_code: response.status_code,
-                        body: response.body,
-                        success: true,
-                        error_message: None,
-                    }
-                    .send_signal_to_dart();
+        set_controller_secret(secret);
+        ControllerSecretResult {
+            success: true,
+            error_message: None,
+        }
+        .send_signal_to_dart();
+    }
+}
 
-                    log::trace!("PUT 请求完成，释放配置更新锁：{}", self.path);
+impl GetProxiesTyped {
+    pub async fn handle(self) {
+        let snapshot = match get_typed("/proxies", proxies::parse_proxies).await {
+            Ok(parsed) => ProxiesSnapshot {
+                request_id: self.request_id,
+                success: true,
+                error_message: None,
+                groups: parsed.groups,
+                nodes: parsed.nodes,
+            },
+            Err(e) => {
+                log::warn!("获取代理列表失败：{}", e);
+                ProxiesSnapshot {
+                    request_id: self.request_id,
+                    success: false,
+                    error_message: Some(e),
+                    groups: Vec::new(),
+                    nodes: Vec::new(),
                 }
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC PUT 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC PUT 请求失败：{}，error：{}", self.path, e);
-                    }
+            }
+        };
+        snapshot.send_signal_to_dart();
+    }
+}
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("IPC 请求失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
+impl GetConnectionsTyped {
+    pub async fn handle(self) {
+        let filter = ConnectionFilter::new(&self.host_filter, &self.chain_filter);
+        let result = get_typed("/connections", |body| {
+            connections::parse_connections(body, &filter)
+        })
+        .await;
+        let snapshot = match result {
+            Ok(parsed) => ConnectionsSnapshot {
+                request_id: self.request_id,
+                success: true,
+                error_message: None,
+                download_total: parsed.download_total,
+                upload_total: parsed.upload_total,
+                total: parsed.total,
+                connections: parsed.connections,
+            },
+            Err(e) => {
+                log::warn!("获取连接列表失败：{}", e);
+                ConnectionsSnapshot {
+                    request_id: self.request_id,
+                    success: false,
+                    error_message: Some(e),
+                    download_total: 0,
+                    upload_total: 0,
+                    total: 0,
+                    connections: Vec::new(),
                 }
             }
-            // _permit 在此处 drop，自动释放锁
-        });
+        };
+        snapshot.send_signal_to_dart();
     }
 }
 
-impl IpcPatchRequest {
+// 关闭连接并通知 Dart 结果（id 为空时关闭所有连接）
+async fn close_connections(request_id: i64, id: Option<String>) {
+    let request_path = match &id {
+        Some(id) => format!("/connections/{}", path::encode_component(id)),
+        None => "/connections".to_string(),
+    };
+    let result = send_ipc_request("DELETE", &request_path, None)
+        .await
+        .and_then(expect_success);
+    if let Err(e) = &result {
+        log::warn!("关闭连接失败：{}，error：{}", request_path, e);
+    }
+    CloseConnectionsResult {
+        request_id,
+        id,
+        success: result.is_ok(),
+        error_message: result.err(),
+    }
+    .send_signal_to_dart();
+}
+
+impl CloseConnection {
+    pub async fn handle(self) {
+        close_connections(self.request_id, Some(self.id)).await;
+    }
+}
+
+impl CloseAllConnections {
+    pub async fn handle(self) {
+        close_connections(self.request_id, None).await;
+    }
+}
+
+// 获取核心版本并探测可选端点
+async fn probe_core_features() -> Result<CoreFeatures, String> {
+    let mut info = get_typed("/version", core_info::parse_version).await?;
+
+    let probes = core_info::OPTIONAL_ENDPOINTS.map(|(feature, endpoint)| async move {
+        match send_ipc_request("HEAD", endpoint, None).await {
+            Ok(response) => core_info::endpoint_supported(response.status_code).then_some(feature),
+            Err(e) => {
+                log::debug!("探测核心端点失败：{}，error：{}", endpoint, e);
+                None
+            }
+        }
+    });
+    let supported = futures_util::future::join_all(probes).await;
+    info.features
+        .extend(supported.into_iter().flatten().map(str::to_string));
+    Ok(info)
+}
+
+impl GetCoreInfo {
+    pub async fn handle(self) {
+        let response = match core_info::get_or_probe(self.refresh, probe_core_features()).await {
+            Ok(info) => {
+                log::debug!("核心信息：{}，功能：{:?}", info.version, info.features);
+                CoreInfo {
+                    version: info.version,
+                    features: info.features,
+                    error_message: None,
+                }
+            }
+            Err(e) => {
+                log::warn!("获取核心信息失败：{}", e);
+                CoreInfo {
+                    version: String::new(),
+                    features: Vec::new(),
+                    error_message: Some(e),
+                }
+            }
+        };
+        response.send_signal_to_dart();
+    }
+}
+
+impl DnsQuery {
+    pub async fn handle(self) {
+        let qtype = match self.qtype.trim() {
+            "" => "A".to_string(),
+            qtype => qtype.to_uppercase(),
+        };
+        let request_path = path::dns_query_path(self.name.trim(), &qtype);
+
+        let result = match send_ipc_request("GET", &request_path, None).await {
+            Ok(response) if response.status_code == 404 => Err((
+                "核心不支持 DNS 查询".to_string(),
+                Some(IpcErrorCode::Unsupported),
+            )),
+            Ok(response) => expect_success(response)
+                .and_then(|response| dns::parse_dns_response(&response.body))
+                .map_err(|e| (e, None)),
+            Err(e) => Err((e, None)),
+        };
+
+        let response = match result {
+            Ok(answer) => DnsQueryResult {
+                request_id: self.request_id,
+                success: true,
+                error_message: None,
+                error_code: None,
+                rcode: answer.rcode,
+                truncated: answer.truncated,
+                recursion_available: answer.recursion_available,
+                authenticated_data: answer.authenticated_data,
+                answers: answer.records,
+            },
+            Err((e, error_code)) => {
+                log::warn!("DNS 查询失败：{} {}，error：{}", self.name, qtype, e);
+                DnsQueryResult {
+                    request_id: self.request_id,
+                    success: false,
+                    error_message: Some(e),
+                    error_code,
+                    rcode: String::new(),
+                    truncated: false,
+                    recursion_available: false,
+                    authenticated_data: false,
+                    answers: Vec::new(),
+                }
+            }
+        };
+        response.send_signal_to_dart();
+    }
+}
+
+impl GetRulesTyped {
+    pub async fn handle(self) {
+        let snapshot = match get_typed("/rules", rules::parse_rules).await {
+            Ok(rules) => RulesSnapshot {
+                request_id: self.request_id,
+                success: true,
+                error_message: None,
+                rules,
+            },
+            Err(e) => {
+                log::warn!("获取规则列表失败：{}", e);
+                RulesSnapshot {
+                    request_id: self.request_id,
+                    success: false,
+                    error_message: Some(e),
+                    rules: Vec::new(),
+                }
+            }
+        };
+        snapshot.send_signal_to_dart();
+    }
+}
+
+impl GetRuleProvidersTyped {
+    pub async fn handle(self) {
+        let snapshot = match get_typed("/providers/rules", rules::parse_rule_providers).await {
+            Ok(providers) => RuleProvidersSnapshot {
+                request_id: self.request_id,
+                success: true,
+                error_message: None,
+                providers,
+            },
+            Err(e) => {
+                log::warn!("获取规则集列表失败：{}", e);
+                RuleProvidersSnapshot {
+                    request_id: self.request_id,
+                    success: false,
+                    error_message: Some(e),
+                    providers: Vec::new(),
+                }
+            }
+        };
+        snapshot.send_signal_to_dart();
+    }
+}
+
+impl UpdateRuleProvider {
+    pub async fn handle(self) {
+        log::info!("更新规则集：{}", self.name);
+        let result = send_ipc_request("PUT", &path::rule_provider_path(&self.name), None)
+            .await
+            .and_then(expect_success);
+        if let Err(e) = &result {
+            log::warn!("更新规则集失败：{}，error：{}", self.name, e);
+        }
+        RuleProviderUpdateResult {
+            name: self.name,
+            success: result.is_ok(),
+            error_message: result.err(),
+        }
+        .send_signal_to_dart();
+    }
+}
+
+impl TestGroupDelay {
+    pub async fn handle(self) {
+        let group = self.group;
+        let complete = |tested: usize, failed: usize, error_message: Option<String>| {
+            GroupDelayComplete {
+                group: group.clone(),
+                tested: tested as u32,
+                failed: failed as u32,
+                error_message,
+            }
+            .send_signal_to_dart();
+        };
+
+        // 同一代理组同时只进行一次测试，结果不会交错
+        let Some(_running) = delay_test::try_start(&group) else {
+            log::debug!("代理组正在测试延迟，拒绝新的请求：{}", group);
+            complete(0, 0, Some("该代理组正在测试延迟".to_string()));
+            return;
+        };
+
+        let members =
+            match get_typed(&path::proxy_select_path(&group), delay_test::parse_members).await {
+                Ok(members) => members,
+                Err(e) => {
+                    log::warn!("获取代理组成员失败：{}，error：{}", group, e);
+                    complete(0, 0, Some(e));
+                    return;
+                }
+            };
+
+        let url = if self.url.is_empty() {
+            delay_test::DEFAULT_TEST_URL
+        } else {
+            self.url.as_str()
+        };
+        let timeout_ms = match self.timeout_ms {
+            0 => delay_test::DEFAULT_TIMEOUT_MS,
+            timeout_ms => timeout_ms,
+        };
+        let concurrency = delay_test::concurrency(self.concurrency);
+        log::debug!(
+            "开始测试代理组延迟：{}（{}个成员，并发 {}）",
+            group,
+            members.len(),
+            concurrency
+        );
+
+        // 每个成员完成后立即发送结果
+        let semaphore = Semaphore::new(concurrency);
+        let results = futures_util::future::join_all(members.iter().map(|proxy| {
+            let semaphore = &semaphore;
+            let group = &group;
+            async move {
+                let Ok(_permit) = semaphore.acquire().await else {
+                    return false;
+                };
+                let request_path = path::proxy_delay_path(proxy, url, timeout_ms);
+                let result =
+                    send_ipc_request("GET", &request_path, None)
+                        .await
+                        .and_then(|response| {
+                            delay_test::parse_delay(response.status_code, &response.body)
+                        });
+                let success = result.is_ok();
+                ProxyDelayResult {
+                    group: group.clone(),
+                    proxy: proxy.clone(),
+                    delay_ms: result.as_ref().map_or(0, |delay| *delay),
+                    error: result.err(),
+                }
+                .send_signal_to_dart();
+                success
+            }
+        }))
+        .await;
+
+        let failed = results.iter().filter(|success| !**success).count();
+        log::debug!(
+            "代理组延迟测试完成：{}（{}个成员，失败{}个）",
+            group,
+            members.len(),
+            failed
+        );
+        complete(members.len(), failed, None);
+    }
+}
+
+impl GetIpcLatencyStats {
+    pub fn handle(self) {
+        IpcLatencyStats {
+            entries: latency::snapshot(),
+        }
+        .send_signal_to_dart();
+
+        if self.reset {
+            latency::reset();
+            log::debug!("IPC 请求耗时统计已重置");
+        }
+    }
+}
+
+impl SetIpcSlowRequestThreshold {
     pub fn handle(self) {
+        latency::set_slow_request_threshold(self.threshold_ms);
+        log::info!("IPC 慢请求阈值已设置为 {} 毫秒", self.threshold_ms);
+    }
+}
+
+impl IpcCancelRequest {
+    pub fn handle(self) {
+        // 合并请求的发起方仍有等待者时请求继续执行，只取消发起方自己的响应
+        if detach_coalesce_leader(self.request_id) {
+            IN_FLIGHT_REQUESTS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&self.request_id);
+            log::debug!("已取消 IPC 请求：{}（合并请求继续执行）", self.request_id);
+            IpcResponse::cancelled(self.request_id).send_signal_to_dart();
+            return;
+        }
+
+        let handle = IN_FLIGHT_REQUESTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.request_id);
+
+        match handle {
+            // 中止任务会丢弃其持有的连接（不归还连接池）与配置锁
+            Some(handle) => handle.abort(),
+            // 等待合并请求的请求没有独立任务，从等待列表中移除即可
+            None if cancel_coalesced_waiter(self.request_id) => {}
+            // 未知或已完成的请求静默忽略
+            None => return,
+        }
+        log::debug!("已取消 IPC 请求：{}", self.request_id);
+
+        IpcResponse::cancelled(self.request_id).send_signal_to_dart();
+    }
+}
+
+impl IpcRequest {
+    pub fn handle(self) {
+        let request_id = self.request_id;
+        if !self.is_coalescable() {
+            spawn_request(request_id, async move {
+                if let Some(response) = self.execute().await {
+                    response.send_signal_to_dart();
+                }
+            });
+            return;
+        }
+
+        // 相同路径的 GET 已在进行中时只登记等待，共享同一个响应
+        {
+            let mut coalesced = COALESCED_GETS.lock().unwrap_or_else(|e| e.into_inner());
+            let key = (self.priority, self.path.clone());
+            if let Some(get) = coalesced.get_mut(&key) {
+                get.waiters.push(request_id);
+                log::trace!("合并相同的 GET 请求：{}（{}）", self.path, request_id);
+                return;
+            }
+            coalesced.insert(
+                key,
+                CoalescedGet {
+                    leader: Some(request_id),
+                    waiters: Vec::new(),
+                },
+            );
+        }
+
+        let mut leader = CoalesceLeader {
+            key: (self.priority, self.path.clone()),
+            request_id,
+            response: None,
+        };
+        spawn_request(request_id, async move {
+            if let Some(response) = self.execute().await {
+                if leader.owns_delivery() {
+                    response.clone().send_signal_to_dart();
+                }
+                leader.complete(response);
+            }
+            // leader 在此处 drop，将响应分发给等待者
+        });
+    }
+
+    // 可合并的请求：不带自定义请求头的普通 GET；/delay 测速需要每次真实请求
+    fn is_coalescable(&self) -> bool {
+        self.method == "GET"
+            && self.headers.is_empty()
+            && !self.stream_response
+            && !self.path.contains("/delay")
+    }
+
+    // 执行请求并返回应发送给 Dart 的响应（已分块发送时为 None）
+    async fn execute(self) -> Option<IpcResponse> {
         let request_id = self.request_id;
-        tokio::spawn(async move {
-            let ipc_conn = match acquire_connection().await {
-                Ok(c) => c,
+        let method = self.method.as_str();
+
+        if let Err(e) = IpcClient::validate_request(method, &self.path, &self.headers) {
+            log::error!("IPC 请求被拒绝：{} {}，error：{}", method, self.path, e);
+            return Some(IpcResponse::failure(request_id, e.to_string()));
+        }
+
+        // PUT 会替换配置，获取配置更新锁确保串行执行（permit 在函数结束时释放）
+        let permit = if method == "PUT" {
+            match CONFIG_UPDATE_SEMAPHORE.acquire().await {
+                Ok(permit) => {
+                    log::trace!("获取配置更新锁，开始处理 PUT 请求：{}", self.path);
+                    Some(permit)
+                }
                 Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC PATCH 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC PATCH 获取连接失败：{}，error：{}", self.path, e);
-                    }
+                    log::error!("获取配置更新锁失败：{}", e);
+                    return Some(IpcResponse::failure(
+                        request_id,
+                        format!("获取配置锁失败：{}", e),
+                    ));
+                }
+            }
+        } else {
+            None
+        };
 
-                    IpcResponse {
+        // 批量请求限制并发，超出时排队（permit 在函数结束时释放）
+        let _bulk_permit = if self.priority == Some(IpcPriority::Bulk) {
+            match BULK_REQUEST_SEMAPHORE.acquire().await {
+                Ok(permit) => Some(permit),
+                Err(e) => {
+                    log::error!("获取批量请求许可失败：{}", e);
+                    return Some(IpcResponse::failure(
                         request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("获取连接失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                    return;
+                        format!("获取批量请求许可失败：{}", e),
+                    ));
+                }
+            }
+        } else {
+            None
+        };
+
+        // 大响应分块返回时请求未压缩的响应体，以便边读边发送
+        let mut chunker =
+            (self.stream_response && method == "GET").then(|| ResponseChunker::new(request_id));
+        let mut headers = self.headers;
+        if chunker.is_some()
+            && !headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("accept-encoding"))
+        {
+            headers.push(("Accept-Encoding".to_string(), "identity".to_string()));
+        }
+
+        // 从连接池获取连接
+        let (ipc_conn, endpoint, reused) = match acquire_connection(self.priority).await {
+            Ok(acquired) => acquired,
+            Err(e) => {
+                if e.is_not_ready() {
+                    log::trace!(
+                        "IPC {} 请求等待中：{}，原因：IPC 尚未就绪",
+                        method,
+                        self.path
+                    );
+                } else {
+                    log::error!("IPC {} 获取连接失败：{}，error：{}", method, self.path, e);
                 }
-            };
 
-            match IpcClient::request_with_connection(
-                "PATCH",
+                return Some(IpcResponse::failure(
+                    request_id,
+                    format!("获取连接失败：{}", e),
+                ));
+            }
+        };
+
+        // 使用连接发送请求
+        let body = self.body.as_deref();
+        let mut result = IpcClient::request_with_connection(
+            method,
+            &self.path,
+            &headers,
+            body,
+            ipc_conn,
+            chunker.as_mut().map(|c| c as &mut dyn ChunkSink),
+        )
+        .await;
+        // 幂等请求：复用的连接已被核心关闭时换新连接重试一次（已发送分块时不能重试）
+        if reused
+            && is_idempotent(method)
+            && !chunker.as_ref().is_some_and(ResponseChunker::started)
+            && let Err(e) = &result
+            && e.is_stale_connection()
+        {
+            result = retry_with_new_connection(
+                &endpoint,
+                method,
                 &self.path,
-                self.body.as_deref(),
-                ipc_conn,
+                &headers,
+                body,
+                chunker.as_mut().map(|c| c as &mut dyn ChunkSink),
+                e,
             )
-            .await
-            {
-                Ok((response, ipc_conn)) => {
-                    release_connection(ipc_conn).await;
+            .await;
+        }
 
-                    IpcResponse {
-                        request_id,
-                        status_code: response.status_code,
-                        body: response.body,
-                        success: true,
-                        error_message: None,
-                    }
-                    .send_signal_to_dart();
+        match result {
+            Ok((response, ipc_conn)) => {
+                // 归还连接（核心要求关闭时直接丢弃）
+                if response.keep_alive {
+                    release_connection(ipc_conn, endpoint).await;
                 }
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC PATCH 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC PATCH 请求失败：{}，error：{}", self.path, e);
-                    }
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("IPC 请求失败：{}", e)),
+                if response.streamed {
+                    log::trace!(
+                        "响应体已分块发送：{}（{}块）",
+                        self.path,
+                        chunker.as_ref().map_or(0, |chunker| chunker.seq)
+                    );
+                    return None;
+                }
+
+                // 日志处理（成功）
+                if response.body.len() > 200 {
+                    let preview = response.body.chars().take(100).collect::<String>();
+                    log::trace!(
+                        "响应体内容（截断）：{}…[总长度：{}字节]",
+                        preview,
+                        response.body.len()
+                    );
+                } else {
+                    log::trace!("响应体内容：{}", response.body);
+                }
+
+                // 核心配置了 secret 而密钥未设置或错误时返回 401
+                let unauthorized = response.status_code == 401;
+                if unauthorized {
+                    log::warn!("IPC {} 请求未授权：{}，请检查控制器密钥", method, self.path);
+                }
+
+                if permit.is_some() {
+                    log::trace!("PUT 请求完成，释放配置更新锁：{}", self.path);
+                }
+
+                Some(IpcResponse {
+                    request_id,
+                    status_code: response.status_code,
+                    body: response.body,
+                    success: !unauthorized,
+                    error_message: unauthorized
+                        .then(|| "控制器拒绝访问，密钥无效或未设置".to_string()),
+                    cancelled: false,
+                    error_code: unauthorized.then_some(IpcErrorCode::Unauthorized),
+                })
+            }
+            Err(e) => {
+                // 连接已失效，不归还
+                if e.is_not_ready() {
+                    log::trace!(
+                        "IPC {} 请求等待中：{}，原因：IPC 尚未就绪",
+                        method,
+                        self.path
+                    );
+                } else {
+                    log::error!("IPC {} 请求失败：{}，error：{}", method, self.path, e);
+                }
+
+                let message = format!("IPC 请求失败：{}", e);
+                match chunker.as_mut().filter(|chunker| chunker.started()) {
+                    // 已发送部分分块，以失败的最后一块结束，避免 Dart 一直等待
+                    Some(chunker) => {
+                        chunker.fail(message);
+                        None
                     }
-                    .send_signal_to_dart();
+                    None => Some(IpcResponse::failure(request_id, message)),
                 }
             }
-        });
+        }
     }
 }
 
-impl IpcDeleteRequest {
+// 合并键（优先级，完整路径含查询参数）：仅合并优先级相同的请求，避免交互请求排在批量请求之后
+type CoalesceKey = (Option<IpcPriority>, String);
+
+// 合并中的 GET 请求
+struct CoalescedGet {
+    // 发起方的请求 ID，发起方已取消时为 None（请求继续为等待者执行）
+    leader: Option<i64>,
+    // 等待同一响应的其他请求 ID
+    waiters: Vec<i64>,
+}
+
+static COALESCED_GETS: Lazy<Mutex<HashMap<CoalesceKey, CoalescedGet>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 合并请求的发起方，结束时（含被取消）将结果分发给所有等待者
+struct CoalesceLeader {
+    key: CoalesceKey,
+    request_id: i64,
+    response: Option<IpcResponse>,
+}
+
+impl CoalesceLeader {
+    fn complete(&mut self, response: IpcResponse) {
+        self.response = Some(response);
+    }
+
+    // 发起方未被取消时才向其发送响应
+    fn owns_delivery(&self) -> bool {
+        COALESCED_GETS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&self.key)
+            .is_some_and(|get| get.leader == Some(self.request_id))
+    }
+}
+
+impl Drop for CoalesceLeader {
+    fn drop(&mut self) {
+        let waiters = COALESCED_GETS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.key)
+            .map(|get| get.waiters)
+            .unwrap_or_default();
+
+        for request_id in waiters {
+            let response = match &self.response {
+                Some(response) => IpcResponse {
+                    request_id,
+                    ..response.clone()
+                },
+                // 请求被中止后才加入的等待者（或任务异常结束），以失败结束以便 Dart 重试
+                None => IpcResponse::failure(request_id, "合并的请求已被取消".to_string()),
+            };
+            response.send_signal_to_dart();
+        }
+    }
+}
+
+// 取消仍在等待合并请求的请求，返回是否找到
+fn cancel_coalesced_waiter(request_id: i64) -> bool {
+    let mut coalesced = COALESCED_GETS.lock().unwrap_or_else(|e| e.into_inner());
+    coalesced.values_mut().any(|get| {
+        let before = get.waiters.len();
+        get.waiters.retain(|waiter| *waiter != request_id);
+        get.waiters.len() != before
+    })
+}
+
+// 发起方被取消但仍有等待者时让请求继续执行，返回是否已分离
+fn detach_coalesce_leader(request_id: i64) -> bool {
+    let mut coalesced = COALESCED_GETS.lock().unwrap_or_else(|e| e.into_inner());
+    coalesced
+        .values_mut()
+        .find(|get| get.leader == Some(request_id) && !get.waiters.is_empty())
+        .map(|get| get.leader = None)
+        .is_some()
+}
+
+// 超过该大小的响应体在请求了 stream_response 时分块发送
+const STREAM_RESPONSE_THRESHOLD: usize = 256 * 1024;
+
+// 将大响应体按顺序分块发送给 Dart
+struct ResponseChunker {
+    request_id: i64,
+    seq: u32,
+    status_code: u16,
+}
+
+impl ResponseChunker {
+    fn new(request_id: i64) -> Self {
+        Self {
+            request_id,
+            seq: 0,
+            status_code: 0,
+        }
+    }
+
+    // 是否已发送过分块（之后的结果都必须以分块结束）
+    fn started(&self) -> bool {
+        self.seq > 0
+    }
+
+    // 传输中途出错，发送失败的最后一块
+    fn fail(&mut self, message: String) {
+        self.send(Vec::new(), true, Some(message));
+    }
+
+    fn send(&mut self, bytes: Vec<u8>, is_last: bool, error_message: Option<String>) {
+        IpcResponseChunk {
+            request_id: self.request_id,
+            seq: self.seq,
+            status_code: self.status_code,
+            bytes,
+            is_last,
+            failed: error_message.is_some(),
+            error_message,
+        }
+        .send_signal_to_dart();
+        self.seq += 1;
+    }
+}
+
+impl ChunkSink for ResponseChunker {
+    fn threshold(&self) -> usize {
+        STREAM_RESPONSE_THRESHOLD
+    }
+
+    fn on_chunk(&mut self, status_code: u16, bytes: Vec<u8>, is_last: bool) {
+        self.status_code = status_code;
+        self.send(bytes, is_last, None);
+    }
+}
+
+// 批量请求的最大并发数
+const BATCH_CONCURRENCY: usize = 4;
+
+impl IpcBatchRequest {
     pub fn handle(self) {
         let request_id = self.request_id;
-        tokio::spawn(async move {
-            let ipc_conn = match acquire_connection().await {
-                Ok(c) => c,
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC DELETE 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC DELETE 获取连接失败：{}，error：{}", self.path, e);
-                    }
-
-                    IpcResponse {
+        spawn_request(request_id, async move {
+            let count = self.items.len();
+            let semaphore = Semaphore::new(BATCH_CONCURRENCY);
+            let semaphore = &semaphore;
+
+            // 各请求独立执行，结果按原顺序收集
+            let items =
+                futures_util::future::join_all(self.items.into_iter().map(|item| async move {
+                    let _permit = semaphore.acquire().await.ok();
+                    let response = IpcRequest {
                         request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("获取连接失败：{}", e)),
+                        method: item.method,
+                        path: item.path,
+                        headers: Vec::new(),
+                        body: item.body,
+                        stream_response: false,
+                        priority: None,
                     }
-                    .send_signal_to_dart();
-                    return;
-                }
-            };
+                    .execute()
+                    .await;
+
+                    match response {
+                        Some(response) => IpcBatchItemResult {
+                            status_code: response.status_code,
+                            body: response.body,
+                            success: response.success,
+                            error_message: response.error_message,
+                            error_code: response.error_code,
+                        },
+                        // 未请求分块时不会出现，保守地按失败处理
+                        None => IpcBatchItemResult {
+                            status_code: 0,
+                            body: String::new(),
+                            success: false,
+                            error_message: Some("响应已分块发送".to_string()),
+                            error_code: None,
+                        },
+                    }
+                }))
+                .await;
+
+            let failed = items.iter().filter(|item| !item.success).count();
+            log::debug!("批量 IPC 请求完成：共{}个，失败{}个", count, failed);
+
+            IpcBatchResponse { request_id, items }.send_signal_to_dart();
+        });
+    }
+}
+
+// 以下五种请求保留用于兼容，统一转换为 IpcRequest 处理
+
+impl IpcGetRequest {
+    pub fn handle(self) {
+        IpcRequest {
+            request_id: self.request_id,
+            method: "GET".to_string(),
+            path: self.path,
+            headers: Vec::new(),
+            body: None,
+            stream_response: self.stream_response,
+            priority: self.priority,
+        }
+        .handle();
+    }
+}
+
+impl IpcPostRequest {
+    pub fn handle(self) {
+        IpcRequest {
+            request_id: self.request_id,
+            method: "POST".to_string(),
+            path: self.path,
+            headers: Vec::new(),
+            body: self.body,
+            stream_response: false,
+            priority: self.priority,
+        }
+        .handle();
+    }
+}
+
+impl IpcPutRequest {
+    pub fn handle(self) {
+        IpcRequest {
+            request_id: self.request_id,
+            method: "PUT".to_string(),
+            path: self.path,
+            headers: Vec::new(),
+            body: self.body,
+            stream_response: false,
+            priority: self.priority,
+        }
+        .handle();
+    }
+}
+
+impl IpcPatchRequest {
+    pub fn handle(self) {
+        IpcRequest {
+            request_id: self.request_id,
+            method: "PATCH".to_string(),
+            path: self.path,
+            headers: Vec::new(),
+            body: self.body,
+            stream_response: false,
+            priority: self.priority,
+        }
+        .handle();
+    }
+}
+
+impl IpcDeleteRequest {
+    pub fn handle(self) {
+        IpcRequest {
+            request_id: self.request_id,
+            method: "DELETE".to_string(),
+            path: self.path,
+            headers: Vec::new(),
+            body: None,
+            stream_response: false,
+            priority: self.priority,
+        }
+        .handle();
+    }
+}
+
+// 初始化 IPC REST API 消息监听器
+pub fn init_rest_api_listeners() {
+    log::info!("初始化 IPC REST API 监听器");
+
+    // 恢复上次保存的控制器密钥与连接池参数，之后的修改经设置变更通知生效
+    restore_controller_secret();
+    restore_pool_settings();
+    watch_settings();
+    start_connection_pool_health_check();
+
+    tokio::spawn(async {
+        let receiver = GetProxiesTyped::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = GetConnectionsTyped::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = CloseConnection::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = CloseAllConnections::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = GetCoreInfo::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = DnsQuery::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = GetRulesTyped::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = GetRuleProvidersTyped::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = UpdateRuleProvider::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = TestGroupDelay::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            tokio::spawn(dart_signal.message.handle());
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = GetIpcLatencyStats::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
 
-            match IpcClient::request_with_connection("DELETE", &self.path, None, ipc_conn).await {
-                Ok((response, ipc_conn)) => {
-                    release_connection(ipc_conn).await;
+    tokio::spawn(async {
+        let receiver = SetIpcSlowRequestThreshold::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
 
-                    IpcResponse {
-                        request_id,
-                        status_code: response.status_code,
-                        body: response.body,
-                        success: true,
-                        error_message: None,
-                    }
-                    .send_signal_to_dart();
-                }
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    if is_ipc_not_ready_error(&error_msg) {
-                        log::trace!("IPC DELETE 请求等待中：{}，原因：IPC 尚未就绪", self.path);
-                    } else {
-                        log::error!("IPC DELETE 请求失败：{}，error：{}", self.path, e);
-                    }
+    tokio::spawn(async {
+        let receiver = SetControllerEndpoint::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle().await;
+        }
+    });
 
-                    IpcResponse {
-                        request_id,
-                        status_code: 0,
-                        body: String::new(),
-                        success: false,
-                        error_message: Some(format!("IPC 请求失败：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                }
-            }
-        });
-    }
-}
+    tokio::spawn(async {
+        let receiver = SetControllerSecret::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
 
-// 初始化 IPC REST API 消息监听器
-pub fn init_rest_api_listeners() {
-    log::info!("初始化 IPC REST API 监听器");
+    tokio::spawn(async {
+        let receiver = IpcBatchRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
 
-    // 启动连接池健康检查
-    start_connection_pool_health_check();
+    tokio::spawn(async {
+        let receiver = IpcRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
 
     tokio::spawn(async {
         let receiver = IpcGetRequest::get_dart_signal_receiver();
@@ -677,11 +1805,25 @@ pub fn init_rest_api_listeners() {
         }
     });
 
+    tokio::spawn(async {
+        let receiver = ConfigureIpcPool::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle().await;
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = IpcCancelRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+    });
+
     // WebSocket 流式数据监听器
     tokio::spawn(async {
         let receiver = StartTrafficStream::get_dart_signal_receiver();
-        while let Some(_dart_signal) = receiver.recv().await {
-            StartTrafficStream::handle_start().await;
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle_start().await;
         }
     });
 
@@ -694,8 +1836,8 @@ pub fn init_rest_api_listeners() {
 
     tokio::spawn(async {
         let receiver = StartLogStream::get_dart_signal_receiver();
-        while let Some(_dart_signal) = receiver.recv().await {
-            StartLogStream::handle_start().await;
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle_start().await;
         }
     });
 
@@ -705,29 +1847,92 @@ pub fn init_rest_api_listeners() {
             StopLogStream::handle_stop().await;
         }
     });
+
+    tokio::spawn(async {
+        let receiver = StartConnectionsStream::get_dart_signal_receiver();
+        while let Some(_dart_signal) = receiver.recv().await {
+            StartConnectionsStream::handle_start().await;
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = StopConnectionsStream::get_dart_signal_receiver();
+        while let Some(_dart_signal) = receiver.recv().await {
+            StopConnectionsStream::handle_stop().await;
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = StartMemoryStream::get_dart_signal_receiver();
+        while let Some(_dart_signal) = receiver.recv().await {
+            StartMemoryStream::handle_start().await;
+        }
+    });
+
+    tokio::spawn(async {
+        let receiver = StopMemoryStream::get_dart_signal_receiver();
+        while let Some(_dart_signal) = receiver.recv().await {
+            StopMemoryStream::handle_stop().await;
+        }
+    });
 }
 
 // WebSocket 流式数据处理器
 
 impl StartTrafficStream {
-    async fn handle_start() {
+    async fn handle_start(&self) {
         log::info!("开始监听流量数据");
 
         // 确保 WebSocket 客户端已初始化
         ensure_ws_client_initialized().await;
 
+        // 聚合时由独立任务按窗口发送，否则经有界缓冲转发（只保留最新一帧）；
+        // 连接断开后回调被释放，任务发送剩余数据后退出
+        let (aggregator, forwarder) = match self.aggregate_window_ms.filter(|ms| *ms > 0) {
+            Some(window_ms) => {
+                let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+                tokio::spawn(aggregate_traffic(
+                    receiver,
+                    Duration::from_millis(window_ms),
+                ));
+                (Some(sender), None)
+            }
+            None => {
+                let (sender, receiver) = stream_buffer::channel(TRAFFIC_BUFFER_SIZE);
+                tokio::spawn(forward_stream(
+                    receiver,
+                    TRAFFIC_FORWARD_INTERVAL,
+                    |batch: StreamBatch<IpcTrafficData>| {
+                        if let Some(frame) = batch.items.into_iter().last() {
+                            frame.send_signal_to_dart();
+                        }
+                    },
+                ));
+                (None, Some(sender))
+            }
+        };
+
         // 建立 WebSocket 连接
         let client = WS_CLIENT.read().await;
         if let Some(ws_client) = client.as_ref() {
             match ws_client
-                .connect("/traffic", |json_value| {
+                .connect("/traffic", move |json_value| {
                     // 解析流量数据
                     if let Some(obj) = json_value.as_object() {
                         let upload = obj.get("up").and_then(|v| v.as_u64()).unwrap_or(0);
                         let download = obj.get("down").and_then(|v| v.as_u64()).unwrap_or(0);
 
-                        // 发送到 Dart 层
-                        IpcTrafficData { upload, download }.send_signal_to_dart();
+                        if let Some(sender) = &aggregator {
+                            let _ = sender.send((upload, download));
+                        } else if let Some(forwarder) = &forwarder {
+                            // 经缓冲发送到 Dart 层
+                            forwarder.push(IpcTrafficData {
+                                upload,
+                                download,
+                                upload_bytes: upload,
+                                download_bytes: download,
+                            });
+                        }
                     }
                 })
                 .await
@@ -746,10 +1951,14 @@ impl StartTrafficStream {
                     .send_signal_to_dart();
                 }
                 Err(e) => {
-                    log::error!("流量监控 WebSocket 连接失败：{}", e);
+                    if e.is_not_ready() {
+                        log::trace!("流量监控 WebSocket 连接等待中，原因：IPC 尚未就绪：{}", e);
+                    } else {
+                        log::error!("流量监控 WebSocket 连接失败：{}", e);
+                    }
                     StreamResult {
                         success: false,
-                        error_message: Some(e),
+                        error_message: Some(e.to_string()),
                     }
                     .send_signal_to_dart();
                 }
@@ -758,6 +1967,82 @@ impl StartTrafficStream {
     }
 }
 
+// 按固定节奏将缓冲中的数据交给 send，写入端释放（连接结束）后发送剩余数据并退出
+async fn forward_stream<T, F>(mut receiver: StreamReceiver<T>, interval: Duration, mut send: F)
+where
+    F: FnMut(StreamBatch<T>),
+{
+    while let Some(batch) = receiver.recv_batch().await {
+        send(batch);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+// 发送一批日志，有行被丢弃时先发送一条提示
+fn send_log_batch(batch: StreamBatch<IpcLogData>) {
+    if batch.dropped > 0 {
+        log::debug!("日志转发积压，丢弃{}行", batch.dropped);
+        IpcLogData {
+            log_type: "warning".to_string(),
+            payload: format!("日志过多，已丢弃 {} 行", batch.dropped),
+        }
+        .send_signal_to_dart();
+    }
+    for item in batch.items {
+        item.send_signal_to_dart();
+    }
+}
+
+// 按窗口聚合流量帧：每个窗口发送一次平均速率与累计字节数
+async fn aggregate_traffic(
+    mut receiver: tokio::sync::mpsc::UnboundedReceiver<(u64, u64)>,
+    window: Duration,
+) {
+    let mut interval = tokio::time::interval(window);
+    interval.tick().await; // 跳过首次立即触发
+
+    let mut frames = 0u64;
+    let mut upload_bytes = 0u64;
+    let mut download_bytes = 0u64;
+
+    loop {
+        let closed = tokio::select! {
+            frame = receiver.recv() => match frame {
+                Some((upload, download)) => {
+                    frames += 1;
+                    upload_bytes = upload_bytes.saturating_add(upload);
+                    download_bytes = download_bytes.saturating_add(download);
+                    continue;
+                }
+                None => true,
+            },
+            _ = interval.tick() => false,
+        };
+
+        // 窗口内没有收到帧时不发送
+        if let (Some(upload), Some(download)) = (
+            upload_bytes.checked_div(frames),
+            download_bytes.checked_div(frames),
+        ) {
+            IpcTrafficData {
+                upload,
+                download,
+                upload_bytes,
+                download_bytes,
+            }
+            .send_signal_to_dart();
+            frames = 0;
+            upload_bytes = 0;
+            download_bytes = 0;
+        }
+
+        if closed {
+            log::debug!("流量聚合任务已结束");
+            return;
+        }
+    }
+}
+
 impl StopTrafficStream {
     async fn handle_stop() {
         log::info!("停止监听流量数据");
@@ -784,8 +2069,213 @@ impl StopTrafficStream {
 }
 
 impl StartLogStream {
+    async fn handle_start(&self) {
+        let level = if self.level.is_empty() {
+            DEFAULT_LOG_LEVEL
+        } else {
+            self.level.as_str()
+        };
+        if !LOG_LEVELS.contains(&level) {
+            log::warn!("无效的日志级别：{}", level);
+            StreamResult {
+                success: false,
+                error_message: Some(format!("无效的日志级别：{}", level)),
+            }
+            .send_signal_to_dart();
+            return;
+        }
+        log::info!("开始监听日志数据（级别：{}）", level);
+
+        // 确保 WebSocket 客户端已初始化
+        ensure_ws_client_initialized().await;
+
+        // 持有连接 ID 的写锁直到新连接建立，避免并发启动留下孤立连接
+        let mut id_guard = LOG_CONNECTION_ID.write().await;
+        let client = WS_CLIENT.read().await;
+        let Some(ws_client) = client.as_ref() else {
+            return;
+        };
+
+        if let Some(id) = id_guard.take() {
+            if !ws_client.is_connected(id).await {
+                // 重连已放弃，连接已结束，重新建立
+                log::info!("日志流连接已结束[{}]，重新连接", id);
+            } else if *LOG_STREAM_LEVEL.read().await == level {
+                log::debug!("日志流已在以 {} 级别监听", level);
+                *id_guard = Some(id);
+                StreamResult {
+                    success: true,
+                    error_message: None,
+                }
+                .send_signal_to_dart();
+                return;
+            } else {
+                // 级别变化时关闭旧连接后重新连接
+                ws_client.disconnect(id).await;
+            }
+        }
+
+        // 日志经有界缓冲转发，Dart 处理不及时丢弃最旧的行
+        let (forwarder, receiver) = stream_buffer::channel(LOG_BUFFER_SIZE);
+        tokio::spawn(forward_stream(
+            receiver,
+            LOG_FORWARD_INTERVAL,
+            send_log_batch,
+        ));
+
+        match ws_client
+            .connect(&format!("/logs?level={}", level), move |json_value| {
+                // 解析日志数据
+                if let Some(obj) = json_value.as_object() {
+                    let log_type = obj
+                        .get("type")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("info")
+                        .to_string();
+                    let payload = obj
+                        .get("payload")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string();
+
+                    // 经缓冲发送到 Dart 层
+                    forwarder.push(IpcLogData { log_type, payload });
+                }
+            })
+            .await
+        {
+            Ok(connection_id) => {
+                log::info!("日志监控 WebSocket 连接已建立：{}", connection_id);
+
+                // 保存连接 ID 与级别
+                *id_guard = Some(connection_id);
+                *LOG_STREAM_LEVEL.write().await = level.to_string();
+
+                StreamResult {
+                    success: true,
+                    error_message: None,
+                }
+                .send_signal_to_dart();
+            }
+            Err(e) => {
+                if e.is_not_ready() {
+                    log::trace!("日志监控 WebSocket 连接等待中，原因：IPC 尚未就绪：{}", e);
+                } else {
+                    log::error!("日志监控 WebSocket 连接失败：{}", e);
+                }
+                StreamResult {
+                    success: false,
+                    error_message: Some(e.to_string()),
+                }
+                .send_signal_to_dart();
+            }
+        }
+    }
+}
+
+impl StopLogStream {
+    async fn handle_stop() {
+        log::info!("停止监听日志数据");
+
+        // 获取并清除连接 ID
+        let connection_id = {
+            let mut id_guard = LOG_CONNECTION_ID.write().await;
+            id_guard.take()
+        };
+
+        if let Some(id) = connection_id {
+            let client = WS_CLIENT.read().await;
+            if let Some(ws_client) = client.as_ref() {
+                ws_client.disconnect(id).await;
+            }
+        }
+
+        StreamResult {
+            success: true,
+            error_message: None,
+        }
+        .send_signal_to_dart();
+    }
+}
+
+impl StartConnectionsStream {
+    async fn handle_start() {
+        log::info!("开始监听连接数据");
+
+        // 确保 WebSocket 客户端已初始化
+        ensure_ws_client_initialized().await;
+
+        // 建立 WebSocket 连接
+        let client = WS_CLIENT.read().await;
+        if let Some(ws_client) = client.as_ref() {
+            match ws_client
+                .connect("/connections", |json_value| {
+                    // 连接列表结构复杂，原样转发由 Dart 层解析
+                    IpcConnectionsData {
+                        json: json_value.to_string(),
+                    }
+                    .send_signal_to_dart();
+                })
+                .await
+            {
+                Ok(connection_id) => {
+                    log::info!("连接监控 WebSocket 连接已建立：{}", connection_id);
+
+                    // 保存连接 ID
+                    let mut id_guard = CONNECTIONS_CONNECTION_ID.write().await;
+                    *id_guard = Some(connection_id);
+
+                    StreamResult {
+                        success: true,
+                        error_message: None,
+                    }
+                    .send_signal_to_dart();
+                }
+                Err(e) => {
+                    if e.is_not_ready() {
+                        log::trace!("连接监控 WebSocket 连接等待中，原因：IPC 尚未就绪：{}", e);
+                    } else {
+                        log::error!("连接监控 WebSocket 连接失败：{}", e);
+                    }
+                    StreamResult {
+                        success: false,
+                        error_message: Some(e.to_string()),
+                    }
+                    .send_signal_to_dart();
+                }
+            }
+        }
+    }
+}
+
+impl StopConnectionsStream {
+    async fn handle_stop() {
+        log::info!("停止监听连接数据");
+
+        // 获取并清除连接 ID
+        let connection_id = {
+            let mut id_guard = CONNECTIONS_CONNECTION_ID.write().await;
+            id_guard.take()
+        };
+
+        if let Some(id) = connection_id {
+            let client = WS_CLIENT.read().await;
+            if let Some(ws_client) = client.as_ref() {
+                ws_client.disconnect(id).await;
+            }
+        }
+
+        StreamResult {
+            success: true,
+            error_message: None,
+        }
+        .send_signal_to_dart();
+    }
+}
+
+impl StartMemoryStream {
     async fn handle_start() {
-        log::info!("开始监听日志数据");
+        log::info!("开始监听内存数据");
 
         // 确保 WebSocket 客户端已初始化
         ensure_ws_client_initialized().await;
@@ -794,31 +2284,23 @@ impl StartLogStream {
         let client = WS_CLIENT.read().await;
         if let Some(ws_client) = client.as_ref() {
             match ws_client
-                .connect("/logs?level=info", |json_value| {
-                    // 解析日志数据
+                .connect("/memory", |json_value| {
+                    // 解析内存数据
                     if let Some(obj) = json_value.as_object() {
-                        let log_type = obj
-                            .get("type")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("info")
-                            .to_string();
-                        let payload = obj
-                            .get("payload")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("")
-                            .to_string();
+                        let inuse = obj.get("inuse").and_then(|v| v.as_u64()).unwrap_or(0);
+                        let oslimit = obj.get("oslimit").and_then(|v| v.as_u64()).unwrap_or(0);
 
                         // 发送到 Dart 层
-                        IpcLogData { log_type, payload }.send_signal_to_dart();
+                        IpcMemoryData { inuse, oslimit }.send_signal_to_dart();
                     }
                 })
                 .await
             {
                 Ok(connection_id) => {
-                    log::info!("日志监控 WebSocket 连接已建立：{}", connection_id);
+                    log::info!("内存监控 WebSocket 连接已建立：{}", connection_id);
 
                     // 保存连接 ID
-                    let mut id_guard = LOG_CONNECTION_ID.write().await;
+                    let mut id_guard = MEMORY_CONNECTION_ID.write().await;
                     *id_guard = Some(connection_id);
 
                     StreamResult {
@@ -828,10 +2310,17 @@ impl StartLogStream {
                     .send_signal_to_dart();
                 }
                 Err(e) => {
-                    log::error!("日志监控 WebSocket 连接失败：{}", e);
+                    // 旧版核心不提供 /memory，握手被拒绝，只报告一次不重试
+                    if let IpcClientError::Protocol(_) = e {
+                        log::warn!("内存监控 WebSocket 连接失败（核心可能不支持）：{}", e);
+                    } else if e.is_not_ready() {
+                        log::trace!("内存监控 WebSocket 连接等待中，原因：IPC 尚未就绪：{}", e);
+                    } else {
+                        log::error!("内存监控 WebSocket 连接失败：{}", e);
+                    }
                     StreamResult {
                         success: false,
-                        error_message: Some(e),
+                        error_message: Some(e.to_string()),
                     }
                     .send_signal_to_dart();
                 }
@@ -840,13 +2329,13 @@ impl StartLogStream {
     }
 }
 
-impl StopLogStream {
+impl StopMemoryStream {
     async fn handle_stop() {
-        log::info!("停止监听日志数据");
+        log::info!("停止监听内存数据");
 
         // 获取并清除连接 ID
         let connection_id = {
-            let mut id_guard = LOG_CONNECTION_ID.write().await;
+            let mut id_guard = MEMORY_CONNECTION_ID.write().await;
             id_guard.take()
         };
 
@@ -864,3 +2353,99 @@ impl StopLogStream {
         .send_signal_to_dart();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    // 以内存管道模拟核心，预先写入 response 作为探测的响应
+    async fn probe_with_response(response: &[u8]) -> Result<bool, String> {
+        let (client, mut server) = tokio::io::duplex(4096);
+        server
+            .write_all(response)
+            .await
+            .map_err(|e| e.to_string())?;
+        let pooled = PooledConnection {
+            conn: Box::new(client),
+            endpoint: ControllerEndpoint::Tcp("127.0.0.1:9090".to_string()),
+            last_used: Instant::now(),
+        };
+        Ok(probe_connection(pooled).await.is_some())
+    }
+
+    #[tokio::test]
+    async fn test_probe_keeps_healthy_connection() -> Result<(), String> {
+        assert!(probe_with_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_drops_connection_with_leftover_data() -> Result<(), String> {
+        // 探测响应之后还有多余数据，复用会让下一个请求读到错误的响应
+        assert!(
+            !probe_with_response(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}HTTP/1.1 200 OK\r\n"
+            )
+            .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_drops_closed_connection() {
+        let (client, server) = tokio::io::duplex(4096);
+        drop(server);
+        let pooled = PooledConnection {
+            conn: Box::new(client),
+            endpoint: ControllerEndpoint::Tcp("127.0.0.1:9090".to_string()),
+            last_used: Instant::now(),
+        };
+        assert!(probe_connection(pooled).await.is_none());
+    }
+
+    #[test]
+    fn test_cancelled_leader_detaches_only_with_waiters() {
+        let key = (None, "/test/coalesce-detach".to_string());
+        COALESCED_GETS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                key.clone(),
+                CoalescedGet {
+                    leader: Some(-101),
+                    waiters: Vec::new(),
+                },
+            );
+        let leader = CoalesceLeader {
+            key: key.clone(),
+            request_id: -101,
+            response: None,
+        };
+
+        // 没有等待者时照常中止
+        assert!(!detach_coalesce_leader(-101));
+        assert!(leader.owns_delivery());
+
+        // 有等待者时请求继续执行，只是不再向发起方发送响应
+        if let Some(get) = COALESCED_GETS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&key)
+        {
+            get.waiters.push(-102);
+        }
+        assert!(detach_coalesce_leader(-101));
+        assert!(!leader.owns_delivery());
+        assert!(!detach_coalesce_leader(-101));
+
+        assert!(cancel_coalesced_waiter(-102));
+        drop(leader);
+        assert!(
+            !COALESCED_GETS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains_key(&key)
+        );
+    }
+}
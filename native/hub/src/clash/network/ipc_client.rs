@@ -1,23 +1,91 @@
 // Clash IPC 客户端
 //
-// 通过 Named Pipe (Windows) 或 Unix Socket (Unix) 与 Clash 核心通信
+// 通过 Named Pipe (Windows) 或 Unix Socket (Unix) 与 Clash 核心通信，也可使用 TCP 控制器端口
 // 使用 Tokio 原生实现 + 手动 HTTP 协议解析
 
-use super::connection;
-use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
-
-#[cfg(unix)]
-use tokio::net::UnixStream;
-
-#[cfg(windows)]
-use tokio::net::windows::named_pipe::NamedPipeClient;
+use super::error::IpcClientError;
+use super::latency;
+use super::path;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+use std::sync::RwLock;
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 
 // HTTP 响应
 pub struct HttpResponse {
     pub status_code: u16,
     pub body: String,
+    // 连接能否继续复用（核心返回 Connection: close 或 HTTP/1.0 未声明 keep-alive 时为 false）
+    pub keep_alive: bool,
+    // 响应体是否已分块交给 ChunkSink（此时 body 为空）
+    pub streamed: bool,
+}
+
+// 响应头中与读取响应体相关的信息
+struct ResponseHead {
+    status_code: u16,
+    keep_alive: bool,
+    content_length: Option<usize>,
+    is_chunked: bool,
+    content_encoding: Option<String>,
+}
+
+// 大响应体的分块接收方
+pub trait ChunkSink: Send {
+    // 响应体超过该字节数时改为分块交付
+    fn threshold(&self) -> usize;
+
+    // 按顺序接收每一块（每块不超过 RESPONSE_CHUNK_SIZE），is_last 表示最后一块
+    fn on_chunk(&mut self, status_code: u16, bytes: Vec<u8>, is_last: bool);
+}
+
+// 分块交付时每块的大小
+pub const RESPONSE_CHUNK_SIZE: usize = 64 * 1024;
+
+// 外部控制器密钥（配置了 secret 时所有请求都需携带）
+static CONTROLLER_SECRET: RwLock<Option<String>> = RwLock::new(None);
+
+// 设置外部控制器密钥，None 表示清除
+pub fn set_controller_secret(secret: Option<String>) {
+    *CONTROLLER_SECRET.write().unwrap_or_else(|e| e.into_inner()) = secret;
+}
+
+// 规范化外部控制器密钥：去除首尾空白，为空时返回 None
+//
+// 密钥会写入 Authorization 请求头，包含控制字符（如 CR/LF）时会破坏请求结构，直接拒绝
+pub fn normalize_controller_secret(secret: &str) -> Result<Option<String>, String> {
+    let secret = secret.trim();
+    if secret.chars().any(char::is_control) {
+        return Err("控制器密钥包含非法的控制字符".to_string());
+    }
+    Ok((!secret.is_empty()).then(|| secret.to_string()))
+}
+
+// 当前的外部控制器密钥
+pub fn controller_secret() -> Option<String> {
+    CONTROLLER_SECRET
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+// 隐藏请求中 Authorization 头的值，用于日志输出
+fn redact_authorization(request: &str) -> String {
+    request
+        .split_inclusive("\r\n")
+        .map(|line| match line.split_once(':') {
+            Some((name, _)) if name.eq_ignore_ascii_case("authorization") => {
+                format!("{}: ***\r\n", name)
+            }
+            _ => line.to_string(),
+        })
+        .collect()
 }
 
+// 允许通过 IPC 发送的请求方法
+const ALLOWED_METHODS: [&str; 7] = ["GET", "HEAD", "OPTIONS", "POST", "PUT", "PATCH", "DELETE"];
+
 // IPC 客户端
 pub struct IpcClient;
 
@@ -50,58 +118,130 @@ impl IpcClient {
         }
     }
 
-    // 使用已有连接发送请求（连接池场景）
-    #[cfg(windows)]
-    pub async fn request_with_connection(
+    // 使用已有连接发送请求（连接池场景），连接可以是 IPC 或 TCP
+    //
+    // 提供 sink 时，超过其阈值的响应体会分块交给 sink；每次请求的耗时计入 latency 统计
+    pub async fn request_with_connection<S>(
         method: &str,
         path: &str,
+        headers: &[(String, String)],
         body: Option<&str>,
-        mut stream: NamedPipeClient,
-    ) -> Result<(HttpResponse, NamedPipeClient), String> {
-        // 1. 构建 HTTP 请求
-        let request = Self::build_http_request_static(method, path, body);
-        log::trace!("发送 IPC 请求：\n{}", request);
-
-        // 2. 发送请求
-        stream
-            .write_all(request.as_bytes())
-            .await
-            .map_err(|e| format!("发送请求失败：{}", e))?;
-
-        // 3. 读取响应
-        let response = Self::read_http_response_static(&mut stream).await?;
+        mut stream: S,
+        sink: Option<&mut dyn ChunkSink>,
+    ) -> Result<(HttpResponse, S), IpcClientError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let started = Instant::now();
+        let result =
+            Self::send_and_read_static(method, path, headers, body, &mut stream, sink).await;
+        latency::record(method, path, started.elapsed(), result.is_ok());
 
-        Ok((response, stream))
+        Ok((result?, stream))
     }
 
-    #[cfg(unix)]
-    pub async fn request_with_connection(
+    async fn send_and_read_static<S>(
         method: &str,
         path: &str,
+        headers: &[(String, String)],
         body: Option<&str>,
-        mut stream: UnixStream,
-    ) -> Result<(HttpResponse, UnixStream), String> {
-        let request = Self::build_http_request_static(method, path, body);
-        log::trace!("发送 IPC 请求：\n{}", request);
+        stream: &mut S,
+        sink: Option<&mut dyn ChunkSink>,
+    ) -> Result<HttpResponse, IpcClientError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        // 1. 构建 HTTP 请求（路径编码后写入请求行）
+        let path = path::encode_request_path(path)?;
+        let secret = controller_secret();
+        let request =
+            Self::build_http_request_static(method, &path, headers, secret.as_deref(), body);
+        log::trace!("发送 IPC 请求：\n{}", redact_authorization(&request));
 
+        // 2. 发送请求
         stream
             .write_all(request.as_bytes())
             .await
-            .map_err(|e| format!("发送请求失败：{}", e))?;
+            .map_err(|e| IpcClientError::io(e, "发送请求失败"))?;
+
+        // 3. 读取响应（HEAD 响应没有响应体）
+        Self::read_http_response_static(stream, method != "HEAD", sink).await
+    }
+
+    // 校验请求方法、路径与自定义请求头，避免构造出畸形请求
+    pub fn validate_request(
+        method: &str,
+        path: &str,
+        headers: &[(String, String)],
+    ) -> Result<(), IpcClientError> {
+        path::encode_request_path(path)?;
+
+        if !ALLOWED_METHODS.contains(&method) {
+            return Err(IpcClientError::InvalidRequest(format!(
+                "不支持的请求方法：{}",
+                method
+            )));
+        }
 
-        let response = Self::read_http_response_static(&mut stream).await?;
+        for (name, value) in headers {
+            let valid_name = !name.is_empty()
+                && name
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b"-_".contains(&b));
+            if !valid_name || value.contains(['\r', '\n']) {
+                return Err(IpcClientError::InvalidRequest(format!(
+                    "非法的请求头：{}",
+                    name
+                )));
+            }
+        }
 
-        Ok((response, stream))
+        Ok(())
     }
 
     // 构建 HTTP 请求字符串（静态方法）
-    fn build_http_request_static(method: &str, path: &str, body: Option<&str>) -> String {
+    //
+    // Host 与 Content-Length 由客户端生成，自定义请求头中的同名项会被忽略；
+    // 自定义了 Content-Type 时不再使用默认的 application/json；
+    // 设置了控制器密钥且未自定义 Authorization 时携带 Bearer 认证
+    fn build_http_request_static(
+        method: &str,
+        path: &str,
+        headers: &[(String, String)],
+        secret: Option<&str>,
+        body: Option<&str>,
+    ) -> String {
         let mut request = format!("{} {} HTTP/1.1\r\n", method, path);
 
         request.push_str("Host: localhost\r\n");
 
+        let has_header = |target: &str| {
+            headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(target))
+        };
+        // 仅声明已支持解码的压缩格式
+        if !has_header("accept-encoding") {
+            request.push_str("Accept-Encoding: gzip\r\n");
+        }
+
+        if let Some(secret) = secret
+            && !has_header("authorization")
+        {
+            request.push_str(&format!("Authorization: Bearer {}\r\n", secret));
+        }
+
+        for (name, value) in headers {
+            if name.eq_ignore_ascii_case("host") || name.eq_ignore_ascii_case("content-length") {
+                continue;
+            }
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
         if let Some(body_str) = body {
-            request.push_str("Content-Type: application/json\r\n");
+            if !has_header("content-type") {
+                request.push_str("Content-Type: application/json\r\n");
+            }
             request.push_str(&format!("Content-Length: {}\r\n", body_str.len()));
             request.push_str("\r\n");
             request.push_str(body_str);
@@ -113,12 +253,142 @@ impl IpcClient {
     }
 
     // 读取 HTTP 响应（静态方法）
-    async fn read_http_response_static<S>(stream: &mut S) -> Result<HttpResponse, String>
+    //
+    // expects_body 为 false 时（HEAD 请求）忽略 Content-Length，不读取响应体；
+    // 提供 sink 且响应体超过其阈值时，响应体改为分块交给 sink，返回的 body 为空。
+    // 响应之后若已读入多余数据，这些数据会随缓冲区丢失，连接标记为不可复用，
+    // 避免下一个请求把它们当作响应解析
+    async fn read_http_response_static<S>(
+        stream: &mut S,
+        expects_body: bool,
+        sink: Option<&mut dyn ChunkSink>,
+    ) -> Result<HttpResponse, IpcClientError>
     where
         S: AsyncReadExt + Unpin,
     {
         let mut reader = BufReader::new(stream);
+        let mut response = Self::read_http_message_static(&mut reader, expects_body, sink).await?;
+
+        let leftover = reader.buffer().len();
+        if leftover > 0 {
+            log::debug!("响应之后还有 {} 字节多余数据，连接不再复用", leftover);
+            response.keep_alive = false;
+        }
+        Ok(response)
+    }
+
+    async fn read_http_message_static<R>(
+        reader: &mut BufReader<R>,
+        expects_body: bool,
+        sink: Option<&mut dyn ChunkSink>,
+    ) -> Result<HttpResponse, IpcClientError>
+    where
+        R: AsyncReadExt + Unpin,
+    {
+        // 1. 读取并解析响应头
+        let head = Self::read_response_head_static(reader).await?;
+        let status_code = head.status_code;
+        let keep_alive = head.keep_alive;
+        let encoding = head
+            .content_encoding
+            .as_deref()
+            .filter(|encoding| *encoding != "identity");
+
+        // 2. 未压缩的大响应体边读边分块交付
+        let sink = match sink {
+            Some(sink) if expects_body && encoding.is_none() => {
+                return Self::read_streamed_body_static(reader, &head, sink).await;
+            }
+            sink => sink,
+        };
+
+        // 3. 读取完整的响应体
+        let mut body_bytes = Vec::new();
+        if expects_body {
+            Self::read_body_static(reader, &head, |data| body_bytes.extend_from_slice(data))
+                .await?;
+        }
+
+        // 4. 按 Content-Encoding 解压
+        if let Some(encoding) = encoding {
+            body_bytes = Self::decode_body_static(encoding, &body_bytes)?;
+        }
+
+        // 压缩的响应无法边读边解压，解压后再按需分块交付
+        if let Some(sink) = sink
+            && body_bytes.len() > sink.threshold()
+        {
+            let mut chunks = body_bytes.chunks(RESPONSE_CHUNK_SIZE).peekable();
+            while let Some(chunk) = chunks.next() {
+                sink.on_chunk(status_code, chunk.to_vec(), chunks.peek().is_none());
+            }
+            return Ok(HttpResponse {
+                status_code,
+                body: String::new(),
+                keep_alive,
+                streamed: true,
+            });
+        }
 
+        Ok(HttpResponse {
+            status_code,
+            body: Self::body_to_string_static(body_bytes)?,
+            keep_alive,
+            streamed: false,
+        })
+    }
+
+    // 读取响应体，超过阈值后按 RESPONSE_CHUNK_SIZE 分块交给 sink（静态方法）
+    async fn read_streamed_body_static<R>(
+        reader: &mut BufReader<R>,
+        head: &ResponseHead,
+        sink: &mut dyn ChunkSink,
+    ) -> Result<HttpResponse, IpcClientError>
+    where
+        R: AsyncReadExt + Unpin,
+    {
+        let status_code = head.status_code;
+        let threshold = sink.threshold();
+        let mut pending = Vec::new();
+        let mut streamed = false;
+        Self::read_body_static(reader, head, |data| {
+            pending.extend_from_slice(data);
+            if streamed || pending.len() > threshold {
+                streamed = true;
+                while pending.len() >= RESPONSE_CHUNK_SIZE {
+                    let rest = pending.split_off(RESPONSE_CHUNK_SIZE);
+                    sink.on_chunk(status_code, std::mem::replace(&mut pending, rest), false);
+                }
+            }
+        })
+        .await?;
+
+        let body = if streamed {
+            sink.on_chunk(status_code, pending, true);
+            String::new()
+        } else {
+            Self::body_to_string_static(pending)?
+        };
+        Ok(HttpResponse {
+            status_code,
+            body,
+            keep_alive: head.keep_alive,
+            streamed,
+        })
+    }
+
+    fn body_to_string_static(body: Vec<u8>) -> Result<String, IpcClientError> {
+        String::from_utf8(body)
+            .map_err(|e| IpcClientError::Protocol(format!("解码响应体失败：{}", e)))
+    }
+
+    // 读取并解析响应头（静态方法）
+    async fn read_response_head_static<R>(
+        reader: &mut BufReader<R>,
+    ) -> Result<ResponseHead, IpcClientError>
+    where
+        R: AsyncReadExt + Unpin,
+    {
         // 1. 读取 header
         let mut header_lines = Vec::new();
         loop {
@@ -126,10 +396,13 @@ impl IpcClient {
             let size = reader
                 .read_line(&mut line)
                 .await
-                .map_err(|e| format!("读取响应行失败：{}", e))?;
+                .map_err(|e| IpcClientError::io(e, "读取响应行失败"))?;
 
             if size == 0 {
-                return Err("连接意外关闭".to_string());
+                return Err(IpcClientError::Io(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "连接意外关闭".to_string(),
+                ));
             }
 
             if line == "\r\n" {
@@ -140,69 +413,154 @@ impl IpcClient {
         }
 
         // 2. 解析 status line
-        let status_line = header_lines.first().ok_or_else(|| "响应为空".to_string())?;
-        let status_code = Self::parse_status_code_static(status_line)?;
+        let status_line = header_lines
+            .first()
+            .ok_or_else(|| IpcClientError::Protocol("响应为空".to_string()))?;
+        let mut head = ResponseHead {
+            status_code: Self::parse_status_code_static(status_line)?,
+            // HTTP/1.1 默认保持连接，HTTP/1.0 默认关闭
+            keep_alive: !status_line.starts_with("HTTP/1.0"),
+            content_length: None,
+            is_chunked: false,
+            content_encoding: None,
+        };
 
         // 3. 解析 headers
-        let mut content_length: Option<usize> = None;
-        let mut is_chunked = false;
-
         for line in &header_lines[1..] {
             if let Some((key, value)) = line.split_once(':') {
                 let key = key.trim();
                 let value = value.trim();
 
                 if key.eq_ignore_ascii_case("content-length") {
-                    content_length = value.parse().ok();
+                    head.content_length = value.parse().ok();
                 }
                 if key.eq_ignore_ascii_case("transfer-encoding") && value.contains("chunked") {
-                    is_chunked = true;
+                    head.is_chunked = true;
+                }
+                if key.eq_ignore_ascii_case("content-encoding") {
+                    head.content_encoding = Some(value.to_ascii_lowercase());
+                }
+                if key.eq_ignore_ascii_case("connection") {
+                    // 可能是逗号分隔的多个选项
+                    for option in value.split(',').map(str::trim) {
+                        if option.eq_ignore_ascii_case("close") {
+                            head.keep_alive = false;
+                        } else if option.eq_ignore_ascii_case("keep-alive") {
+                            head.keep_alive = true;
+                        }
+                    }
                 }
             }
         }
 
-        // 4. 读取 body
-        let body = if is_chunked {
-            Self::read_chunked_body_static(&mut reader).await?
-        } else if let Some(length) = content_length {
-            let mut body_bytes = vec![0u8; length];
-            reader
-                .read_exact(&mut body_bytes)
-                .await
-                .map_err(|e| format!("读取响应体失败：{}", e))?;
-            String::from_utf8(body_bytes).map_err(|e| format!("解码响应体失败：{}", e))?
+        Ok(head)
+    }
+
+    // 读取响应体，按读取顺序逐段交给 on_data（静态方法）
+    async fn read_body_static<R, F>(
+        reader: &mut BufReader<R>,
+        head: &ResponseHead,
+        mut on_data: F,
+    ) -> Result<(), IpcClientError>
+    where
+        R: AsyncReadExt + Unpin,
+        F: FnMut(&[u8]),
+    {
+        if head.is_chunked {
+            Self::read_chunked_body_static(reader, on_data).await
+        } else if let Some(length) = head.content_length {
+            Self::read_exact_static(reader, length, &mut on_data, "读取响应体失败").await
         } else {
-            String::new()
-        };
+            Ok(())
+        }
+    }
 
-        Ok(HttpResponse { status_code, body })
+    // 读取固定长度的数据，分段交给 on_data，避免为大响应体一次性分配缓冲区（静态方法）
+    async fn read_exact_static<R, F>(
+        reader: &mut BufReader<R>,
+        length: usize,
+        on_data: &mut F,
+        context: &str,
+    ) -> Result<(), IpcClientError>
+    where
+        R: AsyncReadExt + Unpin,
+        F: FnMut(&[u8]),
+    {
+        let mut buffer = vec![0u8; length.min(RESPONSE_CHUNK_SIZE)];
+        let mut remaining = length;
+        while remaining > 0 {
+            let size = remaining.min(buffer.len());
+            reader
+                .read_exact(&mut buffer[..size])
+                .await
+                .map_err(|e| IpcClientError::io(e, context))?;
+            on_data(&buffer[..size]);
+            remaining -= size;
+        }
+        Ok(())
     }
 
     // 解析 HTTP 状态码（静态方法）
-    fn parse_status_code_static(status_line: &str) -> Result<u16, String> {
+    fn parse_status_code_static(status_line: &str) -> Result<u16, IpcClientError> {
         let parts: Vec<&str> = status_line.split_whitespace().collect();
         if parts.len() < 2 {
-            return Err(format!("无效的状态行：{}", status_line));
+            return Err(IpcClientError::Protocol(format!(
+                "无效的状态行：{}",
+                status_line
+            )));
         }
 
         parts[1]
             .parse::<u16>()
-            .map_err(|_| format!("无效的状态码：{}", parts[1]))
+            .map_err(|_| IpcClientError::Protocol(format!("无效的状态码：{}", parts[1])))
+    }
+
+    // 解压 gzip/deflate 响应体（静态方法）
+    fn decode_body_static(encoding: &str, body: &[u8]) -> Result<Vec<u8>, IpcClientError> {
+        let mut decoded = Vec::new();
+        let result = match encoding {
+            "gzip" | "x-gzip" => GzDecoder::new(body).read_to_end(&mut decoded),
+            // HTTP 的 deflate 应为 zlib 格式，部分实现发送裸 deflate 流
+            "deflate" => ZlibDecoder::new(body)
+                .read_to_end(&mut decoded)
+                .or_else(|_| {
+                    decoded.clear();
+                    DeflateDecoder::new(body).read_to_end(&mut decoded)
+                }),
+            _ => {
+                return Err(IpcClientError::Protocol(format!(
+                    "不支持的响应编码：{}",
+                    encoding
+                )));
+            }
+        };
+        result.map_err(|e| {
+            IpcClientError::Protocol(format!("解压响应体失败（{}）：{}", encoding, e))
+        })?;
+        Ok(decoded)
     }
 
     // 读取 chunked 编码的响应体（静态方法）
-    async fn read_chunked_body_static<R>(reader: &mut BufReader<R>) -> Result<String, String>
+    async fn read_chunked_body_static<R, F>(
+        reader: &mut BufReader<R>,
+        mut on_data: F,
+    ) -> Result<(), IpcClientError>
     where
         R: AsyncReadExt + Unpin,
+        F: FnMut(&[u8]),
     {
-        let mut body = Vec::new();
-
         loop {
             let mut size_line = String::new();
-            reader
+            let size = reader
                 .read_line(&mut size_line)
                 .await
-                .map_err(|e| format!("读取 chunk 大小失败：{}", e))?;
+                .map_err(|e| IpcClientError::io(e, "读取 chunk 大小失败"))?;
+            if size == 0 {
+                return Err(IpcClientError::Io(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "读取 chunk 大小失败：连接意外关闭".to_string(),
+                ));
+            }
 
             let size_line = size_line.trim();
             if size_line.is_empty() {
@@ -210,7 +568,7 @@ impl IpcClient {
             }
 
             let chunk_size = usize::from_str_radix(size_line, 16)
-                .map_err(|e| format!("解析 chunk 大小失败：{}", e))?;
+                .map_err(|e| IpcClientError::Protocol(format!("解析 chunk 大小失败：{}", e)))?;
 
             if chunk_size == 0 {
                 let mut end = String::new();
@@ -218,17 +576,324 @@ impl IpcClient {
                 break;
             }
 
-            let mut chunk_data = vec![0u8; chunk_size];
-            reader
-                .read_exact(&mut chunk_data)
-                .await
-                .map_err(|e| format!("读取 chunk 数据失败：{}", e))?;
-            body.extend_from_slice(&chunk_data);
+            Self::read_exact_static(reader, chunk_size, &mut on_data, "读取 chunk 数据失败")
+                .await?;
 
             let mut crlf = String::new();
             reader.read_line(&mut crlf).await.ok();
         }
 
-        String::from_utf8(body).map_err(|e| format!("解码 chunked body 失败：{}", e))
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // {"proxies":{}} 的 gzip（mtime 为 0）与 zlib 压缩结果
+    const GZIP_FIXTURE: [u8; 34] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xab, 0x56, 0x2a, 0x28, 0xca,
+        0xaf, 0xc8, 0x4c, 0x2d, 0x56, 0xb2, 0xaa, 0xae, 0xad, 0x05, 0x00, 0xb0, 0x15, 0xd7, 0x1d,
+        0x0e, 0x00, 0x00, 0x00,
+    ];
+    const DEFLATE_FIXTURE: [u8; 22] = [
+        0x78, 0x9c, 0xab, 0x56, 0x2a, 0x28, 0xca, 0xaf, 0xc8, 0x4c, 0x2d, 0x56, 0xb2, 0xaa, 0xae,
+        0xad, 0x05, 0x00, 0x28, 0x6d, 0x05, 0x79,
+    ];
+    const FIXTURE_BODY: &str = r#"{"proxies":{}}"#;
+
+    async fn parse(raw: &str) -> Result<HttpResponse, String> {
+        parse_bytes(raw.as_bytes()).await
+    }
+
+    async fn parse_bytes(raw: &[u8]) -> Result<HttpResponse, String> {
+        let mut stream = raw;
+        IpcClient::read_http_response_static(&mut stream, true, None)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    fn with_body(head: &str, body: &[u8]) -> Vec<u8> {
+        let mut raw = head.as_bytes().to_vec();
+        raw.extend_from_slice(body);
+        raw
+    }
+
+    #[test]
+    fn test_request_accepts_gzip() {
+        let request =
+            IpcClient::build_http_request_static("GET", "/providers/proxies", &[], None, None);
+        assert!(request.contains("Accept-Encoding: gzip\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_gzip_content_length_body() -> Result<(), String> {
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            GZIP_FIXTURE.len()
+        );
+        let response = parse_bytes(&with_body(&head, &GZIP_FIXTURE)).await?;
+        assert_eq!(response.body, FIXTURE_BODY);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gzip_chunked_body() -> Result<(), String> {
+        // 压缩数据拆成两个 chunk
+        let (first, second) = GZIP_FIXTURE.split_at(10);
+        let mut raw =
+            b"HTTP/1.1 200 OK\r\nContent-Encoding: GZIP\r\nTransfer-Encoding: chunked\r\n\r\n"
+                .to_vec();
+        for chunk in [first, second] {
+            raw.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            raw.extend_from_slice(chunk);
+            raw.extend_from_slice(b"\r\n");
+        }
+        raw.extend_from_slice(b"0\r\n\r\n");
+
+        let response = parse_bytes(&raw).await?;
+        assert_eq!(response.body, FIXTURE_BODY);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deflate_body() -> Result<(), String> {
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: deflate\r\nContent-Length: {}\r\n\r\n",
+            DEFLATE_FIXTURE.len()
+        );
+        let response = parse_bytes(&with_body(&head, &DEFLATE_FIXTURE)).await?;
+        assert_eq!(response.body, FIXTURE_BODY);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_encoding_rejected() {
+        let result =
+            parse("HTTP/1.1 200 OK\r\nContent-Encoding: br\r\nContent-Length: 2\r\n\r\n{}").await;
+        assert!(result.is_err_and(|e| e.contains("br")));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_response_is_stale_connection() {
+        let mut stream: &[u8] = b"";
+        let result = IpcClient::read_http_response_static(&mut stream, true, None).await;
+        assert!(result.is_err_and(|e| e.is_stale_connection()));
+    }
+
+    #[tokio::test]
+    async fn test_http11_keeps_alive_by_default() -> Result<(), String> {
+        let response = parse("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}").await?;
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.body, "{}");
+        assert!(response.keep_alive);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_close_header() -> Result<(), String> {
+        let response =
+            parse("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n").await?;
+        assert!(!response.keep_alive);
+
+        // 头名与取值均不区分大小写
+        let response = parse("HTTP/1.1 204 No Content\r\nCONNECTION: Close\r\n\r\n").await?;
+        assert!(!response.keep_alive);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http10_closes_unless_keep_alive() -> Result<(), String> {
+        let response = parse("HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nok").await?;
+        assert_eq!(response.body, "ok");
+        assert!(!response.keep_alive);
+
+        let response =
+            parse("HTTP/1.0 200 OK\r\nconnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n").await?;
+        assert!(response.keep_alive);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_leftover_data_disables_reuse() -> Result<(), String> {
+        let response = parse("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").await?;
+        assert!(response.keep_alive);
+
+        // 响应之后混入了多余数据（如核心流水线发送），连接不能再复用
+        let response = parse(
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nokHTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        )
+        .await?;
+        assert_eq!(response.body, "ok");
+        assert!(!response.keep_alive);
+        Ok(())
+    }
+
+    #[test]
+    fn test_request_folds_custom_headers() {
+        let headers = vec![
+            ("Content-Type".to_string(), "text/plain".to_string()),
+            ("X-Trace".to_string(), "1".to_string()),
+            ("host".to_string(), "evil".to_string()),
+            ("Content-Length".to_string(), "999".to_string()),
+        ];
+        let request =
+            IpcClient::build_http_request_static("POST", "/test", &headers, None, Some("hi"));
+        assert_eq!(
+            request,
+            "POST /test HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\
+             Content-Type: text/plain\r\nX-Trace: 1\r\nContent-Length: 2\r\n\r\nhi"
+        );
+    }
+
+    #[test]
+    fn test_request_carries_controller_secret() {
+        let request =
+            IpcClient::build_http_request_static("GET", "/version", &[], Some("s3cret"), None);
+        assert!(request.contains("Authorization: Bearer s3cret\r\n"));
+
+        // 自定义的 Authorization 优先
+        let headers = vec![("authorization".to_string(), "Basic abc".to_string())];
+        let request =
+            IpcClient::build_http_request_static("GET", "/version", &headers, Some("s3cret"), None);
+        assert!(!request.contains("Bearer"));
+        assert!(request.contains("authorization: Basic abc\r\n"));
+
+        let request = IpcClient::build_http_request_static("GET", "/version", &[], None, None);
+        assert!(!request.contains("Authorization"));
+    }
+
+    #[test]
+    fn test_redact_authorization() {
+        let request =
+            IpcClient::build_http_request_static("GET", "/version", &[], Some("s3cret"), None);
+        let redacted = redact_authorization(&request);
+        assert!(!redacted.contains("s3cret"));
+        assert!(redacted.contains("Authorization: ***\r\n"));
+        assert!(redacted.starts_with("GET /version HTTP/1.1\r\nHost: localhost\r\n"));
+
+        let headers = vec![("authorization".to_string(), "Basic abc".to_string())];
+        let request =
+            IpcClient::build_http_request_static("POST", "/x", &headers, None, Some("{}"));
+        let redacted = redact_authorization(&request);
+        assert!(!redacted.contains("abc"));
+        assert!(redacted.ends_with("\r\n\r\n{}"));
+    }
+
+    #[test]
+    fn test_normalize_controller_secret() {
+        assert_eq!(
+            normalize_controller_secret("  s3cret \n"),
+            Ok(Some("s3cret".to_string()))
+        );
+        assert_eq!(normalize_controller_secret("   "), Ok(None));
+        assert!(normalize_controller_secret("a\r\nX-Injected: 1").is_err());
+        assert!(normalize_controller_secret("a\u{0}b").is_err());
+    }
+
+    #[test]
+    fn test_validate_request() {
+        assert!(IpcClient::validate_request("PATCH", "/version", &[]).is_ok());
+        assert!(IpcClient::validate_request("CONNECT", "/version", &[]).is_err());
+        assert!(IpcClient::validate_request("get", "/version", &[]).is_err());
+
+        let injected = vec![("X-Test".to_string(), "a\r\nX-Evil: 1".to_string())];
+        assert!(IpcClient::validate_request("GET", "/version", &injected).is_err());
+        let bad_name = vec![("X Test:".to_string(), "a".to_string())];
+        assert!(IpcClient::validate_request("GET", "/version", &bad_name).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_head_response_has_no_body() -> Result<(), String> {
+        let mut stream: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 42\r\n\r\n";
+        let response = IpcClient::read_http_response_static(&mut stream, false, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(response.status_code, 200);
+        assert!(response.body.is_empty());
+        assert!(response.keep_alive);
+        Ok(())
+    }
+
+    // 收集分块的测试接收方
+    struct CollectSink {
+        threshold: usize,
+        chunks: Vec<(Vec<u8>, bool)>,
+    }
+
+    impl ChunkSink for CollectSink {
+        fn threshold(&self) -> usize {
+            self.threshold
+        }
+
+        fn on_chunk(&mut self, _status_code: u16, bytes: Vec<u8>, is_last: bool) {
+            self.chunks.push((bytes, is_last));
+        }
+    }
+
+    async fn parse_with_sink(
+        raw: &[u8],
+        sink: &mut CollectSink,
+    ) -> Result<HttpResponse, IpcClientError> {
+        let mut stream = raw;
+        IpcClient::read_http_response_static(&mut stream, true, Some(sink)).await
+    }
+
+    #[tokio::test]
+    async fn test_large_body_is_streamed_in_chunks() -> Result<(), String> {
+        let body = vec![b'x'; RESPONSE_CHUNK_SIZE * 2 + 10];
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+        let mut sink = CollectSink {
+            threshold: 1024,
+            chunks: Vec::new(),
+        };
+        let response = parse_with_sink(&with_body(&head, &body), &mut sink)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        assert!(response.streamed);
+        assert!(response.body.is_empty());
+        let sizes: Vec<usize> = sink.chunks.iter().map(|(bytes, _)| bytes.len()).collect();
+        assert_eq!(sizes, [RESPONSE_CHUNK_SIZE, RESPONSE_CHUNK_SIZE, 10]);
+        let last_flags: Vec<bool> = sink.chunks.iter().map(|(_, is_last)| *is_last).collect();
+        assert_eq!(last_flags, [false, false, true]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_small_body_is_not_streamed() -> Result<(), String> {
+        let mut sink = CollectSink {
+            threshold: 1024,
+            chunks: Vec::new(),
+        };
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n";
+        let response = parse_with_sink(raw, &mut sink)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        assert!(!response.streamed);
+        assert_eq!(response.body, "ok");
+        assert!(sink.chunks.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncated_streamed_body_fails() {
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 4096\r\n\r\n";
+        let mut sink = CollectSink {
+            threshold: 16,
+            chunks: Vec::new(),
+        };
+        let result = parse_with_sink(&with_body(head, &[b'x'; 100]), &mut sink).await;
+        assert!(result.is_err());
+        assert!(sink.chunks.iter().all(|(_, is_last)| !is_last));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_chunked_body_fails() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n";
+        let result = parse_bytes(raw).await;
+        assert!(result.is_err());
     }
 }
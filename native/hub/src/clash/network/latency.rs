@@ -0,0 +1,257 @@
+// IPC 请求耗时统计
+//
+// 目的：按路径记录请求耗时（次数、p50/p95、最大值）并记录慢请求，
+// 用于判断卡顿来自核心、管道还是 Dart 侧。更新时只写入固定大小的采样窗口，不分配内存
+
+use super::signals::IpcLatencyEntry;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// 最多单独统计的路径数，超出后归入 "*"
+const MAX_TRACKED_PATHS: usize = 64;
+// 每个路径保留的最近样本数（用于计算分位数）
+const SAMPLE_WINDOW: usize = 128;
+// 溢出路径的统计键
+const OVERFLOW_KEY: &str = "*";
+// 名称类路径段（如代理名）归并为同一个键，避免占满统计表
+const NAMED_RESOURCES: [&str; 5] = [
+    "proxies",
+    "group",
+    "providers/proxies",
+    "providers/rules",
+    "connections",
+];
+const NAME_PLACEHOLDER: &str = ":name";
+
+pub const DEFAULT_SLOW_REQUEST_THRESHOLD_MS: u64 = 1000;
+
+// 慢请求阈值（毫秒），0 表示不记录慢请求
+static SLOW_REQUEST_THRESHOLD_MS: AtomicU64 = AtomicU64::new(DEFAULT_SLOW_REQUEST_THRESHOLD_MS);
+
+static LATENCY_TABLE: Lazy<Mutex<LatencyTable>> = Lazy::new(|| Mutex::new(LatencyTable::new()));
+
+struct PathStats {
+    key: String,
+    count: u64,
+    errors: u64,
+    max_us: u64,
+    // 环形缓冲区，next 为下一个写入位置
+    samples: [u32; SAMPLE_WINDOW],
+    next: usize,
+}
+
+impl PathStats {
+    fn new(key: String) -> Self {
+        Self {
+            key,
+            count: 0,
+            errors: 0,
+            max_us: 0,
+            samples: [0; SAMPLE_WINDOW],
+            next: 0,
+        }
+    }
+
+    fn record(&mut self, elapsed_us: u64, success: bool) {
+        if !success {
+            self.errors += 1;
+            return;
+        }
+        self.count += 1;
+        self.max_us = self.max_us.max(elapsed_us);
+        self.samples[self.next] = u32::try_from(elapsed_us).unwrap_or(u32::MAX);
+        self.next = (self.next + 1) % SAMPLE_WINDOW;
+    }
+
+    fn to_entry(&self) -> IpcLatencyEntry {
+        let filled = usize::try_from(self.count)
+            .unwrap_or(usize::MAX)
+            .min(SAMPLE_WINDOW);
+        let mut samples = self.samples[..filled].to_vec();
+        samples.sort_unstable();
+        IpcLatencyEntry {
+            path: self.key.clone(),
+            count: self.count,
+            errors: self.errors,
+            p50_us: percentile(&samples, 50),
+            p95_us: percentile(&samples, 95),
+            max_us: self.max_us,
+        }
+    }
+}
+
+struct LatencyTable {
+    paths: Vec<PathStats>,
+    overflow: PathStats,
+}
+
+impl LatencyTable {
+    fn new() -> Self {
+        Self {
+            paths: Vec::with_capacity(MAX_TRACKED_PATHS),
+            overflow: PathStats::new(OVERFLOW_KEY.to_string()),
+        }
+    }
+
+    fn stats_for(&mut self, path: &str) -> &mut PathStats {
+        let index = self
+            .paths
+            .iter()
+            .position(|stats| key_matches(&stats.key, path));
+        match index {
+            Some(index) => &mut self.paths[index],
+            None if self.paths.len() < MAX_TRACKED_PATHS => {
+                // 仅在首次出现的路径上分配键
+                self.paths.push(PathStats::new(normalize_path(path)));
+                let last = self.paths.len() - 1;
+                &mut self.paths[last]
+            }
+            None => &mut self.overflow,
+        }
+    }
+}
+
+// 去掉查询参数，并将名称类路径段替换为占位符（不分配内存）
+fn normalized_segments(path: &str) -> impl Iterator<Item = &str> {
+    let path = path.split('?').next().unwrap_or_default();
+    let name_index = NAMED_RESOURCES.iter().find_map(|resource| {
+        let rest = path
+            .strip_prefix('/')?
+            .strip_prefix(resource)?
+            .strip_prefix('/')?;
+        let name = rest.split('/').next().unwrap_or_default();
+        (!name.is_empty()).then(|| resource.split('/').count() + 1)
+    });
+    path.split('/').enumerate().map(move |(index, segment)| {
+        if Some(index) == name_index {
+            NAME_PLACEHOLDER
+        } else {
+            segment
+        }
+    })
+}
+
+fn normalize_path(path: &str) -> String {
+    normalized_segments(path).collect::<Vec<_>>().join("/")
+}
+
+fn key_matches(key: &str, path: &str) -> bool {
+    key.split('/').eq(normalized_segments(path))
+}
+
+// 已排序样本的分位数（最近秩法）
+fn percentile(sorted: &[u32], percent: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    u64::from(sorted[rank - 1])
+}
+
+// 记录一次请求的耗时，超过阈值时记录慢请求日志
+pub fn record(method: &str, path: &str, elapsed: Duration, success: bool) {
+    let elapsed_us = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+
+    let threshold_ms = SLOW_REQUEST_THRESHOLD_MS.load(Ordering::Relaxed);
+    if threshold_ms > 0 && elapsed >= Duration::from_millis(threshold_ms) {
+        log::warn!(
+            "IPC 慢请求：{} {} 耗时 {} 毫秒（阈值 {} 毫秒）",
+            method,
+            path,
+            elapsed.as_millis(),
+            threshold_ms
+        );
+    }
+
+    LATENCY_TABLE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .stats_for(path)
+        .record(elapsed_us, success);
+}
+
+// 当前统计（按请求次数降序）
+pub fn snapshot() -> Vec<IpcLatencyEntry> {
+    let table = LATENCY_TABLE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries: Vec<IpcLatencyEntry> = table
+        .paths
+        .iter()
+        .chain(std::iter::once(&table.overflow))
+        .filter(|stats| stats.count > 0 || stats.errors > 0)
+        .map(PathStats::to_entry)
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.count));
+    entries
+}
+
+pub fn reset() {
+    *LATENCY_TABLE.lock().unwrap_or_else(|e| e.into_inner()) = LatencyTable::new();
+}
+
+pub fn set_slow_request_threshold(threshold_ms: u64) {
+    SLOW_REQUEST_THRESHOLD_MS.store(threshold_ms, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("/configs?force=true"), "/configs");
+        assert_eq!(
+            normalize_path("/proxies/%E9%A6%99%E6%B8%AF/delay?timeout=5000"),
+            "/proxies/:name/delay"
+        );
+        assert_eq!(normalize_path("/proxies"), "/proxies");
+        assert_eq!(
+            normalize_path("/providers/proxies/sub/healthcheck"),
+            "/providers/proxies/:name/healthcheck"
+        );
+        assert!(key_matches("/proxies/:name", "/proxies/DIRECT"));
+        assert!(!key_matches("/proxies/:name", "/proxies"));
+    }
+
+    #[test]
+    fn test_path_stats_percentiles() {
+        let mut stats = PathStats::new("/version".to_string());
+        for elapsed_us in 1..=100 {
+            stats.record(elapsed_us, true);
+        }
+        stats.record(5_000_000, false);
+
+        let entry = stats.to_entry();
+        assert_eq!(entry.count, 100);
+        assert_eq!(entry.errors, 1);
+        assert_eq!(entry.p50_us, 50);
+        assert_eq!(entry.p95_us, 95);
+        assert_eq!(entry.max_us, 100);
+    }
+
+    #[test]
+    fn test_sample_window_keeps_recent_samples() {
+        let mut stats = PathStats::new("/version".to_string());
+        for _ in 0..SAMPLE_WINDOW {
+            stats.record(1000, true);
+        }
+        for _ in 0..SAMPLE_WINDOW {
+            stats.record(10, true);
+        }
+
+        let entry = stats.to_entry();
+        assert_eq!(entry.p95_us, 10);
+        assert_eq!(entry.max_us, 1000);
+    }
+
+    #[test]
+    fn test_overflow_bucket() {
+        let mut table = LatencyTable::new();
+        for index in 0..=MAX_TRACKED_PATHS {
+            table.stats_for(&format!("/path{}", index)).record(1, true);
+        }
+        assert_eq!(table.paths.len(), MAX_TRACKED_PATHS);
+        assert_eq!(table.overflow.count, 1);
+    }
+}
@@ -0,0 +1,152 @@
+// IPC 请求路径编码
+//
+// 目的：订阅中的代理名常含空格、#、| 与中文，原样写入请求行会产生畸形请求；
+// 写入前对路径段与查询参数做百分号编码（保留已编码的部分），并拒绝控制字符
+
+use super::error::IpcClientError;
+use std::fmt::Write;
+
+// RFC 3986 中无需编码的字符
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~".contains(&byte)
+}
+
+// 路径中可保留的字符（含分隔符 /）
+fn is_path_char(byte: u8) -> bool {
+    is_unreserved(byte) || b"!$&'()*+,;=:@/".contains(&byte)
+}
+
+// 查询串中可保留的字符（含参数分隔符 & 与 =）
+fn is_query_char(byte: u8) -> bool {
+    is_path_char(byte) || byte == b'?'
+}
+
+// 按 keep 编码，preserve_escapes 为 true 时保留已有的 %XX
+fn encode_into(out: &mut String, input: &str, keep: fn(u8) -> bool, preserve_escapes: bool) {
+    let bytes = input.as_bytes();
+    for (index, &byte) in bytes.iter().enumerate() {
+        let escaped = preserve_escapes
+            && byte == b'%'
+            && bytes.get(index + 1).is_some_and(u8::is_ascii_hexdigit)
+            && bytes.get(index + 2).is_some_and(u8::is_ascii_hexdigit);
+        if escaped || keep(byte) {
+            out.push(char::from(byte));
+        } else {
+            let _ = write!(out, "%{:02X}", byte);
+        }
+    }
+}
+
+// 编码 Dart 传入的请求路径（可含查询串），返回可直接写入请求行的形式
+pub fn encode_request_path(path: &str) -> Result<String, IpcClientError> {
+    if let Some(c) = path.chars().find(|c| c.is_control()) {
+        return Err(IpcClientError::InvalidRequest(format!(
+            "请求路径包含控制字符（U+{:04X}）：{}",
+            u32::from(c),
+            path.escape_debug()
+        )));
+    }
+    if !path.starts_with('/') {
+        return Err(IpcClientError::InvalidRequest(format!(
+            "请求路径必须以 / 开头：{}",
+            path
+        )));
+    }
+
+    let mut encoded = String::with_capacity(path.len());
+    match path.split_once('?') {
+        Some((path, query)) => {
+            encode_into(&mut encoded, path, is_path_char, true);
+            encoded.push('?');
+            encode_into(&mut encoded, query, is_query_char, true);
+        }
+        None => encode_into(&mut encoded, path, is_path_char, true),
+    }
+    Ok(encoded)
+}
+
+// 编码单个路径段或查询参数值（如代理名），其中的 % 与 / 也会被编码
+pub fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    encode_into(&mut encoded, value, is_unreserved, false);
+    encoded
+}
+
+// 代理延迟测试路径：/proxies/{name}/delay?url=…&timeout=…
+pub fn proxy_delay_path(name: &str, url: &str, timeout_ms: u64) -> String {
+    format!(
+        "/proxies/{}/delay?url={}&timeout={}",
+        encode_component(name),
+        encode_component(url),
+        timeout_ms
+    )
+}
+
+// 代理（组）路径：GET 获取详情，PUT 选择代理组的节点
+pub fn proxy_select_path(group: &str) -> String {
+    format!("/proxies/{}", encode_component(group))
+}
+
+// 规则集路径：PUT /providers/rules/{name} 触发更新
+pub fn rule_provider_path(name: &str) -> String {
+    format!("/providers/rules/{}", encode_component(name))
+}
+
+// DNS 查询路径：/dns/query?name=…&type=…
+pub fn dns_query_path(name: &str, qtype: &str) -> String {
+    format!(
+        "/dns/query?name={}&type={}",
+        encode_component(name),
+        encode_component(qtype)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_proxy_names() {
+        assert_eq!(
+            proxy_delay_path("香港 01 | x2", "http://www.gstatic.com/generate_204", 5000),
+            "/proxies/%E9%A6%99%E6%B8%AF%2001%20%7C%20x2/delay\
+             ?url=http%3A%2F%2Fwww.gstatic.com%2Fgenerate_204&timeout=5000"
+        );
+        assert_eq!(proxy_select_path("A/B #1"), "/proxies/A%2FB%20%231");
+        assert_eq!(encode_component("100%"), "100%25");
+    }
+
+    #[test]
+    fn test_encode_request_path() -> Result<(), String> {
+        let encode = |path| encode_request_path(path).map_err(|e| e.to_string());
+
+        assert_eq!(encode("/configs?force=true")?, "/configs?force=true");
+        assert_eq!(
+            encode("/proxies/香港 01/delay?url=http://a/b&timeout=5000")?,
+            "/proxies/%E9%A6%99%E6%B8%AF%2001/delay?url=http://a/b&timeout=5000"
+        );
+        // 已编码的部分保持不变，孤立的 % 会被编码
+        assert_eq!(
+            encode("/proxies/%E9%A6%99%E6%B8%AF%2001")?,
+            "/proxies/%E9%A6%99%E6%B8%AF%2001"
+        );
+        assert_eq!(encode("/proxies/50%off")?, "/proxies/50%25off");
+        assert_eq!(encode("/proxies/a#b")?, "/proxies/a%23b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_control_characters() {
+        for path in [
+            "/version\r\nX-Evil: 1",
+            "/proxies/a\nb",
+            "/proxies/\u{0}",
+            "version",
+        ] {
+            assert!(matches!(
+                encode_request_path(path),
+                Err(IpcClientError::InvalidRequest(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// 代理列表解析
+//
+// 目的：/proxies 响应可达数百 KB，Dart 每次轮询都要重新解析。在 Rust 侧解析为
+// 结构化数据，只保留界面使用的字段，并解析代理组最终选中的节点
+
+use super::signals::{ProxyDelay, ProxyGroupInfo, ProxyNodeInfo};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+// 包含所有代理组的内置组
+const GLOBAL_GROUP: &str = "GLOBAL";
+
+#[derive(Deserialize)]
+struct RawProxies {
+    proxies: HashMap<String, serde_json::Value>,
+}
+
+// 核心返回的单个代理（未使用的字段忽略，type 保留为字符串以兼容新协议）
+#[derive(Deserialize)]
+struct RawProxy {
+    #[serde(rename = "type", default)]
+    proxy_type: String,
+    #[serde(default)]
+    now: String,
+    // 只有代理组带有 all
+    all: Option<Vec<String>>,
+    #[serde(default)]
+    history: Vec<RawDelay>,
+    #[serde(default)]
+    udp: bool,
+    alive: Option<bool>,
+    #[serde(default)]
+    hidden: bool,
+    #[serde(default)]
+    icon: String,
+}
+
+#[derive(Deserialize)]
+struct RawDelay {
+    #[serde(default)]
+    time: String,
+    #[serde(default)]
+    delay: u32,
+}
+
+// 解析结果
+pub struct ParsedProxies {
+    pub groups: Vec<ProxyGroupInfo>,
+    pub nodes: Vec<ProxyNodeInfo>,
+}
+
+// 解析 GET /proxies 的响应体，无法解析的单个代理会被跳过
+pub fn parse_proxies(body: &str) -> Result<ParsedProxies, String> {
+    let raw: RawProxies =
+        serde_json::from_str(body).map_err(|e| format!("解析代理列表失败：{}", e))?;
+
+    let proxies: HashMap<String, RawProxy> = raw
+        .proxies
+        .into_iter()
+        .filter_map(|(name, value)| match serde_json::from_value(value) {
+            Ok(proxy) => Some((name, proxy)),
+            Err(e) => {
+                log::debug!("跳过无法解析的代理：{}，error：{}", name, e);
+                None
+            }
+        })
+        .collect();
+
+    // 代理组名 → 当前选择
+    let selections: HashMap<&str, &str> = proxies
+        .iter()
+        .filter(|(_, proxy)| proxy.all.is_some())
+        .map(|(name, proxy)| (name.as_str(), proxy.now.as_str()))
+        .collect();
+
+    let mut groups = Vec::new();
+    let mut nodes = Vec::new();
+    for name in display_order(&proxies) {
+        let Some(proxy) = proxies.get(name) else {
+            continue;
+        };
+        match &proxy.all {
+            Some(all) => groups.push(ProxyGroupInfo {
+                name: name.to_string(),
+                proxy_type: proxy.proxy_type.clone(),
+                now: proxy.now.clone(),
+                all: all.clone(),
+                resolved: resolve_selected(&selections, name),
+                hidden: proxy.hidden,
+                icon: proxy.icon.clone(),
+            }),
+            None => nodes.push(ProxyNodeInfo {
+                name: name.to_string(),
+                proxy_type: proxy.proxy_type.clone(),
+                udp: proxy.udp,
+                alive: proxy.alive.unwrap_or(true),
+                delay: proxy.history.last().map_or(0, |entry| entry.delay),
+                history: proxy
+                    .history
+                    .iter()
+                    .map(|entry| ProxyDelay {
+                        time: entry.time.clone(),
+                        delay: entry.delay,
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    Ok(ParsedProxies { groups, nodes })
+}
+
+// 排列顺序：GLOBAL 组中列出的代理在前（配置文件顺序），其余按名称排序，GLOBAL 在最后
+fn display_order(proxies: &HashMap<String, RawProxy>) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut order: Vec<&str> = proxies
+        .get(GLOBAL_GROUP)
+        .and_then(|global| global.all.as_ref())
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .filter(|name| *name != GLOBAL_GROUP && proxies.contains_key(*name))
+        .filter(|name| seen.insert(*name))
+        .collect();
+
+    let mut rest: Vec<&str> = proxies
+        .keys()
+        .map(String::as_str)
+        .filter(|name| *name != GLOBAL_GROUP && !seen.contains(name))
+        .collect();
+    rest.sort_unstable();
+    order.extend(rest);
+
+    if proxies.contains_key(GLOBAL_GROUP) {
+        order.push(GLOBAL_GROUP);
+    }
+    order
+}
+
+// 沿 now 穿过嵌套代理组，返回最终选中的节点
+fn resolve_selected(selections: &HashMap<&str, &str>, group: &str) -> Option<String> {
+    let mut current = group;
+    // 每个代理组最多经过一次，超过说明存在循环引用
+    for _ in 0..=selections.len() {
+        match selections.get(current) {
+            Some(&"") => return None,
+            Some(now) => current = now,
+            None => return Some(current.to_string()),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{"proxies":{
+        "GLOBAL":{"type":"Selector","now":"Proxy","all":["DIRECT","Proxy","Auto","HK 01","Future"]},
+        "Proxy":{"type":"Selector","now":"Auto","all":["Auto","HK 01"],"hidden":false,"icon":"p.png"},
+        "Auto":{"type":"URLTest","now":"HK 01","all":["HK 01","Future"],"history":[]},
+        "Loop A":{"type":"Selector","now":"Loop B","all":["Loop B"]},
+        "Loop B":{"type":"Selector","now":"Loop A","all":["Loop A"]},
+        "HK 01":{"type":"Shadowsocks","udp":true,"alive":true,
+            "history":[{"time":"t1","delay":120},{"time":"t2","delay":80}]},
+        "Future":{"type":"FancyNewProtocol","udp":false,"extra":{"x":1},"history":[]},
+        "DIRECT":{"type":"Direct","udp":true,"history":[]},
+        "Broken":42
+    }}"#;
+
+    #[test]
+    fn test_parse_proxies() -> Result<(), String> {
+        let parsed = parse_proxies(FIXTURE)?;
+
+        let group_names: Vec<&str> = parsed.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(group_names, ["Proxy", "Auto", "Loop A", "Loop B", "GLOBAL"]);
+        let node_names: Vec<&str> = parsed.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(node_names, ["DIRECT", "HK 01", "Future"]);
+
+        let future = parsed
+            .nodes
+            .iter()
+            .find(|node| node.name == "Future")
+            .ok_or("缺少未知类型的节点")?;
+        assert_eq!(future.proxy_type, "FancyNewProtocol");
+        assert_eq!(future.delay, 0);
+
+        let hk = parsed
+            .nodes
+            .iter()
+            .find(|node| node.name == "HK 01")
+            .ok_or("缺少节点")?;
+        assert_eq!(hk.delay, 80);
+        assert_eq!(hk.history.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_nested_selection() -> Result<(), String> {
+        let parsed = parse_proxies(FIXTURE)?;
+        let resolved = |name: &str| {
+            parsed
+                .groups
+                .iter()
+                .find(|group| group.name == name)
+                .and_then(|group| group.resolved.clone())
+        };
+
+        assert_eq!(resolved("GLOBAL").as_deref(), Some("HK 01"));
+        assert_eq!(resolved("Proxy").as_deref(), Some("HK 01"));
+        assert_eq!(resolved("Auto").as_deref(), Some("HK 01"));
+        // 循环引用无法解析
+        assert_eq!(resolved("Loop A"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+// 规则与规则集解析
+//
+// 目的：在 Rust 侧解析 /rules 与 /providers/rules，统一不同 mihomo 版本的字段差异
+// （如命中次数在新版本中位于 extra 内），Dart 只接收结构化列表
+
+use super::signals::{RuleInfo, RuleProviderInfo};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+struct RawRules {
+    #[serde(default)]
+    rules: Vec<RawRule>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawRule {
+    #[serde(rename = "type", default)]
+    rule_type: String,
+    #[serde(default)]
+    payload: String,
+    #[serde(default)]
+    proxy: String,
+    index: Option<u32>,
+    // 旧版本直接提供命中次数
+    hit_count: Option<u64>,
+    #[serde(default)]
+    extra: RawRuleExtra,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RawRuleExtra {
+    disabled: bool,
+    hit_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RawRuleProviders {
+    #[serde(default)]
+    providers: HashMap<String, RawRuleProvider>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct RawRuleProvider {
+    behavior: String,
+    format: String,
+    vehicle_type: String,
+    rule_count: u64,
+    updated_at: String,
+}
+
+// 解析 GET /rules 的响应体（未提供 index 时按列表顺序编号）
+pub fn parse_rules(body: &str) -> Result<Vec<RuleInfo>, String> {
+    let raw: RawRules = serde_json::from_str(body).map_err(|e| format!("解析规则失败：{}", e))?;
+    Ok(raw
+        .rules
+        .into_iter()
+        .enumerate()
+        .map(|(position, rule)| RuleInfo {
+            index: rule.index.unwrap_or(position as u32),
+            rule_type: rule.rule_type,
+            payload: rule.payload,
+            proxy: rule.proxy,
+            hit_count: rule.extra.hit_count.or(rule.hit_count),
+            disabled: rule.extra.disabled,
+        })
+        .collect())
+}
+
+// 解析 GET /providers/rules 的响应体（按名称排序）
+pub fn parse_rule_providers(body: &str) -> Result<Vec<RuleProviderInfo>, String> {
+    let raw: RawRuleProviders =
+        serde_json::from_str(body).map_err(|e| format!("解析规则集失败：{}", e))?;
+    let mut providers: Vec<RuleProviderInfo> = raw
+        .providers
+        .into_iter()
+        .map(|(name, provider)| RuleProviderInfo {
+            name,
+            behavior: provider.behavior,
+            format: provider.format,
+            vehicle_type: provider.vehicle_type,
+            rule_count: provider.rule_count,
+            updated_at: provider.updated_at,
+        })
+        .collect();
+    providers.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    Ok(providers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules_across_versions() -> Result<(), String> {
+        let rules = parse_rules(
+            r#"{"rules":[
+                {"type":"DomainSuffix","payload":"google.com","proxy":"Proxy","size":-1},
+                {"type":"RuleSet","payload":"cn","proxy":"DIRECT","index":7,
+                    "extra":{"disabled":true,"hitCount":42,"hitAt":"2025-01-01T00:00:00Z"}},
+                {"type":"Match","payload":"","proxy":"Final","hitCount":3}
+            ]}"#,
+        )?;
+
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[0].index, 0);
+        assert_eq!(rules[0].hit_count, None);
+        assert_eq!(rules[1].index, 7);
+        assert_eq!(rules[1].hit_count, Some(42));
+        assert!(rules[1].disabled);
+        assert_eq!(rules[2].hit_count, Some(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rule_providers() -> Result<(), String> {
+        let providers = parse_rule_providers(
+            r#"{"providers":{
+                "reject":{"behavior":"Domain","format":"YamlRule","name":"reject","ruleCount":100,
+                    "type":"Rule","updatedAt":"2025-01-01T00:00:00Z","vehicleType":"HTTP"},
+                "cn":{"behavior":"IPCIDR","ruleCount":5,"vehicleType":"File"}
+            }}"#,
+        )?;
+
+        let names: Vec<&str> = providers.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["cn", "reject"]);
+        assert_eq!(providers[1].rule_count, 100);
+        assert_eq!(providers[0].updated_at, "");
+        Ok(())
+    }
+}
@@ -2,16 +2,33 @@
 //
 // 定义 Dart 与 Rust 之间的 IPC 通信消息
 
-use rinf::{DartSignal, RustSignal};
+use rinf::{DartSignal, RustSignal, SignalPiece};
 use serde::{Deserialize, Serialize};
 
 // REST API 调用
 
+// IPC 请求优先级（未设置时不做限制，与以往行为一致）
+//
+// 交互请求（如切换节点）始终可使用连接池中预留的连接；
+// 批量请求（如 /connections、/providers 的轮询）并发受限，不会占满连接
+#[derive(Deserialize, SignalPiece, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IpcPriority {
+    Interactive = 0, // 用户操作触发，需要尽快完成
+    Bulk = 1,        // 后台刷新或大批量请求
+}
+
 // Dart → Rust：通过 IPC 发送 GET 请求
+//
+// stream_response 为 true 时，超过 256 KB 的响应体以 IpcResponseChunk 分块返回；
+// 路径（含查询参数）与优先级相同的并发请求会合并为一次，响应分发给每个请求（/delay 测速除外）
 #[derive(Deserialize, DartSignal)]
 pub struct IpcGetRequest {
     pub request_id: i64,
     pub path: String,
+    #[serde(default)]
+    pub stream_response: bool,
+    #[serde(default)]
+    pub priority: Option<IpcPriority>,
 }
 
 // Dart → Rust：通过 IPC 发送 POST 请求
@@ -20,6 +37,8 @@ pub struct IpcPostRequest {
     pub request_id: i64,
     pub path: String,
     pub body: Option<String>,
+    #[serde(default)]
+    pub priority: Option<IpcPriority>,
 }
 
 // Dart → Rust：通过 IPC 发送 PUT 请求
@@ -28,6 +47,8 @@ pub struct IpcPutRequest {
     pub request_id: i64,
     pub path: String,
     pub body: Option<String>,
+    #[serde(default)]
+    pub priority: Option<IpcPriority>,
 }
 
 // Dart → Rust：通过 IPC 发送 PATCH 请求
@@ -36,6 +57,8 @@ pub struct IpcPatchRequest {
     pub request_id: i64,
     pub path: String,
     pub body: Option<String>,
+    #[serde(default)]
+    pub priority: Option<IpcPriority>,
 }
 
 // Dart → Rust：通过 IPC 发送 DELETE 请求
@@ -43,10 +66,133 @@ pub struct IpcPatchRequest {
 pub struct IpcDeleteRequest {
     pub request_id: i64,
     pub path: String,
+    #[serde(default)]
+    pub priority: Option<IpcPriority>,
 }
 
-// Rust → Dart：IPC 请求响应
+// Dart → Rust：通过 IPC 发送任意方法的请求
+//
+// method 须为 GET/HEAD/OPTIONS/POST/PUT/PATCH/DELETE 之一；
+// headers 会追加到默认请求头之后（Host 与 Content-Length 由 Rust 侧生成）
+#[derive(Deserialize, DartSignal)]
+pub struct IpcRequest {
+    pub request_id: i64,
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: Option<String>,
+    // 仅对 GET 生效，含义同 IpcGetRequest
+    #[serde(default)]
+    pub stream_response: bool,
+    #[serde(default)]
+    pub priority: Option<IpcPriority>,
+}
+
+// 批量请求中的单个请求
+#[derive(Deserialize, SignalPiece)]
+pub struct IpcBatchItem {
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+// Dart → Rust：批量发送 IPC 请求（用于启动时的连续请求）
+//
+// 各请求并发执行（最多 4 个同时进行），单个请求失败不影响其他请求；
+// 可用 request_id 通过 IpcCancelRequest 取消整个批次
+#[derive(Deserialize, DartSignal)]
+pub struct IpcBatchRequest {
+    pub request_id: i64,
+    pub items: Vec<IpcBatchItem>,
+}
+
+// 批量请求中单个请求的结果（字段含义同 IpcResponse）
+#[derive(Serialize, SignalPiece, Clone, Debug)]
+pub struct IpcBatchItemResult {
+    pub status_code: u16,
+    pub body: String,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub error_code: Option<IpcErrorCode>,
+}
+
+// Rust → Dart：批量请求的结果（与请求的 items 顺序一致）
+#[derive(Serialize, RustSignal)]
+pub struct IpcBatchResponse {
+    pub request_id: i64,
+    pub items: Vec<IpcBatchItemResult>,
+}
+
+// Dart → Rust：取消进行中的 IPC 请求（未知或已完成的请求 ID 会被忽略）
+#[derive(Deserialize, DartSignal)]
+pub struct IpcCancelRequest {
+    pub request_id: i64,
+}
+
+// Dart → Rust：调整 IPC 连接池参数（超出范围的值会被限制到合法范围）
+#[derive(Deserialize, DartSignal)]
+pub struct ConfigureIpcPool {
+    pub max_size: u64,
+    pub idle_timeout_ms: u64,
+    pub health_check_interval_s: u64,
+    // 健康检查时是否对空闲连接发送 GET /version 主动探测
+    #[serde(default)]
+    pub active_probe: bool,
+}
+
+// Rust → Dart：连接池参数调整结果（实际生效的值）
+#[derive(Serialize, RustSignal)]
+pub struct IpcPoolConfigured {
+    pub max_size: u64,
+    pub idle_timeout_ms: u64,
+    pub health_check_interval_s: u64,
+    pub active_probe: bool,
+    // 是否有参数被限制到合法范围
+    pub clamped: bool,
+}
+
+// Dart → Rust：设置外部控制器密钥（对应配置中的 secret，为空时清除）
+#[derive(Deserialize, DartSignal)]
+pub struct SetControllerSecret {
+    pub secret: String,
+}
+
+// Rust → Dart：控制器密钥设置结果
+#[derive(Serialize, RustSignal)]
+pub struct ControllerSecretResult {
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：切换控制器端点
+//
+// endpoint 为空时使用本地 IPC（Named Pipe/Unix Socket），为 tcp://host:port 时使用
+// external-controller 的 TCP 端口；切换后旧端点的连接会被丢弃，WebSocket 流需要重新订阅
+#[derive(Deserialize, DartSignal)]
+pub struct SetControllerEndpoint {
+    pub endpoint: String,
+}
+
+// Rust → Dart：控制器端点切换结果（endpoint 为实际生效的端点）
 #[derive(Serialize, RustSignal)]
+pub struct ControllerEndpointResult {
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub endpoint: String,
+}
+
+// IPC 请求失败的原因
+#[derive(Serialize, SignalPiece, Clone, Copy, Debug, PartialEq)]
+pub enum IpcErrorCode {
+    Unauthorized = 0, // 核心返回 401，需要设置正确的控制器密钥
+    Unsupported = 1,  // 核心不支持该端点（返回 404）
+}
+
+// Rust → Dart：IPC 请求响应
+#[derive(Serialize, RustSignal, Clone)]
 pub struct IpcResponse {
     // 请求 ID（用于匹配请求和响应）
     pub request_id: i64,
@@ -58,13 +204,370 @@ pub struct IpcResponse {
     pub success: bool,
     // 错误消息（如果有）
     pub error_message: Option<String>,
+    // 请求是否已被取消
+    pub cancelled: bool,
+    // 结构化的失败原因，供 UI 区分处理
+    pub error_code: Option<IpcErrorCode>,
+}
+
+impl IpcResponse {
+    // 未收到核心响应的失败
+    pub fn failure(request_id: i64, message: String) -> Self {
+        Self {
+            request_id,
+            status_code: 0,
+            body: String::new(),
+            success: false,
+            error_message: Some(message),
+            cancelled: false,
+            error_code: None,
+        }
+    }
+
+    pub fn cancelled(request_id: i64) -> Self {
+        Self {
+            error_message: None,
+            cancelled: true,
+            ..Self::failure(request_id, String::new())
+        }
+    }
+}
+
+// Rust → Dart：大响应体的分块，代替 IpcResponse 发送
+//
+// 按 seq 顺序拼接 bytes 即为完整响应体（块边界与 JSON 结构无关），is_last 为 true 的块是最后一块；
+// 传输中途出错时同样以 is_last 结束，failed 为 true 且 error_message 为错误信息
+#[derive(Serialize, RustSignal)]
+pub struct IpcResponseChunk {
+    pub request_id: i64,
+    pub seq: u32,
+    pub status_code: u16,
+    pub bytes: Vec<u8>,
+    pub is_last: bool,
+    pub failed: bool,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：获取 IPC 请求耗时统计（reset 为 true 时发送后清空统计）
+#[derive(Deserialize, DartSignal)]
+pub struct GetIpcLatencyStats {
+    #[serde(default)]
+    pub reset: bool,
+}
+
+// Dart → Rust：设置慢请求日志阈值（毫秒，0 表示不记录）
+#[derive(Deserialize, DartSignal)]
+pub struct SetIpcSlowRequestThreshold {
+    pub threshold_ms: u64,
+}
+
+// 单个路径的请求耗时统计（分位数基于最近 128 次成功请求，单位微秒）
+//
+// 名称类路径段（如代理名）归并为 :name，路径过多时归入 "*"
+#[derive(Serialize, SignalPiece, Clone, Debug)]
+pub struct IpcLatencyEntry {
+    pub path: String,
+    pub count: u64,
+    pub errors: u64,
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub max_us: u64,
+}
+
+// Rust → Dart：IPC 请求耗时统计（按请求次数降序）
+#[derive(Serialize, RustSignal)]
+pub struct IpcLatencyStats {
+    pub entries: Vec<IpcLatencyEntry>,
+}
+
+// 类型化 API（在 Rust 侧解析核心返回的 JSON，只发送界面使用的字段）
+
+// Dart → Rust：获取代理与代理组（GET /proxies）
+#[derive(Deserialize, DartSignal)]
+pub struct GetProxiesTyped {
+    pub request_id: i64,
+}
+
+// 一次延迟测试记录（delay 为 0 表示超时或失败）
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct ProxyDelay {
+    pub time: String,
+    pub delay: u32,
+}
+
+// 代理组
+//
+// resolved 为沿 now 穿过嵌套代理组后最终选中的节点，未选择或存在循环引用时为空
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct ProxyGroupInfo {
+    pub name: String,
+    pub proxy_type: String,
+    pub now: String,
+    pub all: Vec<String>,
+    pub resolved: Option<String>,
+    pub hidden: bool,
+    pub icon: String,
+}
+
+// 代理节点（proxy_type 原样保留核心返回的类型，未知类型同样传递）
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct ProxyNodeInfo {
+    pub name: String,
+    pub proxy_type: String,
+    pub udp: bool,
+    pub alive: bool,
+    // 最近一次测试的延迟（毫秒，0 表示未测试或失败）
+    pub delay: u32,
+    pub history: Vec<ProxyDelay>,
+}
+
+// Rust → Dart：代理快照
+//
+// 代理组按 GLOBAL 组中的顺序排列（即配置文件中的顺序），GLOBAL 排在最后
+#[derive(Serialize, RustSignal)]
+pub struct ProxiesSnapshot {
+    pub request_id: i64,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub groups: Vec<ProxyGroupInfo>,
+    pub nodes: Vec<ProxyNodeInfo>,
+}
+
+// Dart → Rust：获取核心版本与支持的功能（结果缓存到核心停止，refresh 为 true 时重新探测）
+#[derive(Deserialize, DartSignal)]
+pub struct GetCoreInfo {
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+// Rust → Dart：核心信息
+//
+// features 为支持的功能名：meta、premium，以及探测到的 memory、dns_query、group_delay、
+// rule_providers、geo_update、upgrade；获取失败时 version 为空并附带 error_message
+#[derive(Serialize, RustSignal)]
+pub struct CoreInfo {
+    pub version: String,
+    pub features: Vec<String>,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：通过核心的解析器查询 DNS（GET /dns/query）
+//
+// qtype 为记录类型名（如 A、AAAA、CNAME），为空时查询 A 记录
+#[derive(Deserialize, DartSignal)]
+pub struct DnsQuery {
+    pub request_id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub qtype: String,
+}
+
+// 单条 DNS 记录（record_type 为类型名，未知类型为 TYPEn）
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub record_type: String,
+    pub ttl: u32,
+    pub data: String,
+}
+
+// Rust → Dart：DNS 查询结果
+//
+// rcode 为响应码名（如 NOERROR、NXDOMAIN）；核心不支持 /dns/query 时
+// error_code 为 Unsupported
+#[derive(Serialize, RustSignal)]
+pub struct DnsQueryResult {
+    pub request_id: i64,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub error_code: Option<IpcErrorCode>,
+    pub rcode: String,
+    pub truncated: bool,
+    pub recursion_available: bool,
+    pub authenticated_data: bool,
+    pub answers: Vec<DnsRecord>,
+}
+
+// Dart → Rust：获取规则列表（GET /rules）
+#[derive(Deserialize, DartSignal)]
+pub struct GetRulesTyped {
+    pub request_id: i64,
+}
+
+// 单条规则（hit_count 仅在核心提供命中统计时有值）
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct RuleInfo {
+    pub index: u32,
+    pub rule_type: String,
+    pub payload: String,
+    pub proxy: String,
+    pub hit_count: Option<u64>,
+    pub disabled: bool,
+}
+
+// Rust → Dart：规则列表
+#[derive(Serialize, RustSignal)]
+pub struct RulesSnapshot {
+    pub request_id: i64,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub rules: Vec<RuleInfo>,
+}
+
+// Dart → Rust：获取规则集列表（GET /providers/rules）
+#[derive(Deserialize, DartSignal)]
+pub struct GetRuleProvidersTyped {
+    pub request_id: i64,
+}
+
+// 单个规则集（updated_at 为 RFC 3339 时间，核心未提供时为空）
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct RuleProviderInfo {
+    pub name: String,
+    pub behavior: String,
+    pub format: String,
+    pub vehicle_type: String,
+    pub rule_count: u64,
+    pub updated_at: String,
+}
+
+// Rust → Dart：规则集列表（按名称排序）
+#[derive(Serialize, RustSignal)]
+pub struct RuleProvidersSnapshot {
+    pub request_id: i64,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub providers: Vec<RuleProviderInfo>,
+}
+
+// Dart → Rust：更新规则集（PUT /providers/rules/{name}）
+#[derive(Deserialize, DartSignal)]
+pub struct UpdateRuleProvider {
+    pub name: String,
+}
+
+// Rust → Dart：规则集更新结果（失败时 error_message 为核心返回的错误）
+#[derive(Serialize, RustSignal)]
+pub struct RuleProviderUpdateResult {
+    pub name: String,
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：测试代理组中所有成员的延迟
+//
+// url 为空时使用 generate_204 测试地址，timeout_ms 与 concurrency 为 0 时使用默认值
+// （5000 毫秒、8 个并发）；每个成员完成后发送 ProxyDelayResult，全部结束后发送
+// GroupDelayComplete。同一代理组已在测试时新的请求会被拒绝
+#[derive(Deserialize, DartSignal)]
+pub struct TestGroupDelay {
+    pub group: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub timeout_ms: u64,
+    #[serde(default)]
+    pub concurrency: u32,
+}
+
+// Rust → Dart：单个代理的延迟测试结果（失败时 delay_ms 为 0）
+#[derive(Serialize, RustSignal)]
+pub struct ProxyDelayResult {
+    pub group: String,
+    pub proxy: String,
+    pub delay_ms: u32,
+    pub error: Option<String>,
+}
+
+// Rust → Dart：代理组延迟测试结束
+//
+// error_message 不为空表示测试未能开始（如该组已在测试中或获取成员失败）
+#[derive(Serialize, RustSignal)]
+pub struct GroupDelayComplete {
+    pub group: String,
+    pub tested: u32,
+    pub failed: u32,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：获取连接列表（GET /connections）
+//
+// host_filter 为主机名或目标 IP 的子串（不区分大小写），chain_filter 为代理链中的名称，
+// 为空时不过滤；过滤在 Rust 侧完成
+#[derive(Deserialize, DartSignal)]
+pub struct GetConnectionsTyped {
+    pub request_id: i64,
+    #[serde(default)]
+    pub host_filter: String,
+    #[serde(default)]
+    pub chain_filter: String,
+}
+
+// 单条连接（source/destination 为 ip:port 形式）
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct ConnectionInfo {
+    pub id: String,
+    pub network: String,
+    pub conn_type: String,
+    pub host: String,
+    pub source: String,
+    pub destination: String,
+    pub process: String,
+    pub chains: Vec<String>,
+    pub rule: String,
+    pub rule_payload: String,
+    pub upload: u64,
+    pub download: u64,
+    // 连接建立时间（RFC 3339）
+    pub start: String,
+}
+
+// Rust → Dart：连接快照（total 为过滤前的连接数）
+#[derive(Serialize, RustSignal)]
+pub struct ConnectionsSnapshot {
+    pub request_id: i64,
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub download_total: u64,
+    pub upload_total: u64,
+    pub total: u64,
+    pub connections: Vec<ConnectionInfo>,
+}
+
+// Dart → Rust：关闭指定连接（DELETE /connections/{id}）
+#[derive(Deserialize, DartSignal)]
+pub struct CloseConnection {
+    pub request_id: i64,
+    pub id: String,
+}
+
+// Dart → Rust：关闭所有连接（DELETE /connections）
+#[derive(Deserialize, DartSignal)]
+pub struct CloseAllConnections {
+    pub request_id: i64,
+}
+
+// Rust → Dart：关闭连接的结果（id 为空表示关闭所有连接）
+#[derive(Serialize, RustSignal)]
+pub struct CloseConnectionsResult {
+    pub request_id: i64,
+    pub id: Option<String>,
+    pub success: bool,
+    pub error_message: Option<String>,
 }
 
 // WebSocket 流式数据
 
 // Dart → Rust：开始监听 Clash 日志
+//
+// level 为 silent/error/warning/info/debug，为空时使用 info；
+// 已在监听其他级别时会以新级别重新连接；Dart 处理不及时只保留最近 500 行，
+// 并以一条 warning 日志提示丢弃的行数
 #[derive(Deserialize, DartSignal)]
-pub struct StartLogStream;
+pub struct StartLogStream {
+    #[serde(default)]
+    pub level: String,
+}
 
 // Dart → Rust：停止监听 Clash 日志
 #[derive(Deserialize, DartSignal)]
@@ -78,18 +581,73 @@ pub struct IpcLogData {
 }
 
 // Dart → Rust：开始监听流量数据
+//
+// aggregate_window_ms 大于 0 时在 Rust 侧按窗口聚合，每个窗口只发送一次数据；
+// 为空或 0 时逐帧转发
 #[derive(Deserialize, DartSignal)]
-pub struct StartTrafficStream;
+pub struct StartTrafficStream {
+    #[serde(default)]
+    pub aggregate_window_ms: Option<u64>,
+}
 
 // Dart → Rust：停止监听流量数据
 #[derive(Deserialize, DartSignal)]
 pub struct StopTrafficStream;
 
 // Rust → Dart：流量数据
+//
+// upload/download 为速率（字节/秒，聚合时为窗口内的平均值），
+// upload_bytes/download_bytes 为本次数据覆盖的字节数（逐帧转发时与速率相同）
 #[derive(Serialize, RustSignal)]
 pub struct IpcTrafficData {
     pub upload: u64,
     pub download: u64,
+    pub upload_bytes: u64,
+    pub download_bytes: u64,
+}
+
+// Dart → Rust：开始监听连接数据
+#[derive(Deserialize, DartSignal)]
+pub struct StartConnectionsStream;
+
+// Dart → Rust：停止监听连接数据
+#[derive(Deserialize, DartSignal)]
+pub struct StopConnectionsStream;
+
+// Rust → Dart：连接数据（/connections 推送的原始 JSON，含累计流量与连接列表）
+#[derive(Serialize, RustSignal)]
+pub struct IpcConnectionsData {
+    pub json: String,
+}
+
+// Dart → Rust：开始监听核心内存占用
+#[derive(Deserialize, DartSignal)]
+pub struct StartMemoryStream;
+
+// Dart → Rust：停止监听核心内存占用
+#[derive(Deserialize, DartSignal)]
+pub struct StopMemoryStream;
+
+// Rust → Dart：核心内存占用（字节）
+#[derive(Serialize, RustSignal)]
+pub struct IpcMemoryData {
+    pub inuse: u64,
+    pub oslimit: u64,
+}
+
+// WebSocket 流的连接状态
+#[derive(Serialize, SignalPiece, Clone, Copy, Debug, PartialEq)]
+pub enum WsConnectionState {
+    Connected = 0,    // 已连接（含重连成功）
+    Reconnecting = 1, // 连接意外断开，正在重连
+    GaveUp = 2,       // 重连失败次数达到上限或端点被拒绝，不再重试
+}
+
+// Rust → Dart：WebSocket 流状态变化，供 UI 在断开期间置灰图表
+#[derive(Serialize, RustSignal)]
+pub struct WsStreamState {
+    pub endpoint: String,
+    pub state: WsConnectionState,
 }
 
 // Rust → Dart：流操作结果
@@ -0,0 +1,167 @@
+// WebSocket 流的有界缓冲
+//
+// 目的：Flutter isolate 繁忙（窗口缩放、大量重建）时，流量与日志帧会在 rinf 中堆积，
+// 之后集中到达。读循环只把帧放入有界缓冲，由转发任务按固定节奏取出发送给 Dart；
+// 缓冲已满时丢弃最旧的帧并累计丢弃数量，内存占用不随积压增长
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+struct State<T> {
+    items: VecDeque<T>,
+    dropped: u64,
+    closed: bool,
+}
+
+struct Shared<T> {
+    capacity: usize,
+    state: Mutex<State<T>>,
+    notify: Notify,
+}
+
+// 写入端（由 WebSocket 读循环持有），释放后接收端取完剩余数据即结束
+pub struct StreamSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// 读取端（由转发任务持有）
+pub struct StreamReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+// 一次取出的数据：按到达顺序排列，dropped 为自上次取出以来丢弃的旧帧数
+pub struct StreamBatch<T> {
+    pub items: Vec<T>,
+    pub dropped: u64,
+}
+
+// 创建容量为 capacity 的缓冲（capacity 为 1 时只保留最新一帧）
+pub fn channel<T>(capacity: usize) -> (StreamSender<T>, StreamReceiver<T>) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        capacity,
+        state: Mutex::new(State {
+            items: VecDeque::with_capacity(capacity),
+            dropped: 0,
+            closed: false,
+        }),
+        notify: Notify::new(),
+    });
+    (
+        StreamSender {
+            shared: shared.clone(),
+        },
+        StreamReceiver { shared },
+    )
+}
+
+impl<T> StreamSender<T> {
+    // 写入一帧，缓冲已满时丢弃最旧的一帧
+    pub fn push(&self, item: T) {
+        {
+            let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.items.len() >= self.shared.capacity {
+                state.items.pop_front();
+                state.dropped += 1;
+            }
+            state.items.push_back(item);
+        }
+        self.shared.notify.notify_one();
+    }
+}
+
+impl<T> Drop for StreamSender<T> {
+    fn drop(&mut self) {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .closed = true;
+        self.shared.notify.notify_one();
+    }
+}
+
+impl<T> StreamReceiver<T> {
+    // 等待并取出当前缓冲的全部数据；写入端已释放且没有剩余数据时返回 None
+    pub async fn recv_batch(&mut self) -> Option<StreamBatch<T>> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+                if !state.items.is_empty() || state.dropped > 0 {
+                    return Some(StreamBatch {
+                        items: state.items.drain(..).collect(),
+                        dropped: std::mem::take(&mut state.dropped),
+                    });
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            // notify_one 在没有等待者时保留一次通知，不会错过锁释放后到达的数据
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_keep_latest_frame() -> Result<(), String> {
+        let (sender, mut receiver) = channel(1);
+        for frame in 0..10 {
+            sender.push(frame);
+        }
+
+        let batch = receiver.recv_batch().await.ok_or("缓冲不应为空")?;
+        assert_eq!(batch.items, vec![9]);
+        assert_eq!(batch.dropped, 9);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer_keeps_recent_lines() -> Result<(), String> {
+        let (sender, mut receiver) = channel(100);
+        for line in 0..250 {
+            sender.push(line);
+        }
+
+        let batch = receiver.recv_batch().await.ok_or("缓冲不应为空")?;
+        assert_eq!(batch.items, (150..250).collect::<Vec<_>>());
+        assert_eq!(batch.dropped, 150);
+
+        // 丢弃计数在取出后清零
+        sender.push(250);
+        let batch = receiver.recv_batch().await.ok_or("缓冲不应为空")?;
+        assert_eq!(batch.items, vec![250]);
+        assert_eq!(batch.dropped, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_drains_remaining_after_close() -> Result<(), String> {
+        let (sender, mut receiver) = channel(4);
+        sender.push("a");
+        sender.push("b");
+        drop(sender);
+
+        let batch = receiver.recv_batch().await.ok_or("剩余数据应被取出")?;
+        assert_eq!(batch.items, vec!["a", "b"]);
+        assert!(receiver.recv_batch().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wakes_waiting_consumer() -> Result<(), String> {
+        let (sender, mut receiver) = channel(4);
+        let consumer = tokio::spawn(async move { receiver.recv_batch().await.map(|b| b.items) });
+        tokio::task::yield_now().await;
+        sender.push(1);
+
+        let items = consumer.await.map_err(|e| e.to_string())?;
+        assert_eq!(items, Some(vec![1]));
+        Ok(())
+    }
+}
@@ -1,39 +1,56 @@
 // WebSocket over IPC 客户端
-// 通过 Named Pipe/Unix Socket 建立 WebSocket 连接
+// 通过 Named Pipe/Unix Socket（或 TCP 控制器端口）建立 WebSocket 连接，连接意外断开时自动重连
 
-use super::connection;
+use super::connection::{self, ControllerConn};
+use super::error::IpcClientError;
+use super::ipc_client::controller_secret;
+use super::path;
+use super::signals::{WsConnectionState, WsStreamState};
 use base64::Engine;
 use futures_util::stream::StreamExt;
+use rinf::RustSignal;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Error as WsError;
 use tokio_tungstenite::{WebSocketStream, client_async, tungstenite::protocol::Message};
 
-#[cfg(unix)]
-use tokio::net::UnixStream;
-
-#[cfg(windows)]
-use tokio::net::windows::named_pipe::NamedPipeClient;
-
 // HTTP Request 构建器 (来自 http crate)
 use http::Request;
-use http::header::{CONNECTION, HOST, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE};
+use http::StatusCode;
+use http::header::{
+    AUTHORIZATION, CONNECTION, HOST, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+
+// 每次连接（含重连）都使用当前的控制器端点
+type IpcStream = ControllerConn;
 
 // WebSocket 连接 ID
 pub type ConnectionId = u32;
 
+// 重连退避：首次等待 500 毫秒，之后翻倍，上限 10 秒
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(10);
+// 连续重连失败次数上限
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
 // WebSocket 客户端
 pub struct WebSocketClient {
-    ipc_path: String,
     next_connection_id: Arc<tokio::sync::Mutex<u32>>,
     // 存储活跃的连接任务，用于断开连接
     connections: Arc<tokio::sync::Mutex<HashMap<ConnectionId, tokio::task::JoinHandle<()>>>>,
 }
 
+impl Default for WebSocketClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WebSocketClient {
     // 创建新的 WebSocket 客户端
-    pub fn new(ipc_path: String) -> Self {
+    pub fn new() -> Self {
         Self {
-            ipc_path,
             next_connection_id: Arc::new(tokio::sync::Mutex::new(1)),
             connections: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
         }
@@ -55,10 +72,15 @@ impl WebSocketClient {
     // - `on_message`: 消息回调函数
     //
     // # 返回
-    // 连接 ID，用于后续管理和断开连接
-    pub async fn connect<F>(&self, endpoint: &str, on_message: F) -> Result<ConnectionId, String>
+    // 连接 ID，用于后续管理和断开连接。首次连接失败时直接返回错误；
+    // 之后连接意外断开会按退避策略重连，直到调用 disconnect 或重连失败次数达到上限
+    pub async fn connect<F>(
+        &self,
+        endpoint: &str,
+        on_message: F,
+    ) -> Result<ConnectionId, IpcClientError>
     where
-        F: Fn(serde_json::Value) + Send + 'static,
+        F: Fn(serde_json::Value) + Send + Sync + 'static,
     {
         log::debug!("开始建立 WebSocket 连接：{}", endpoint);
 
@@ -70,107 +92,192 @@ impl WebSocketClient {
             id
         };
 
-        // 2. 连接到 IPC 端点
-        #[cfg(windows)]
-        let stream = self.connect_windows().await?;
+        // 2. 建立首次连接
+        let ws_stream = Self::open(endpoint).await?;
+
+        log::info!("WebSocket 连接建立成功[{}]：{}", connection_id, endpoint);
+        send_state(endpoint, WsConnectionState::Connected);
+
+        // 3. 启动消息接收循环（断开后自动重连）
+        let connections = self.connections.clone();
+        let endpoint = endpoint.to_string();
+        let handle = tokio::spawn(async move {
+            let mut ws_stream = ws_stream;
+            loop {
+                Self::receive(connection_id, ws_stream, &on_message).await;
+
+                // 主动断开会中止本任务，走到这里说明订阅仍然有效（如核心重启）
+                match Self::reconnect(&endpoint, connection_id).await {
+                    Some(stream) => ws_stream = stream,
+                    None => break,
+                }
+            }
+
+            // 连接结束后，从连接表中移除
+            let mut conns = connections.lock().await;
+            conns.remove(&connection_id);
+        });
+
+        // 存储连接句柄
+        {
+            let mut conns = self.connections.lock().await;
+            conns.insert(connection_id, handle);
+        }
+
+        Ok(connection_id)
+    }
 
-        #[cfg(unix)]
-        let stream = self.connect_unix().await?;
+    // 建立 IPC 连接并完成 WebSocket 握手
+    //
+    // 握手被拒绝（如核心不支持该端点）时返回 Protocol 错误，密钥错误时返回 Unauthorized，
+    // 两者重试都没有意义
+    async fn open(endpoint: &str) -> Result<WebSocketStream<IpcStream>, IpcClientError> {
+        // 1. 连接到控制器端点
+        let stream = connection::connect(&connection::current_endpoint()).await?;
 
-        // 3. 构造 WebSocket 握手请求（使用 http::Request）
+        // 2. 构造 WebSocket 握手请求（使用 http::Request）
         // 关键：使用 ws:// scheme 以通过 tungstenite 的 URI 验证
-        let uri = format!("ws://localhost{}", endpoint);
+        let uri = format!("ws://localhost{}", path::encode_request_path(endpoint)?);
         log::trace!("构造 URI：{}", uri);
 
-        let request = Request::builder()
+        let mut builder = Request::builder()
             .uri(&uri)
             .header(HOST, "stelliberty")
             .header(SEC_WEBSOCKET_KEY, Self::generate_websocket_key())
             .header(CONNECTION, "Upgrade")
             .header(UPGRADE, "websocket")
-            .header(SEC_WEBSOCKET_VERSION, "13")
+            .header(SEC_WEBSOCKET_VERSION, "13");
+        if let Some(secret) = controller_secret() {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", secret));
+        }
+
+        let request = builder
             .body(())
-            .map_err(|e| format!("构造 WebSocket 请求失败：{}", e))?;
+            .map_err(|e| IpcClientError::Protocol(format!("构造 WebSocket 请求失败：{}", e)))?;
 
         log::trace!("WebSocket 请求构造成功，URI：{:?}", request.uri());
 
         log::trace!("发送 WebSocket 握手请求：{}", endpoint);
 
-        // 4. 使用 client_async 建立 WebSocket 连接
-        let (ws_stream, _) = client_async(request, stream)
-            .await
-            .map_err(|e| format!("WebSocket 握手失败：{}", e))?;
+        // 3. 使用 client_async 建立 WebSocket 连接
+        let (ws_stream, _) = client_async(request, stream).await.map_err(|e| match e {
+            WsError::Http(response) if response.status() == StatusCode::UNAUTHORIZED => {
+                IpcClientError::Unauthorized(format!("WebSocket 握手失败：{}", endpoint))
+            }
+            // 核心以 HTTP 错误响应拒绝握手（如 404），说明不支持该端点
+            WsError::Http(_) => IpcClientError::Protocol(format!("WebSocket 握手失败：{}", e)),
+            WsError::Io(io_error) => IpcClientError::io(io_error, "WebSocket 握手失败"),
+            e => IpcClientError::Protocol(format!("WebSocket 握手失败：{}", e)),
+        })?;
 
-        log::info!("WebSocket 连接建立成功[{}]：{}", connection_id, endpoint);
+        Ok(ws_stream)
+    }
 
-        // 5. 分离读写流
+    // 接收消息直到连接关闭或出错
+    async fn receive<F>(
+        connection_id: ConnectionId,
+        ws_stream: WebSocketStream<IpcStream>,
+        on_message: &F,
+    ) where
+        F: Fn(serde_json::Value) + Sync,
+    {
+        // 分离读写流
         let (_writer, mut reader) = ws_stream.split();
 
-        // 6. 启动消息接收循环
-        let connections = self.connections.clone();
-        let handle = tokio::spawn(async move {
-            log::trace!("WebSocket 消息接收循环已启动 [{}]", connection_id);
-
-            while let Some(message) = reader.next().await {
-                match message {
-                    Ok(Message::Text(text)) => {
-                        // 解析 JSON 消息
-                        match serde_json::from_str::<serde_json::Value>(&text) {
-                            Ok(json_value) => {
-                                log::trace!(
-                                    "WebSocket 收到消息[{}]：{}bytes",
-                                    connection_id,
-                                    text.len()
-                                );
-                                on_message(json_value);
-                            }
-                            Err(e) => {
-                                log::error!(
-                                    "WebSocket 消息 JSON 解析失败[{}]：{}",
-                                    connection_id,
-                                    e
-                                );
-                            }
+        log::trace!("WebSocket 消息接收循环已启动 [{}]", connection_id);
+
+        while let Some(message) = reader.next().await {
+            match message {
+                Ok(Message::Text(text)) => {
+                    // 解析 JSON 消息
+                    match serde_json::from_str::<serde_json::Value>(&text) {
+                        Ok(json_value) => {
+                            log::trace!(
+                                "WebSocket 收到消息[{}]：{}bytes",
+                                connection_id,
+                                text.len()
+                            );
+                            on_message(json_value);
+                        }
+                        Err(e) => {
+                            log::error!("WebSocket 消息 JSON 解析失败[{}]：{}", connection_id, e);
                         }
                     }
-                    Ok(Message::Close(close_frame)) => {
-                        log::info!("WebSocket 连接关闭[{}]：{:?}", connection_id, close_frame);
-                        break;
-                    }
-                    Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => {
-                        // Ping/Pong 由 tokio-tungstenite 自动处理
-                    }
-                    Ok(Message::Binary(data)) => {
-                        log::debug!(
-                            "WebSocket 收到二进制消息[{}]：{}bytes",
-                            connection_id,
-                            data.len()
-                        );
-                    }
-                    Ok(Message::Frame(_)) => {
-                        // 忽略原始帧
-                    }
-                    Err(e) => {
-                        log::error!("WebSocket 消息读取错误[{}]：{}", connection_id, e);
-                        break;
-                    }
+                }
+                Ok(Message::Close(close_frame)) => {
+                    log::info!("WebSocket 连接关闭[{}]：{:?}", connection_id, close_frame);
+                    break;
+                }
+                Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => {
+                    // Ping/Pong 由 tokio-tungstenite 自动处理
+                }
+                Ok(Message::Binary(data)) => {
+                    log::debug!(
+                        "WebSocket 收到二进制消息[{}]：{}bytes",
+                        connection_id,
+                        data.len()
+                    );
+                }
+                Ok(Message::Frame(_)) => {
+                    // 忽略原始帧
+                }
+                Err(e) => {
+                    log::error!("WebSocket 消息读取错误[{}]：{}", connection_id, e);
+                    break;
                 }
             }
+        }
 
-            log::debug!("WebSocket 消息接收循环已结束[{}]", connection_id);
-
-            // 连接结束后，从连接表中移除
-            let mut conns = connections.lock().await;
-            conns.remove(&connection_id);
-        });
+        log::debug!("WebSocket 消息接收循环已结束[{}]", connection_id);
+    }
 
-        // 存储连接句柄
-        {
-            let mut conns = self.connections.lock().await;
-            conns.insert(connection_id, handle);
+    // 按指数退避重连，失败次数达到上限或握手被拒绝时返回 None
+    async fn reconnect(
+        endpoint: &str,
+        connection_id: ConnectionId,
+    ) -> Option<WebSocketStream<IpcStream>> {
+        send_state(endpoint, WsConnectionState::Reconnecting);
+
+        let mut delay = RECONNECT_INITIAL_DELAY;
+        for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+            log::debug!(
+                "{} 毫秒后重连 WebSocket[{}]：{}（第 {} 次）",
+                delay.as_millis(),
+                connection_id,
+                endpoint,
+                attempt
+            );
+            tokio::time::sleep(delay).await;
+
+            match Self::open(endpoint).await {
+                Ok(ws_stream) => {
+                    log::info!("WebSocket 重连成功[{}]：{}", connection_id, endpoint);
+                    send_state(endpoint, WsConnectionState::Connected);
+                    return Some(ws_stream);
+                }
+                Err(
+                    e @ (IpcClientError::Protocol(_)
+                    | IpcClientError::Unauthorized(_)
+                    | IpcClientError::InvalidRequest(_)),
+                ) => {
+                    log::warn!("WebSocket 重连被拒绝[{}]：{}", connection_id, e);
+                    break;
+                }
+                Err(e) => {
+                    log::debug!("WebSocket 重连失败[{}]：{}", connection_id, e);
+                }
+            }
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
         }
 
-        Ok(connection_id)
+        log::warn!("WebSocket 重连已放弃[{}]：{}", connection_id, endpoint);
+        send_state(endpoint, WsConnectionState::GaveUp);
+        None
+    }
+
+    // 连接是否仍在运行（重连放弃后连接会从连接表中移除）
+    pub async fn is_connected(&self, connection_id: ConnectionId) -> bool {
+        self.connections.lock().await.contains_key(&connection_id)
     }
 
     // 断开指定的 WebSocket 连接
@@ -203,18 +310,15 @@ impl WebSocketClient {
             log::info!("所有 WebSocket 连接已断开");
         }
     }
+}
 
-    // Windows: 连接到 Named Pipe
-    #[cfg(windows)]
-    async fn connect_windows(&self) -> Result<NamedPipeClient, String> {
-        connection::connect_named_pipe(&self.ipc_path).await
-    }
-
-    // Unix: 连接到 Unix Socket
-    #[cfg(unix)]
-    async fn connect_unix(&self) -> Result<UnixStream, String> {
-        connection::connect_unix_socket(&self.ipc_path).await
+// 通知 Dart 流状态变化
+fn send_state(endpoint: &str, state: WsConnectionState) {
+    WsStreamState {
+        endpoint: endpoint.to_string(),
+        state,
     }
+    .send_signal_to_dart();
 }
 
 #[cfg(test)]
@@ -239,9 +343,21 @@ mod tests {
 
     #[test]
     fn test_connection_id_increment() {
-        let client = WebSocketClient::new(String::from("test"));
+        let client = WebSocketClient::new();
 
         // 验证初始 ID 从 1 开始
         assert_eq!(*client.next_connection_id.blocking_lock(), 1);
     }
+
+    #[tokio::test]
+    async fn test_is_connected_tracks_connection_table() {
+        let client = WebSocketClient::new();
+        let handle = tokio::spawn(async {});
+        client.connections.lock().await.insert(7, handle);
+        assert!(client.is_connected(7).await);
+
+        // 重连放弃后任务会自行移除条目
+        client.connections.lock().await.remove(&7);
+        assert!(!client.is_connected(7).await);
+    }
 }
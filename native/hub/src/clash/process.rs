@@ -2,24 +2,133 @@
 //
 // 负责启动、停止和管理 Clash 核心进程
 
-use super::signals::{ClashProcessResult, StartClashProcess, StopClashProcess};
+mod log_file;
+mod manager;
+mod output;
+mod pidfile;
+mod precheck;
+mod restart;
+mod stats;
+
+use super::signals::{
+    ClashProcessExited, ClashProcessRestarted, ClashProcessResult, ClashStartError,
+    CoreOutputStream, CoreOutputTailResponse, GetClashProcessStatus, GetCoreOutputTail,
+    MAIN_INSTANCE_TAG, RestartClashProcess, StartClashProcess, StopClashProcess,
+};
 use once_cell::sync::Lazy;
 use rinf::RustSignal;
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// 默认优雅停止等待时间，超时后强制终止
+const DEFAULT_FORCE_AFTER: Duration = Duration::from_millis(5000);
+// 强制终止后的等待时间
+const FORCE_KILL_WAIT: Duration = Duration::from_secs(2);
+// 等待控制接口就绪的轮询间隔
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// 核心启动即退出时附带的输出行数
+const EXIT_OUTPUT_LINES: usize = 20;
+
+// 最近一次成功启动的核心路径与参数（供重启复用）
+static LAST_LAUNCH: Lazy<Mutex<Option<LaunchParams>>> = Lazy::new(|| Mutex::new(None));
+
+// 是否有重启操作正在进行
+static RESTART_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+// 核心启动参数
+#[derive(Clone)]
+struct LaunchParams {
+    // 实例标签，仅主实例接管 TUN、PID 文件、输出捕获与自动重启
+    instance_tag: String,
+    executable_path: String,
+    args: Vec<String>,
+    // 追加的环境变量（在继承当前进程环境的基础上覆盖）
+    env: HashMap<String, String>,
+    // 工作目录，空字符串表示继承当前目录
+    working_dir: String,
+    // 核心输出日志文件，为空时不写入
+    log_file: Option<log_file::LogFileOptions>,
+    // 核心文件的预期 SHA-256，为空时不校验
+    expected_sha256: Option<String>,
+}
+
+impl LaunchParams {
+    fn is_main(&self) -> bool {
+        self.instance_tag == MAIN_INSTANCE_TAG
+    }
+}
+
+// 启动失败的原因
+struct StartFailure {
+    message: String,
+    // 可供 UI 针对性处理的结构化错误
+    start_error: Option<ClashStartError>,
+}
+
+impl From<String> for StartFailure {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            start_error: None,
+        }
+    }
+}
 
-// 全局进程管理器
-static PROCESS_MANAGER: Lazy<Mutex<Option<ClashProcess>>> = Lazy::new(|| Mutex::new(None));
+impl From<(ClashStartError, String)> for StartFailure {
+    fn from((start_error, message): (ClashStartError, String)) -> Self {
+        Self {
+            message,
+            start_error: Some(start_error),
+        }
+    }
+}
+
+// 进程退出状态（等待线程与停止操作共享）
+#[derive(Default)]
+struct ExitWatch {
+    // 是否由 StopClashProcess 或应用退出主动停止
+    requested: AtomicBool,
+    // 进程退出码（外层 None 表示尚未退出，内层 None 表示被信号终止）
+    exit_code: Mutex<Option<Option<i32>>>,
+    exited: Condvar,
+}
+
+impl ExitWatch {
+    fn has_exited(&self) -> bool {
+        self.exit_code
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+
+    fn mark_exited(&self, code: Option<i32>) {
+        *self.exit_code.lock().unwrap_or_else(|e| e.into_inner()) = Some(code);
+        self.exited.notify_all();
+    }
+
+    // 等待进程退出，超时返回 None
+    fn wait_timeout(&self, timeout: Duration) -> Option<Option<i32>> {
+        let guard = self.exit_code.lock().unwrap_or_else(|e| e.into_inner());
+        let (guard, _) = self
+            .exited
+            .wait_timeout_while(guard, timeout, |code| code.is_none())
+            .unwrap_or_else(|e| e.into_inner());
+        *guard
+    }
+}
 
 // Clash 进程封装
 struct ClashProcess {
-    #[cfg(unix)]
-    child: std::process::Child,
     #[cfg(windows)]
     process_handle: winapi::um::winnt::HANDLE,
     #[cfg(windows)]
     job_handle: winapi::um::winnt::HANDLE,
-    #[cfg(windows)]
     pid: u32,
+    // 启动时间
+    started_at: Instant,
+    watch: Arc<ExitWatch>,
 }
 
 #[cfg(windows)]
@@ -27,47 +136,120 @@ unsafe impl Send for ClashProcess {}
 
 impl ClashProcess {
     // 启动新的 Clash 进程
-    fn start(executable_path: String, args: Vec<String>) -> Result<Self, String> {
-        log::info!("启动 Clash 进程：{}", executable_path);
+    fn start(launch: &LaunchParams) -> Result<Self, StartFailure> {
+        let LaunchParams {
+            instance_tag,
+            executable_path,
+            args,
+            env,
+            working_dir,
+            log_file,
+            expected_sha256,
+        } = launch;
+
+        log::info!(
+            "启动 Clash 进程（实例：{}）：{}",
+            instance_tag,
+            executable_path
+        );
+        let capture = launch.is_main();
         log::info!("参数：{:?}", args);
+        if !env.is_empty() {
+            log::info!("环境变量：{:?}", env.keys().collect::<Vec<_>>());
+        }
+        if !working_dir.is_empty() {
+            log::info!("工作目录：{}", working_dir);
+        }
 
         #[cfg(unix)]
         {
+            use std::os::unix::process::CommandExt;
             use std::process::{Command, Stdio};
 
-            let child = Command::new(&executable_path)
-                .args(&args)
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
+            if let Some(expected) = expected_sha256 {
+                precheck::verify_sha256(executable_path, expected)?;
+            }
+
+            let mut command = Command::new(executable_path);
+            command
+                .args(args)
+                .envs(env)
+                // 独立进程组，停止时可连同核心派生的子进程一起终止
+                .process_group(0)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            if !working_dir.is_empty() {
+                command.current_dir(working_dir);
+            }
+
+            let mut child = command
                 .spawn()
                 .map_err(|e| format!("启动进程失败：{}", e))?;
 
-            Ok(ClashProcess { child })
+            if capture {
+                output::reset();
+            }
+            let sink = log_file.clone().and_then(log_file::open);
+            if let Some(stdout) = child.stdout.take() {
+                output::spawn_reader(CoreOutputStream::Stdout, stdout, sink.clone(), capture);
+            }
+            if let Some(stderr) = child.stderr.take() {
+                output::spawn_reader(CoreOutputStream::Stderr, stderr, sink, capture);
+            }
+
+            let pid = child.id();
+            let watch = Arc::new(ExitWatch::default());
+            spawn_exit_waiter(
+                pid,
+                instance_tag.clone(),
+                watch.clone(),
+                move || match child.wait() {
+                    Ok(status) => status.code(),
+                    Err(e) => {
+                        log::error!("等待进程退出失败：{}", e);
+                        None
+                    }
+                },
+            );
+
+            Ok(ClashProcess {
+                pid,
+                started_at: Instant::now(),
+                watch,
+            })
         }
 
         #[cfg(windows)]
         {
             use std::ffi::OsStr;
             use std::os::windows::ffi::OsStrExt;
+            use std::os::windows::io::FromRawHandle;
             use std::ptr;
-            use winapi::shared::minwindef::FALSE;
-            use winapi::um::handleapi::CloseHandle;
+            use winapi::shared::minwindef::{FALSE, TRUE};
+            use winapi::um::handleapi::{CloseHandle, DuplicateHandle, SetHandleInformation};
             use winapi::um::jobapi2::{
                 AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject,
             };
+            use winapi::um::minwinbase::SECURITY_ATTRIBUTES;
+            use winapi::um::namedpipeapi::CreatePipe;
             use winapi::um::processthreadsapi::{
-                CreateProcessW, PROCESS_INFORMATION, ResumeThread, STARTUPINFOW, TerminateProcess,
+                CreateProcessW, GetCurrentProcess, PROCESS_INFORMATION, ResumeThread, STARTUPINFOW,
+                TerminateProcess,
+            };
+            use winapi::um::winbase::{
+                CREATE_NO_WINDOW, CREATE_SUSPENDED, CREATE_UNICODE_ENVIRONMENT,
+                HANDLE_FLAG_INHERIT, STARTF_USESHOWWINDOW, STARTF_USESTDHANDLES,
             };
-            use winapi::um::winbase::{CREATE_NO_WINDOW, CREATE_SUSPENDED, STARTF_USESHOWWINDOW};
             use winapi::um::winnt::{
-                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                DUPLICATE_SAME_ACCESS, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+                JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
             };
             use winapi::um::winuser::SW_HIDE;
 
             unsafe {
                 // 构建命令行
                 let mut command_line = format!("\"{}\"", executable_path);
-                for arg in &args {
+                for arg in args {
                     command_line.push(' ');
                     if arg.contains(' ') {
                         command_line.push_str(&format!("\"{}\"", arg));
@@ -81,10 +263,19 @@ impl ClashProcess {
                     .chain(std::iter::once(0))
                     .collect();
 
+                // 有追加环境变量时需要显式传入完整的环境块
+                let environment_block = (!env.is_empty()).then(|| build_environment_block(env));
+                let working_dir_wide: Option<Vec<u16>> = (!working_dir.is_empty()).then(|| {
+                    OsStr::new(working_dir)
+                        .encode_wide()
+                        .chain(std::iter::once(0))
+                        .collect()
+                });
+
                 // 创建 Job Object（确保子进程跟随父进程终止）
                 let job_handle = CreateJobObjectW(ptr::null_mut(), ptr::null());
                 if job_handle.is_null() {
-                    return Err("创建 Job Object 失败".to_string());
+                    return Err("创建 Job Object 失败".to_string().into());
                 }
 
                 let mut job_info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
@@ -98,42 +289,95 @@ impl ClashProcess {
                 ) == FALSE
                 {
                     CloseHandle(job_handle);
-                    return Err("设置 Job Object 信息失败".to_string());
+                    return Err("设置 Job Object 信息失败".to_string().into());
                 }
 
-                // 配置启动信息（隐藏窗口）
+                // 创建匿名管道捕获 stdout/stderr（仅写端可被子进程继承）
+                let mut pipe_attributes = SECURITY_ATTRIBUTES {
+                    nLength: std::mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+                    lpSecurityDescriptor: ptr::null_mut(),
+                    bInheritHandle: TRUE,
+                };
+                let mut pipes = [(ptr::null_mut(), ptr::null_mut()); 2];
+                for (read, write) in pipes.iter_mut() {
+                    if CreatePipe(read, write, &mut pipe_attributes, 0) == FALSE
+                        || SetHandleInformation(*read, HANDLE_FLAG_INHERIT, 0) == FALSE
+                    {
+                        for &(read, write) in pipes.iter() {
+                            if !read.is_null() {
+                                CloseHandle(read);
+                            }
+                            if !write.is_null() {
+                                CloseHandle(write);
+                            }
+                        }
+                        CloseHandle(job_handle);
+                        return Err("创建输出管道失败".to_string().into());
+                    }
+                }
+                let [(stdout_read, stdout_write), (stderr_read, stderr_write)] = pipes;
+
+                // 配置启动信息（隐藏窗口，重定向输出）
                 let mut startup_info: STARTUPINFOW = std::mem::zeroed();
                 startup_info.cb = std::mem::size_of::<STARTUPINFOW>() as u32;
-                startup_info.dwFlags = STARTF_USESHOWWINDOW;
+                startup_info.dwFlags = STARTF_USESHOWWINDOW | STARTF_USESTDHANDLES;
                 startup_info.wShowWindow = SW_HIDE as u16;
+                startup_info.hStdOutput = stdout_write;
+                startup_info.hStdError = stderr_write;
 
                 let mut process_info: PROCESS_INFORMATION = std::mem::zeroed();
 
                 // 创建进程（挂起状态）
-                if CreateProcessW(
+                let created = CreateProcessW(
                     ptr::null(),
                     command_line_wide.as_mut_ptr(),
                     ptr::null_mut(),
                     ptr::null_mut(),
-                    FALSE,
-                    CREATE_NO_WINDOW | CREATE_SUSPENDED,
-                    ptr::null_mut(),
-                    ptr::null(),
+                    TRUE,
+                    CREATE_NO_WINDOW | CREATE_SUSPENDED | CREATE_UNICODE_ENVIRONMENT,
+                    environment_block
+                        .as_ref()
+                        .map_or(ptr::null_mut(), |block| block.as_ptr() as *mut _),
+                    working_dir_wide
+                        .as_ref()
+                        .map_or(ptr::null(), |dir| dir.as_ptr()),
                     &mut startup_info,
                     &mut process_info,
-                ) == FALSE
-                {
+                );
+
+                // 关闭本进程持有的写端，子进程退出后读端才能读到 EOF
+                CloseHandle(stdout_write);
+                CloseHandle(stderr_write);
+
+                if created == FALSE {
+                    CloseHandle(stdout_read);
+                    CloseHandle(stderr_read);
                     CloseHandle(job_handle);
-                    return Err("创建进程失败".to_string());
+                    return Err("创建进程失败".to_string().into());
                 }
 
+                // 读端交给 File 管理，读取线程结束时自动关闭
+                let stdout = std::fs::File::from_raw_handle(stdout_read as _);
+                let stderr = std::fs::File::from_raw_handle(stderr_read as _);
+
                 // 将进程分配到 Job Object
                 if AssignProcessToJobObject(job_handle, process_info.hProcess) == FALSE {
                     TerminateProcess(process_info.hProcess, 1);
                     CloseHandle(process_info.hProcess);
                     CloseHandle(process_info.hThread);
                     CloseHandle(job_handle);
-                    return Err("分配进程到 Job Object 失败".to_string());
+                    return Err("分配进程到 Job Object 失败".to_string().into());
+                }
+
+                // 进程挂起期间映像文件无法被修改，此时校验可确保被篡改的文件不会运行
+                if let Some(expected) = expected_sha256
+                    && let Err(failure) = precheck::verify_sha256(executable_path, expected)
+                {
+                    TerminateProcess(process_info.hProcess, 1);
+                    CloseHandle(process_info.hProcess);
+                    CloseHandle(process_info.hThread);
+                    CloseHandle(job_handle);
+                    return Err(failure.into());
                 }
 
                 // 恢复进程运行
@@ -142,16 +386,59 @@ impl ClashProcess {
                     CloseHandle(process_info.hProcess);
                     CloseHandle(process_info.hThread);
                     CloseHandle(job_handle);
-                    return Err("恢复进程线程失败".to_string());
+                    return Err("恢复进程线程失败".to_string().into());
                 }
 
                 let pid = process_info.dwProcessId;
                 CloseHandle(process_info.hThread);
 
+                if capture {
+                    output::reset();
+                }
+                let sink = log_file.clone().and_then(log_file::open);
+                output::spawn_reader(CoreOutputStream::Stdout, stdout, sink.clone(), capture);
+                output::spawn_reader(CoreOutputStream::Stderr, stderr, sink, capture);
+
+                // 复制进程句柄供等待线程使用，与停止操作持有的句柄互不影响
+                let mut wait_handle = ptr::null_mut();
+                if DuplicateHandle(
+                    GetCurrentProcess(),
+                    process_info.hProcess,
+                    GetCurrentProcess(),
+                    &mut wait_handle,
+                    0,
+                    FALSE,
+                    DUPLICATE_SAME_ACCESS,
+                ) == FALSE
+                {
+                    TerminateProcess(process_info.hProcess, 1);
+                    CloseHandle(process_info.hProcess);
+                    CloseHandle(job_handle);
+                    return Err("复制进程句柄失败".to_string().into());
+                }
+
+                let watch = Arc::new(ExitWatch::default());
+                let wait_handle = wait_handle as usize;
+                spawn_exit_waiter(pid, instance_tag.clone(), watch.clone(), move || {
+                    use winapi::um::processthreadsapi::GetExitCodeProcess;
+                    use winapi::um::synchapi::WaitForSingleObject;
+                    use winapi::um::winbase::INFINITE;
+
+                    // 复制句柄由等待线程独占，使用后关闭
+                    let handle = wait_handle as winapi::um::winnt::HANDLE;
+                    WaitForSingleObject(handle, INFINITE);
+                    let mut code = 0u32;
+                    let ok = GetExitCodeProcess(handle, &mut code) != FALSE;
+                    CloseHandle(handle);
+                    ok.then_some(code as i32)
+                });
+
                 Ok(ClashProcess {
                     process_handle: process_info.hProcess,
                     job_handle,
                     pid,
+                    started_at: Instant::now(),
+                    watch,
                 })
             }
         }
@@ -159,193 +446,675 @@ impl ClashProcess {
 
     // 获取进程 PID
     fn pid(&self) -> u32 {
-        #[cfg(unix)]
-        {
-            self.child.id()
-        }
-        #[cfg(windows)]
-        {
-            self.pid
-        }
+        self.pid
+    }
+
+    // 检查进程是否仍然存活 - Unix 实现
+    #[cfg(unix)]
+    fn is_alive(&self) -> bool {
+        use nix::sys::signal::kill;
+        use nix::unistd::Pid;
+
+        // 等待线程回收后 PID 可能被复用，优先以退出记录为准
+        !self.watch.has_exited() && kill(Pid::from_raw(self.pid as i32), None).is_ok()
+    }
+
+    // 检查进程是否仍然存活 - Windows 实现
+    #[cfg(windows)]
+    fn is_alive(&self) -> bool {
+        use winapi::um::minwinbase::STILL_ACTIVE;
+        use winapi::um::processthreadsapi::GetExitCodeProcess;
+
+        let mut code = 0u32;
+        // SAFETY: process_handle 在进程条目存在期间始终有效
+        let ok = unsafe { GetExitCodeProcess(self.process_handle, &mut code) } != 0;
+        ok && code == STILL_ACTIVE
     }
 
     // 停止进程 - Unix 实现
+    //
+    // 向整个进程组发送 SIGTERM，超过 force_after 后进程组仍未全部退出时发送 SIGKILL。
+    // 返回是否进行了强制终止
     #[cfg(unix)]
-    fn stop(mut self) -> Result<(), String> {
+    fn stop(self, force_after: Duration) -> Result<bool, String> {
         let pid = self.pid();
         log::info!("正在停止 Clash 进程，PID：{}", pid);
 
-        use nix::sys::signal::{Signal, kill};
+        use nix::sys::signal::{Signal, killpg};
         use nix::unistd::Pid;
 
-        // 发送 SIGTERM 信号
-        let nix_pid = Pid::from_raw(pid as i32);
-        if let Err(e) = kill(nix_pid, Signal::SIGTERM) {
-            log::error!("发送 SIGTERM 失败：{}", e);
+        self.watch.requested.store(true, Ordering::SeqCst);
+        // 核心以自身 PID 作为进程组 ID 启动
+        let pgid = Pid::from_raw(pid as i32);
+        let deadline = Instant::now() + force_after;
+
+        // 发送 SIGTERM 信号（进程已退出时跳过，避免误伤复用的 PID）
+        if !self.watch.has_exited()
+            && let Err(e) = killpg(pgid, Signal::SIGTERM)
+        {
+            log::error!("向进程组发送 SIGTERM 失败：{}", e);
         }
 
-        // 等待进程退出
-        match self.child.wait() {
-            Ok(status) => {
-                log::info!("进程已退出，状态：{:?}", status);
-                Ok(())
+        // 等待核心退出（由等待线程回收），再等待派生的子进程退出
+        if let Some(exit_code) = self.watch.wait_timeout(force_after) {
+            log::info!("进程已退出，退出码：{:?}", exit_code);
+            if wait_group_exit(pgid, deadline) {
+                return Ok(false);
             }
-            Err(e) => {
-                log::error!("等待进程退出失败：{}", e);
-                Err(format!("等待进程退出失败：{}", e))
+            log::warn!("核心已退出但进程组仍有残留进程，PGID：{}", pid);
+        }
+
+        log::warn!(
+            "进程组在 {} 毫秒内未响应 SIGTERM，强制终止，PGID：{}",
+            force_after.as_millis(),
+            pid
+        );
+        // 进程组内仍有成员时 PGID 不会被复用，核心已退出时也可安全发送
+        if let Err(e) = killpg(pgid, Signal::SIGKILL) {
+            log::debug!("向进程组发送 SIGKILL 失败：{}", e);
+        }
+
+        match self.watch.wait_timeout(FORCE_KILL_WAIT) {
+            Some(exit_code) => {
+                log::info!("进程已被强制终止，退出码：{:?}", exit_code);
+                Ok(true)
             }
+            None => Err(format!("强制终止后进程仍未退出，PID：{}", pid)),
         }
     }
 
     // 停止进程 - Windows 实现
+    //
+    // 关闭 Job Object 触发终止，超过 force_after 仍未退出时调用 TerminateProcess。
+    // 返回是否进行了强制终止
     #[cfg(windows)]
-    fn stop(self) -> Result<(), String> {
+    fn stop(self, force_after: Duration) -> Result<bool, String> {
         let pid = self.pid();
         log::info!("正在停止 Clash 进程，PID：{}", pid);
 
-        use std::time::Duration;
         use winapi::um::handleapi::CloseHandle;
+        use winapi::um::processthreadsapi::TerminateProcess;
         use winapi::um::synchapi::WaitForSingleObject;
         use winapi::um::winbase::WAIT_OBJECT_0;
 
+        self.watch.requested.store(true, Ordering::SeqCst);
+
         unsafe {
             // 关闭 Job Object 触发子进程自动终止
             CloseHandle(self.job_handle);
 
-            // 等待进程退出（最多 5 秒）
-            let timeout_ms = Duration::from_secs(5).as_millis() as u32;
-            let wait_result = WaitForSingleObject(self.process_handle, timeout_ms);
+            let timeout_ms = force_after.as_millis().min(u32::MAX as u128) as u32;
+            if WaitForSingleObject(self.process_handle, timeout_ms) == WAIT_OBJECT_0 {
+                log::info!("进程已安全退出");
+                CloseHandle(self.process_handle);
+                return Ok(false);
+            }
 
-            match wait_result {
-                WAIT_OBJECT_0 => {
-                    log::info!("进程已安全退出");
-                    CloseHandle(self.process_handle);
-                    Ok(())
-                }
-                _ => {
-                    log::warn!("进程在 5 秒后仍未退出");
-                    CloseHandle(self.process_handle);
-                    Ok(())
+            log::warn!(
+                "进程在 {} 毫秒后仍未退出，强制终止，PID：{}",
+                force_after.as_millis(),
+                pid
+            );
+            TerminateProcess(self.process_handle, 1);
+
+            let result =
+                if WaitForSingleObject(self.process_handle, FORCE_KILL_WAIT.as_millis() as u32)
+                    == WAIT_OBJECT_0
+                {
+                    log::info!("进程已被强制终止");
+                    Ok(true)
+                } else {
+                    Err(format!("强制终止后进程仍未退出，PID：{}", pid))
+                };
+            CloseHandle(self.process_handle);
+            result
+        }
+    }
+}
+
+// 构建 CreateProcessW 所需的 UTF-16 环境块
+//
+// 以当前进程环境为基础叠加覆盖项，按名称（不区分大小写）排序，
+// 格式为 "名称=值\0...\0\0"
+#[cfg(windows)]
+fn build_environment_block(overrides: &HashMap<String, String>) -> Vec<u16> {
+    use std::collections::BTreeMap;
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut vars: BTreeMap<String, (OsString, OsString)> = std::env::vars_os()
+        .map(|(key, value)| (key.to_string_lossy().to_uppercase(), (key, value)))
+        .collect();
+    for (key, value) in overrides {
+        vars.insert(key.to_uppercase(), (key.into(), value.into()));
+    }
+
+    let mut block = Vec::new();
+    for (key, value) in vars.values() {
+        block.extend(key.encode_wide());
+        block.push('=' as u16);
+        block.extend(value.encode_wide());
+        block.push(0);
+    }
+    block.push(0);
+    block
+}
+
+// 进程意外退出时释放句柄（Job Object 已无进程可终止）
+#[cfg(windows)]
+fn release_handles(process: ClashProcess) {
+    use winapi::um::handleapi::CloseHandle;
+
+    // SAFETY: 句柄由 start 创建，进程条目已从管理器移除，不会被重复关闭
+    unsafe {
+        CloseHandle(process.job_handle);
+        CloseHandle(process.process_handle);
+    }
+}
+
+// 等待进程组内的进程全部退出，超过 deadline 返回 false
+#[cfg(unix)]
+fn wait_group_exit(pgid: nix::unistd::Pid, deadline: Instant) -> bool {
+    use nix::sys::signal::killpg;
+
+    while killpg(pgid, None).is_ok() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    true
+}
+
+// 启动等待线程，进程退出后通知 Dart
+//
+// 非主动停止时从管理器移除进程条目；主实例还会清理网络资源，并按策略自动重启
+fn spawn_exit_waiter<F>(pid: u32, instance_tag: String, watch: Arc<ExitWatch>, wait: F)
+where
+    F: FnOnce() -> Option<i32> + Send + 'static,
+{
+    // 等待线程不在 Tokio 运行时内，需要借用句柄执行异步清理
+    let runtime = tokio::runtime::Handle::try_current().ok();
+
+    let result = std::thread::Builder::new()
+        .name("clash-exit-waiter".to_string())
+        .spawn(move || {
+            let exit_code = wait();
+            watch.mark_exited(exit_code);
+            let is_main = instance_tag == MAIN_INSTANCE_TAG;
+            if is_main {
+                pidfile::remove(pid);
+            }
+
+            let was_requested = watch.requested.load(Ordering::SeqCst);
+            if was_requested {
+                log::debug!(
+                    "Clash 进程已按请求退出，PID：{}（实例：{}）",
+                    pid,
+                    instance_tag
+                );
+            } else {
+                log::warn!(
+                    "Clash 进程意外退出，PID：{}，退出码：{:?}（实例：{}）",
+                    pid,
+                    exit_code,
+                    instance_tag
+                );
+                if is_main {
+                    if let Some(runtime) = &runtime
+                        && let Some(uptime) = handle_unexpected_exit(&watch, runtime)
+                        && auto_restart(uptime)
+                    {
+                        return;
+                    }
+                } else {
+                    manager::take_exited_blocking(&instance_tag, watch.clone());
                 }
             }
+
+            ClashProcessExited {
+                pid,
+                exit_code,
+                was_requested,
+                instance_tag,
+            }
+            .send_signal_to_dart();
+        });
+
+    if let Err(e) = result {
+        log::error!("创建进程等待线程失败：{}", e);
+    }
+}
+
+// 清理意外退出的主实例进程，返回其运行时长
+//
+// 进程条目已被其他操作移除（如正在停止或已重新启动）时返回 None
+fn handle_unexpected_exit(
+    watch: &Arc<ExitWatch>,
+    runtime: &tokio::runtime::Handle,
+) -> Option<Duration> {
+    let uptime = manager::take_exited_blocking(MAIN_INSTANCE_TAG, watch.clone())?;
+
+    // 同步清理，确保自动重启前连接池与 TUN 状态已复位；
+    // WebSocket 流保留订阅，自动重启后重连，未重启时重连失败达到上限后放弃
+    log::info!("开始清理网络资源（进程意外退出）");
+    runtime.block_on(super::network::handlers::cleanup_ipc_connection_pool());
+    log::info!("网络资源清理完成（进程意外退出）");
+
+    if let Some(device) = super::tun::take_active_device() {
+        runtime
+            .block_on(super::tun::verify(device, false))
+            .send_signal_to_dart();
+    }
+
+    Some(uptime)
+}
+
+// 按策略自动重启，成功时返回 true（此时不再发送退出信号）
+fn auto_restart(uptime: Duration) -> bool {
+    let generation = restart::generation();
+    if uptime >= restart::STABLE_UPTIME {
+        restart::reset_attempts();
+    }
+
+    while let Some((attempt, delay)) = restart::next_attempt() {
+        log::info!(
+            "{} 毫秒后自动重启 Clash 进程（第 {} 次）",
+            delay.as_millis(),
+            attempt
+        );
+        std::thread::sleep(delay);
+
+        let Some(launch_params) = LAST_LAUNCH
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+        else {
+            return false;
+        };
+
+        let Some(result) = manager::auto_restart_blocking(generation, launch_params) else {
+            // 手动操作会发送自己的结果信号
+            log::info!("等待期间发生手动启停，取消自动重启");
+            return true;
+        };
+        if let (true, Some(pid)) = (result.success, result.pid) {
+            ClashProcessRestarted { attempt, pid }.send_signal_to_dart();
+            return true;
         }
     }
+
+    false
 }
 
 // 处理启动 Clash 进程的请求
 impl StartClashProcess {
-    pub fn handle(&self) {
-        log::info!("收到启动 Clash 进程请求");
+    pub async fn handle(&self) {
+        let instance_tag = tag_or_main(&self.instance_tag);
+        log::info!("收到启动 Clash 进程请求（实例：{}）", instance_tag);
+        let is_main = instance_tag == MAIN_INSTANCE_TAG;
+        if is_main {
+            restart::on_manual_operation();
+        }
 
-        let mut manager = PROCESS_MANAGER.lock().unwrap_or_else(|e| {
-            log::error!("获取进程管理器锁失败：{}", e);
-            e.into_inner()
-        });
+        let mut result = manager::start(LaunchParams {
+            executable_path: self.executable_path.clone(),
+            args: self.args.clone(),
+            env: self.env.clone(),
+            working_dir: self.working_dir.clone(),
+            log_file: self
+                .log_file_dir
+                .as_ref()
+                .map(|dir| log_file::LogFileOptions {
+                    dir: dir.into(),
+                    file_name: log_file::file_name(&instance_tag),
+                    max_file_bytes: self
+                        .max_file_bytes
+                        .unwrap_or(log_file::DEFAULT_MAX_FILE_BYTES),
+                    max_files: self.max_files.unwrap_or(log_file::DEFAULT_MAX_FILES),
+                }),
+            expected_sha256: self.expected_sha256.clone(),
+            instance_tag,
+        })
+        .await;
 
-        // 检查是否已有进程在运行
-        if manager.is_some() {
-            log::warn!("Clash 进程已在运行");
-            ClashProcessResult {
-                success: false,
-                error_message: Some("进程已在运行".to_string()),
-                pid: None,
-            }
-            .send_signal_to_dart();
+        // 控制接口连接只指向主实例
+        if let (true, true, Some(wait_ready_ms)) = (is_main, result.success, self.wait_ready_ms) {
+            wait_ready(&mut result, Duration::from_millis(wait_ready_ms)).await;
+        }
+
+        result.send_signal_to_dart();
+    }
+}
+
+// 轮询控制接口（GET /version）直到就绪、进程退出或超时
+async fn wait_ready(result: &mut ClashProcessResult, timeout: Duration) {
+    let Some(pid) = result.pid else {
+        return;
+    };
+    // 进程条目已不存在说明核心在返回结果前就已退出
+    let watch = match manager::current(MAIN_INSTANCE_TAG).await {
+        Some((current, watch)) if current == pid => Some(watch),
+        _ => None,
+    };
+
+    let started = Instant::now();
+    loop {
+        if watch.as_ref().is_none_or(|watch| watch.has_exited()) {
+            log::error!("Clash 进程在就绪前退出，PID：{}", pid);
+            result.success = false;
+            result.pid = None;
+            result.error_message = Some(exit_output().await);
             return;
         }
 
-        // 启动新进程
-        match ClashProcess::start(self.executable_path.clone(), self.args.clone()) {
-            Ok(process) => {
-                let pid = process.pid();
-                *manager = Some(process);
-
-                log::info!("Clash 进程启动成功，PID：{}", pid);
-                ClashProcessResult {
-                    success: true,
-                    error_message: None,
-                    pid: Some(pid),
-                }
-                .send_signal_to_dart();
-            }
-            Err(e) => {
-                log::error!("启动 Clash 进程失败：{}", e);
-                ClashProcessResult {
-                    success: false,
-                    error_message: Some(e),
-                    pid: None,
-                }
+        if let Ok(response) =
+            super::network::handlers::send_ipc_request("GET", "/version", None).await
+            && response.status_code == 200
+        {
+            let elapsed = started.elapsed().as_millis() as u64;
+            log::info!("Clash 控制接口已就绪，耗时 {} 毫秒", elapsed);
+            result.ready = true;
+            result.ready_after_ms = Some(elapsed);
+            tokio::spawn(super::network::handlers::prewarm_ipc_pool(
+                super::network::handlers::DEFAULT_PREWARM_CONNECTIONS,
+            ));
+            return;
+        }
+
+        if started.elapsed() >= timeout {
+            log::warn!(
+                "等待 Clash 控制接口就绪超时（{} 毫秒）",
+                timeout.as_millis()
+            );
+            return;
+        }
+        tokio::time::sleep(READY_POLL_INTERVAL).await;
+    }
+}
+
+// 核心启动即退出时的错误信息，优先使用 stderr 输出
+async fn exit_output() -> String {
+    // 进程退出后读取线程可能仍有未处理的输出
+    tokio::time::sleep(READY_POLL_INTERVAL).await;
+
+    let lines = output::tail();
+    let stderr: Vec<_> = lines
+        .iter()
+        .filter(|line| line.stream == CoreOutputStream::Stderr)
+        .collect();
+    let source = if stderr.is_empty() {
+        lines.iter().collect()
+    } else {
+        stderr
+    };
+
+    let skip = source.len().saturating_sub(EXIT_OUTPUT_LINES);
+    let text = source
+        .iter()
+        .skip(skip)
+        .map(|line| line.line.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if text.is_empty() {
+        "Clash 进程启动后立即退出".to_string()
+    } else {
+        format!("Clash 进程启动后立即退出：\n{}", text)
+    }
+}
+
+// 处理重启 Clash 进程的请求
+//
+// 由管理器在一次请求内完成停止、等待退出和启动，避免与其他启停请求交错
+impl RestartClashProcess {
+    pub async fn handle(&self) {
+        log::info!("收到重启 Clash 进程请求");
+
+        if RESTART_IN_PROGRESS
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            log::warn!("已有重启操作正在进行，拒绝本次请求");
+            ClashProcessResult::failure("已有重启操作正在进行，请稍后重试".to_string(), None)
                 .send_signal_to_dart();
+            return;
+        }
+
+        restart::on_manual_operation();
+        let result = self.restart().await;
+        RESTART_IN_PROGRESS.store(false, Ordering::SeqCst);
+        result.send_signal_to_dart();
+    }
+
+    async fn restart(&self) -> ClashProcessResult {
+        // 未提供的参数沿用上次启动时的值
+        let last_launch = LAST_LAUNCH
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        // 日志文件配置不在重启消息中，始终沿用
+        let log_file = last_launch.as_ref().and_then(|last| last.log_file.clone());
+        // 核心路径不变时沿用上次的校验值
+        let expected_sha256 = last_launch
+            .as_ref()
+            .filter(|last| {
+                self.executable_path
+                    .as_ref()
+                    .is_none_or(|path| *path == last.executable_path)
+            })
+            .and_then(|last| last.expected_sha256.clone());
+        let launch_params = match (last_launch, &self.executable_path, &self.args) {
+            (_, Some(executable_path), Some(args)) => Some(LaunchParams {
+                instance_tag: MAIN_INSTANCE_TAG.to_string(),
+                executable_path: executable_path.clone(),
+                args: args.clone(),
+                env: self.env.clone().unwrap_or_default(),
+                working_dir: self.working_dir.clone().unwrap_or_default(),
+                log_file,
+                expected_sha256,
+            }),
+            (Some(last), executable_path, args) => Some(LaunchParams {
+                instance_tag: last.instance_tag,
+                executable_path: executable_path.clone().unwrap_or(last.executable_path),
+                args: args.clone().unwrap_or(last.args),
+                env: self.env.clone().unwrap_or(last.env),
+                working_dir: self.working_dir.clone().unwrap_or(last.working_dir),
+                log_file,
+                expected_sha256,
+            }),
+            (None, _, _) => None,
+        };
+        let Some(launch_params) = launch_params else {
+            return ClashProcessResult::failure(
+                "没有可复用的启动参数，请提供核心路径和参数".to_string(),
+                None,
+            );
+        };
+
+        manager::restart(launch_params).await
+    }
+}
+
+// 处理查询 Clash 进程状态的请求
+impl GetClashProcessStatus {
+    pub async fn handle(&self) {
+        manager::status(tag_or_main(&self.instance_tag))
+            .await
+            .send_signal_to_dart();
+    }
+}
+
+// 启动新进程（阻塞，由管理器在 spawn_blocking 中调用）
+//
+// tun_device 为配置中启用的 TUN 设备名（仅主实例），启动成功后记录用于停止后清理。
+// 返回启动成功的进程与发送给 Dart 的结果
+fn launch(
+    launch_params: LaunchParams,
+    tun_device: Option<String>,
+) -> (Option<ClashProcess>, ClashProcessResult) {
+    let is_main = launch_params.is_main();
+
+    // 应用上次异常退出时遗留的核心会占用端口，启动前先终止
+    let orphan_cleaned =
+        is_main && pidfile::cleanup_orphan(&launch_params.executable_path, DEFAULT_FORCE_AFTER);
+
+    if let Err((start_error, message)) = precheck::check(&launch_params) {
+        log::error!("Clash 进程启动前检查失败：{}", message);
+        let result = ClashProcessResult {
+            orphan_cleaned,
+            start_error: Some(start_error),
+            ..ClashProcessResult::failure(message, None)
+        };
+        return (None, result);
+    }
+
+    // 启动新进程
+    match ClashProcess::start(&launch_params) {
+        Ok(process) => {
+            let pid = process.pid();
+            if is_main {
+                pidfile::write(pid, &launch_params.executable_path);
+                super::tun::set_active_device(tun_device);
+                *LAST_LAUNCH.lock().unwrap_or_else(|e| e.into_inner()) = Some(launch_params);
             }
+
+            log::info!("Clash 进程启动成功，PID：{}", pid);
+            let result = ClashProcessResult {
+                orphan_cleaned,
+                ..ClashProcessResult::started(Some(pid))
+            };
+            (Some(process), result)
+        }
+        Err(failure) => {
+            log::error!("启动 Clash 进程失败：{}", failure.message);
+            let result = ClashProcessResult {
+                orphan_cleaned,
+                start_error: failure.start_error,
+                ..ClashProcessResult::failure(failure.message, None)
+            };
+            (None, result)
         }
     }
 }
 
 // 处理停止 Clash 进程的请求
 impl StopClashProcess {
-    pub fn handle(&self) {
-        log::info!("收到停止 Clash 进程请求");
+    pub async fn handle(&self) {
+        let instance_tag = tag_or_main(&self.instance_tag);
+        log::info!("收到停止 Clash 进程请求（实例：{}）", instance_tag);
+        if instance_tag == MAIN_INSTANCE_TAG {
+            restart::on_manual_operation();
+        }
 
-        let mut manager = PROCESS_MANAGER.lock().unwrap_or_else(|e| {
-            log::error!("获取进程管理器锁失败：{}", e);
-            e.into_inner()
-        });
+        let force_after = self
+            .force_after_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_FORCE_AFTER);
 
-        match manager.take() {
-            Some(process) => match process.stop() {
-                Ok(()) => {
-                    log::info!("Clash 进程已停止");
-
-                    // 异步清理网络资源（IPC 连接池和 WebSocket）
-                    tokio::spawn(async {
-                        log::info!("开始清理网络资源");
-                        super::network::handlers::cleanup_all_network_resources().await;
-                        log::info!("网络资源清理完成");
-                    });
-
-                    ClashProcessResult {
-                        success: true,
-                        error_message: None,
-                        pid: None,
-                    }
-                    .send_signal_to_dart();
-                }
-                Err(e) => {
-                    log::error!("停止 Clash 进程失败：{}", e);
-                    ClashProcessResult {
-                        success: false,
-                        error_message: Some(e),
-                        pid: None,
-                    }
-                    .send_signal_to_dart();
-                }
-            },
-            None => {
-                log::warn!("没有运行中的 Clash 进程");
-                ClashProcessResult {
-                    success: true,
-                    error_message: None,
-                    pid: None,
-                }
-                .send_signal_to_dart();
-            }
+        manager::stop(instance_tag, force_after)
+            .await
+            .send_signal_to_dart();
+    }
+}
+
+// 空标签视为主实例
+fn tag_or_main(instance_tag: &str) -> String {
+    if instance_tag.is_empty() {
+        MAIN_INSTANCE_TAG.to_string()
+    } else {
+        instance_tag.to_string()
+    }
+}
+
+// 处理获取核心最近输出的请求
+impl GetCoreOutputTail {
+    pub fn handle(&self) {
+        CoreOutputTailResponse {
+            lines: output::tail(),
         }
+        .send_signal_to_dart();
     }
 }
 
-// 清理资源（应用退出时调用）
-pub fn cleanup() {
+// 初始化进程管理器
+pub fn init() {
+    manager::init();
+}
+
+// 清理资源（应用退出时调用，返回时所有实例的核心进程均已退出）
+pub async fn cleanup() {
     log::info!("清理 Clash 进程管理器…");
+    manager::shutdown().await;
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
 
-    let mut manager = PROCESS_MANAGER.lock().unwrap_or_else(|e| {
-        log::error!("获取进程管理器锁失败：{}", e);
-        e.into_inner()
-    });
+    // 进程存在且不是僵尸进程（容器内的 init 可能不会及时回收孤儿进程）
+    fn is_running(pid: u32) -> bool {
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| {
+                let state = stat.rsplit(')').next()?.trim_start().chars().next()?;
+                Some(state != 'Z')
+            })
+            .unwrap_or(false)
+    }
 
-    if let Some(process) = manager.take() {
-        log::info!("发现运行中的 Clash 进程，正在清理…");
-        if let Err(e) = process.stop() {
-            log::error!("清理 Clash 进程失败：{}", e);
+    fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !condition() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(20));
         }
+        true
+    }
+
+    #[test]
+    fn test_stop_kills_process_group() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("stelliberty-pgid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let pid_file = dir.join("sleeper.pid");
+
+        // 派生一个忽略 SIGTERM 的子进程，只有向进程组发送 SIGKILL 才能终止它
+        let script = format!(
+            "(trap '' TERM; exec sleep 300) & echo $! > '{}'; wait",
+            pid_file.display()
+        );
+        let process = ClashProcess::start(&LaunchParams {
+            instance_tag: MAIN_INSTANCE_TAG.to_string(),
+            executable_path: "/bin/sh".to_string(),
+            args: vec!["-c".to_string(), script],
+            env: HashMap::new(),
+            working_dir: String::new(),
+            log_file: None,
+            expected_sha256: None,
+        })
+        .map_err(|failure| failure.message)?;
+
+        let mut sleeper_pid = None;
+        let started = wait_until(Duration::from_secs(5), || {
+            sleeper_pid = std::fs::read_to_string(&pid_file)
+                .ok()
+                .and_then(|content| content.trim().parse::<u32>().ok());
+            sleeper_pid.is_some_and(is_running)
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+        let Some(sleeper_pid) = sleeper_pid.filter(|_| started) else {
+            let _ = process.stop(Duration::ZERO);
+            return Err("子进程未启动".to_string());
+        };
+
+        let forced = process.stop(Duration::from_millis(500))?;
+        assert!(forced, "忽略 SIGTERM 的子进程应触发强制终止");
+        assert!(
+            wait_until(FORCE_KILL_WAIT, || !is_running(sleeper_pid)),
+            "进程组内的子进程 {} 仍在运行",
+            sleeper_pid
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,186 @@
+// Clash 核心日志文件
+//
+// 目的：将捕获的核心输出写入 clash-core.log（其他实例为 clash-core-<标签>.log）并按大小轮转，
+// 便于用户提交问题时附带完整日志。写入在异步任务中进行，不阻塞读取线程
+
+use super::super::signals::MAIN_INSTANCE_TAG;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+
+const LOG_FILE_NAME: &str = "clash-core.log";
+
+// 默认单个文件 10 MB，保留 5 个文件（含当前文件）
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+pub const DEFAULT_MAX_FILES: u32 = 5;
+
+// 日志文件配置
+#[derive(Clone, Debug)]
+pub struct LogFileOptions {
+    pub dir: PathBuf,
+    pub file_name: String,
+    pub max_file_bytes: u64,
+    // 保留的文件数量（含当前文件），为 1 时轮转直接清空当前文件
+    pub max_files: u32,
+}
+
+// 实例对应的日志文件名，主实例沿用原文件名
+pub fn file_name(instance_tag: &str) -> String {
+    if instance_tag == MAIN_INSTANCE_TAG {
+        LOG_FILE_NAME.to_string()
+    } else {
+        format!("clash-core-{}.log", instance_tag)
+    }
+}
+
+// 日志写入端，由各读取线程持有；全部释放后写入任务刷新并同步文件后退出
+pub type LogSink = mpsc::UnboundedSender<String>;
+
+// 启动写入任务（需在 Tokio 运行时上下文中调用）
+pub fn open(options: LogFileOptions) -> Option<LogSink> {
+    let runtime = match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            log::warn!("无法启动核心日志写入任务：{}", e);
+            return None;
+        }
+    };
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    runtime.spawn(async move {
+        if let Err(e) = run(options, receiver).await {
+            log::warn!("写入核心日志文件失败，停止写入：{}", e);
+        }
+    });
+    Some(sender)
+}
+
+async fn run(
+    options: LogFileOptions,
+    mut receiver: mpsc::UnboundedReceiver<String>,
+) -> Result<(), String> {
+    tokio::fs::create_dir_all(&options.dir)
+        .await
+        .map_err(|e| format!("创建日志目录失败：{}", e))?;
+    let path = options.dir.join(&options.file_name);
+    let mut writer = LogWriter::open(path, options).await?;
+
+    while let Some(line) = receiver.recv().await {
+        writer.write_line(&line).await?;
+        // 批量写入当前积压的行后再刷新
+        while let Ok(line) = receiver.try_recv() {
+            writer.write_line(&line).await?;
+        }
+        writer.flush().await?;
+    }
+
+    // 所有读取线程已结束（进程已退出）
+    writer.sync().await
+}
+
+struct LogWriter {
+    path: PathBuf,
+    options: LogFileOptions,
+    // 轮转期间为 None（Windows 无法重命名已打开的文件）
+    file: Option<BufWriter<File>>,
+    size: u64,
+}
+
+impl LogWriter {
+    async fn open(path: PathBuf, options: LogFileOptions) -> Result<Self, String> {
+        let file = open_append(&path).await?;
+        // 继续写入上次的文件
+        let size = file.metadata().await.map(|meta| meta.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            options,
+            file: Some(BufWriter::new(file)),
+            size,
+        })
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<(), String> {
+        let len = line.len() as u64 + 1;
+        // 当前文件为空时即使单行超过上限也直接写入，避免反复轮转
+        if self.size > 0 && self.size + len > self.options.max_file_bytes {
+            self.rotate().await?;
+        }
+
+        let file = self.file()?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| format!("写入日志失败：{}", e))?;
+        file.write_all(b"\n")
+            .await
+            .map_err(|e| format!("写入日志失败：{}", e))?;
+        self.size += len;
+        Ok(())
+    }
+
+    fn file(&mut self) -> Result<&mut BufWriter<File>, String> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| "日志文件未打开".to_string())
+    }
+
+    async fn flush(&mut self) -> Result<(), String> {
+        self.file()?
+            .flush()
+            .await
+            .map_err(|e| format!("刷新日志失败：{}", e))
+    }
+
+    async fn sync(&mut self) -> Result<(), String> {
+        self.flush().await?;
+        self.file()?
+            .get_ref()
+            .sync_all()
+            .await
+            .map_err(|e| format!("同步日志文件失败：{}", e))
+    }
+
+    // clash-core.log → .1 → .2 …，超出数量的最旧文件被覆盖
+    async fn rotate(&mut self) -> Result<(), String> {
+        self.sync().await?;
+        self.file = None;
+        self.size = 0;
+
+        let backups = self.options.max_files.saturating_sub(1);
+        if backups == 0 {
+            let file = File::create(&self.path)
+                .await
+                .map_err(|e| format!("清空日志文件失败：{}", e))?;
+            self.file = Some(BufWriter::new(file));
+            return Ok(());
+        }
+
+        for index in (1..backups).rev() {
+            let from = backup_path(&self.path, index);
+            if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+                let _ = tokio::fs::rename(&from, backup_path(&self.path, index + 1)).await;
+            }
+        }
+        tokio::fs::rename(&self.path, backup_path(&self.path, 1))
+            .await
+            .map_err(|e| format!("轮转日志文件失败：{}", e))?;
+
+        self.file = Some(BufWriter::new(open_append(&self.path).await?));
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|e| format!("打开日志文件失败：{}", e))
+}
+
+fn backup_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
@@ -0,0 +1,392 @@
+// Clash 进程管理 Actor
+//
+// 目的：由单个异步任务独占所有 ClashProcess（按实例标签区分），启停与查询请求通过通道串行投递，
+// 停止等待与启动等阻塞操作放到 spawn_blocking，不再占用 Tokio 工作线程
+
+use super::super::signals::{ClashProcessResult, ClashProcessStatusResponse, MAIN_INSTANCE_TAG};
+use super::{ClashProcess, DEFAULT_FORCE_AFTER, ExitWatch, LaunchParams, launch, restart};
+use once_cell::sync::OnceCell;
+use rinf::RustSignal;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+// 同时运行的核心实例上限（含主实例）
+const MAX_INSTANCES: usize = 3;
+
+static COMMANDS: OnceCell<mpsc::UnboundedSender<Command>> = OnceCell::new();
+
+enum Command {
+    Start {
+        params: LaunchParams,
+        reply: oneshot::Sender<ClashProcessResult>,
+    },
+    Stop {
+        tag: String,
+        force_after: Duration,
+        reply: oneshot::Sender<ClashProcessResult>,
+    },
+    // 重启主实例
+    Restart {
+        params: LaunchParams,
+        reply: oneshot::Sender<ClashProcessResult>,
+    },
+    Status {
+        tag: String,
+        reply: oneshot::Sender<ClashProcessStatusResponse>,
+    },
+    Current {
+        tag: String,
+        reply: oneshot::Sender<Option<(u32, Arc<ExitWatch>)>>,
+    },
+    // 等待线程上报进程意外退出
+    TakeExited {
+        tag: String,
+        watch: Arc<ExitWatch>,
+        reply: oneshot::Sender<Option<Duration>>,
+    },
+    // 自动重启主实例（期间发生手动启停时回复 None）
+    AutoRestart {
+        generation: u64,
+        params: LaunchParams,
+        reply: oneshot::Sender<Option<ClashProcessResult>>,
+    },
+    Shutdown {
+        reply: oneshot::Sender<()>,
+    },
+}
+
+// 启动 Actor 任务（需在 Tokio 运行时内调用）
+pub fn init() {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    if COMMANDS.set(sender).is_err() {
+        log::warn!("Clash 进程管理器已初始化");
+        return;
+    }
+    tokio::spawn(run(receiver));
+}
+
+fn send<T>(make: impl FnOnce(oneshot::Sender<T>) -> Command) -> Option<oneshot::Receiver<T>> {
+    let (reply, receiver) = oneshot::channel();
+    let sender = COMMANDS.get()?;
+    sender.send(make(reply)).ok()?;
+    Some(receiver)
+}
+
+async fn request<T>(make: impl FnOnce(oneshot::Sender<T>) -> Command) -> Option<T> {
+    send(make)?.await.ok()
+}
+
+// 供运行时之外的线程（等待线程、采样线程）使用
+fn request_blocking<T>(make: impl FnOnce(oneshot::Sender<T>) -> Command) -> Option<T> {
+    send(make)?.blocking_recv().ok()
+}
+
+fn unavailable() -> ClashProcessResult {
+    error_result("进程管理器不可用".to_string())
+}
+
+fn error_result(message: String) -> ClashProcessResult {
+    ClashProcessResult::failure(message, None)
+}
+
+pub async fn start(params: LaunchParams) -> ClashProcessResult {
+    request(|reply| Command::Start { params, reply })
+        .await
+        .unwrap_or_else(unavailable)
+}
+
+pub async fn stop(tag: String, force_after: Duration) -> ClashProcessResult {
+    request(|reply| Command::Stop {
+        tag,
+        force_after,
+        reply,
+    })
+    .await
+    .unwrap_or_else(unavailable)
+}
+
+pub async fn restart(params: LaunchParams) -> ClashProcessResult {
+    request(|reply| Command::Restart { params, reply })
+        .await
+        .unwrap_or_else(unavailable)
+}
+
+pub async fn status(tag: String) -> ClashProcessStatusResponse {
+    let fallback = not_running(&tag);
+    request(|reply| Command::Status { tag, reply })
+        .await
+        .unwrap_or(fallback)
+}
+
+pub async fn current(tag: &str) -> Option<(u32, Arc<ExitWatch>)> {
+    let tag = tag.to_string();
+    request(|reply| Command::Current { tag, reply })
+        .await
+        .flatten()
+}
+
+pub fn take_exited_blocking(tag: &str, watch: Arc<ExitWatch>) -> Option<Duration> {
+    let tag = tag.to_string();
+    request_blocking(|reply| Command::TakeExited { tag, watch, reply }).flatten()
+}
+
+pub fn auto_restart_blocking(generation: u64, params: LaunchParams) -> Option<ClashProcessResult> {
+    request_blocking(|reply| Command::AutoRestart {
+        generation,
+        params,
+        reply,
+    })
+    .flatten()
+}
+
+pub async fn shutdown() {
+    let _ = request(|reply| Command::Shutdown { reply }).await;
+}
+
+async fn run(mut receiver: mpsc::UnboundedReceiver<Command>) {
+    let mut processes: HashMap<String, ClashProcess> = HashMap::new();
+
+    while let Some(command) = receiver.recv().await {
+        match command {
+            Command::Start { params, reply } => {
+                let tag = params.instance_tag.clone();
+                let result = if !is_valid_tag(&tag) {
+                    error_result("实例标签只能包含字母、数字、- 和 _".to_string())
+                } else if processes.contains_key(&tag) {
+                    log::warn!("Clash 进程已在运行（实例：{}）", tag);
+                    error_result("进程已在运行".to_string())
+                } else if processes.len() >= MAX_INSTANCES {
+                    log::warn!("Clash 实例数量已达上限（{}）", MAX_INSTANCES);
+                    error_result(format!("最多同时运行 {} 个核心实例", MAX_INSTANCES))
+                } else {
+                    let (started, result) = launch_blocking(params).await;
+                    if let Some(started) = started {
+                        processes.insert(tag, started);
+                    }
+                    result
+                };
+                let _ = reply.send(result);
+            }
+
+            Command::Stop {
+                tag,
+                force_after,
+                reply,
+            } => {
+                let result = handle_stop(&tag, processes.remove(&tag), force_after).await;
+                let _ = reply.send(result);
+            }
+
+            Command::Restart { params, reply } => {
+                let result = match handle_restart_stop(processes.remove(MAIN_INSTANCE_TAG)).await {
+                    Ok(()) => {
+                        let (started, result) = launch_blocking(params).await;
+                        if let Some(started) = started {
+                            processes.insert(MAIN_INSTANCE_TAG.to_string(), started);
+                        }
+                        result
+                    }
+                    Err(result) => result,
+                };
+                let _ = reply.send(result);
+            }
+
+            Command::Status { tag, reply } => {
+                let response = match processes.get(&tag) {
+                    Some(running) if running.is_alive() => ClashProcessStatusResponse {
+                        running: true,
+                        pid: Some(running.pid()),
+                        uptime_seconds: Some(running.started_at.elapsed().as_secs()),
+                        instance_tag: tag,
+                    },
+                    _ => {
+                        // 记录中的进程已退出，清理条目使状态自愈
+                        if let Some(exited) = processes.remove(&tag) {
+                            log::warn!(
+                                "Clash 进程已不存在，清理记录，PID：{}（实例：{}）",
+                                exited.pid(),
+                                tag
+                            );
+                            release(exited);
+                        }
+                        not_running(&tag)
+                    }
+                };
+                let _ = reply.send(response);
+            }
+
+            Command::Current { tag, reply } => {
+                let current = processes
+                    .get(&tag)
+                    .map(|running| (running.pid(), running.watch.clone()));
+                let _ = reply.send(current);
+            }
+
+            Command::TakeExited { tag, watch, reply } => {
+                // 仅移除上报线程对应的进程，避免误删已重新启动的新进程
+                let uptime = match processes.get(&tag) {
+                    Some(running) if Arc::ptr_eq(&running.watch, &watch) => {
+                        processes.remove(&tag).map(|exited| {
+                            let uptime = exited.started_at.elapsed();
+                            release(exited);
+                            uptime
+                        })
+                    }
+                    _ => None,
+                };
+                let _ = reply.send(uptime);
+            }
+
+            Command::AutoRestart {
+                generation,
+                params,
+                reply,
+            } => {
+                let result = if restart::generation() != generation
+                    || processes.contains_key(MAIN_INSTANCE_TAG)
+                {
+                    None
+                } else {
+                    let (started, result) = launch_blocking(params).await;
+                    if let Some(started) = started {
+                        processes.insert(MAIN_INSTANCE_TAG.to_string(), started);
+                    }
+                    Some(result)
+                };
+                let _ = reply.send(result);
+            }
+
+            Command::Shutdown { reply } => {
+                // 并行停止所有实例，避免逐个等待优雅退出
+                let stops = processes.drain().map(|(tag, running)| async move {
+                    log::info!("发现运行中的 Clash 进程，正在清理…（实例：{}）", tag);
+                    if let Err(e) = stop_blocking(running, DEFAULT_FORCE_AFTER).await {
+                        log::error!("清理 Clash 进程失败（实例：{}）：{}", tag, e);
+                    }
+                });
+                futures_util::future::join_all(stops).await;
+                let _ = reply.send(());
+            }
+        }
+    }
+
+    log::info!("Clash 进程管理器通道已关闭，退出");
+}
+
+// 标签会用于日志文件名，仅允许安全字符
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= 32
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn not_running(tag: &str) -> ClashProcessStatusResponse {
+    ClashProcessStatusResponse {
+        running: false,
+        pid: None,
+        uptime_seconds: None,
+        instance_tag: tag.to_string(),
+    }
+}
+
+async fn launch_blocking(params: LaunchParams) -> (Option<ClashProcess>, ClashProcessResult) {
+    // TUN 模式启动前检测残留状态（仅主实例接管 TUN）
+    let tun_device = if params.is_main() {
+        let args = params.args.clone();
+        tokio::task::spawn_blocking(move || super::super::tun::detect_tun_device(&args))
+            .await
+            .ok()
+            .flatten()
+    } else {
+        None
+    };
+    if let Some(device) = &tun_device {
+        super::super::tun::run_preflight(device.clone()).await;
+    }
+
+    tokio::task::spawn_blocking(move || launch(params, tun_device))
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("启动进程的任务执行失败：{}", e);
+            (None, error_result(format!("任务执行失败：{}", e)))
+        })
+}
+
+async fn stop_blocking(process: ClashProcess, force_after: Duration) -> Result<bool, String> {
+    tokio::task::spawn_blocking(move || process.stop(force_after))
+        .await
+        .unwrap_or_else(|e| Err(format!("任务执行失败：{}", e)))
+}
+
+async fn handle_stop(
+    tag: &str,
+    process: Option<ClashProcess>,
+    force_after: Duration,
+) -> ClashProcessResult {
+    let Some(process) = process else {
+        log::warn!("没有运行中的 Clash 进程（实例：{}）", tag);
+        return ClashProcessResult::stopped(None, false);
+    };
+
+    match stop_blocking(process, force_after).await {
+        Ok(forced_kill) => {
+            log::info!("Clash 进程已停止（实例：{}）", tag);
+
+            // 网络资源与 TUN 仅属于主实例
+            if tag == MAIN_INSTANCE_TAG {
+                // 异步清理网络资源（IPC 连接池和 WebSocket）
+                tokio::spawn(async {
+                    log::info!("开始清理网络资源");
+                    super::super::network::handlers::cleanup_all_network_resources().await;
+                    log::info!("网络资源清理完成");
+                });
+
+                // 检测并清理 TUN 残留
+                super::super::tun::spawn_post_stop_verification();
+            }
+
+            ClashProcessResult::stopped(None, forced_kill)
+        }
+        Err(e) => {
+            // 仅在强制终止后仍未退出时失败
+            log::error!("停止 Clash 进程失败：{}", e);
+            ClashProcessResult {
+                forced_kill: true,
+                ..error_result(e)
+            }
+        }
+    }
+}
+
+// 重启前停止旧进程，并在启动新核心前清理网络资源与 TUN 残留
+async fn handle_restart_stop(process: Option<ClashProcess>) -> Result<(), ClashProcessResult> {
+    let Some(process) = process else {
+        return Ok(());
+    };
+
+    if let Err(e) = stop_blocking(process, DEFAULT_FORCE_AFTER).await {
+        log::error!("重启时停止 Clash 进程失败：{}", e);
+        return Err(error_result(e));
+    }
+
+    // 旧核心的连接已失效，需在新核心启动前完成清理；
+    // WebSocket 流保留订阅，由客户端在新核心就绪后自动重连
+    super::super::network::handlers::cleanup_ipc_connection_pool().await;
+    if let Some(device) = super::super::tun::take_active_device() {
+        super::super::tun::verify(device, false)
+            .await
+            .send_signal_to_dart();
+    }
+    Ok(())
+}
+
+fn release(process: ClashProcess) {
+    #[cfg(windows)]
+    super::release_handles(process);
+    #[cfg(not(windows))]
+    drop(process);
+}
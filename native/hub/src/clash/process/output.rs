@@ -0,0 +1,100 @@
+// Clash 核心输出捕获
+//
+// 目的：逐行读取核心的 stdout/stderr 并转发给 Dart，
+// 同时保留最近的输出，便于核心启动即退出时排查配置错误
+
+use super::super::signals::{ClashCoreOutput, CoreOutputLine, CoreOutputStream};
+use super::log_file::LogSink;
+use once_cell::sync::Lazy;
+use rinf::RustSignal;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::Mutex;
+
+// 保留的最近输出行数
+const TAIL_CAPACITY: usize = 500;
+
+static OUTPUT_TAIL: Lazy<Mutex<VecDeque<CoreOutputLine>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(TAIL_CAPACITY)));
+
+// 新进程启动前清空上一次的输出
+pub fn reset() {
+    OUTPUT_TAIL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+// 获取最近的输出
+pub fn tail() -> Vec<CoreOutputLine> {
+    OUTPUT_TAIL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
+
+// 启动读取线程，提供 sink 时同时写入日志文件
+//
+// capture 为 false 时（非主实例）不写入最近输出也不推送到 Dart，仅排空管道。
+// 管道在进程退出（或被终止）后关闭，读取到 EOF 时线程自然结束
+pub fn spawn_reader<R>(stream: CoreOutputStream, reader: R, sink: Option<LogSink>, capture: bool)
+where
+    R: Read + Send + 'static,
+{
+    let result = std::thread::Builder::new()
+        .name(format!("clash-{:?}", stream).to_lowercase())
+        .spawn(move || read_lines(stream, reader, sink, capture));
+
+    if let Err(e) = result {
+        log::error!("创建核心输出读取线程失败：{}", e);
+    }
+}
+
+fn read_lines<R: Read>(stream: CoreOutputStream, reader: R, sink: Option<LogSink>, capture: bool) {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+
+    loop {
+        buffer.clear();
+        match reader.read_until(b'\n', &mut buffer) {
+            Ok(0) => break,
+            Ok(_) => {
+                // 核心输出不保证是 UTF-8（Windows 控制台代码页）
+                let line = String::from_utf8_lossy(&buffer)
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string();
+                if !line.is_empty() {
+                    if let Some(sink) = &sink {
+                        let _ = sink.send(line.clone());
+                    }
+                    if capture {
+                        push(stream, line);
+                    }
+                }
+            }
+            Err(e) => {
+                log::debug!("读取核心输出失败（{:?}）：{}", stream, e);
+                break;
+            }
+        }
+    }
+
+    log::debug!("核心输出读取结束（{:?}）", stream);
+}
+
+fn push(stream: CoreOutputStream, line: String) {
+    {
+        let mut tail = OUTPUT_TAIL.lock().unwrap_or_else(|e| e.into_inner());
+        if tail.len() >= TAIL_CAPACITY {
+            tail.pop_front();
+        }
+        tail.push_back(CoreOutputLine {
+            stream,
+            line: line.clone(),
+        });
+    }
+
+    ClashCoreOutput { stream, line }.send_signal_to_dart();
+}
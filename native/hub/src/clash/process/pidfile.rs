@@ -0,0 +1,175 @@
+// 残留核心进程检测
+//
+// 目的：应用崩溃后上一次启动的核心可能仍在运行并占用端口，
+// 启动时记录 pidfile，下次启动前据此识别并终止残留进程
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+const PID_FILE: &str = "clash_core.pid";
+
+// 轮询进程是否退出的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Deserialize)]
+struct PidRecord {
+    pid: u32,
+    exe: String,
+    // 进程启动时间（Unix 时间戳，秒），用于排除 PID 复用
+    start_time: u64,
+}
+
+fn pid_file_path() -> Option<PathBuf> {
+    crate::utils::init_logger::get_app_data_dir()
+        .ok()
+        .map(|dir| dir.join(PID_FILE))
+}
+
+// 记录新启动的核心进程
+pub fn write(pid: u32, exe: &str) {
+    let Some(path) = pid_file_path() else {
+        return;
+    };
+
+    let start_time = query_process(pid).map_or(0, |(_, start_time)| start_time);
+    let record = PidRecord {
+        pid,
+        exe: exe.to_string(),
+        start_time,
+    };
+
+    let result = serde_json::to_string(&record)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, json).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        log::warn!("写入核心 pidfile 失败：{}", e);
+    }
+}
+
+// 进程退出后删除对应的 pidfile（记录的已是其他进程时保留）
+pub fn remove(pid: u32) {
+    let Some(path) = pid_file_path() else {
+        return;
+    };
+
+    if read_record(&path).is_some_and(|record| record.pid == pid) {
+        let _ = std::fs::remove_file(&path);
+    }
+}
+
+// 检测并终止上次遗留的核心进程，返回是否进行了清理
+//
+// 仅当记录的进程仍存活、启动时间一致且可执行文件与 core_path 相同时才终止
+pub fn cleanup_orphan(core_path: &str, force_after: Duration) -> bool {
+    let Some(path) = pid_file_path() else {
+        return false;
+    };
+    let Some(record) = read_record(&path) else {
+        return false;
+    };
+
+    let is_orphan = match query_process(record.pid) {
+        Some((exe, start_time)) => {
+            // 无法读取启动时间时（记录为 0）仅比较可执行文件
+            let same_start = record.start_time == 0 || record.start_time == start_time;
+            same_start && exe.is_some_and(|exe| same_file(&exe, Path::new(core_path)))
+        }
+        None => false,
+    };
+
+    if !is_orphan {
+        log::debug!("删除过期的核心 pidfile（PID：{}）", record.pid);
+        let _ = std::fs::remove_file(&path);
+        return false;
+    }
+
+    log::warn!("检测到上次遗留的 Clash 进程，正在终止，PID：{}", record.pid);
+    let terminated = terminate(record.pid, force_after);
+    if terminated {
+        let _ = std::fs::remove_file(&path);
+    } else {
+        log::error!("终止遗留的 Clash 进程失败，PID：{}", record.pid);
+    }
+    terminated
+}
+
+fn read_record(path: &Path) -> Option<PidRecord> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// 查询进程的可执行文件路径与启动时间，进程不存在时返回 None
+fn query_process(pid: u32) -> Option<(Option<PathBuf>, u64)> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_exe(UpdateKind::Always),
+    );
+
+    let process = system.process(pid)?;
+    Some((process.exe().map(Path::to_path_buf), process.start_time()))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn is_alive(system: &mut System, pid: Pid) -> bool {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+    system.process(pid).is_some()
+}
+
+fn wait_exit(system: &mut System, pid: Pid, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while is_alive(system, pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    true
+}
+
+// 终止进程：Unix 先 SIGTERM 再 SIGKILL；Windows 无法向非子进程发送优雅停止请求，直接终止
+fn terminate(pid: u32, force_after: Duration) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    if !is_alive(&mut system, pid) {
+        return true;
+    }
+
+    #[cfg(unix)]
+    {
+        if let Some(process) = system.process(pid) {
+            process.kill_with(sysinfo::Signal::Term);
+        }
+        if wait_exit(&mut system, pid, force_after) {
+            return true;
+        }
+        log::warn!("遗留进程未响应 SIGTERM，强制终止，PID：{}", pid);
+    }
+    #[cfg(not(unix))]
+    let _ = force_after;
+
+    if let Some(process) = system.process(pid) {
+        process.kill();
+    }
+    wait_exit(&mut system, pid, super::FORCE_KILL_WAIT)
+}
@@ -0,0 +1,327 @@
+// Clash 核心启动前检查
+//
+// 目的：启动前确认核心与配置文件存在、所需端口未被占用，
+// 以结构化错误返回给 Dart，避免核心启动即退出时用户只能猜测原因
+
+use super::super::signals::{ClashStartError, ClashStartErrorCode};
+use super::LaunchParams;
+use crate::utils::hash::sha256_file;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+// 检查失败时返回结构化错误与可读的错误信息
+pub fn check(launch: &LaunchParams) -> Result<(), (ClashStartError, String)> {
+    check_core(&launch.executable_path)?;
+
+    let config = match find_arg(&launch.args, "-f") {
+        Some(config_path) => {
+            let path = resolve(config_path, &launch.working_dir);
+            if !path.is_file() {
+                return Err(file_error(
+                    ClashStartErrorCode::ConfigNotFound,
+                    &path,
+                    "配置文件不存在",
+                ));
+            }
+            load_config(&path)
+        }
+        None => None,
+    };
+
+    for (host, port) in required_ports(&launch.args, config.as_ref()) {
+        check_port(&host, port)?;
+    }
+
+    Ok(())
+}
+
+fn check_core(executable_path: &str) -> Result<(), (ClashStartError, String)> {
+    let path = Path::new(executable_path);
+    // 不含路径分隔符时由系统在 PATH 中查找，不做检查
+    if path.components().count() <= 1 && !path.is_absolute() {
+        return Ok(());
+    }
+
+    if !path.is_file() {
+        return Err(file_error(
+            ClashStartErrorCode::CoreNotFound,
+            path,
+            "核心文件不存在",
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let executable = std::fs::metadata(path)
+            .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+        if !executable {
+            return Err(file_error(
+                ClashStartErrorCode::CoreNotExecutable,
+                path,
+                "核心文件没有可执行权限",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// 流式计算核心文件的 SHA-256 并与预期值比较（不区分大小写）
+pub fn verify_sha256(
+    executable_path: &str,
+    expected: &str,
+) -> Result<(), (ClashStartError, String)> {
+    let actual = sha256_file(Path::new(executable_path)).map_err(|e| {
+        file_error(
+            ClashStartErrorCode::CoreNotFound,
+            Path::new(executable_path),
+            &format!("读取核心文件失败（{}）", e),
+        )
+    })?;
+
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        log::debug!("核心文件校验通过：{}", actual);
+        return Ok(());
+    }
+
+    let message = format!(
+        "核心文件校验失败，预期 SHA-256：{}，实际：{}",
+        expected, actual
+    );
+    Err((
+        ClashStartError {
+            code: ClashStartErrorCode::BinaryHashMismatch,
+            path: Some(executable_path.to_string()),
+            port: None,
+            owner_process: None,
+            actual_sha256: Some(actual),
+        },
+        message,
+    ))
+}
+
+fn file_error(code: ClashStartErrorCode, path: &Path, reason: &str) -> (ClashStartError, String) {
+    let path = path.to_string_lossy().to_string();
+    let message = format!("{}：{}", reason, path);
+    (
+        ClashStartError {
+            code,
+            path: Some(path),
+            port: None,
+            owner_process: None,
+            actual_sha256: None,
+        },
+        message,
+    )
+}
+
+// 相对路径以工作目录为基准（与核心进程一致）
+fn resolve(path: &str, working_dir: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() || working_dir.is_empty() {
+        path.to_path_buf()
+    } else {
+        Path::new(working_dir).join(path)
+    }
+}
+
+fn find_arg<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
+
+fn load_config(path: &Path) -> Option<serde_yaml_ng::Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_yaml_ng::from_str(&content).ok()
+}
+
+// 核心将要监听的端口：外部控制器（参数优先于配置）与混合端口
+fn required_ports(args: &[String], config: Option<&serde_yaml_ng::Value>) -> Vec<(String, u16)> {
+    let mut ports = Vec::new();
+
+    let external_controller = find_arg(args, "-ext-ctl").map(str::to_string).or_else(|| {
+        config
+            .and_then(|config| config.get("external-controller"))
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    });
+    if let Some((host, port)) = external_controller.as_deref().and_then(parse_address) {
+        ports.push((host, port));
+    }
+
+    if let Some(config) = config
+        && let Some(port) = config
+            .get("mixed-port")
+            .and_then(|value| value.as_u64())
+            .and_then(|port| u16::try_from(port).ok())
+            .filter(|port| *port != 0)
+    {
+        // 未开启局域网访问时核心仅监听本地回环地址
+        let allow_lan = config
+            .get("allow-lan")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        let host = if allow_lan {
+            config
+                .get("bind-address")
+                .and_then(|value| value.as_str())
+                .filter(|address| *address != "*")
+                .unwrap_or("0.0.0.0")
+        } else {
+            "127.0.0.1"
+        };
+        ports.push((host.to_string(), port));
+    }
+
+    ports
+}
+
+// 解析 host:port，host 为空时表示监听所有地址
+fn parse_address(address: &str) -> Option<(String, u16)> {
+    let (host, port) = address.rsplit_once(':')?;
+    let port = port.parse::<u16>().ok().filter(|port| *port != 0)?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let host = if host.is_empty() { "0.0.0.0" } else { host };
+    Some((host.to_string(), port))
+}
+
+fn check_port(host: &str, port: u16) -> Result<(), (ClashStartError, String)> {
+    match TcpListener::bind((host, port)) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            let owner_process = find_port_owner(port);
+            let message = match &owner_process {
+                Some(owner) => format!("端口 {} 已被 {} 占用", port, owner),
+                None => format!("端口 {} 已被占用", port),
+            };
+            Err((
+                ClashStartError {
+                    code: ClashStartErrorCode::PortInUse,
+                    path: None,
+                    port: Some(port),
+                    owner_process,
+                    actual_sha256: None,
+                },
+                message,
+            ))
+        }
+        Err(e) => {
+            // 地址不可用等其他错误交由核心自行报告
+            log::debug!("检查端口 {}:{} 失败：{}", host, port, e);
+            Ok(())
+        }
+    }
+}
+
+// 查找监听指定端口的进程，返回“进程名（PID）”
+fn find_port_owner(port: u16) -> Option<String> {
+    let pid = find_listening_pid(port)?;
+
+    let sys_pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[sys_pid]),
+        true,
+        ProcessRefreshKind::nothing(),
+    );
+
+    Some(match system.process(sys_pid) {
+        Some(process) => format!("{}（PID：{}）", process.name().to_string_lossy(), pid),
+        None => format!("PID：{}", pid),
+    })
+}
+
+// Linux：从 /proc/net/tcp 找到监听套接字的 inode，再在各进程的 fd 中查找
+#[cfg(target_os = "linux")]
+fn find_listening_pid(port: u16) -> Option<u32> {
+    // 状态 0A 为 LISTEN
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let local_port = fields.get(1)?.rsplit_once(':')?.1;
+                    let listening = *fields.get(3)? == "0A";
+                    (listening && u16::from_str_radix(local_port, 16).ok()? == port)
+                        .then(|| fields.get(9).map(|inode| format!("socket:[{}]", inode)))
+                        .flatten()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+
+    // 无权限读取其他用户进程的 fd 时自然跳过
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .find_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let fds = std::fs::read_dir(entry.path().join("fd")).ok()?;
+            fds.flatten()
+                .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+                .any(|target| {
+                    inodes
+                        .iter()
+                        .any(|inode| target.as_os_str() == inode.as_str())
+                })
+                .then_some(pid)
+        })
+}
+
+// macOS：通过 lsof 查询
+#[cfg(target_os = "macos")]
+fn find_listening_pid(port: u16) -> Option<u32> {
+    let output = std::process::Command::new("/usr/sbin/lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fp"])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix('p')?.parse::<u32>().ok())
+}
+
+// Windows：通过 netstat 查询
+#[cfg(windows)]
+fn find_listening_pid(port: u16) -> Option<u32> {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let output = std::process::Command::new("netstat")
+        .args(["-ano", "-p", "TCP"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+
+    let suffix = format!(":{}", port);
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            // 协议 本地地址 外部地址 状态 PID
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [_, local, _, state, pid] = fields.as_slice() else {
+                return None;
+            };
+            (local.ends_with(&suffix) && *state == "LISTENING")
+                .then(|| pid.parse::<u32>().ok())
+                .flatten()
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn find_listening_pid(_port: u16) -> Option<u32> {
+    None
+}
@@ -0,0 +1,112 @@
+// Clash 核心自动重启策略
+//
+// 目的：直接进程模式下核心意外退出时按策略自动重启，
+// 重启间隔按指数退避增长，达到次数上限后放弃
+
+use super::super::signals::SetProcessRestartPolicy;
+use crate::settings::{self, keys};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+// 默认策略：关闭，最多 3 次，首次间隔 2 秒
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_BACKOFF_MS: u64 = 2000;
+
+// 退避间隔上限
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// 运行超过该时长后崩溃视为新的故障，重新计数
+pub const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+// 本轮已尝试的重启次数
+static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+// 手动启停计数，自动重启等待期间发生手动操作时放弃重启
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+struct RestartPolicy {
+    enabled: bool,
+    max_attempts: u32,
+    backoff_ms: u64,
+}
+
+impl RestartPolicy {
+    fn load() -> Self {
+        Self {
+            enabled: settings::get_bool(keys::PROCESS_RESTART_ENABLED).unwrap_or(false),
+            max_attempts: settings::get_u64(keys::PROCESS_RESTART_MAX_ATTEMPTS)
+                .and_then(|value| u32::try_from(value).ok())
+                .unwrap_or(DEFAULT_MAX_ATTEMPTS),
+            backoff_ms: settings::get_u64(keys::PROCESS_RESTART_BACKOFF_MS)
+                .unwrap_or(DEFAULT_BACKOFF_MS),
+        }
+    }
+}
+
+impl SetProcessRestartPolicy {
+    pub fn handle(&self) {
+        log::info!(
+            "更新自动重启策略：启用 {}，最多 {} 次，间隔 {} 毫秒",
+            self.enabled,
+            self.max_attempts,
+            self.backoff_ms
+        );
+
+        let result = settings::set(keys::PROCESS_RESTART_ENABLED, Some(self.enabled.into()))
+            .and_then(|()| {
+                settings::set(
+                    keys::PROCESS_RESTART_MAX_ATTEMPTS,
+                    Some(self.max_attempts.into()),
+                )
+            })
+            .and_then(|()| {
+                settings::set(
+                    keys::PROCESS_RESTART_BACKOFF_MS,
+                    Some(self.backoff_ms.into()),
+                )
+            });
+
+        if let Err(e) = result {
+            log::warn!("保存自动重启策略失败：{}", e);
+        }
+    }
+}
+
+// 手动启动、停止或重启时调用：重置计数并取消等待中的自动重启
+pub fn on_manual_operation() {
+    ATTEMPTS.store(0, Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+// 进程稳定运行一段时间后崩溃，重新开始计数
+pub fn reset_attempts() {
+    ATTEMPTS.store(0, Ordering::SeqCst);
+}
+
+// 申请下一次重启，返回（第几次，等待时间）
+//
+// 策略未启用或已达到次数上限时返回 None
+pub fn next_attempt() -> Option<(u32, Duration)> {
+    let policy = RestartPolicy::load();
+    if !policy.enabled {
+        return None;
+    }
+
+    let attempt = ATTEMPTS.fetch_add(1, Ordering::SeqCst) + 1;
+    if attempt > policy.max_attempts {
+        log::warn!(
+            "自动重启已达到次数上限（{} 次），放弃重启",
+            policy.max_attempts
+        );
+        return None;
+    }
+
+    // 指数退避：backoff × 2^(attempt - 1)
+    let factor = 1u64 << (attempt - 1).min(16);
+    let delay = Duration::from_millis(policy.backoff_ms.saturating_mul(factor)).min(MAX_BACKOFF);
+    Some((attempt, delay))
+}
@@ -0,0 +1,85 @@
+// Clash 核心资源占用采样
+//
+// 目的：直接进程模式下定期采样核心的内存与 CPU 占用并推送给 Dart，
+// 用于仪表盘绘制资源曲线
+
+use super::super::signals::{
+    ClashProcessStats, MAIN_INSTANCE_TAG, StartProcessStatsStream, StopProcessStatsStream,
+};
+use rinf::RustSignal;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+// 采样间隔下限，避免过于频繁地读取进程信息
+const MIN_INTERVAL: Duration = Duration::from_millis(200);
+
+// 采样流编号，开始新流或停止时递增，旧的采样线程据此退出
+static STREAM_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+impl StartProcessStatsStream {
+    pub async fn handle(&self) {
+        let interval = Duration::from_millis(self.interval_ms).max(MIN_INTERVAL);
+        let generation = STREAM_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+        // 仅获取 PID 与退出状态，采样线程不占用管理器
+        let Some((pid, watch)) = super::manager::current(MAIN_INSTANCE_TAG).await else {
+            log::warn!("Clash 进程未运行，无法采样资源占用");
+            return;
+        };
+
+        log::info!(
+            "开始采样 Clash 进程资源占用，PID：{}，间隔：{} 毫秒",
+            pid,
+            interval.as_millis()
+        );
+
+        let result = std::thread::Builder::new()
+            .name("clash-stats".to_string())
+            .spawn(move || {
+                let pid = Pid::from_u32(pid);
+                let mut system = System::new();
+                // 首次刷新作为 CPU 占用的计算基准
+                refresh(&mut system, pid);
+
+                // 等待期间进程退出时立即结束采样
+                while watch.wait_timeout(interval).is_none() {
+                    if STREAM_GENERATION.load(Ordering::SeqCst) != generation {
+                        return;
+                    }
+                    refresh(&mut system, pid);
+                    let Some(process) = system.process(pid) else {
+                        break;
+                    };
+
+                    ClashProcessStats {
+                        memory_bytes: process.memory(),
+                        cpu_percent: process.cpu_usage(),
+                        timestamp: chrono::Utc::now().timestamp_millis(),
+                    }
+                    .send_signal_to_dart();
+                }
+
+                log::debug!("Clash 进程已退出，停止资源采样");
+            });
+
+        if let Err(e) = result {
+            log::error!("创建资源采样线程失败：{}", e);
+        }
+    }
+}
+
+impl StopProcessStatsStream {
+    pub fn handle(&self) {
+        log::info!("停止采样 Clash 进程资源占用");
+        STREAM_GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn refresh(system: &mut System, pid: Pid) {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory().with_cpu(),
+    );
+}
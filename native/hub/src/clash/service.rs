@@ -4,12 +4,49 @@
 
 use crate::clash::signals::ClashProcessResult;
 use anyhow::{Context, Result};
-use rinf::{DartSignal, RustSignal};
+use once_cell::sync::Lazy;
+use rinf::{DartSignal, RustSignal, SignalPiece};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 #[cfg(not(windows))]
 use std::process::Command;
-use stelliberty_service::ipc::{IpcClient, IpcCommand, IpcResponse};
+use stelliberty_service::clash::stats::ClashStats;
+use stelliberty_service::clash::{DEFAULT_FORCE_AFTER, StopOutcome};
+use stelliberty_service::ipc::auth::TOKEN_FILE_NAME;
+use stelliberty_service::ipc::error::ErrorCode;
+use stelliberty_service::ipc::protocol::STOP_FORCE_AFTER_MAX_MS;
+use stelliberty_service::ipc::{IpcClient, IpcCommand, IpcError, IpcResponse};
+use tokio::sync::{Mutex, MutexGuard};
+
+// 服务安装/卸载操作锁（进程级，防止多个提权操作并发执行）
+//
+// 仅保护安装、卸载（含覆盖安装升级），核心启停与状态查询不受影响
+static SERVICE_LIFECYCLE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+// 进程内共享的服务 IPC 客户端
+static SERVICE_IPC_CLIENT: Lazy<IpcClient> =
+    Lazy::new(|| match ServiceManager::get_service_exe_path() {
+        Ok(path) => IpcClient::new().with_token_path(path.with_file_name(TOKEN_FILE_NAME)),
+        Err(_) => IpcClient::new(),
+    });
+
+// 服务启动核心后等待控制接口可达（用于预热连接池）的最长时间
+const SERVICE_PREWARM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// 通过服务启动核心的响应超时时间（包含停止旧核心与等待新核心启动）
+const START_RESPONSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+// 停止核心时在等待时间之外预留的响应时间（强制终止与 TUN 清理）
+const STOP_RESPONSE_MARGIN: std::time::Duration = std::time::Duration::from_secs(5);
+
+// 卸载前请求服务自行退出的响应超时时间（服务先停止核心）
+const SHUTDOWN_RESPONSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(6);
+
+// 服务确认退出后等待 IPC 不再响应的最长时间
+const SHUTDOWN_EXIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+// 通过服务更新 Geo 数据库的响应超时时间（服务等待核心下载完成，最长 5 分钟）
+const GEO_UPDATE_RESPONSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(310);
 
 // 服务管理器
 
@@ -19,13 +56,23 @@ pub enum ServiceStatus {
     // 服务已安装并运行
     Running {
         pid: u32,
+        // 服务运行时长（秒）
         uptime: u64,
+        // 核心运行时长（秒），核心非由服务启动时为 None
+        core_uptime: Option<u64>,
+        // 核心启动参数（用于主程序重启后重新绑定控制器）
+        running_params: Option<CoreRunningParams>,
     },
     // 服务已安装但未运行
     Stopped,
     // 服务未安装
     #[cfg(windows)]
     NotInstalled,
+    // 服务协议版本与主程序不一致，需要重新安装服务
+    NeedsUpgrade {
+        ours: u32,
+        theirs: u32,
+    },
     // 无法检测（IPC 连接失败）
     Unknown,
 }
@@ -37,11 +84,25 @@ pub struct ServiceManager {
 }
 
 impl ServiceManager {
+    // 尝试获取安装/卸载操作锁
+    //
+    // 已有操作进行中时立即返回 None，而不是排队等待
+    pub fn try_lock_lifecycle() -> Option<MutexGuard<'static, ()>> {
+        SERVICE_LIFECYCLE_LOCK.try_lock().ok()
+    }
+
+    // 连接服务的 IPC 客户端（认证令牌位于服务的私有目录）
+    //
+    // 返回共享客户端的克隆，所有调用方复用同一条长连接
+    pub fn ipc_client() -> IpcClient {
+        SERVICE_IPC_CLIENT.clone()
+    }
+
     // 创建服务管理器
     pub fn new() -> Result<Self> {
         let service_exe_path = Self::get_service_exe_path()?;
         Ok(Self {
-            ipc_client: IpcClient::default(),
+            ipc_client: Self::ipc_client(),
             service_exe_path,
         })
     }
@@ -72,18 +133,25 @@ impl ServiceManager {
                 return ServiceStatus::Stopped;
             }
 
+            if let Some(status) = self.check_protocol().await {
+                return status;
+            }
+
             // 服务正在运行，获取详细状态
             match self.ipc_client.send_command(IpcCommand::GetStatus).await {
                 Ok(IpcResponse::Status {
                     clash_running: _,
                     clash_pid,
                     service_uptime,
+                    clash_uptime,
                 }) => {
                     if let Some(pid) = clash_pid {
                         // Clash 核心正在运行
                         ServiceStatus::Running {
                             pid,
                             uptime: service_uptime,
+                            core_uptime: clash_uptime,
+                            running_params: self.get_running_params().await,
                         }
                     } else {
                         // 服务进程运行，但 Clash 核心未运行
@@ -108,17 +176,24 @@ impl ServiceManager {
 
                 // 服务已安装，检查是否运行中
                 if Self::is_systemd_service_active() {
+                    if let Some(status) = self.check_protocol().await {
+                        return status;
+                    }
+
                     // 服务正在运行，尝试 IPC 获取详细状态
                     if let Ok(IpcResponse::Status {
                         clash_running: _,
                         clash_pid,
                         service_uptime,
+                        clash_uptime,
                     }) = self.ipc_client.send_command(IpcCommand::GetStatus).await
                     {
                         if let Some(pid) = clash_pid {
                             ServiceStatus::Running {
                                 pid,
                                 uptime: service_uptime,
+                                core_uptime: clash_uptime,
+                                running_params: self.get_running_params().await,
                             }
                         } else {
                             log::debug!("服务进程运行中，但 Clash 核心未启动");
@@ -139,17 +214,24 @@ impl ServiceManager {
             // macOS 或其他平台：使用 IPC 检测
             #[cfg(not(target_os = "linux"))]
             {
-                if self.ipc_client.is_service_running().await
+                if let Some(status) = self.check_protocol().await {
+                    return status;
+                }
+
+                if self.ipc_client.is_service_running(false).await
                     && let Ok(IpcResponse::Status {
                         clash_running: _,
                         clash_pid,
                         service_uptime,
+                        clash_uptime,
                     }) = self.ipc_client.send_command(IpcCommand::GetStatus).await
                 {
                     if let Some(pid) = clash_pid {
                         return ServiceStatus::Running {
                             pid,
                             uptime: service_uptime,
+                            core_uptime: clash_uptime,
+                            running_params: self.get_running_params().await,
                         };
                     } else {
                         log::debug!("服务进程运行中，但 Clash 核心未启动");
@@ -161,6 +243,47 @@ impl ServiceManager {
         }
     }
 
+    // 服务协议版本不兼容时返回 NeedsUpgrade（握手失败时由后续状态检测处理）
+    async fn check_protocol(&self) -> Option<ServiceStatus> {
+        match self.ipc_client.ensure_compatible().await {
+            Err(IpcError::IncompatibleProtocol { ours, theirs }) => {
+                Some(ServiceStatus::NeedsUpgrade { ours, theirs })
+            }
+            _ => None,
+        }
+    }
+
+    // 获取服务正在运行的核心的启动参数
+    pub async fn get_running_params(&self) -> Option<CoreRunningParams> {
+        match self
+            .ipc_client
+            .send_command(IpcCommand::GetRunningParams)
+            .await
+        {
+            Ok(IpcResponse::RunningParams {
+                core_path,
+                config_path,
+                data_dir,
+                external_controller,
+                tun_device,
+            }) => Some(CoreRunningParams {
+                core_path,
+                config_path,
+                data_dir,
+                external_controller,
+                tun_device,
+            }),
+            Ok(resp) => {
+                log::warn!("获取核心启动参数收到意外响应：{:?}", resp);
+                None
+            }
+            Err(e) => {
+                log::debug!("获取核心启动参数失败：{}", e);
+                None
+            }
+        }
+    }
+
     // 安装服务
     pub async fn install_service(&self) -> Result<()> {
         log::info!("安装 Stelliberty Service…");
@@ -185,7 +308,7 @@ impl ServiceManager {
             // 现在可以安全地停止核心了
             if clash_was_running {
                 log::info!("权限确认成功，停止 Clash 核心...");
-                if let Err(e) = self.stop_clash().await {
+                if let Err(e) = self.stop_clash(None).await {
                     log::warn!("停止 Clash 核心失败：{}，但服务已安装", e);
                 } else {
                     log::info!("Clash 核心已停止");
@@ -253,6 +376,9 @@ impl ServiceManager {
             }
         }
 
+        // 新安装的服务可能使用不同的协议版本，下次通信时重新握手
+        IpcClient::reset_protocol_version();
+
         Ok(())
     }
 
@@ -260,6 +386,9 @@ impl ServiceManager {
     pub async fn uninstall_service(&self) -> Result<()> {
         log::info!("卸载 Stelliberty Service…");
 
+        // 先让服务停止核心并自行退出，卸载命令随后不必等待服务释放文件
+        self.request_shutdown().await;
+
         // 执行卸载命令（会弹 UAC，用户可能取消）
         // uninstall 命令会自动停止服务进程（包括 Clash 核心）
         #[cfg(windows)]
@@ -326,10 +455,36 @@ impl ServiceManager {
 
         // 只有卸载成功后才删除私有目录中的服务二进制文件
         self.remove_service_binary_from_private().await?;
+        IpcClient::reset_protocol_version();
 
         Ok(())
     }
 
+    // 请求服务退出（尽力而为：服务已被停止或版本过旧时不响应，不影响卸载）
+    async fn request_shutdown(&self) {
+        let client = self
+            .ipc_client
+            .clone()
+            .with_timeout(SHUTDOWN_RESPONSE_TIMEOUT)
+            .with_max_retries(0);
+        if let Err(e) = client.send_command(IpcCommand::Shutdown).await {
+            log::debug!("服务未响应关闭命令（可能已停止）：{}", e);
+            return;
+        }
+
+        // 服务关闭 IPC 监听后即将退出
+        let deadline = tokio::time::Instant::now() + SHUTDOWN_EXIT_TIMEOUT;
+        let probe = ServiceManager::ipc_client().with_max_retries(0);
+        while probe.is_service_running(true).await {
+            if tokio::time::Instant::now() >= deadline {
+                log::warn!("服务未在 {:?} 内退出，继续卸载", SHUTDOWN_EXIT_TIMEOUT);
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        log::info!("服务已自行退出");
+    }
+
     // 复制服务二进制到私有目录（安装时调用）
     fn copy_service_binary_to_private(&self) -> Result<()> {
         let app_data_dir = Self::get_app_data_dir()?;
@@ -446,9 +601,9 @@ impl ServiceManager {
                 private_service_exe.display()
             );
 
-            // 问题 14：卸载后服务进程可能还在释放文件句柄，需要等待并重试
+            // 问题 14：服务已在卸载前自行退出，仅为杀毒软件等短暂占用保留少量重试
             let mut retry_count = 0;
-            const MAX_RETRIES: u32 = 15; // 最多重试 15 次（3 秒）
+            const MAX_RETRIES: u32 = 5; // 最多重试 5 次（1 秒）
 
             loop {
                 match std::fs::remove_file(&private_service_exe) {
@@ -585,18 +740,37 @@ impl ServiceManager {
         config_path: String,
         data_dir: String,
         external_controller: String,
+        tun: bool,
+        tun_device: Option<String>,
     ) -> Result<Option<u32>> {
         log::debug!("通过服务启动 Clash 核心…");
-        let response = self
+        // 服务会先停止旧核心，并等待新核心开放控制接口后才响应
+        let response = match self
             .ipc_client
+            .clone()
+            .with_timeout(START_RESPONSE_TIMEOUT)
             .send_command(IpcCommand::StartClash {
                 core_path,
                 config_path,
                 data_dir,
                 external_controller,
+                tun,
+                tun_device,
             })
             .await
-            .context("发送启动命令失败")?;
+        {
+            Ok(response) => response,
+            Err(IpcError::ServiceError(code @ ErrorCode::TunPrerequisite, message)) => {
+                let text = format!("TUN 模式前置条件缺失：{}", message);
+                return Err(with_message(IpcError::ServiceError(code, message), text));
+            }
+            // 原样返回，其中包含核心启动时打印的输出（如配置文件出错的行号）
+            Err(IpcError::ServiceError(code @ ErrorCode::StartFailed, message)) => {
+                let text = message.clone();
+                return Err(with_message(IpcError::ServiceError(code, message), text));
+            }
+            Err(e) => return Err(e).context("发送启动命令失败"),
+        };
 
         match response {
             IpcResponse::Success { message } => {
@@ -622,18 +796,30 @@ impl ServiceManager {
     }
 
     // 停止 Clash 核心（通过服务）
-    pub async fn stop_clash(&self) -> Result<()> {
+    //
+    // force_after_ms 为优雅停止的等待时间（None 使用服务默认值），超时后服务强制终止核心
+    pub async fn stop_clash(&self, force_after_ms: Option<u64>) -> Result<StopOutcome> {
         log::debug!("通过服务停止 Clash 核心…");
+        let force_after = force_after_ms.map_or(DEFAULT_FORCE_AFTER, |ms| {
+            std::time::Duration::from_millis(ms.min(STOP_FORCE_AFTER_MAX_MS))
+        });
+        // 响应要等核心退出后才返回，超时时间需覆盖等待时间
         let response = self
             .ipc_client
-            .send_command(IpcCommand::StopClash)
+            .clone()
+            .with_timeout(force_after + STOP_RESPONSE_MARGIN)
+            .send_command(IpcCommand::StopClash { force_after_ms })
             .await
             .context("发送停止命令失败")?;
 
         match response {
-            IpcResponse::Success { message } => {
-                log::debug!("Clash 停止成功：{:?}", message);
-                Ok(())
+            IpcResponse::Stopped { forced, exit_code } => {
+                log::debug!(
+                    "Clash 停止成功，强制终止：{}，退出码：{:?}",
+                    forced,
+                    exit_code
+                );
+                Ok(StopOutcome { forced, exit_code })
             }
             IpcResponse::Error { code, message } => {
                 anyhow::bail!("Clash 停止失败（code={}）：{}", code, message)
@@ -642,6 +828,81 @@ impl ServiceManager {
         }
     }
 
+    // 获取服务自身日志尾部（用于核心启动失败后排查问题）
+    pub async fn get_logs_tail(&self, lines: u32) -> Result<Vec<String>> {
+        let response = self
+            .ipc_client
+            .send_command(IpcCommand::GetLogsTail { lines })
+            .await
+            .context("获取服务日志失败")?;
+
+        match response {
+            IpcResponse::Logs { lines } => Ok(lines),
+            _ => anyhow::bail!("收到意外响应：{:?}", response),
+        }
+    }
+
+    // 获取核心连接与流量统计（核心未运行时返回 None）
+    pub async fn get_clash_stats(&self) -> Result<Option<ClashStats>> {
+        match self
+            .ipc_client
+            .send_command(IpcCommand::GetClashStats)
+            .await
+        {
+            Ok(IpcResponse::Stats {
+                connections,
+                upload_total,
+                download_total,
+            }) => Ok(Some(ClashStats {
+                connections,
+                upload_total,
+                download_total,
+            })),
+            Err(IpcError::ServiceError(ErrorCode::CoreNotRunning, _)) => Ok(None),
+            Ok(response) => anyhow::bail!("收到意外响应：{:?}", response),
+            Err(e) => Err(e).context("获取核心统计失败"),
+        }
+    }
+
+    // 修改服务正在运行的核心的日志级别
+    pub async fn set_clash_log_level(&self, level: String) -> Result<()> {
+        log::debug!("通过服务修改核心日志级别：{}", level);
+        match self
+            .ipc_client
+            .send_command(IpcCommand::SetClashLogLevel { level })
+            .await
+        {
+            Ok(IpcResponse::Success { .. }) => Ok(()),
+            Ok(response) => anyhow::bail!("收到意外响应：{:?}", response),
+            Err(e) => Err(e).context("修改核心日志级别失败"),
+        }
+    }
+
+    // 让服务正在运行的核心更新 Geo 数据库，进度行发送到 progress
+    pub async fn update_geo_databases(
+        &self,
+        progress: tokio::sync::mpsc::Sender<String>,
+    ) -> Result<()> {
+        log::debug!("通过服务更新 Geo 数据库…");
+        // 核心下载期间可能长时间没有进度，每帧的等待时间覆盖整个更新过程
+        match self
+            .ipc_client
+            .clone()
+            .with_timeout(GEO_UPDATE_RESPONSE_TIMEOUT)
+            .send_with_progress(
+                IpcCommand::UpdateGeoDatabases {
+                    stream_progress: true,
+                },
+                progress,
+            )
+            .await
+        {
+            Ok(IpcResponse::Success { .. }) => Ok(()),
+            Ok(response) => anyhow::bail!("收到意外响应：{:?}", response),
+            Err(e) => Err(e).context("更新 Geo 数据库失败"),
+        }
+    }
+
     // 获取服务二进制路径（始终使用私有目录）
     fn get_service_exe_path() -> Result<PathBuf> {
         let app_data_dir = Self::get_app_data_dir()?;
@@ -807,7 +1068,7 @@ impl Default for ServiceManager {
                 });
 
             Self {
-                ipc_client: IpcClient::default(),
+                ipc_client: Self::ipc_client(),
                 service_exe_path,
             }
         })
@@ -835,22 +1096,134 @@ pub struct StartClash {
     pub config_path: String,
     pub data_dir: String,
     pub external_controller: String,
+    // 是否以 TUN 模式启动（由服务检查前置条件并在停止后清理）
+    pub tun: bool,
+    pub tun_device: Option<String>,
 }
 
 // Dart → Rust：通过服务停止 Clash
+//
+// force_after_ms 为优雅停止的等待时间（默认 3000），超时后服务强制终止核心
+#[derive(Deserialize, DartSignal)]
+pub struct StopClash {
+    #[serde(default)]
+    pub force_after_ms: Option<u64>,
+}
+
+// Dart → Rust：获取服务日志尾部
+#[derive(Deserialize, DartSignal)]
+pub struct GetServiceLogs {
+    pub lines: u32,
+}
+
+// Dart -> Rust: 获取服务模式下核心的连接与流量统计
+#[derive(Deserialize, DartSignal)]
+pub struct GetServiceClashStats;
+
+// Dart -> Rust: 通过服务修改核心日志级别（silent、error、warning、info、debug）
 #[derive(Deserialize, DartSignal)]
-pub struct StopClash;
+pub struct SetServiceClashLogLevel {
+    pub level: String,
+}
+
+// Dart -> Rust: 通过服务更新 Geo 数据库（数据目录属于 root/SYSTEM 时使用）
+#[derive(Deserialize, DartSignal)]
+pub struct UpdateServiceGeoDatabases;
 
 // Dart -> Rust: 向服务发送心跳
 #[derive(Deserialize, DartSignal)]
 pub struct SendServiceHeartbeat;
 
+// 服务正在运行的核心的启动参数
+#[derive(Serialize, SignalPiece, Clone, Debug, PartialEq)]
+pub struct CoreRunningParams {
+    pub core_path: String,
+    pub config_path: String,
+    pub data_dir: String,
+    pub external_controller: String,
+    pub tun_device: Option<String>,
+}
+
+// 错误链中 IPC 错误的稳定错误码（传给 Dart 的字符串形式），非 IPC 错误返回 None
+fn ipc_error_code(e: &anyhow::Error) -> Option<String> {
+    e.chain()
+        .find_map(|cause| cause.downcast_ref::<IpcError>())
+        .map(|ipc_error| ipc_error.code().as_str().to_string())
+}
+
+// 使用 message 作为错误信息，同时保留 IPC 错误以便提取错误码
+fn with_message(ipc_error: IpcError, message: String) -> anyhow::Error {
+    anyhow::Error::new(ipc_error).context(message)
+}
+
 // Rust → Dart：服务状态响应
 #[derive(Serialize, RustSignal)]
 pub struct ServiceStatusResponse {
     pub status: String,
     pub pid: Option<u32>,
+    // 服务运行时长（秒）
     pub uptime: Option<u64>,
+    // Clash 核心运行时长（秒）
+    pub core_uptime: Option<u64>,
+    // 核心启动参数，主程序重启后据此重新绑定控制器而无需重启核心
+    pub running_params: Option<CoreRunningParams>,
+}
+
+// Rust → Dart：服务日志响应
+#[derive(Serialize, RustSignal)]
+pub struct ServiceLogsResponse {
+    pub success: bool,
+    pub lines: Vec<String>,
+    pub error_message: Option<String>,
+    pub error_code: Option<String>,
+}
+
+// Rust → Dart：核心统计响应
+#[derive(Serialize, RustSignal)]
+pub struct ServiceClashStatsResponse {
+    pub success: bool,
+    // 核心未运行时为 false，统计字段均为 0
+    pub core_running: bool,
+    pub connections: u32,
+    pub upload_total: u64,
+    pub download_total: u64,
+    pub error_message: Option<String>,
+    pub error_code: Option<String>,
+}
+
+// Rust → Dart：通过服务转发给核心的请求结果
+#[derive(Serialize, RustSignal)]
+pub struct ServiceClashLogLevelResult {
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub error_code: Option<String>,
+    // 核心拒绝请求时的响应内容
+    pub core_response: Option<String>,
+}
+
+// Rust → Dart：Geo 数据库更新结果
+#[derive(Serialize, RustSignal)]
+pub struct ServiceGeoUpdateResult {
+    pub success: bool,
+    pub error_message: Option<String>,
+    pub error_code: Option<String>,
+    // 核心拒绝请求时的响应内容
+    pub core_response: Option<String>,
+}
+
+// Rust → Dart：Geo 数据库更新进度（单行）
+#[derive(Serialize, RustSignal)]
+pub struct ServiceGeoUpdateProgress {
+    pub line: String,
+}
+
+// 核心拒绝请求时的响应内容（服务将其作为错误消息返回）
+fn core_response(e: &anyhow::Error) -> Option<String> {
+    e.chain()
+        .find_map(|cause| match cause.downcast_ref::<IpcError>() {
+            Some(IpcError::ServiceError(ErrorCode::ControllerRejected, body)) => Some(body.clone()),
+            _ => None,
+        })
 }
 
 // Rust → Dart：服务操作结果
@@ -858,6 +1231,21 @@ pub struct ServiceStatusResponse {
 pub struct ServiceOperationResult {
     pub success: bool,
     pub error_message: Option<String>,
+    // 与服务通信失败时的稳定错误码，其他原因失败时为 None
+    pub error_code: Option<String>,
+    // 是否因已有安装/卸载操作进行中而被拒绝
+    pub in_progress: bool,
+}
+
+impl ServiceOperationResult {
+    fn operation_in_progress() -> Self {
+        Self {
+            success: false,
+            error_message: Some("已有服务安装或卸载操作正在进行，请稍后重试".to_string()),
+            error_code: None,
+            in_progress: true,
+        }
+    }
 }
 
 // 消息处理逻辑
@@ -872,6 +1260,8 @@ impl GetServiceStatus {
                     status: "unknown".to_string(),
                     pid: None,
                     uptime: None,
+                    core_uptime: None,
+                    running_params: None,
                 }
                 .send_signal_to_dart();
                 return;
@@ -880,26 +1270,53 @@ impl GetServiceStatus {
 
         let status = service_manager.get_status().await;
         let response = match status {
-            ServiceStatus::Running { pid, uptime } => ServiceStatusResponse {
+            ServiceStatus::Running {
+                pid,
+                uptime,
+                core_uptime,
+                running_params,
+            } => ServiceStatusResponse {
                 status: "running".to_string(),
                 pid: Some(pid),
                 uptime: Some(uptime),
+                core_uptime,
+                running_params,
             },
             ServiceStatus::Stopped => ServiceStatusResponse {
                 status: "stopped".to_string(),
                 pid: None,
                 uptime: None,
+                core_uptime: None,
+                running_params: None,
             },
             #[cfg(windows)]
             ServiceStatus::NotInstalled => ServiceStatusResponse {
                 status: "not_installed".to_string(),
                 pid: None,
                 uptime: None,
+                core_uptime: None,
+                running_params: None,
             },
+            ServiceStatus::NeedsUpgrade { ours, theirs } => {
+                log::warn!(
+                    "服务协议版本不兼容（主程序：{}，服务：{}），需要重新安装服务",
+                    ours,
+                    theirs
+                );
+                ServiceStatusResponse {
+                    status: "needs_upgrade".to_string(),
+                    pid: None,
+                    uptime: None,
+                    core_uptime: None,
+                    running_params: None,
+                }
+            }
             ServiceStatus::Unknown => ServiceStatusResponse {
                 status: "unknown".to_string(),
                 pid: None,
                 uptime: None,
+                core_uptime: None,
+                running_params: None,
             },
         };
 
@@ -909,6 +1326,12 @@ impl GetServiceStatus {
 
 impl InstallService {
     pub async fn handle(&self) {
+        let Some(_lifecycle_guard) = ServiceManager::try_lock_lifecycle() else {
+            log::warn!("已有服务操作正在进行，拒绝本次安装请求");
+            ServiceOperationResult::operation_in_progress().send_signal_to_dart();
+            return;
+        };
+
         let service_manager = match ServiceManager::new() {
             Ok(sm) => sm,
             Err(e) => {
@@ -916,6 +1339,8 @@ impl InstallService {
                 ServiceOperationResult {
                     success: false,
                     error_message: Some(format!("创建服务管理器失败：{}", e)),
+                    error_code: None,
+                    in_progress: false,
                 }
                 .send_signal_to_dart();
                 return;
@@ -928,6 +1353,8 @@ impl InstallService {
                 ServiceOperationResult {
                     success: true,
                     error_message: None,
+                    error_code: None,
+                    in_progress: false,
                 }
                 .send_signal_to_dart();
             }
@@ -936,6 +1363,8 @@ impl InstallService {
                 ServiceOperationResult {
                     success: false,
                     error_message: Some(e.to_string()),
+                    error_code: ipc_error_code(&e),
+                    in_progress: false,
                 }
                 .send_signal_to_dart();
             }
@@ -945,6 +1374,12 @@ impl InstallService {
 
 impl UninstallService {
     pub async fn handle(&self) {
+        let Some(_lifecycle_guard) = ServiceManager::try_lock_lifecycle() else {
+            log::warn!("已有服务操作正在进行，拒绝本次卸载请求");
+            ServiceOperationResult::operation_in_progress().send_signal_to_dart();
+            return;
+        };
+
         let service_manager = match ServiceManager::new() {
             Ok(sm) => sm,
             Err(e) => {
@@ -952,6 +1387,8 @@ impl UninstallService {
                 ServiceOperationResult {
                     success: false,
                     error_message: Some(format!("创建服务管理器失败：{}", e)),
+                    error_code: None,
+                    in_progress: false,
                 }
                 .send_signal_to_dart();
                 return;
@@ -964,6 +1401,8 @@ impl UninstallService {
                 ServiceOperationResult {
                     success: true,
                     error_message: None,
+                    error_code: None,
+                    in_progress: false,
                 }
                 .send_signal_to_dart();
             }
@@ -972,6 +1411,8 @@ impl UninstallService {
                 ServiceOperationResult {
                     success: false,
                     error_message: Some(e.to_string()),
+                    error_code: ipc_error_code(&e),
+                    in_progress: false,
                 }
                 .send_signal_to_dart();
             }
@@ -981,46 +1422,63 @@ impl UninstallService {
 
 impl StartClash {
     pub async fn handle(&self) {
+        let tun_device = if self.tun {
+            super::tun::resolve_tun_device(self.tun_device.clone(), self.config_path.clone()).await
+        } else {
+            None
+        };
+
         let service_manager = match ServiceManager::new() {
             Ok(sm) => sm,
             Err(e) => {
                 log::error!("创建 ServiceManager 失败：{}", e);
-                ClashProcessResult {
-                    success: false,
-                    error_message: Some(format!("创建服务管理器失败：{}", e)),
-                    pid: None,
-                }
-                .send_signal_to_dart();
+                ClashProcessResult::failure(format!("创建服务管理器失败：{}", e), None)
+                    .send_signal_to_dart();
                 return;
             }
         };
 
+        // 服务已以相同参数运行核心时由服务直接接管，此时 TUN 网卡属于正在运行的核心，不做残留检测
+        let requested = CoreRunningParams {
+            core_path: self.core_path.clone(),
+            config_path: self.config_path.clone(),
+            data_dir: self.data_dir.clone(),
+            external_controller: self.external_controller.clone(),
+            tun_device: tun_device.clone(),
+        };
+        let adopting = service_manager.get_running_params().await.as_ref() == Some(&requested);
+        if adopting {
+            log::info!("服务已以相同参数运行 Clash，接管现有核心");
+        } else if let Some(device) = &tun_device {
+            super::tun::run_preflight(device.clone()).await;
+        }
+
         match service_manager
             .start_clash(
                 self.core_path.clone(),
                 self.config_path.clone(),
                 self.data_dir.clone(),
                 self.external_controller.clone(),
+                self.tun,
+                tun_device.clone(),
             )
             .await
         {
             Ok(pid) => {
                 log::info!("通过服务启动 Clash 成功，PID：{:?}", pid);
-                ClashProcessResult {
-                    success: true,
-                    error_message: None,
-                    pid,
-                }
-                .send_signal_to_dart();
+                super::tun::set_active_device(tun_device);
+
+                // 核心可达后预热 IPC 连接池
+                tokio::spawn(super::network::handlers::prewarm_ipc_pool_when_ready(
+                    super::network::handlers::DEFAULT_PREWARM_CONNECTIONS,
+                    SERVICE_PREWARM_TIMEOUT,
+                ));
+                ClashProcessResult::started(pid).send_signal_to_dart();
             }
             Err(e) => {
                 log::error!("通过服务启动 Clash 失败：{}", e);
-                ClashProcessResult {
-                    success: false,
-                    error_message: Some(e.to_string()),
-                    pid: None,
-                }
-                .send_signal_to_dart();
+                ClashProcessResult::failure(e.to_string(), ipc_error_code(&e))
+                    .send_signal_to_dart();
             }
         }
     }
@@ -1032,19 +1490,15 @@ impl StopClash {
             Ok(sm) => sm,
             Err(e) => {
                 log::error!("创建 ServiceManager 失败：{}", e);
-                ClashProcessResult {
-                    success: false,
-                    error_message: Some(format!("创建服务管理器失败：{}", e)),
-                    pid: None,
-                }
-                .send_signal_to_dart();
+                ClashProcessResult::failure(format!("创建服务管理器失败：{}", e), None)
+                    .send_signal_to_dart();
                 return;
             }
         };
 
-        match service_manager.stop_clash().await {
-            Ok(()) => {
-                log::info!("通过服务停止 Clash 成功");
+        match service_manager.stop_clash(self.force_after_ms).await {
+            Ok(outcome) => {
+                log::info!("通过服务停止 Clash 成功，强制终止：{}", outcome.forced);
 
                 // 异步清理网络资源（IPC 连接池和 WebSocket）
                 tokio::spawn(async {
@@ -1053,19 +1507,56 @@ impl StopClash {
                     log::info!("网络资源清理完成（服务模式）");
                 });
 
-                ClashProcessResult {
+                // 检测并清理 TUN 残留（服务已在停止核心时清理一次，这里确认结果）
+                super::tun::spawn_post_stop_verification();
+
+                ClashProcessResult::stopped(outcome.exit_code, outcome.forced)
+                    .send_signal_to_dart();
+            }
+            Err(e) => {
+                log::error!("通过服务停止 Clash 失败：{}", e);
+                ClashProcessResult::failure(e.to_string(), ipc_error_code(&e))
+                    .send_signal_to_dart();
+            }
+        }
+    }
+}
+
+impl GetServiceLogs {
+    pub async fn handle(&self) {
+        let service_manager = match ServiceManager::new() {
+            Ok(sm) => sm,
+            Err(e) => {
+                log::error!("创建 ServiceManager 失败：{}", e);
+                ServiceLogsResponse {
+                    success: false,
+                    lines: Vec::new(),
+                    error_message: Some(format!("创建服务管理器失败：{}", e)),
+                    error_code: None,
+                }
+                .send_signal_to_dart();
+                return;
+            }
+        };
+
+        match service_manager.get_logs_tail(self.lines).await {
+            Ok(lines) => {
+                log::debug!("获取服务日志成功：{} 行", lines.len());
+                ServiceLogsResponse {
                     success: true,
+                    lines,
                     error_message: None,
-                    pid: None,
+                    error_code: None,
                 }
                 .send_signal_to_dart();
             }
             Err(e) => {
-                log::error!("通过服务停止 Clash 失败：{}", e);
-                ClashProcessResult {
+                log::error!("获取服务日志失败：{}", e);
+                ServiceLogsResponse {
                     success: false,
+                    lines: Vec::new(),
                     error_message: Some(e.to_string()),
-                    pid: None,
+                    error_code: ipc_error_code(&e),
                 }
                 .send_signal_to_dart();
             }
@@ -1073,9 +1564,129 @@ impl StopClash {
     }
 }
 
+impl GetServiceClashStats {
+    pub async fn handle(&self) {
+        let response = match ServiceManager::new() {
+            Ok(service_manager) => service_manager.get_clash_stats().await,
+            Err(e) => Err(e.context("创建服务管理器失败")),
+        };
+
+        let (core_running, stats, error_message, error_code) = match response {
+            Ok(Some(stats)) => (true, Some(stats), None, None),
+            Ok(None) => (false, None, None, None),
+            Err(e) => {
+                log::debug!("获取核心统计失败：{:#}", e);
+                (false, None, Some(format!("{:#}", e)), ipc_error_code(&e))
+            }
+        };
+        let stats = stats.unwrap_or(ClashStats {
+            connections: 0,
+            upload_total: 0,
+            download_total: 0,
+        });
+
+        ServiceClashStatsResponse {
+            success: error_message.is_none(),
+            core_running,
+            connections: stats.connections,
+            upload_total: stats.upload_total,
+            download_total: stats.download_total,
+            error_message,
+            error_code,
+        }
+        .send_signal_to_dart();
+    }
+}
+
+impl SetServiceClashLogLevel {
+    pub async fn handle(&self) {
+        let result = match ServiceManager::new() {
+            Ok(service_manager) => {
+                service_manager
+                    .set_clash_log_level(self.level.clone())
+                    .await
+            }
+            Err(e) => Err(e.context("创建服务管理器失败")),
+        };
+
+        let response = match result {
+            Ok(()) => {
+                log::info!("核心日志级别已设为：{}", self.level);
+                ServiceClashLogLevelResult {
+                    success: true,
+                    error_message: None,
+                    error_code: None,
+                    core_response: None,
+                }
+            }
+            Err(e) => {
+                log::error!("修改核心日志级别失败：{:#}", e);
+                ServiceClashLogLevelResult {
+                    success: false,
+                    error_message: Some(format!("{:#}", e)),
+                    error_code: ipc_error_code(&e),
+                    core_response: core_response(&e),
+                }
+            }
+        };
+        response.send_signal_to_dart();
+    }
+}
+
+impl UpdateServiceGeoDatabases {
+    pub async fn handle(&self) {
+        let service_manager = match ServiceManager::new() {
+            Ok(sm) => sm,
+            Err(e) => {
+                log::error!("创建服务管理器失败：{}", e);
+                ServiceGeoUpdateResult {
+                    success: false,
+                    error_message: Some(format!("创建服务管理器失败：{}", e)),
+                    error_code: None,
+                    core_response: None,
+                }
+                .send_signal_to_dart();
+                return;
+            }
+        };
+
+        let (progress_tx, mut progress) = tokio::sync::mpsc::channel::<String>(32);
+        let forwarder = tokio::spawn(async move {
+            while let Some(line) = progress.recv().await {
+                ServiceGeoUpdateProgress { line }.send_signal_to_dart();
+            }
+        });
+        let result = service_manager.update_geo_databases(progress_tx).await;
+        // 发送方已释放，等待剩余进度发送完再发送结果
+        let _ = forwarder.await;
+
+        let response = match result {
+            Ok(()) => {
+                log::info!("Geo 数据库更新完成");
+                ServiceGeoUpdateResult {
+                    success: true,
+                    error_message: None,
+                    error_code: None,
+                    core_response: None,
+                }
+            }
+            Err(e) => {
+                log::error!("更新 Geo 数据库失败：{:#}", e);
+                ServiceGeoUpdateResult {
+                    success: false,
+                    error_message: Some(format!("{:#}", e)),
+                    error_code: ipc_error_code(&e),
+                    core_response: core_response(&e),
+                }
+            }
+        };
+        response.send_signal_to_dart();
+    }
+}
+
 impl SendServiceHeartbeat {
     pub async fn handle(&self) {
-        let client = IpcClient::new()
+        let client = ServiceManager::ipc_client()
             .with_timeout(std::time::Duration::from_secs(2))
             .with_max_retries(0);
 
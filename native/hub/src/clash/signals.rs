@@ -2,24 +2,228 @@
 //
 // 定义 Dart 与 Rust 之间的通信消息
 
-use rinf::{DartSignal, RustSignal};
+use rinf::{DartSignal, RustSignal, SignalPiece};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// 主实例标签；其他标签的实例用于配置测试等场景，不接管系统代理与 TUN
+pub const MAIN_INSTANCE_TAG: &str = "main";
 
 // Dart → Rust：启动 Clash 进程
 #[derive(Deserialize, DartSignal)]
 pub struct StartClashProcess {
+    // 实例标签（为空时为 main），同一标签同时只能运行一个进程
+    #[serde(default)]
+    pub instance_tag: String,
     pub executable_path: String,
     pub args: Vec<String>,
+    // 追加的环境变量（如 SAFE_PATHS），在继承当前环境的基础上覆盖
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    // 工作目录，为空时继承当前目录
+    #[serde(default)]
+    pub working_dir: String,
+    // 启动后等待控制接口就绪的最长时间，为空时不等待
+    #[serde(default)]
+    pub wait_ready_ms: Option<u64>,
+    // 核心输出日志文件目录，为空时不写入文件
+    #[serde(default)]
+    pub log_file_dir: Option<String>,
+    // 单个日志文件的大小上限（默认 10 MB）
+    #[serde(default)]
+    pub max_file_bytes: Option<u64>,
+    // 保留的日志文件数量，含当前文件（默认 5）
+    #[serde(default)]
+    pub max_files: Option<u32>,
+    // 核心文件的预期 SHA-256（十六进制），提供时启动前校验
+    #[serde(default)]
+    pub expected_sha256: Option<String>,
 }
 
 // Dart → Rust：停止 Clash 进程
+//
+// force_after_ms 为优雅停止的等待时间（默认 5000），超时后强制终止
 #[derive(Deserialize, DartSignal)]
-pub struct StopClashProcess;
+pub struct StopClashProcess {
+    pub force_after_ms: Option<u64>,
+    #[serde(default)]
+    pub instance_tag: String,
+}
 
-// Rust → Dart：Clash 进程操作结果
+// Dart → Rust：重启主实例 Clash 进程（未提供的字段沿用上次启动的值）
+#[derive(Deserialize, DartSignal)]
+pub struct RestartClashProcess {
+    pub executable_path: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub env: Option<HashMap<String, String>>,
+    pub working_dir: Option<String>,
+}
+
+// Dart → Rust：查询 Clash 进程状态（会校验进程是否仍然存活）
+#[derive(Deserialize, DartSignal)]
+pub struct GetClashProcessStatus {
+    #[serde(default)]
+    pub instance_tag: String,
+}
+
+// Rust → Dart：Clash 进程状态
 #[derive(Serialize, RustSignal)]
+pub struct ClashProcessStatusResponse {
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub uptime_seconds: Option<u64>,
+    pub instance_tag: String,
+}
+
+// Rust → Dart：Clash 进程操作结果
+#[derive(Serialize, RustSignal, Default)]
 pub struct ClashProcessResult {
     pub success: bool,
     pub error_message: Option<String>,
+    // 服务模式下操作失败时的稳定错误码（如 start_failed、service_unavailable）
+    pub error_code: Option<String>,
     pub pid: Option<u32>,
+    // 停止时是否进行了强制终止
+    pub forced_kill: bool,
+    // 服务模式停止核心后的退出码（本地进程的退出码由 ClashProcessExited 上报）
+    pub exit_code: Option<i32>,
+    // 启动前是否终止了上次遗留的核心进程
+    pub orphan_cleaned: bool,
+    // 启动前检查失败时的结构化错误
+    pub start_error: Option<ClashStartError>,
+    // 控制接口是否已就绪（仅在启动时指定 wait_ready_ms 才会检测）
+    pub ready: bool,
+    // 启动到就绪所用的时间
+    pub ready_after_ms: Option<u64>,
+}
+
+impl ClashProcessResult {
+    pub fn failure(message: String, error_code: Option<String>) -> Self {
+        Self {
+            error_message: Some(message),
+            error_code,
+            ..Default::default()
+        }
+    }
+
+    pub fn started(pid: Option<u32>) -> Self {
+        Self {
+            success: true,
+            pid,
+            ..Default::default()
+        }
+    }
+
+    pub fn stopped(exit_code: Option<i32>, forced_kill: bool) -> Self {
+        Self {
+            success: true,
+            exit_code,
+            forced_kill,
+            ..Default::default()
+        }
+    }
+}
+
+// 启动前检查失败的原因
+#[derive(Serialize, SignalPiece, Clone, Copy, Debug, PartialEq)]
+pub enum ClashStartErrorCode {
+    CoreNotFound = 0, // 核心文件不存在
+    #[cfg_attr(windows, allow(dead_code))]
+    CoreNotExecutable = 1, // 核心文件没有可执行权限（仅 Unix 检查）
+    ConfigNotFound = 2, // -f 指定的配置文件不存在
+    PortInUse = 3,    // 外部控制器或混合端口已被占用
+    BinaryHashMismatch = 4, // 核心文件的 SHA-256 与预期不符
+}
+
+// 启动前检查失败的详细信息，供 UI 给出针对性的修复建议
+#[derive(Serialize, SignalPiece, Clone, Debug)]
+pub struct ClashStartError {
+    pub code: ClashStartErrorCode,
+    // 核心或配置文件路径
+    pub path: Option<String>,
+    pub port: Option<u16>,
+    // 占用端口的进程（平台支持时可查询）
+    pub owner_process: Option<String>,
+    // 核心文件实际的 SHA-256
+    pub actual_sha256: Option<String>,
+}
+
+// Rust → Dart：Clash 进程已退出
+//
+// was_requested 为 true 表示由停止请求触发，UI 不应提示崩溃
+#[derive(Serialize, RustSignal)]
+pub struct ClashProcessExited {
+    pub pid: u32,
+    pub exit_code: Option<i32>,
+    pub was_requested: bool,
+    // 退出进程所属的实例，供 UI 正确归属崩溃
+    pub instance_tag: String,
+}
+
+// Dart → Rust：设置核心意外退出后的自动重启策略
+//
+// backoff_ms 为首次重启前的等待时间，之后每次翻倍（上限 60 秒）
+#[derive(Deserialize, DartSignal)]
+pub struct SetProcessRestartPolicy {
+    pub enabled: bool,
+    pub max_attempts: u32,
+    pub backoff_ms: u64,
+}
+
+// Rust → Dart：核心意外退出后已自动重启
+#[derive(Serialize, RustSignal)]
+pub struct ClashProcessRestarted {
+    pub attempt: u32,
+    pub pid: u32,
+}
+
+// Dart → Rust：开始定期采样核心的资源占用（进程退出后自动停止）
+#[derive(Deserialize, DartSignal)]
+pub struct StartProcessStatsStream {
+    pub interval_ms: u64,
+}
+
+// Dart → Rust：停止采样核心的资源占用
+#[derive(Deserialize, DartSignal)]
+pub struct StopProcessStatsStream;
+
+// Rust → Dart：核心资源占用
+//
+// cpu_percent 以单核为 100%，多核满载时可能超过 100；timestamp 为 Unix 毫秒时间戳
+#[derive(Serialize, RustSignal)]
+pub struct ClashProcessStats {
+    pub memory_bytes: u64,
+    pub cpu_percent: f32,
+    pub timestamp: i64,
+}
+
+// Dart → Rust：获取核心最近的输出
+#[derive(Deserialize, DartSignal)]
+pub struct GetCoreOutputTail;
+
+// 核心输出来源
+#[derive(Serialize, SignalPiece, Clone, Copy, Debug, PartialEq)]
+pub enum CoreOutputStream {
+    Stdout = 0,
+    Stderr = 1,
+}
+
+// 单行核心输出
+#[derive(Serialize, SignalPiece, Clone, Debug)]
+pub struct CoreOutputLine {
+    pub stream: CoreOutputStream,
+    pub line: String,
+}
+
+// Rust → Dart：核心输出（逐行实时推送）
+#[derive(Serialize, RustSignal)]
+pub struct ClashCoreOutput {
+    pub stream: CoreOutputStream,
+    pub line: String,
+}
+
+// Rust → Dart：核心最近的输出（最多 500 行）
+#[derive(Serialize, RustSignal)]
+pub struct CoreOutputTailResponse {
+    pub lines: Vec<CoreOutputLine>,
 }
@@ -2,12 +2,21 @@
 //
 // 处理订阅源的解析、转换和配置生成
 
+pub mod alerts;
+pub mod content;
+pub mod diff;
 pub mod downloader;
+pub mod headers;
 pub mod parser;
 pub mod signals;
+pub mod storage;
 
+pub use alerts::ConfigureSubscriptionAlerts;
 pub use parser::ProxyParser;
-pub use signals::DownloadSubscriptionRequest;
+pub use signals::{
+    ConvertUriListRequest, DiffSubscriptionRequest, DownloadSubscriptionRequest,
+    UpdateAllSubscriptions,
+};
 
 use rinf::DartSignal;
 use tokio::spawn;
@@ -27,4 +36,45 @@ pub fn init_message_listeners() {
         }
         log::info!("订阅下载消息通道已关闭，退出监听器");
     });
+
+    // 批量更新订阅监听器
+    spawn(async {
+        let receiver = UpdateAllSubscriptions::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("批量更新订阅消息通道已关闭，退出监听器");
+    });
+
+    // 代理链接列表转换监听器
+    spawn(async {
+        let receiver = ConvertUriListRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+        log::info!("代理链接转换消息通道已关闭，退出监听器");
+    });
+
+    // 订阅差异监听器
+    spawn(async {
+        let receiver = DiffSubscriptionRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+        log::info!("订阅差异消息通道已关闭，退出监听器");
+    });
+
+    // 订阅提醒阈值配置监听器
+    spawn(async {
+        let receiver = ConfigureSubscriptionAlerts::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+        log::info!("订阅提醒配置消息通道已关闭，退出监听器");
+    });
+
+    alerts::watch_settings();
 }
@@ -0,0 +1,345 @@
+// 订阅用量提醒
+//
+// 目的：根据 subscription-userinfo 判断流量用量和到期时间是否越过阈值，
+// 同一订阅同一类提醒每天最多发送一次，去重状态持久化到应用数据目录
+
+use super::signals::SubscriptionInfoData;
+use crate::settings::{self, keys};
+use once_cell::sync::Lazy;
+use rinf::{DartSignal, RustSignal, SignalPiece};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const ALERT_STATE_FILE: &str = "subscription_alerts.json";
+
+// 默认阈值：已用 90%，7 天内到期
+const DEFAULT_PERCENT_THRESHOLD: f64 = 90.0;
+const DEFAULT_EXPIRE_DAYS_THRESHOLD: u32 = 7;
+
+// ============================================================================
+// 消息协议
+// ============================================================================
+
+// 提醒类型
+#[derive(Serialize, SignalPiece, Clone, Copy, Debug, PartialEq)]
+pub enum SubscriptionAlertKind {
+    Usage = 0,  // 流量用量越过阈值
+    Expire = 1, // 即将到期（或已到期）
+}
+
+// Dart → Rust：配置提醒阈值
+#[derive(Deserialize, DartSignal)]
+pub struct ConfigureSubscriptionAlerts {
+    pub percent_threshold: f64,
+    pub expire_days_threshold: u32,
+}
+
+// Rust → Dart：订阅提醒
+#[derive(Serialize, RustSignal)]
+pub struct SubscriptionAlert {
+    pub subscription_id: String,
+    pub kind: SubscriptionAlertKind,
+    pub used_percent: Option<f64>,
+    pub days_to_expire: Option<i64>,
+}
+
+struct AlertThresholds {
+    percent: f64,
+    expire_days: u32,
+}
+
+impl AlertThresholds {
+    // 从 Hub 设置读取阈值，未设置时使用默认值
+    fn from_settings() -> Self {
+        Self {
+            percent: settings::get_f64(keys::SUBSCRIPTION_ALERT_PERCENT)
+                .filter(|&percent| is_valid_percent(percent))
+                .unwrap_or(DEFAULT_PERCENT_THRESHOLD),
+            expire_days: settings::get_u64(keys::SUBSCRIPTION_ALERT_EXPIRE_DAYS)
+                .and_then(|days| u32::try_from(days).ok())
+                .unwrap_or(DEFAULT_EXPIRE_DAYS_THRESHOLD),
+        }
+    }
+}
+
+static THRESHOLDS: Lazy<Mutex<AlertThresholds>> =
+    Lazy::new(|| Mutex::new(AlertThresholds::from_settings()));
+
+// 去重状态：提醒键（订阅 ID + 类型）→ 最近一次发送日期（YYYY-MM-DD）
+static ALERT_STATE: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(load_state()));
+
+// ============================================================================
+// 消息处理器
+// ============================================================================
+
+impl ConfigureSubscriptionAlerts {
+    pub fn handle(self) {
+        if !is_valid_percent(self.percent_threshold) {
+            log::warn!("忽略无效的用量提醒阈值：{}", self.percent_threshold);
+            return;
+        }
+
+        log::info!(
+            "更新订阅提醒阈值：用量 {}%，到期 {} 天",
+            self.percent_threshold,
+            self.expire_days_threshold
+        );
+
+        // 持久化后由设置变更通知刷新内存中的阈值
+        let result = settings::set(
+            keys::SUBSCRIPTION_ALERT_PERCENT,
+            Some(self.percent_threshold.into()),
+        )
+        .and_then(|()| {
+            settings::set(
+                keys::SUBSCRIPTION_ALERT_EXPIRE_DAYS,
+                Some(self.expire_days_threshold.into()),
+            )
+        });
+
+        if let Err(e) = result {
+            log::warn!("保存订阅提醒阈值失败：{}", e);
+            let mut thresholds = THRESHOLDS.lock().unwrap_or_else(|e| e.into_inner());
+            thresholds.percent = self.percent_threshold;
+            thresholds.expire_days = self.expire_days_threshold;
+        }
+    }
+}
+
+// 监听 Hub 设置变更，阈值相关项更新时重新加载
+pub fn watch_settings() {
+    let mut changes = settings::subscribe();
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(key)
+                    if key != keys::SUBSCRIPTION_ALERT_PERCENT
+                        && key != keys::SUBSCRIPTION_ALERT_EXPIRE_DAYS =>
+                {
+                    continue;
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    *THRESHOLDS.lock().unwrap_or_else(|e| e.into_inner()) =
+                        AlertThresholds::from_settings();
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// 计算订阅信息中的用量与到期字段
+//
+// 阈值未提供（或用量阈值无效）时使用提醒设置中的阈值
+pub fn compute_usage(
+    info: &mut SubscriptionInfoData,
+    percent_threshold: Option<f64>,
+    expire_days_threshold: Option<u32>,
+) {
+    let (default_percent, default_expire_days) = {
+        let thresholds = THRESHOLDS.lock().unwrap_or_else(|e| e.into_inner());
+        (thresholds.percent, thresholds.expire_days)
+    };
+    let thresholds = AlertThresholds {
+        percent: percent_threshold
+            .filter(|&percent| is_valid_percent(percent))
+            .unwrap_or(default_percent),
+        expire_days: expire_days_threshold.unwrap_or(default_expire_days),
+    };
+    apply_usage(info, &thresholds, chrono::Utc::now().timestamp());
+}
+
+// 评估订阅信息并在越过阈值时发送提醒
+//
+// 手动下载和自动更新共用此入口
+pub fn evaluate(subscription_id: &str, info: &SubscriptionInfoData) {
+    let (percent_threshold, expire_days_threshold) = {
+        let thresholds = THRESHOLDS.lock().unwrap_or_else(|e| e.into_inner());
+        (thresholds.percent, thresholds.expire_days)
+    };
+
+    let used_percent = used_percent(info);
+    let days_to_expire = days_to_expire(info, chrono::Utc::now().timestamp());
+
+    if let Some(percent) = used_percent
+        && percent >= percent_threshold
+    {
+        fire_once(SubscriptionAlert {
+            subscription_id: subscription_id.to_string(),
+            kind: SubscriptionAlertKind::Usage,
+            used_percent,
+            days_to_expire,
+        });
+    }
+
+    if let Some(days) = days_to_expire
+        && days <= i64::from(expire_days_threshold)
+    {
+        fire_once(SubscriptionAlert {
+            subscription_id: subscription_id.to_string(),
+            kind: SubscriptionAlertKind::Expire,
+            used_percent,
+            days_to_expire,
+        });
+    }
+}
+
+// ============================================================================
+// 内部实现
+// ============================================================================
+
+fn is_valid_percent(percent: f64) -> bool {
+    percent.is_finite() && percent > 0.0 && percent <= 100.0
+}
+
+fn used_bytes(info: &SubscriptionInfoData) -> u64 {
+    info.upload
+        .unwrap_or(0)
+        .saturating_add(info.download.unwrap_or(0))
+}
+
+// total 为 0 表示不限流量
+fn used_percent(info: &SubscriptionInfoData) -> Option<f64> {
+    let total = info.total.filter(|&total| total > 0)?;
+    Some(used_bytes(info) as f64 / total as f64 * 100.0)
+}
+
+// expire 为 0 表示长期有效
+fn days_to_expire(info: &SubscriptionInfoData, now: i64) -> Option<i64> {
+    let expire = info.expire.filter(|&expire| expire > 0)?;
+    Some((expire - now).div_euclid(86400))
+}
+
+fn apply_usage(info: &mut SubscriptionInfoData, thresholds: &AlertThresholds, now: i64) {
+    info.used_bytes = used_bytes(info);
+    info.remaining_bytes = info
+        .total
+        .filter(|&total| total > 0)
+        .map(|total| total.saturating_sub(info.used_bytes));
+    info.usage_percent = used_percent(info);
+    info.expires_in_days = days_to_expire(info, now);
+    info.near_quota = info
+        .usage_percent
+        .is_some_and(|percent| percent >= thresholds.percent);
+    info.near_expiry = info
+        .expires_in_days
+        .is_some_and(|days| days <= i64::from(thresholds.expire_days));
+}
+
+fn fire_once(alert: SubscriptionAlert) {
+    let key = format!("{}:{:?}", alert.subscription_id, alert.kind);
+    let today = chrono::Local::now().date_naive().to_string();
+
+    {
+        let mut state = ALERT_STATE.lock().unwrap_or_else(|e| e.into_inner());
+        if state.get(&key) == Some(&today) {
+            log::debug!("今日已发送过订阅提醒，跳过：{}", key);
+            return;
+        }
+        state.insert(key, today);
+        save_state(&state);
+    }
+
+    log::info!(
+        "发送订阅提醒：{}（{:?}）",
+        alert.subscription_id,
+        alert.kind
+    );
+    alert.send_signal_to_dart();
+}
+
+fn state_file_path() -> Option<PathBuf> {
+    crate::utils::init_logger::get_app_data_dir()
+        .ok()
+        .map(|dir| dir.join(ALERT_STATE_FILE))
+}
+
+fn load_state() -> HashMap<String, String> {
+    let Some(path) = state_file_path() else {
+        return HashMap::new();
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("订阅提醒状态文件解析失败，已重置：{}", e);
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_state(state: &HashMap<String, String>) {
+    let Some(path) = state_file_path() else {
+        return;
+    };
+
+    let result = serde_json::to_string_pretty(state)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, json).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        log::warn!("保存订阅提醒状态失败：{}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_usage_handles_unlimited_and_thresholds() {
+        let thresholds = AlertThresholds {
+            percent: 90.0,
+            expire_days: 3,
+        };
+        let now = 1_700_000_000;
+
+        let mut info = SubscriptionInfoData {
+            upload: Some(20),
+            download: Some(72),
+            total: Some(100),
+            expire: Some(now + 2 * 86400 + 3600),
+            ..Default::default()
+        };
+        apply_usage(&mut info, &thresholds, now);
+        assert_eq!(info.used_bytes, 92);
+        assert_eq!(info.remaining_bytes, Some(8));
+        assert_eq!(info.usage_percent, Some(92.0));
+        assert_eq!(info.expires_in_days, Some(2));
+        assert!(info.near_quota && info.near_expiry);
+
+        // total 与 expire 为 0 表示不限流量、长期有效
+        let mut info = SubscriptionInfoData {
+            upload: Some(u64::MAX),
+            download: Some(1),
+            total: Some(0),
+            expire: Some(0),
+            ..Default::default()
+        };
+        apply_usage(&mut info, &thresholds, now);
+        assert_eq!(info.used_bytes, u64::MAX);
+        assert_eq!(info.remaining_bytes, None);
+        assert_eq!(info.usage_percent, None);
+        assert_eq!(info.expires_in_days, None);
+        assert!(!info.near_quota && !info.near_expiry);
+
+        // 超出总量时剩余为 0，已到期时天数为负
+        let mut info = SubscriptionInfoData {
+            download: Some(150),
+            total: Some(100),
+            expire: Some(now - 3600),
+            ..Default::default()
+        };
+        apply_usage(&mut info, &thresholds, now);
+        assert_eq!(info.remaining_bytes, Some(0));
+        assert_eq!(info.expires_in_days, Some(-1));
+        assert!(info.near_quota && info.near_expiry);
+    }
+}
@@ -0,0 +1,200 @@
+// 订阅内容类型识别
+//
+// 部分机场返回 Base64 编码的代理链接列表（ss://、vmess:// 等）而不是 Clash YAML。
+// 下载完成后识别内容类型：整个响应体为单个 Base64 串（允许换行折行）且解码后
+// 每行都是代理链接时，返回解码后的内容。同时支持标准与 URL 安全字母表，填充可有可无
+//
+// 随后做轻量校验：YAML 需为包含 proxies、proxy-providers 或 rules 的映射。
+// 服务器返回网页（验证页、过期提示等）或无法识别的内容时校验不通过，
+// 错误信息带上内容开头便于排查，内容本身仍然返回
+
+use super::signals::ContentKind;
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+
+// Clash 配置应包含的顶层字段（至少一个）
+const EXPECTED_KEYS: [&str; 3] = ["proxies", "proxy-providers", "rules"];
+
+// 错误信息中内容预览的字符数
+const PREVIEW_CHARS: usize = 200;
+
+// 兼容有无填充的解码器（URL 安全字符在解码前转换为标准字母表）
+const BASE64_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// 识别内容类型，Base64 编码的链接列表返回解码后的内容
+pub fn detect(content: String) -> (String, ContentKind) {
+    if let Some(decoded) = decode_uri_list(&content) {
+        log::info!(
+            "检测到 Base64 编码的代理链接列表，解码后长度：{} 字节",
+            decoded.len()
+        );
+        return (decoded, ContentKind::UriList);
+    }
+    if is_uri_list(&content) {
+        return (content, ContentKind::UriList);
+    }
+    if is_yaml_mapping(&content) {
+        return (content, ContentKind::Yaml);
+    }
+    (content, ContentKind::Unknown)
+}
+
+// 解码 Base64 串，解码结果不是代理链接列表时返回 None
+fn decode_uri_list(content: &str) -> Option<String> {
+    let token: String = content.split_whitespace().collect();
+    if token.is_empty()
+        || !token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_' | b'='))
+    {
+        return None;
+    }
+
+    let bytes = decode_base64(&token).ok()?;
+    let decoded = String::from_utf8(bytes).ok()?;
+    is_uri_list(&decoded).then_some(decoded)
+}
+
+// 解码 Base64，兼容标准与 URL 安全字母表，填充可有可无
+pub fn decode_base64(input: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    let standard = input.trim().replace('-', "+").replace('_', "/");
+    BASE64_LENIENT.decode(standard.trim_end_matches('='))
+}
+
+// 每个非空、非注释行都是 scheme://… 形式的链接，且至少有一行
+fn is_uri_list(content: &str) -> bool {
+    let mut lines = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+    lines.peek().is_some() && lines.all(is_uri)
+}
+
+fn is_uri(line: &str) -> bool {
+    line.split_once("://").is_some_and(|(scheme, rest)| {
+        !rest.is_empty()
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+fn is_yaml_mapping(content: &str) -> bool {
+    matches!(
+        serde_yaml_ng::from_str::<serde_yaml_ng::Value>(content),
+        Ok(serde_yaml_ng::Value::Mapping(_))
+    )
+}
+
+// 校验内容是否可用作订阅，失败时返回带内容预览的原因
+pub fn validate(content: &str, kind: ContentKind) -> Result<(), String> {
+    let reason = match kind {
+        ContentKind::UriList => return Ok(()),
+        _ if is_html(content) => "服务器返回的是网页，不是订阅配置".to_string(),
+        ContentKind::Yaml => match serde_yaml_ng::from_str::<serde_yaml_ng::Value>(content) {
+            Ok(serde_yaml_ng::Value::Mapping(mapping)) => {
+                if EXPECTED_KEYS.iter().any(|key| mapping.contains_key(*key)) {
+                    return Ok(());
+                }
+                format!("配置中缺少 {} 中的任何字段", EXPECTED_KEYS.join("、"))
+            }
+            Ok(_) => "内容不是 Clash 配置".to_string(),
+            Err(e) => format!("YAML 解析失败：{}", e),
+        },
+        ContentKind::Unknown => "无法识别的订阅内容".to_string(),
+    };
+    Err(format!("{}，内容开头：{}", reason, preview(content)))
+}
+
+fn is_html(content: &str) -> bool {
+    let head: String = content
+        .trim_start()
+        .chars()
+        .take(16)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    head.starts_with("<!doctype html") || head.starts_with("<html")
+}
+
+fn preview(content: &str) -> String {
+    let content = content.trim();
+    let mut preview: String = content.chars().take(PREVIEW_CHARS).collect();
+    if preview.len() < content.len() {
+        preview.push('…');
+    }
+    preview
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+
+    #[test]
+    fn test_detect_base64_variants() -> Result<(), String> {
+        let links =
+            "ss://YWVzLTI1Ni1nY206cGFzcw@1.2.3.4:8388#hk??????\nvmess://eyJhZGQiOiIxLjIuMy40In0=\n";
+
+        let padded = STANDARD.encode(links);
+        // 按 76 列折行的标准 Base64
+        let wrapped = padded
+            .as_bytes()
+            .chunks(76)
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        let url_safe = URL_SAFE_NO_PAD.encode(links);
+        assert!(url_safe.contains(['-', '_']), "{url_safe}");
+
+        for body in [padded, wrapped, url_safe] {
+            let (content, kind) = detect(body);
+            assert_eq!(kind, ContentKind::UriList);
+            assert_eq!(content, links);
+        }
+
+        let (content, kind) = detect(links.to_string());
+        assert_eq!((content.as_str(), kind), (links, ContentKind::UriList));
+
+        let yaml = "proxies: []\nrules: []\n";
+        assert_eq!(detect(yaml.to_string()).1, ContentKind::Yaml);
+
+        // 能解码但内容不是链接列表
+        let not_links = STANDARD.encode("hello world");
+        assert_eq!(detect(not_links.clone()), (not_links, ContentKind::Unknown));
+        assert_eq!(detect("<html></html>".to_string()).1, ContentKind::Unknown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_web_pages_and_missing_keys() -> Result<(), String> {
+        let check = |content: &str| {
+            let (content, kind) = detect(content.to_string());
+            validate(&content, kind)
+        };
+        check("proxy-providers:\n  a: {}\n")?;
+        check("ss://YWVzOnBhc3M@1.2.3.4:8388")?;
+
+        let page = format!(
+            "<!DOCTYPE html>\n<title>Just a moment...</title>{}",
+            "x".repeat(500)
+        );
+        let error = check(&page).err().ok_or("网页应校验失败")?;
+        assert!(error.starts_with("服务器返回的是网页"), "{error}");
+        assert!(
+            error.contains("Just a moment") && error.ends_with('…'),
+            "{error}"
+        );
+        assert!(check("  <HTML><body>expired</body></HTML>").is_err());
+
+        let error = check("port: 7890\n").err().ok_or("缺少字段应校验失败")?;
+        assert!(error.contains("proxies"), "{error}");
+        assert!(check("订阅已过期").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// 订阅更新前后的节点差异
+//
+// 按节点名称比较新旧配置的 proxies：新增、移除，以及同名但内容不同的节点。
+// 变更详情为 server:port。任一方无法解析时差异不可用，不影响订阅更新本身
+
+use serde_yaml_ng::Value;
+
+// 同名但内容不同的节点
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifiedProxy {
+    pub name: String,
+    pub old_endpoint: String,
+    pub new_endpoint: String,
+}
+
+// 节点差异
+#[derive(Debug, Default)]
+pub struct ProxyDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<ModifiedProxy>,
+    pub unchanged: usize,
+}
+
+// 比较新旧配置中的节点
+pub fn diff_configs(old_yaml: &str, new_yaml: &str) -> Result<ProxyDiff, String> {
+    let old = parse_proxies(old_yaml).map_err(|e| format!("旧配置{}", e))?;
+    let new = parse_proxies(new_yaml).map_err(|e| format!("新配置{}", e))?;
+
+    let mut diff = ProxyDiff::default();
+    for (name, proxy) in &new {
+        match old.iter().find(|(old_name, _)| old_name == name) {
+            None => diff.added.push(name.clone()),
+            Some((_, old_proxy)) if old_proxy != proxy => diff.modified.push(ModifiedProxy {
+                name: name.clone(),
+                old_endpoint: endpoint(old_proxy),
+                new_endpoint: endpoint(proxy),
+            }),
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    diff.removed = old
+        .into_iter()
+        .filter(|(name, _)| !new.iter().any(|(new_name, _)| new_name == name))
+        .map(|(name, _)| name)
+        .collect();
+    Ok(diff)
+}
+
+// 解析配置中的节点：(名称, 节点)，没有 proxies 字段时视为没有节点
+fn parse_proxies(yaml: &str) -> Result<Vec<(String, Value)>, String> {
+    let config: Value = serde_yaml_ng::from_str(yaml).map_err(|e| format!("解析失败：{}", e))?;
+    if !config.is_mapping() {
+        return Err("不是 Clash 配置".to_string());
+    }
+    let proxies = match config.get("proxies") {
+        None | Some(Value::Null) => return Ok(Vec::new()),
+        Some(Value::Sequence(proxies)) => proxies,
+        Some(_) => return Err("的 proxies 不是列表".to_string()),
+    };
+    Ok(proxies
+        .iter()
+        .filter_map(|proxy| {
+            let name = proxy.get("name")?.as_str()?;
+            Some((name.to_string(), proxy.clone()))
+        })
+        .collect())
+}
+
+fn endpoint(proxy: &Value) -> String {
+    let server = proxy.get("server").and_then(Value::as_str).unwrap_or("?");
+    let port = match proxy.get("port") {
+        Some(Value::Number(port)) => port.to_string(),
+        Some(Value::String(port)) => port.clone(),
+        _ => "?".to_string(),
+    };
+    format!("{}:{}", server, port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_by_proxy_name() -> Result<(), String> {
+        let old = r#"
+proxies:
+  - { name: "香港 01", type: ss, server: hk1.example.com, port: 443, password: a }
+  - { name: "日本 01", type: ss, server: jp1.example.com, port: 443, password: a }
+  - { name: "美国 01", type: ss, server: us1.example.com, port: 443, password: a }
+  - { name: "旧节点", type: ss, server: old.example.com, port: 443, password: a }
+"#;
+        let new = r#"
+proxies:
+  - { name: "香港 01", type: ss, server: hk1.example.com, port: 443, password: a }
+  - { name: "日本 01", type: ss, server: jp2.example.com, port: "8443", password: a }
+  - { name: "美国 01", type: ss, server: us1.example.com, port: 443, password: b }
+  - { name: "新加坡 01", type: ss, server: sg1.example.com, port: 443, password: a }
+rules: []
+"#;
+        let diff = diff_configs(old, new)?;
+        assert_eq!(diff.added, ["新加坡 01"]);
+        assert_eq!(diff.removed, ["旧节点"]);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            diff.modified[0],
+            ModifiedProxy {
+                name: "日本 01".to_string(),
+                old_endpoint: "jp1.example.com:443".to_string(),
+                new_endpoint: "jp2.example.com:8443".to_string(),
+            }
+        );
+        // 端点不变但其他字段变化同样视为修改
+        assert_eq!(diff.modified[1].name, "美国 01");
+
+        // 没有 proxies 的旧配置视为没有节点
+        assert_eq!(diff_configs("rules: []", new)?.added.len(), 4);
+        assert!(diff_configs("<html>", new).is_err());
+        assert!(diff_configs(old, "proxies: 1").is_err());
+        Ok(())
+    }
+}
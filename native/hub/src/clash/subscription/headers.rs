@@ -0,0 +1,153 @@
+// 订阅响应头解析
+//
+// - content-disposition：订阅文件名，用于命名配置。优先使用 RFC 5987 的 filename*
+//   （如 filename*=UTF-8''%E6%9C%BA%E5%9C%BA.yaml），其次为带引号或不带引号的 filename。
+//   部分机场直接发送 UTF-8 字节或百分号编码的 filename，同样解码
+// - profile-update-interval：建议的更新间隔（小时）
+
+use reqwest::header::HeaderMap;
+
+// 订阅文件名，只保留最后一级路径
+pub fn parse_filename(headers: &HeaderMap) -> Option<String> {
+    let value =
+        String::from_utf8_lossy(headers.get("content-disposition")?.as_bytes()).into_owned();
+    log::debug!("解析 content-disposition 头：{}", value);
+
+    let params = split_params(&value);
+    let extended = params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("filename*"))
+        .and_then(|(_, value)| decode_extended(value));
+    let name = extended.or_else(|| {
+        params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("filename"))
+            .map(|(_, value)| decode_plain(value))
+    })?;
+
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// 建议的更新间隔（小时），0 或无法解析时返回 None
+pub fn parse_update_interval(headers: &HeaderMap) -> Option<u32> {
+    let value = headers.get("profile-update-interval")?.to_str().ok()?;
+    value.trim().parse::<u32>().ok().filter(|hours| *hours > 0)
+}
+
+// 按 ; 分割参数（引号内的 ; 不分割），返回 (参数名, 去掉引号的值)
+fn split_params(value: &str) -> Vec<(String, String)> {
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut parts = Vec::new();
+    for c in value.chars() {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            }
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    parts
+        .iter()
+        .filter_map(|part| part.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+// RFC 5987：charset'language'percent-encoded
+fn decode_extended(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+    let bytes = urlencoding::decode_binary(encoded.as_bytes());
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes.into_owned()).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.iter().map(|&b| char::from(b)).collect())
+    } else {
+        None
+    }
+}
+
+// 普通 filename，包含百分号编码且解码为有效 UTF-8 时使用解码结果
+fn decode_plain(value: &str) -> String {
+    if value.contains('%')
+        && let Ok(decoded) = urlencoding::decode(value)
+    {
+        return decoded.into_owned();
+    }
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn filename(value: &[u8]) -> Result<Option<String>, String> {
+        let mut headers = HeaderMap::new();
+        let value = HeaderValue::from_bytes(value).map_err(|e| e.to_string())?;
+        headers.insert("content-disposition", value);
+        Ok(parse_filename(&headers))
+    }
+
+    #[test]
+    fn test_parse_filename_variants() -> Result<(), String> {
+        let cases: [(&[u8], &str); 8] = [
+            (
+                br#"attachment; filename="MyAirport.yaml""#,
+                "MyAirport.yaml",
+            ),
+            (b"attachment; filename=MyAirport.yaml", "MyAirport.yaml"),
+            (
+                br#"attachment;filename="a; b \"c\".yaml""#,
+                r#"a; b "c".yaml"#,
+            ),
+            (
+                "attachment; filename*=UTF-8''%E6%9C%BA%E5%9C%BA.yaml".as_bytes(),
+                "机场.yaml",
+            ),
+            // filename* 优先于 filename
+            (
+                br#"attachment; filename="fallback.yaml"; filename*=utf-8''%F0%9F%9A%80%20Fast"#,
+                "🚀 Fast",
+            ),
+            (b"attachment; filename=%E6%9C%BA%E5%9C%BA", "机场"),
+            ("attachment; filename=\"机场订阅\"".as_bytes(), "机场订阅"),
+            (
+                br#"attachment; filename="../../etc/clash.yaml""#,
+                "clash.yaml",
+            ),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(filename(value)?.as_deref(), Some(expected), "{value:?}");
+        }
+
+        assert_eq!(filename(b"attachment")?, None);
+        assert_eq!(filename(br#"inline; filename="""#)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_update_interval() {
+        let interval = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("profile-update-interval", HeaderValue::from_static(value));
+            parse_update_interval(&headers)
+        };
+        assert_eq!(interval("24"), Some(24));
+        assert_eq!(interval(" 12 "), Some(12));
+        assert_eq!(interval("0"), None);
+        assert_eq!(interval("daily"), None);
+        assert_eq!(parse_update_interval(&HeaderMap::new()), None);
+    }
+}
@@ -5,8 +5,9 @@
 // - Base64 编码的代理链接列表
 // - 纯文本代理链接列表（vless、vmess、hysteria2、ss、trojan 等）
 //
-// 将各种格式统一转换为标准 Clash 配置
+// 将各种格式统一转换为标准 Clash 配置。转换链接列表时记录每个无效行的行号与原因
 
+use super::content::decode_base64;
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use serde_json::{Value as JsonValue, json};
 use std::collections::HashMap;
@@ -15,6 +16,13 @@ use url::Url;
 // 代理链接解析器
 pub struct ProxyParser;
 
+// 无法解析的链接行（行号从 1 开始）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineError {
+    pub line: u32,
+    pub message: String,
+}
+
 impl ProxyParser {
     // 解析订阅内容为标准 Clash 配置
     //
@@ -66,7 +74,7 @@ impl ProxyParser {
 
         // 解析代理链接
         log::info!("开始解析代理链接…");
-        let proxies = Self::parse_proxy_links(&decoded)?;
+        let (proxies, _) = Self::parse_proxy_links(&decoded);
 
         if proxies.is_empty() {
             return Err("未找到任何有效的代理链接".to_string());
@@ -78,6 +86,30 @@ impl ProxyParser {
         Self::generate_clash_config(proxies)
     }
 
+    // 将代理链接列表（可为 Base64 编码）转换为 Clash 配置
+    //
+    // 返回：(YAML 配置, 无效的行)，没有任何有效链接时返回错误
+    pub fn convert_uri_list(content: &str) -> Result<(String, Vec<LineError>), String> {
+        let (content, _) = super::content::detect(content.to_string());
+        let (proxies, errors) = Self::parse_proxy_links(&content);
+        if proxies.is_empty() {
+            return Err(match errors.first() {
+                Some(error) => format!(
+                    "未找到任何有效的代理链接（第 {} 行：{}）",
+                    error.line, error.message
+                ),
+                None => "未找到任何有效的代理链接".to_string(),
+            });
+        }
+
+        log::info!(
+            "代理链接转换完成：{} 个节点，{} 行无效",
+            proxies.len(),
+            errors.len()
+        );
+        Ok((Self::generate_clash_config(proxies)?, errors))
+    }
+
     // 判断是否为 YAML 配置
     // 必须是合法的 YAML 格式且包含 Clash 配置的关键字段
     fn is_yaml_config(content: &str) -> bool {
@@ -127,11 +159,12 @@ impl ProxyParser {
         Ok(proxies_array.clone())
     }
 
-    // 解析代理链接列表
-    fn parse_proxy_links(content: &str) -> Result<Vec<JsonValue>, String> {
+    // 解析代理链接列表，返回 (代理节点, 无效的行)
+    fn parse_proxy_links(content: &str) -> (Vec<JsonValue>, Vec<LineError>) {
         let mut proxies = Vec::new();
+        let mut errors = Vec::new();
 
-        for line in content.lines() {
+        for (index, line) in content.lines().enumerate() {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
                 continue;
@@ -147,11 +180,15 @@ impl ProxyParser {
                         .map(|(_, c)| c)
                         .collect::<String>();
                     log::warn!("跳过无效代理：{} - {}", preview, e);
+                    errors.push(LineError {
+                        line: index as u32 + 1,
+                        message: e,
+                    });
                 }
             }
         }
 
-        Ok(proxies)
+        (proxies, errors)
     }
 
     // 解析单个代理链接
@@ -185,7 +222,7 @@ impl ProxyParser {
     fn parse_vless(link: &str) -> Result<JsonValue, String> {
         let url = Url::parse(link).map_err(|e| format!("URL 解析失败：{}", e))?;
 
-        let uuid = url.username();
+        let uuid = Self::url_decode(url.username());
         let server = url.host_str().ok_or("缺少服务器地址")?.to_string();
         let port = url.port().ok_or("缺少端口")? as i64;
 
@@ -248,21 +285,32 @@ impl ProxyParser {
     // 解析 VMess 链接
     fn parse_vmess(link: &str) -> Result<JsonValue, String> {
         let encoded = link.strip_prefix("vmess://").ok_or("无效的 VMess 链接")?;
-        let decoded = BASE64
-            .decode(encoded.as_bytes())
-            .map_err(|e| format!("Base64 解码失败：{}", e))?;
+        let decoded = decode_base64(encoded).map_err(|e| format!("Base64 解码失败：{}", e))?;
         let json_str = String::from_utf8(decoded).map_err(|e| format!("UTF-8 转换失败：{}", e))?;
         let data: JsonValue =
             serde_json::from_str(&json_str).map_err(|e| format!("JSON 解析失败：{}", e))?;
 
+        let server = data["add"].as_str().filter(|s| !s.is_empty());
+        let uuid = data["id"].as_str().filter(|s| !s.is_empty());
+        let (Some(server), Some(uuid)) = (server, uuid) else {
+            return Err("VMess 配置缺少服务器地址或 UUID".to_string());
+        };
+
+        // port 与 aid 在不同客户端导出的链接中可能是字符串或数字
+        let number = |value: &JsonValue| {
+            value
+                .as_i64()
+                .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+        };
+
         let mut proxy = json!({
-            "name": data["ps"].as_str().unwrap_or("VMess"),
+            "name": data["ps"].as_str().filter(|s| !s.is_empty()).unwrap_or("VMess"),
             "type": "vmess",
-            "server": data["add"].as_str().unwrap_or(""),
-            "port": data["port"].as_str().unwrap_or("443").parse::<i64>().unwrap_or(443),
-            "uuid": data["id"].as_str().unwrap_or(""),
-            "alterId": data["aid"].as_str().unwrap_or("0").parse::<i64>().unwrap_or(0),
-            "cipher": data["scy"].as_str().unwrap_or("auto"),
+            "server": server,
+            "port": number(&data["port"]).unwrap_or(443),
+            "uuid": uuid,
+            "alterId": number(&data["aid"]).unwrap_or(0),
+            "cipher": data["scy"].as_str().filter(|s| !s.is_empty()).unwrap_or("auto"),
             "udp": true,
         });
 
@@ -370,37 +418,51 @@ impl ProxyParser {
 
     // 解析 Shadowsocks 链接
     fn parse_shadowsocks(link: &str) -> Result<JsonValue, String> {
-        // ss://method:password@server:port#name
-        // 或 ss://base64(method:password)@server:port#name
+        // SIP002：ss://base64url(method:password)@server:port/?plugin=...#name
+        //        （SS2022 的认证部分为百分号编码的 method:password）
+        // 旧格式：ss://base64(method:password@server:port)#name
         let link = link.strip_prefix("ss://").ok_or("无效的 SS 链接")?;
 
-        let (auth_part, rest) = link.split_once('@').ok_or("SS 链接格式错误：缺少 @")?;
+        let (link, name_part) = link.split_once('#').unwrap_or((link, "Shadowsocks"));
+        let (link, query) = link.split_once('?').unwrap_or((link, ""));
+        let link = link.trim_end_matches('/');
 
-        // 解析认证部分
-        let decoded_auth = if auth_part.contains(':') {
-            auth_part.to_string()
-        } else {
-            // Base64 编码
-            let decoded = BASE64
-                .decode(auth_part.as_bytes())
-                .map_err(|e| format!("Base64 解码失败：{}", e))?;
-            String::from_utf8(decoded).map_err(|e| format!("UTF-8 转换失败：{}", e))?
+        let (auth_part, server_port) = match link.rsplit_once('@') {
+            Some((auth_part, server_port)) => {
+                let decoded_auth = match decode_base64(auth_part) {
+                    Ok(decoded) if !auth_part.contains(':') => {
+                        String::from_utf8(decoded).map_err(|e| format!("UTF-8 转换失败：{}", e))?
+                    }
+                    _ => Self::url_decode(auth_part),
+                };
+                (decoded_auth, server_port.to_string())
+            }
+            None => {
+                let decoded = decode_base64(link).map_err(|e| format!("Base64 解码失败：{}", e))?;
+                let decoded =
+                    String::from_utf8(decoded).map_err(|e| format!("UTF-8 转换失败：{}", e))?;
+                let (auth_part, server_port) =
+                    decoded.rsplit_once('@').ok_or("SS 链接格式错误：缺少 @")?;
+                (auth_part.to_string(), server_port.to_string())
+            }
         };
 
-        let (method, password) = decoded_auth.split_once(':').ok_or("SS 认证格式错误")?;
-
-        // 解析服务器和端口
-        let (server_port, name_part) = rest.split_once('#').unwrap_or((rest, "Shadowsocks"));
+        let (method, password) = auth_part.split_once(':').ok_or("SS 认证格式错误")?;
 
+        // 解析服务器和端口（IPv6 地址带方括号）
         let (server, port_str) = server_port
             .rsplit_once(':')
             .ok_or("SS 链接格式错误：缺少端口")?;
+        let server = server.trim_start_matches('[').trim_end_matches(']');
+        if server.is_empty() {
+            return Err("缺少服务器地址".to_string());
+        }
 
         let port = port_str.parse::<i64>().map_err(|_| "端口解析失败")?;
 
         let name = Self::url_decode(name_part);
 
-        Ok(json!({
+        let mut proxy = json!({
             "name": name,
             "type": "ss",
             "server": server,
@@ -408,7 +470,60 @@ impl ProxyParser {
             "cipher": method,
             "password": password,
             "udp": true,
-        }))
+        });
+
+        let params = Self::parse_query_params(query);
+        if let Some(plugin) = params.get("plugin") {
+            Self::apply_ss_plugin(&mut proxy, plugin);
+        }
+
+        Ok(proxy)
+    }
+
+    // SIP002 插件参数，如 obfs-local;obfs=http;obfs-host=example.com
+    // 或 v2ray-plugin;mode=websocket;host=example.com;path=/;tls
+    fn apply_ss_plugin(proxy: &mut JsonValue, plugin: &str) {
+        let mut parts = plugin.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let mut opts = serde_json::Map::new();
+        for part in parts {
+            let (key, value) = match part.split_once('=') {
+                Some((key, value)) => (key.trim(), json!(value.trim())),
+                // 不带值的参数为开关，如 tls
+                None => (part.trim(), json!(true)),
+            };
+            if !key.is_empty() {
+                opts.insert(key.to_string(), value);
+            }
+        }
+
+        match name {
+            "obfs-local" | "simple-obfs" | "obfs" => {
+                proxy["plugin"] = json!("obfs");
+                proxy["plugin-opts"] = json!({
+                    "mode": opts.get("obfs").cloned().unwrap_or(json!("http")),
+                    "host": opts.get("obfs-host").cloned().unwrap_or(json!("")),
+                });
+            }
+            "v2ray-plugin" => {
+                let mut plugin_opts = json!({
+                    "mode": opts.get("mode").cloned().unwrap_or(json!("websocket")),
+                    "tls": opts.contains_key("tls"),
+                });
+                for key in ["host", "path"] {
+                    if let Some(value) = opts.get(key) {
+                        plugin_opts[key] = value.clone();
+                    }
+                }
+                proxy["plugin"] = json!("v2ray-plugin");
+                proxy["plugin-opts"] = plugin_opts;
+            }
+            "" => {}
+            _ => {
+                proxy["plugin"] = json!(name);
+                proxy["plugin-opts"] = JsonValue::Object(opts);
+            }
+        }
     }
     // 解析 ShadowsocksR 链接
     fn parse_shadowsocksr(link: &str) -> Result<JsonValue, String> {
@@ -490,7 +605,10 @@ impl ProxyParser {
         // trojan://password@server:port?params#name
         let url = Url::parse(link).map_err(|e| format!("URL 解析失败：{}", e))?;
 
-        let password = url.username();
+        let password = Self::url_decode(url.username());
+        if password.is_empty() {
+            return Err("缺少密码".to_string());
+        }
         let server = url.host_str().ok_or("缺少服务器地址")?.to_string();
         let port = url.port().unwrap_or(443) as i64;
 
@@ -664,11 +782,22 @@ impl ProxyParser {
     //
     // 注意：端口、模式、日志、DNS 等运行时参数会由 ConfigInjector 统一注入
     // 这里只生成核心的代理节点、代理组、规则配置
-    fn generate_clash_config(proxies: Vec<JsonValue>) -> Result<String, String> {
-        let proxy_names: Vec<String> = proxies
-            .iter()
-            .filter_map(|p| p["name"].as_str().map(|s| s.to_string()))
-            .collect();
+    fn generate_clash_config(mut proxies: Vec<JsonValue>) -> Result<String, String> {
+        // 节点名称必须唯一，重名时追加序号
+        let mut proxy_names: Vec<String> = Vec::new();
+        for proxy in &mut proxies {
+            let Some(base) = proxy["name"].as_str().map(str::to_string) else {
+                continue;
+            };
+            let mut name = base.clone();
+            let mut index = 2;
+            while proxy_names.contains(&name) {
+                name = format!("{} {}", base, index);
+                index += 1;
+            }
+            proxy["name"] = json!(name);
+            proxy_names.push(name);
+        }
 
         let config = json!({
             // 代理节点（必需）
@@ -711,3 +840,82 @@ impl ProxyParser {
         Ok(yaml_string)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+    fn proxies(yaml: &str) -> Result<Vec<serde_yaml_ng::Value>, String> {
+        let config: serde_yaml_ng::Value =
+            serde_yaml_ng::from_str(yaml).map_err(|e| e.to_string())?;
+        config["proxies"]
+            .as_sequence()
+            .cloned()
+            .ok_or_else(|| "缺少 proxies".to_string())
+    }
+
+    #[test]
+    fn test_convert_uri_list_schemes() -> Result<(), String> {
+        let vmess = BASE64.encode(
+            r#"{"v":"2","ps":"VM 节点","add":"vm.example.com","port":443,"id":"uuid-1","aid":"0","net":"ws","path":"/ws","tls":"tls"}"#,
+        );
+        let ss_auth = URL_SAFE_NO_PAD.encode("chacha20-ietf-poly1305:p@ss");
+        let legacy_ss = BASE64.encode("aes-128-gcm:secret@10.0.0.2:8389");
+        let list = [
+            format!("ss://{ss_auth}@10.0.0.1:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dcdn.example.com#SS%20%E9%A6%99%E6%B8%AF"),
+            format!("ss://{legacy_ss}#Legacy"),
+            format!("vmess://{vmess}"),
+            "trojan://pa%23ss@tr.example.com:443?sni=tr.example.com#Trojan".to_string(),
+            "vless://uuid-2@vl.example.com:443?security=reality&pbk=key&sid=ab&type=grpc&serviceName=svc#VL".to_string(),
+            "hysteria2://pw@hy.example.com:8443?sni=hy.example.com&insecure=1#Trojan".to_string(),
+        ]
+        .join("\n");
+
+        // 同时验证 Base64 包裹的列表
+        for content in [list.clone(), BASE64.encode(&list)] {
+            let (yaml, errors) = ProxyParser::convert_uri_list(&content)?;
+            assert!(errors.is_empty(), "{errors:?}");
+            let proxies = proxies(&yaml)?;
+            assert_eq!(proxies.len(), 6);
+
+            let ss = &proxies[0];
+            assert_eq!(ss["name"].as_str(), Some("SS 香港"));
+            assert_eq!(ss["password"].as_str(), Some("p@ss"));
+            assert_eq!(ss["plugin"].as_str(), Some("obfs"));
+            assert_eq!(ss["plugin-opts"]["host"].as_str(), Some("cdn.example.com"));
+            assert_eq!(proxies[1]["server"].as_str(), Some("10.0.0.2"));
+            assert_eq!(proxies[1]["port"].as_i64(), Some(8389));
+            assert_eq!(proxies[2]["port"].as_i64(), Some(443));
+            assert_eq!(proxies[2]["ws-opts"]["path"].as_str(), Some("/ws"));
+            assert_eq!(proxies[3]["password"].as_str(), Some("pa#ss"));
+            assert_eq!(
+                proxies[4]["reality-opts"]["public-key"].as_str(),
+                Some("key")
+            );
+            // 重名节点追加序号
+            assert_eq!(proxies[5]["name"].as_str(), Some("Trojan 2"));
+
+            let config: serde_yaml_ng::Value =
+                serde_yaml_ng::from_str(&yaml).map_err(|e| e.to_string())?;
+            assert_eq!(config["proxy-groups"][0]["type"].as_str(), Some("select"));
+            assert_eq!(config["rules"][0].as_str(), Some("MATCH,PROXY"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_uri_list_reports_line_errors() -> Result<(), String> {
+        let content = "# 注释\n\ntrojan://pw@tr.example.com:443#ok\nvmess://not-base64!\nss://missing-port@host\nfoo://bar";
+        let (_, errors) = ProxyParser::convert_uri_list(content)?;
+        let lines: Vec<_> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [4, 5, 6]);
+        assert!(errors[2].message.contains("不支持的协议"), "{errors:?}");
+
+        let error = ProxyParser::convert_uri_list("foo://bar")
+            .err()
+            .ok_or("应失败")?;
+        assert!(error.contains("第 1 行"), "{error}");
+        Ok(())
+    }
+}
@@ -2,85 +2,438 @@
 //
 // 目的：定义订阅下载的通信接口
 
+use super::diff::{ModifiedProxy, diff_configs};
+use super::downloader::{
+    ClientPool, DEFAULT_CONNECT_TIMEOUT_SECONDS, DEFAULT_MAX_BODY_BYTES, DEFAULT_MAX_REDIRECTS,
+    DEFAULT_MAX_RETRIES, DEFAULT_RETRY_BACKOFF_MS, DownloadOptions, RetryPolicy,
+};
+use super::parser::{LineError, ProxyParser};
 use rinf::{DartSignal, RustSignal};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+// 批量更新的默认并发数
+const DEFAULT_BATCH_CONCURRENCY: usize = 3;
 
 // ============================================================================
 // 订阅下载消息协议
 // ============================================================================
 
 // 代理模式枚举
-#[derive(Deserialize, Serialize, Clone, Copy, Debug, rinf::SignalPiece)]
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash, rinf::SignalPiece)]
 pub enum ProxyMode {
     Direct = 0, // 直连
     System = 1, // 系统代理
     Core = 2,   // Clash 核心代理
+    Custom = 3, // 自定义代理地址
 }
 
-// Dart → Rust：下载订阅请求
-#[derive(Deserialize, DartSignal)]
+// 下载内容类型
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, rinf::SignalPiece)]
+pub enum ContentKind {
+    Yaml,    // Clash YAML 配置
+    UriList, // 代理链接列表（Base64 编码时已解码）
+    Unknown, // 无法识别
+}
+
+// Dart → Rust：下载订阅请求（也作为批量更新中的单项）
+#[derive(Deserialize, DartSignal, rinf::SignalPiece)]
 pub struct DownloadSubscriptionRequest {
     pub url: String,
     pub proxy_mode: ProxyMode,
     pub user_agent: String,
-    pub timeout_seconds: u64,
-    pub mixed_port: u16, // Clash 混合端口（用于 Core 代理模式）
+    pub timeout_seconds: u64,                 // 总超时（秒），包含重试与等待
+    pub connect_timeout_seconds: Option<u64>, // 连接超时（秒，默认 10）
+    pub read_timeout_seconds: Option<u64>,    // 读取超时（秒），两次收到数据的最大间隔
+    pub mixed_port: u16,                      // Clash 混合端口（用于 Core 代理模式）
+    pub subscription_id: Option<String>,      // 订阅 ID（提供时评估用量提醒）
+    pub max_retries: Option<u32>,             // 失败后的重试次数（默认 2）
+    pub retry_backoff_ms: Option<u64>,        // 首次重试前的等待时间（毫秒，默认 1000）
+    pub custom_proxy_url: Option<String>,     // 自定义代理地址（用于 Custom 代理模式）
+    pub max_body_bytes: Option<u64>,          // 响应体大小上限（字节，默认 32 MB）
+    pub fallback_modes: Option<Vec<ProxyMode>>, // 主代理模式连接失败时依次尝试的备用模式
+    pub max_redirects: Option<u32>,           // 最多跟随的重定向次数（默认 5）
+    pub save_path: Option<String>,            // 提供时将通过校验的内容原子写入该路径
+    pub quota_warning_percent: Option<f64>,   // 用量提醒阈值（百分比，默认使用提醒设置）
+    pub expiry_warning_days: Option<u32>,     // 到期提醒阈值（天，默认使用提醒设置）
+    pub allow_invalid_certs: bool,            // 不校验服务器证书（自签名证书）
+    pub override_sni: Option<String>,         // TLS 握手使用的名称（仅直连模式）
+}
+
+// 批量更新中的单个订阅
+#[derive(Deserialize, rinf::SignalPiece)]
+pub struct SubscriptionDownloadItem {
+    pub item_id: String, // 用于对应下载响应
+    pub request: DownloadSubscriptionRequest,
+}
+
+// Dart → Rust：批量更新订阅（限制并发数）
+#[derive(Deserialize, DartSignal)]
+pub struct UpdateAllSubscriptions {
+    pub items: Vec<SubscriptionDownloadItem>,
+    pub max_concurrency: Option<u32>, // 同时下载的数量（默认 3）
 }
 
 // Rust → Dart：下载订阅响应
 #[derive(Serialize, RustSignal)]
 pub struct DownloadSubscriptionResponse {
+    pub item_id: Option<String>, // 批量更新时对应的单项 ID
     pub success: bool,
     pub content: String,                                 // 下载的配置内容
+    pub content_kind: ContentKind,                       // 内容类型
+    pub validated: bool, // 内容是否通过校验（未通过时 error_message 说明原因）
     pub subscription_info: Option<SubscriptionInfoData>, // 订阅信息
+    pub filename: Option<String>, // 响应头中的订阅文件名
+    pub update_interval_hours: Option<u32>, // 响应头中建议的更新间隔（小时）
+    pub final_url: Option<String>, // 跟随重定向后的最终地址
+    pub redirect_downgraded: bool, // 重定向是否从 https 降级为 http
+    pub saved_path: Option<String>, // 已保存的文件路径（请求提供 save_path 且保存成功时）
+    pub error_message: Option<String>,
+    pub attempts: u32,                      // 尝试次数（包含首次请求）
+    pub used_proxy_mode: Option<ProxyMode>, // 下载成功时使用的代理模式
+}
+
+// Rust → Dart：订阅下载进度（约每秒 4 次）
+#[derive(Serialize, RustSignal)]
+pub struct SubscriptionDownloadProgress {
+    pub url: String,
+    pub received_bytes: u64,
+    pub total_bytes: Option<u64>, // 来自 Content-Length，未知时为空
+}
+
+// Rust → Dart：批量更新完成
+#[derive(Serialize, RustSignal)]
+pub struct BatchUpdateComplete {
+    pub succeeded: u32,
+    pub failed: u32,
+}
+
+// Dart → Rust：将代理链接列表转换为 Clash 配置
+#[derive(Deserialize, DartSignal)]
+pub struct ConvertUriListRequest {
+    pub content: String, // 每行一个链接，可为 Base64 编码
+}
+
+// Rust → Dart：代理链接列表转换结果
+#[derive(Serialize, RustSignal)]
+pub struct ConvertUriListResponse {
+    pub success: bool,
+    pub yaml: String,              // 生成的 Clash 配置
+    pub errors: Vec<UriLineError>, // 无法解析的行
+    pub error_message: Option<String>,
+}
+
+// 无法解析的链接行
+#[derive(Serialize, Clone, Debug, rinf::SignalPiece)]
+pub struct UriLineError {
+    pub line: u32, // 行号（从 1 开始）
+    pub message: String,
+}
+
+impl From<LineError> for UriLineError {
+    fn from(error: LineError) -> Self {
+        Self {
+            line: error.line,
+            message: error.message,
+        }
+    }
+}
+
+// Dart → Rust：比较订阅更新前后的节点
+#[derive(Deserialize, DartSignal)]
+pub struct DiffSubscriptionRequest {
+    pub old_yaml: String,
+    pub new_yaml: String,
+}
+
+// Rust → Dart：订阅节点差异
+#[derive(Serialize, RustSignal)]
+pub struct SubscriptionDiffResult {
+    pub available: bool, // 任一方无法解析时为 false，error_message 说明原因
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<ModifiedProxyItem>,
+    pub added_count: u32,
+    pub removed_count: u32,
+    pub modified_count: u32,
+    pub unchanged_count: u32,
     pub error_message: Option<String>,
 }
 
+// 同名但内容不同的节点
+#[derive(Serialize, Clone, Debug, rinf::SignalPiece)]
+pub struct ModifiedProxyItem {
+    pub name: String,
+    pub old_endpoint: String, // server:port
+    pub new_endpoint: String,
+}
+
+impl From<ModifiedProxy> for ModifiedProxyItem {
+    fn from(proxy: ModifiedProxy) -> Self {
+        Self {
+            name: proxy.name,
+            old_endpoint: proxy.old_endpoint,
+            new_endpoint: proxy.new_endpoint,
+        }
+    }
+}
+
 // 订阅信息数据
-#[derive(Serialize, Deserialize, Clone, Debug, rinf::SignalPiece)]
+//
+// total 或 expire 为 0 表示不限流量或长期有效，相应的计算字段为空且不会提醒
+#[derive(Serialize, Deserialize, Clone, Debug, Default, rinf::SignalPiece)]
 pub struct SubscriptionInfoData {
     pub upload: Option<u64>,
     pub download: Option<u64>,
     pub total: Option<u64>,
-    pub expire: Option<i64>, // Unix 时间戳
+    pub expire: Option<i64>,          // Unix 时间戳
+    pub used_bytes: u64,              // 已用流量（上传 + 下载）
+    pub remaining_bytes: Option<u64>, // 剩余流量
+    pub usage_percent: Option<f64>,   // 已用百分比
+    pub expires_in_days: Option<i64>, // 距到期的天数（已到期时为负数）
+    pub near_quota: bool,             // 已用百分比达到阈值
+    pub near_expiry: bool,            // 距到期天数不超过阈值（含已到期）
 }
 
 impl DownloadSubscriptionRequest {
     // 处理下载订阅请求
     pub async fn handle(self) {
         log::info!("收到下载订阅请求：{}", self.url);
+        self.download(&ClientPool::default(), None)
+            .await
+            .send_signal_to_dart();
+    }
+
+    // 下载并生成响应，批量更新时带上单项 ID
+    async fn download(
+        &self,
+        clients: &ClientPool,
+        item_id: Option<String>,
+    ) -> DownloadSubscriptionResponse {
+        let options = DownloadOptions {
+            proxy_mode: self.proxy_mode,
+            fallback_modes: self.fallback_modes.as_deref().unwrap_or_default(),
+            user_agent: &self.user_agent,
+            timeout_seconds: self.timeout_seconds,
+            connect_timeout: Duration::from_secs(
+                self.connect_timeout_seconds
+                    .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECONDS),
+            ),
+            read_timeout: self.read_timeout_seconds.map(Duration::from_secs),
+            mixed_port: self.mixed_port,
+            custom_proxy_url: self.custom_proxy_url.as_deref(),
+            retry: RetryPolicy {
+                max_retries: self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+                backoff_ms: self.retry_backoff_ms.unwrap_or(DEFAULT_RETRY_BACKOFF_MS),
+            },
+            max_body_bytes: self.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES),
+            max_redirects: self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+            allow_invalid_certs: self.allow_invalid_certs,
+            override_sni: self.override_sni.as_deref(),
+        };
+        let on_progress = |received_bytes, total_bytes| {
+            SubscriptionDownloadProgress {
+                url: self.url.clone(),
+                received_bytes,
+                total_bytes,
+            }
+            .send_signal_to_dart();
+        };
 
         // 调用下载器
-        let result = super::downloader::download_subscription(
-            &self.url,
-            self.proxy_mode,
-            &self.user_agent,
-            self.timeout_seconds,
-            self.mixed_port,
-        )
-        .await;
+        let outcome =
+            super::downloader::download_subscription(&self.url, &options, clients, &on_progress)
+                .await;
+        let attempts = outcome.attempts;
+
+        match outcome.result {
+            Ok(downloaded) => {
+                let info = downloaded.subscription_info.map(|mut info| {
+                    super::alerts::compute_usage(
+                        &mut info,
+                        self.quota_warning_percent,
+                        self.expiry_warning_days,
+                    );
+                    info
+                });
+                let (content, content_kind) = super::content::detect(downloaded.content);
+                let validation = super::content::validate(&content, content_kind);
+                if let Err(e) = &validation {
+                    log::warn!("订阅内容校验未通过：{}", e);
+                }
+                log::info!(
+                    "订阅下载成功，内容长度：{} 字节，尝试次数：{}",
+                    content.len(),
+                    attempts
+                );
+
+                if let (Some(id), Some(info)) = (&self.subscription_id, &info) {
+                    super::alerts::evaluate(id, info);
+                }
+
+                // 只保存通过校验的内容，失败时原文件不变，仍返回内容供预览
+                let saved = match (&self.save_path, &validation) {
+                    (None, _) => Ok(None),
+                    (Some(_), Err(e)) => Err(format!("{}，未保存订阅文件", e)),
+                    (Some(path), Ok(())) => super::storage::save_atomic(path, &content)
+                        .await
+                        .map(|path| Some(path.to_string_lossy().into_owned())),
+                };
+                if let Err(e) = &saved {
+                    log::error!("保存订阅失败：{}", e);
+                }
+                let error_message = match &saved {
+                    Err(e) => Some(e.clone()),
+                    Ok(_) => validation.as_ref().err().cloned(),
+                };
 
-        let response = match result {
-            Ok((content, info)) => {
-                log::info!("订阅下载成功，内容长度：{} 字节", content.len());
                 DownloadSubscriptionResponse {
-                    success: true,
+                    item_id,
+                    success: saved.is_ok(),
                     content,
+                    content_kind,
+                    validated: validation.is_ok(),
                     subscription_info: info,
-                    error_message: None,
+                    filename: downloaded.filename,
+                    update_interval_hours: downloaded.update_interval_hours,
+                    final_url: Some(downloaded.final_url),
+                    redirect_downgraded: downloaded.redirect_downgraded,
+                    saved_path: saved.ok().flatten(),
+                    error_message,
+                    attempts,
+                    used_proxy_mode: outcome.used_proxy_mode,
                 }
             }
             Err(e) => {
-                log::error!("订阅下载失败：{}", e);
+                log::error!("订阅下载失败（尝试 {} 次）：{}", attempts, e);
                 DownloadSubscriptionResponse {
+                    item_id,
                     success: false,
                     content: String::new(),
+                    content_kind: ContentKind::Unknown,
+                    validated: false,
                     subscription_info: None,
+                    filename: None,
+                    update_interval_hours: None,
+                    final_url: None,
+                    redirect_downgraded: false,
+                    saved_path: None,
                     error_message: Some(e.to_string()),
+                    attempts,
+                    used_proxy_mode: None,
+                }
+            }
+        }
+    }
+}
+
+impl UpdateAllSubscriptions {
+    // 处理批量更新请求：每项完成后立即发送响应，单项失败不影响其余订阅
+    pub async fn handle(self) {
+        let concurrency = self
+            .max_concurrency
+            .map_or(DEFAULT_BATCH_CONCURRENCY, |n| n.max(1) as usize);
+        log::info!(
+            "开始批量更新订阅：{} 个，并发 {}",
+            self.items.len(),
+            concurrency
+        );
+
+        let semaphore = Semaphore::new(concurrency);
+        let clients = ClientPool::default();
+        let results = futures_util::future::join_all(self.items.into_iter().map(|item| {
+            let semaphore = &semaphore;
+            let clients = &clients;
+            async move {
+                let Ok(_permit) = semaphore.acquire().await else {
+                    return false;
+                };
+                let response = item.request.download(clients, Some(item.item_id)).await;
+                let success = response.success;
+                response.send_signal_to_dart();
+                success
+            }
+        }))
+        .await;
+
+        let succeeded = results.iter().filter(|success| **success).count() as u32;
+        let failed = results.len() as u32 - succeeded;
+        log::info!(
+            "批量更新订阅完成：成功 {} 个，失败 {} 个",
+            succeeded,
+            failed
+        );
+        BatchUpdateComplete { succeeded, failed }.send_signal_to_dart();
+    }
+}
+
+impl ConvertUriListRequest {
+    // 处理代理链接列表转换请求
+    pub fn handle(self) {
+        let response = match ProxyParser::convert_uri_list(&self.content) {
+            Ok((yaml, errors)) => ConvertUriListResponse {
+                success: true,
+                yaml,
+                errors: errors.into_iter().map(UriLineError::from).collect(),
+                error_message: None,
+            },
+            Err(e) => {
+                log::warn!("代理链接列表转换失败：{}", e);
+                ConvertUriListResponse {
+                    success: false,
+                    yaml: String::new(),
+                    errors: Vec::new(),
+                    error_message: Some(e),
                 }
             }
         };
+        response.send_signal_to_dart();
+    }
+}
 
+impl DiffSubscriptionRequest {
+    // 处理订阅差异请求
+    pub fn handle(self) {
+        let response = match diff_configs(&self.old_yaml, &self.new_yaml) {
+            Ok(diff) => {
+                log::debug!(
+                    "订阅差异：新增 {}，移除 {}，修改 {}",
+                    diff.added.len(),
+                    diff.removed.len(),
+                    diff.modified.len()
+                );
+                SubscriptionDiffResult {
+                    available: true,
+                    added_count: diff.added.len() as u32,
+                    removed_count: diff.removed.len() as u32,
+                    modified_count: diff.modified.len() as u32,
+                    unchanged_count: diff.unchanged as u32,
+                    added: diff.added,
+                    removed: diff.removed,
+                    modified: diff
+                        .modified
+                        .into_iter()
+                        .map(ModifiedProxyItem::from)
+                        .collect(),
+                    error_message: None,
+                }
+            }
+            Err(e) => {
+                log::warn!("订阅差异不可用：{}", e);
+                SubscriptionDiffResult {
+                    available: false,
+                    added: Vec::new(),
+                    removed: Vec::new(),
+                    modified: Vec::new(),
+                    added_count: 0,
+                    removed_count: 0,
+                    modified_count: 0,
+                    unchanged_count: 0,
+                    error_message: Some(e),
+                }
+            }
+        };
         response.send_signal_to_dart();
     }
 }
@@ -0,0 +1,87 @@
+// 订阅文件的保存
+//
+// 正在运行的核心会重新加载当前配置，写入中断（崩溃、磁盘已满）不能损坏原文件：
+// 先写入同目录下的 <文件名>.tmp 并同步到磁盘，再重命名覆盖目标文件。
+// 任一步骤失败时删除临时文件，原文件保持不变
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// 原子写入订阅内容，返回保存的路径
+pub async fn save_atomic(path: &str, content: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    let content = content.to_string();
+    tokio::task::spawn_blocking(move || {
+        write_atomic(&path, content.as_bytes())?;
+        Ok(path)
+    })
+    .await
+    .map_err(|e| format!("保存订阅任务失败：{}", e))?
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("保存路径无效：{}", path.display()))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建订阅目录失败：{}", e))?;
+    }
+
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .map_err(|e| format!("写入临时订阅文件失败：{}", e))
+        .and_then(|()| {
+            std::fs::rename(&temp_path, path).map_err(|e| format!("替换订阅文件失败：{}", e))
+        });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // 同步目录项，确保重命名已落盘（Windows 上无法打开目录）
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty())
+        && let Err(e) = File::open(parent).and_then(|dir| dir.sync_all())
+    {
+        log::warn!("同步订阅目录失败：{} - {}", parent.display(), e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_or_keeps_previous() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!(
+            "stelliberty-subscription-storage-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("profiles").join("a.yaml");
+
+        write_atomic(&path, b"proxies: []\n")?;
+        write_atomic(&path, b"proxies: [new]\n")?;
+        let saved = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        assert_eq!(saved, "proxies: [new]\n");
+        assert!(!dir.join("profiles").join("a.yaml.tmp").exists());
+
+        // 临时文件无法创建时原文件不变
+        std::fs::create_dir_all(dir.join("profiles").join("a.yaml.tmp"))
+            .map_err(|e| e.to_string())?;
+        let error = write_atomic(&path, b"broken").err().ok_or("应写入失败")?;
+        assert!(error.starts_with("写入临时订阅文件失败"), "{error}");
+        let saved = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        assert_eq!(saved, "proxies: [new]\n");
+        Ok(())
+    }
+}
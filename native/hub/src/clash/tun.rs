@@ -0,0 +1,251 @@
+// TUN 残留检测
+//
+// 目的：核心停止后检测并清理残留的 TUN 网卡和路由，
+// 启动 TUN 前提示其他客户端遗留的状态
+
+use super::service::ServiceManager;
+use once_cell::sync::Lazy;
+use rinf::{DartSignal, RustSignal};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use stelliberty_service::clash::tun::{self as service_tun, TunCleanupOutcome};
+use stelliberty_service::ipc::{IpcCommand, IpcResponse};
+use tokio::spawn;
+
+// 启动前预检最多等待的时间
+//
+// 预检需要在核心创建网卡前完成，否则会把核心自己的网卡当作残留；超时后不再等待结果
+const PREFLIGHT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// 当前运行核心使用的 TUN 设备名（未启用 TUN 或设备名未知时为 None）
+static ACTIVE_TUN_DEVICE: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+// ============================================================================
+// 消息协议
+// ============================================================================
+
+// Dart → Rust：检测 TUN 残留（preflight 为 true 时仅检测不清理）
+#[derive(Deserialize, DartSignal)]
+pub struct VerifyTunCleanup {
+    pub device: String,
+    pub preflight: bool,
+}
+
+// Rust → Dart：TUN 残留检测报告
+#[derive(Serialize, RustSignal)]
+pub struct TunCleanupReport {
+    pub device: String,
+    pub preflight: bool,
+    // 是否通过服务以管理员权限执行
+    pub elevated: bool,
+    pub interfaces: Vec<String>,
+    pub routes: Vec<String>,
+    pub removed: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl TunCleanupReport {
+    fn from_outcome(
+        device: String,
+        preflight: bool,
+        elevated: bool,
+        outcome: TunCleanupOutcome,
+    ) -> Self {
+        Self {
+            device,
+            preflight,
+            elevated,
+            interfaces: outcome.interfaces,
+            routes: outcome.routes,
+            removed: outcome.removed,
+            failed: outcome.failed,
+        }
+    }
+}
+
+// ============================================================================
+// 消息处理器
+// ============================================================================
+
+impl VerifyTunCleanup {
+    pub async fn handle(self) {
+        verify(self.device, self.preflight)
+            .await
+            .send_signal_to_dart();
+    }
+}
+
+// 检测 TUN 残留
+//
+// 清理时优先通过服务执行（需要管理员权限），服务不可用时在本进程尝试
+pub async fn verify(device: String, preflight: bool) -> TunCleanupReport {
+    if !preflight {
+        let client = ServiceManager::ipc_client()
+            .with_timeout(Duration::from_secs(5))
+            .with_max_retries(0);
+        match client
+            .send_command(IpcCommand::VerifyTunCleanup {
+                device: device.clone(),
+                remove: true,
+            })
+            .await
+        {
+            Ok(IpcResponse::TunCleanup {
+                interfaces,
+                routes,
+                removed,
+                failed,
+            }) => {
+                return TunCleanupReport {
+                    device,
+                    preflight,
+                    elevated: true,
+                    interfaces,
+                    routes,
+                    removed,
+                    failed,
+                };
+            }
+            Ok(resp) => log::warn!("TUN 清理收到意外响应：{:?}", resp),
+            Err(e) => log::debug!("服务不可用，在本进程清理 TUN 残留：{}", e),
+        }
+    }
+
+    let scan_device = device.clone();
+    let outcome =
+        tokio::task::spawn_blocking(move || service_tun::verify_cleanup(&scan_device, !preflight))
+            .await
+            .unwrap_or_else(|e| {
+                log::error!("TUN 残留检测任务失败：{}", e);
+                TunCleanupOutcome::default()
+            });
+
+    TunCleanupReport::from_outcome(device, preflight, false, outcome)
+}
+
+// 从核心启动参数（-f 配置文件）中读取 TUN 设备名（读取文件，需在阻塞线程中执行）
+//
+// 配置未启用 TUN，或设备名未知时返回 None
+pub fn detect_tun_device(args: &[String]) -> Option<String> {
+    let config_path = args
+        .iter()
+        .position(|arg| arg == "-f")
+        .and_then(|index| args.get(index + 1))?;
+    let tun = read_tun_section(config_path)?;
+
+    if !tun.get("enable").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return None;
+    }
+    device_name(&tun)
+}
+
+// 服务模式启动时的 TUN 设备名：优先使用请求指定的名称，其次是配置中的 tun.device
+pub async fn resolve_tun_device(requested: Option<String>, config_path: String) -> Option<String> {
+    if let Some(device) = requested.filter(|device| !device.is_empty()) {
+        return Some(device);
+    }
+    tokio::task::spawn_blocking(move || {
+        read_tun_section(&config_path)
+            .and_then(|tun| device_name(&tun))
+            .or_else(|| service_tun::DEFAULT_TUN_DEVICE.map(str::to_string))
+    })
+    .await
+    .unwrap_or_else(|e| {
+        log::error!("读取 TUN 设备名的任务失败：{}", e);
+        None
+    })
+}
+
+fn read_tun_section(config_path: &str) -> Option<serde_yaml_ng::Value> {
+    let content = std::fs::read_to_string(config_path).ok()?;
+    let mut config: serde_yaml_ng::Value = serde_yaml_ng::from_str(&content).ok()?;
+    config.get_mut("tun").map(std::mem::take)
+}
+
+// 配置中的设备名，未指定时使用平台默认名（macOS 没有确定的默认名）
+fn device_name(tun: &serde_yaml_ng::Value) -> Option<String> {
+    let device = tun
+        .get("device")
+        .and_then(|v| v.as_str())
+        .filter(|device| !device.is_empty())
+        .or(service_tun::DEFAULT_TUN_DEVICE)
+        .map(str::to_string);
+    if device.is_none() {
+        log::info!("未指定 TUN 设备名，无法确定核心创建的网卡，跳过 TUN 残留检测与清理");
+    }
+    device
+}
+
+// 启动 TUN 核心前检测其他客户端遗留的状态
+//
+// 列举网卡与路由（macOS 上需执行 netstat）在阻塞线程中进行，最多等待 PREFLIGHT_TIMEOUT
+pub async fn run_preflight(device: String) {
+    let scan_device = device.clone();
+    let scan =
+        tokio::task::spawn_blocking(move || service_tun::verify_cleanup(&scan_device, false));
+    let outcome = match tokio::time::timeout(PREFLIGHT_TIMEOUT, scan).await {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(e)) => {
+            log::error!("TUN 预检任务失败：{}", e);
+            return;
+        }
+        Err(_) => {
+            log::warn!("TUN 预检超时，跳过：{}", device);
+            return;
+        }
+    };
+
+    if !outcome.is_clean() {
+        log::warn!(
+            "检测到 TUN 残留状态：{}（网卡 {} 个，路由 {} 条）",
+            device,
+            outcome.interfaces.len(),
+            outcome.routes.len()
+        );
+        TunCleanupReport::from_outcome(device, true, false, outcome).send_signal_to_dart();
+    }
+}
+
+// 记录核心启动成功后使用的 TUN 设备名，供停止后清理
+pub fn set_active_device(device: Option<String>) {
+    *ACTIVE_TUN_DEVICE.lock().unwrap_or_else(|e| e.into_inner()) = device;
+}
+
+// 取出当前记录的 TUN 设备名（核心停止后由调用方负责清理）
+pub fn take_active_device() -> Option<String> {
+    ACTIVE_TUN_DEVICE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+}
+
+// 核心停止后异步检测并清理 TUN 残留
+pub fn spawn_post_stop_verification() {
+    if let Some(device) = take_active_device() {
+        spawn(async move {
+            let report = verify(device, false).await;
+            if !report.interfaces.is_empty() || !report.routes.is_empty() {
+                log::info!(
+                    "TUN 残留清理：已清理 {} 项，失败 {} 项",
+                    report.removed.len(),
+                    report.failed.len()
+                );
+            }
+            report.send_signal_to_dart();
+        });
+    }
+}
+
+pub fn init_message_listeners() {
+    spawn(async {
+        let receiver = VerifyTunCleanup::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("TUN 残留检测消息通道已关闭，退出监听器");
+    });
+}
@@ -2,6 +2,7 @@
 
 mod clash;
 mod network;
+mod settings;
 mod system;
 mod utils;
 
@@ -12,10 +13,11 @@ write_interface!();
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     utils::init();
+    settings::init();
     network::init();
     system::init();
     clash::init();
 
     dart_shutdown().await;
-    clash::process::cleanup();
+    clash::process::cleanup().await;
 }
@@ -0,0 +1,336 @@
+// Hub 运行时设置持久化
+//
+// 目的：将可在运行时调整的参数保存到应用数据目录的 hub_settings.json，
+// 启动时自动恢复，避免 Dart 每次启动都重放一遍配置信号
+
+mod secret;
+mod signals;
+
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use tokio::sync::broadcast;
+
+const SETTINGS_FILE: &str = "hub_settings.json";
+
+// 加密值在 JSON 中的包装字段：{"$secret": "<密文>"}
+const SECRET_FIELD: &str = "$secret";
+
+// 变更通知通道容量
+const CHANGE_CHANNEL_CAPACITY: usize = 32;
+
+// 已知设置项键名
+//
+// 通过 SetHubSetting 直接写入时，订阅提醒阈值、控制器密钥与连接池参数经变更通知立即生效，
+// 自动重启与自动备份策略在下次使用时读取
+pub mod keys {
+    // 订阅用量提醒阈值（百分比）
+    pub const SUBSCRIPTION_ALERT_PERCENT: &str = "subscription_alert_percent";
+    // 订阅到期提醒阈值（天）
+    pub const SUBSCRIPTION_ALERT_EXPIRE_DAYS: &str = "subscription_alert_expire_days";
+    // 直接进程模式的自动重启策略
+    pub const PROCESS_RESTART_ENABLED: &str = "process_restart_enabled";
+    pub const PROCESS_RESTART_MAX_ATTEMPTS: &str = "process_restart_max_attempts";
+    pub const PROCESS_RESTART_BACKOFF_MS: &str = "process_restart_backoff_ms";
+    // 定时自动备份策略
+    pub const AUTO_BACKUP_ENABLED: &str = "auto_backup_enabled";
+    pub const AUTO_BACKUP_INTERVAL_HOURS: &str = "auto_backup_interval_hours";
+    pub const AUTO_BACKUP_TARGET_DIR: &str = "auto_backup_target_dir";
+    pub const AUTO_BACKUP_MAX_KEEP: &str = "auto_backup_max_keep";
+    pub const AUTO_BACKUP_APP_DATA_PATH: &str = "auto_backup_app_data_path";
+    pub const AUTO_BACKUP_APP_VERSION: &str = "auto_backup_app_version";
+    // 额外备份的数据目录文件（以 / 分隔的相对路径数组）
+    pub const BACKUP_EXTRA_FILES: &str = "backup_extra_files";
+    // 外部控制器密钥（加密保存）
+    pub const CONTROLLER_SECRET: &str = "controller_secret";
+    // IPC 连接池参数
+    pub const IPC_POOL_MAX_SIZE: &str = "ipc_pool_max_size";
+    pub const IPC_POOL_IDLE_TIMEOUT_MS: &str = "ipc_pool_idle_timeout_ms";
+    pub const IPC_POOL_HEALTH_CHECK_INTERVAL_S: &str = "ipc_pool_health_check_interval_s";
+    pub const IPC_POOL_ACTIVE_PROBE: &str = "ipc_pool_active_probe";
+}
+
+static SETTINGS: Lazy<RwLock<Map<String, Value>>> = Lazy::new(|| RwLock::new(load()));
+
+// 串行化设置文件的写入
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+// 设置变更通知（内容为变更的键名）
+static CHANGES: Lazy<broadcast::Sender<String>> = Lazy::new(|| {
+    let (tx, _) = broadcast::channel(CHANGE_CHANNEL_CAPACITY);
+    tx
+});
+
+// 初始化设置模块
+pub fn init() {
+    let count = SETTINGS.read().map(|s| s.len()).unwrap_or(0);
+    log::info!("Hub 设置已加载（{}项）", count);
+    signals::init_message_listeners();
+}
+
+// 订阅设置变更通知
+pub fn subscribe() -> broadcast::Receiver<String> {
+    CHANGES.subscribe()
+}
+
+// 读取原始值（加密值不解密）
+fn get_raw(key: &str) -> Option<Value> {
+    SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(key)
+        .cloned()
+}
+
+pub fn get_bool(key: &str) -> Option<bool> {
+    get_raw(key)?.as_bool()
+}
+
+pub fn get_u64(key: &str) -> Option<u64> {
+    get_raw(key)?.as_u64()
+}
+
+pub fn get_f64(key: &str) -> Option<f64> {
+    get_raw(key)?.as_f64()
+}
+
+pub fn get_string(key: &str) -> Option<String> {
+    get_raw(key)?.as_str().map(|s| s.to_string())
+}
+
+// 读取字符串数组，忽略其中的非字符串元素
+pub fn get_string_list(key: &str) -> Option<Vec<String>> {
+    let items = get_raw(key)?.as_array()?.clone();
+    Some(
+        items
+            .into_iter()
+            .filter_map(|item| item.as_str().map(|s| s.to_string()))
+            .collect(),
+    )
+}
+
+// 读取并解密密钥类设置项
+pub fn get_secret(key: &str) -> Option<String> {
+    let value = get_raw(key)?;
+    let encrypted = value.get(SECRET_FIELD)?.as_str()?;
+    secret::decrypt(encrypted)
+        .map_err(|e| log::warn!("解密设置项失败：{}，{}", key, e))
+        .ok()
+}
+
+// 写入设置项（None 表示删除）
+pub fn set(key: &str, value: Option<Value>) -> Result<(), String> {
+    set_many([(key, value)])
+}
+
+// 批量写入设置项，只写一次文件，失败时全部不生效
+//
+// 读写锁只在更新内存时持有，写文件期间读取不受影响；写文件由 SAVE_LOCK 串行执行，
+// 回滚时不会覆盖其他写入者的修改
+pub fn set_many<'a>(
+    entries: impl IntoIterator<Item = (&'a str, Option<Value>)>,
+) -> Result<(), String> {
+    let _save = SAVE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (snapshot, previous) = {
+        let mut settings = SETTINGS.write().unwrap_or_else(|e| e.into_inner());
+        let previous: Vec<_> = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let previous = match value {
+                    Some(value) => settings.insert(key.to_string(), value),
+                    None => settings.remove(key),
+                };
+                (key, previous)
+            })
+            .collect();
+        (settings.clone(), previous)
+    };
+
+    if let Err(e) = save(&snapshot) {
+        // 持久化失败时按相反顺序回滚内存状态
+        let mut settings = SETTINGS.write().unwrap_or_else(|e| e.into_inner());
+        for (key, previous) in previous.into_iter().rev() {
+            match previous {
+                Some(previous) => settings.insert(key.to_string(), previous),
+                None => settings.remove(key),
+            };
+        }
+        return Err(e);
+    }
+
+    for (key, _) in previous {
+        log::debug!("设置项已更新：{}", key);
+        let _ = CHANGES.send(key.to_string());
+    }
+    Ok(())
+}
+
+// 加密后写入密钥类设置项
+pub fn set_secret(key: &str, plain: &str) -> Result<(), String> {
+    let encrypted = secret::encrypt(plain)?;
+    let mut wrapper = Map::new();
+    wrapper.insert(SECRET_FIELD.to_string(), Value::String(encrypted));
+    set(key, Some(Value::Object(wrapper)))
+}
+
+// 导出所有设置项（密钥类仅显示为已设置）
+pub fn snapshot_redacted() -> Map<String, Value> {
+    SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(key, value)| {
+            let value = if is_secret_value(value) {
+                Value::String("******".to_string())
+            } else {
+                value.clone()
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+fn is_secret_value(value: &Value) -> bool {
+    value
+        .as_object()
+        .is_some_and(|obj| obj.contains_key(SECRET_FIELD))
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    crate::utils::init_logger::get_app_data_dir().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+// 加载设置文件，文件损坏时隔离并重新生成
+fn load() -> Map<String, Value> {
+    match settings_path() {
+        Ok(path) => load_from(&path),
+        Err(e) => {
+            log::warn!("无法获取设置文件路径：{}", e);
+            Map::new()
+        }
+    }
+}
+
+fn load_from(path: &Path) -> Map<String, Value> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Map::new(),
+        Err(e) => {
+            log::warn!("读取设置文件失败：{}", e);
+            return Map::new();
+        }
+    };
+
+    match serde_json::from_str::<Value>(&content) {
+        Ok(Value::Object(settings)) => settings,
+        Ok(_) => {
+            quarantine(path, "根节点不是对象");
+            Map::new()
+        }
+        Err(e) => {
+            quarantine(path, &e.to_string());
+            Map::new()
+        }
+    }
+}
+
+// 将损坏的设置文件重命名保留，便于排查
+fn quarantine(path: &Path, reason: &str) {
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let quarantined = path.with_extension(format!("json.corrupt-{}", timestamp));
+
+    match std::fs::rename(path, &quarantined) {
+        Ok(()) => log::warn!(
+            "设置文件已损坏（{}），已隔离到：{}",
+            reason,
+            quarantined.display()
+        ),
+        Err(e) => log::error!("设置文件已损坏（{}），隔离失败：{}", reason, e),
+    }
+}
+
+// 原子写入：先写临时文件再重命名，避免写入中断导致文件损坏
+fn save(settings: &Map<String, Value>) -> Result<(), String> {
+    save_to(&settings_path()?, settings)
+}
+
+fn save_to(path: &Path, settings: &Map<String, Value>) -> Result<(), String> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建设置目录失败：{}", e))?;
+    }
+
+    let content =
+        serde_json::to_string_pretty(settings).map_err(|e| format!("序列化设置失败：{}", e))?;
+
+    let temp_path = path.with_extension("json.tmp");
+    let mut file =
+        std::fs::File::create(&temp_path).map_err(|e| format!("创建临时设置文件失败：{}", e))?;
+    file.write_all(content.as_bytes())
+        .and_then(|()| file.sync_all())
+        .map_err(|e| format!("写入临时设置文件失败：{}", e))?;
+    drop(file);
+
+    std::fs::rename(&temp_path, path).map_err(|e| format!("替换设置文件失败：{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> Result<PathBuf, String> {
+        let dir = std::env::temp_dir().join(format!(
+            "stelliberty-settings-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() -> Result<(), String> {
+        let dir = temp_dir("save")?;
+        let path = dir.join(SETTINGS_FILE);
+
+        let mut settings = Map::new();
+        settings.insert("a".to_string(), Value::from(1));
+        save_to(&path, &settings)?;
+        settings.insert("b".to_string(), Value::from("x"));
+        save_to(&path, &settings)?;
+
+        assert_eq!(load_from(&path), settings);
+        assert!(!path.with_extension("json.tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_quarantines_corrupt_file() -> Result<(), String> {
+        let dir = temp_dir("corrupt")?;
+        let path = dir.join(SETTINGS_FILE);
+        std::fs::write(&path, "{ broken").map_err(|e| e.to_string())?;
+
+        assert!(load_from(&path).is_empty());
+        assert!(!path.exists());
+        let quarantined: Vec<_> = std::fs::read_dir(&dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(quarantined.len(), 1);
+        assert!(
+            quarantined[0].starts_with("hub_settings.json.corrupt-"),
+            "{:?}",
+            quarantined
+        );
+
+        // 根节点不是对象时同样隔离
+        std::fs::write(&path, "[1, 2]").map_err(|e| e.to_string())?;
+        assert!(load_from(&path).is_empty());
+        assert!(!path.exists());
+        Ok(())
+    }
+}
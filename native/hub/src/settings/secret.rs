@@ -0,0 +1,281 @@
+// 设置项密钥加密
+//
+// 目的：使用与本机绑定的数据密钥（AES-256-GCM）加密 controller secret 等敏感设置
+//
+// 数据密钥的保护方式：
+// - Windows: DPAPI 加密后保存到密钥文件
+// - macOS: 保存在钥匙串中，不可用时回退到密钥文件
+// - 其他平台: 仅当前用户可读写（0600）的密钥文件
+
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const KEY_FILE: &str = "hub_settings.key";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[cfg(target_os = "macos")]
+const KEYCHAIN_SERVICE: &str = "Stelliberty";
+#[cfg(target_os = "macos")]
+const KEYCHAIN_ACCOUNT: &str = "hub_settings";
+
+// 缓存的数据密钥
+static DATA_KEY: Lazy<Mutex<Option<[u8; KEY_LEN]>>> = Lazy::new(|| Mutex::new(None));
+
+// 加密：返回 base64(nonce || 密文)
+pub fn encrypt(plain: &str) -> Result<String, String> {
+    encrypt_with(&data_key()?, plain)
+}
+
+pub fn decrypt(encoded: &str) -> Result<String, String> {
+    decrypt_with(&data_key()?, encoded)
+}
+
+fn encrypt_with(key: &[u8; KEY_LEN], plain: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
+
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rng().fill(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plain.as_bytes())
+        .map_err(|e| format!("加密失败：{}", e))?;
+
+    let mut combined = nonce.to_vec();
+    combined.extend_from_slice(&ciphertext);
+    Ok(BASE64.encode(combined))
+}
+
+fn decrypt_with(key: &[u8; KEY_LEN], encoded: &str) -> Result<String, String> {
+    let combined = BASE64
+        .decode(encoded)
+        .map_err(|e| format!("密文格式错误：{}", e))?;
+    if combined.len() <= NONCE_LEN {
+        return Err("密文长度不足".to_string());
+    }
+
+    let (nonce, ciphertext) = combined.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| e.to_string())?;
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "解密失败（密钥不匹配或数据已损坏）".to_string())?;
+
+    String::from_utf8(plain).map_err(|e| format!("解密结果不是有效文本：{}", e))
+}
+
+// 获取数据密钥（首次使用时生成并保存）
+fn data_key() -> Result<[u8; KEY_LEN], String> {
+    let mut cached = DATA_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(key) = *cached {
+        return Ok(key);
+    }
+
+    let key = match load_key()? {
+        Some(key) => key,
+        None => {
+            let mut key = [0u8; KEY_LEN];
+            rand::rng().fill(&mut key);
+            store_key(&key)?;
+            log::info!("已生成设置项数据密钥");
+            key
+        }
+    };
+
+    *cached = Some(key);
+    Ok(key)
+}
+
+fn key_file_path() -> Result<PathBuf, String> {
+    crate::utils::init_logger::get_app_data_dir().map(|dir| dir.join(KEY_FILE))
+}
+
+fn to_key(bytes: &[u8]) -> Result<[u8; KEY_LEN], String> {
+    bytes.try_into().map_err(|_| "数据密钥长度错误".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn load_key() -> Result<Option<[u8; KEY_LEN]>, String> {
+    use security_framework::passwords::get_generic_password;
+
+    match get_generic_password(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT) {
+        Ok(bytes) => to_key(&bytes).map(Some),
+        Err(e) => {
+            log::debug!("钥匙串中未找到数据密钥，尝试密钥文件：{}", e);
+            load_key_file()
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn store_key(key: &[u8; KEY_LEN]) -> Result<(), String> {
+    use security_framework::passwords::set_generic_password;
+
+    match set_generic_password(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT, key) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            log::warn!("写入钥匙串失败，回退到密钥文件：{}", e);
+            store_key_file(key)
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn load_key() -> Result<Option<[u8; KEY_LEN]>, String> {
+    load_key_file()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn store_key(key: &[u8; KEY_LEN]) -> Result<(), String> {
+    store_key_file(key)
+}
+
+fn load_key_file() -> Result<Option<[u8; KEY_LEN]>, String> {
+    let path = key_file_path()?;
+    let content = match std::fs::read(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("读取密钥文件失败：{}", e)),
+    };
+
+    let protected = BASE64
+        .decode(content.trim_ascii())
+        .map_err(|e| format!("密钥文件格式错误：{}", e))?;
+    to_key(&unprotect(&protected)?).map(Some)
+}
+
+fn store_key_file(key: &[u8; KEY_LEN]) -> Result<(), String> {
+    let path = key_file_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建密钥目录失败：{}", e))?;
+    }
+
+    let content = BASE64.encode(protect(key)?);
+
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(|e| format!("创建密钥文件失败：{}", e))?;
+        file.write_all(content.as_bytes())
+            .map_err(|e| format!("写入密钥文件失败：{}", e))
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::fs::write(&path, content).map_err(|e| format!("写入密钥文件失败：{}", e))
+    }
+}
+
+// Windows：使用 DPAPI 将密钥绑定到当前用户
+#[cfg(windows)]
+fn protect(data: &[u8]) -> Result<Vec<u8>, String> {
+    use windows::Win32::Security::Cryptography::{
+        CRYPT_INTEGER_BLOB, CRYPTPROTECT_UI_FORBIDDEN, CryptProtectData,
+    };
+
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+
+    // SAFETY: input 指向有效内存，output 由系统分配并在 take_blob 中释放
+    unsafe {
+        CryptProtectData(
+            &input,
+            None,
+            None,
+            None,
+            None,
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+        .map_err(|e| format!("DPAPI 加密失败：{}", e))?;
+        Ok(take_blob(output))
+    }
+}
+
+#[cfg(windows)]
+fn unprotect(data: &[u8]) -> Result<Vec<u8>, String> {
+    use windows::Win32::Security::Cryptography::{
+        CRYPT_INTEGER_BLOB, CRYPTPROTECT_UI_FORBIDDEN, CryptUnprotectData,
+    };
+
+    let input = CRYPT_INTEGER_BLOB {
+        cbData: data.len() as u32,
+        pbData: data.as_ptr() as *mut u8,
+    };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+
+    // SAFETY: 同 protect
+    unsafe {
+        CryptUnprotectData(
+            &input,
+            None,
+            None,
+            None,
+            None,
+            CRYPTPROTECT_UI_FORBIDDEN,
+            &mut output,
+        )
+        .map_err(|e| format!("DPAPI 解密失败：{}", e))?;
+        Ok(take_blob(output))
+    }
+}
+
+// 复制 DPAPI 输出并释放系统分配的内存
+#[cfg(windows)]
+unsafe fn take_blob(blob: windows::Win32::Security::Cryptography::CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    use windows::Win32::Foundation::{HLOCAL, LocalFree};
+
+    // SAFETY: blob 由 CryptProtectData/CryptUnprotectData 成功返回
+    unsafe {
+        let bytes = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+        LocalFree(Some(HLOCAL(blob.pbData as *mut _)));
+        bytes
+    }
+}
+
+// 非 Windows：密钥文件依靠文件权限保护
+#[cfg(not(windows))]
+fn protect(data: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(data.to_vec())
+}
+
+#[cfg(not(windows))]
+fn unprotect(data: &[u8]) -> Result<Vec<u8>, String> {
+    Ok(data.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() -> Result<(), String> {
+        let key = [7u8; KEY_LEN];
+        let first = encrypt_with(&key, "s3cret")?;
+        let second = encrypt_with(&key, "s3cret")?;
+        // 每次加密使用新的 nonce
+        assert_ne!(first, second);
+        assert_eq!(decrypt_with(&key, &first)?, "s3cret");
+        assert_eq!(decrypt_with(&key, &second)?, "s3cret");
+
+        assert!(decrypt_with(&[8u8; KEY_LEN], &first).is_err());
+        assert!(decrypt_with(&key, "not base64!").is_err());
+        assert!(decrypt_with(&key, &BASE64.encode([0u8; NONCE_LEN])).is_err());
+        Ok(())
+    }
+}
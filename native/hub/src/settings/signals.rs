@@ -0,0 +1,105 @@
+// Hub 设置消息协议
+//
+// 目的：为 Dart 提供通用的设置读写信号
+
+use rinf::{DartSignal, RustSignal};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::spawn;
+
+// ============================================================================
+// 消息协议
+// ============================================================================
+
+// Dart → Rust：写入设置项
+//
+// value_json 为 JSON 编码的值，"null" 表示删除该项；
+// secret 为 true 时值必须是字符串，加密后保存
+#[derive(Deserialize, DartSignal)]
+pub struct SetHubSetting {
+    pub key: String,
+    pub value_json: String,
+    pub secret: bool,
+}
+
+// Dart → Rust：读取全部设置项
+#[derive(Deserialize, DartSignal)]
+pub struct GetHubSettings;
+
+// Rust → Dart：写入结果
+#[derive(Serialize, RustSignal)]
+pub struct HubSettingResult {
+    pub key: String,
+    pub success: bool,
+    pub error_message: Option<String>,
+}
+
+// Rust → Dart：全部设置项（JSON 对象，密钥类已脱敏）
+#[derive(Serialize, RustSignal)]
+pub struct HubSettingsResponse {
+    pub settings_json: String,
+}
+
+// ============================================================================
+// 消息处理器
+// ============================================================================
+
+impl SetHubSetting {
+    pub fn handle(self) {
+        let result = apply(&self.key, &self.value_json, self.secret);
+        if let Err(e) = &result {
+            log::warn!("写入设置项失败：{}，{}", self.key, e);
+        }
+
+        HubSettingResult {
+            key: self.key,
+            success: result.is_ok(),
+            error_message: result.err(),
+        }
+        .send_signal_to_dart();
+    }
+}
+
+impl GetHubSettings {
+    pub fn handle(self) {
+        let settings = Value::Object(super::snapshot_redacted());
+        HubSettingsResponse {
+            settings_json: settings.to_string(),
+        }
+        .send_signal_to_dart();
+    }
+}
+
+fn apply(key: &str, value_json: &str, secret: bool) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("设置项键名不能为空".to_string());
+    }
+
+    let value: Value =
+        serde_json::from_str(value_json).map_err(|e| format!("设置值不是有效 JSON：{}", e))?;
+
+    match value {
+        Value::Null => super::set(key, None),
+        Value::String(plain) if secret => super::set_secret(key, &plain),
+        _ if secret => Err("密钥类设置项必须是字符串".to_string()),
+        value => super::set(key, Some(value)),
+    }
+}
+
+pub fn init_message_listeners() {
+    spawn(async {
+        let receiver = SetHubSetting::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+        log::info!("设置写入消息通道已关闭，退出监听器");
+    });
+
+    spawn(async {
+        let receiver = GetHubSettings::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+        log::info!("设置读取消息通道已关闭，退出监听器");
+    });
+}
@@ -16,19 +16,35 @@ pub use auto_start::{get_auto_start_status, set_auto_start_status};
 #[allow(unused_imports)]
 pub use signals::{
     // 应用更新消息
+    AppUpdateDownloadErrorCode,
+    AppUpdateDownloadProgress,
+    AppUpdateDownloadResult,
     AppUpdateResult,
+    // 备份与还原消息
+    AutoBackupCompleted,
     // 自启动消息
     AutoStartStatusResult,
-    // 备份与还原消息
+    BackupListItem,
+    BackupListResponse,
+    BackupMetadataResponse,
     BackupOperationResult,
+    BackupProgress,
     CheckAppUpdateRequest,
+    ConfigureAutoBackup,
     CreateBackupRequest,
+    DownloadAppUpdateRequest,
     GetAutoStartStatus,
+    InspectBackupRequest,
+    ListBackupsRequest,
     // URL 启动消息
     OpenUrl,
     OpenUrlResult,
     RestoreBackupRequest,
+    RestoreDryRunResult,
+    RestoreDryRunSection,
     SetAutoStartStatus,
+    VerifyBackupRequest,
+    VerifyBackupResult,
 };
 
 // UWP 回环豁免消息（仅 Windows）
@@ -78,6 +94,27 @@ fn init_message_listeners() {
         log::info!("应用更新检查消息通道已关闭，退出监听器");
     });
 
+    // 监听下载应用更新信号
+    spawn(async {
+        let receiver = DownloadAppUpdateRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("下载应用更新消息通道已关闭，退出监听器");
+    });
+
+    // 监听配置自动备份信号
+    spawn(async {
+        let receiver = ConfigureAutoBackup::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            dart_signal.message.handle();
+        }
+        log::info!("配置自动备份消息通道已关闭，退出监听器");
+    });
+
     // 监听创建备份信号
     spawn(async {
         let receiver = CreateBackupRequest::get_dart_signal_receiver();
@@ -101,12 +138,49 @@ fn init_message_listeners() {
         }
         log::info!("还原备份消息通道已关闭，退出监听器");
     });
+
+    // 监听校验备份信号
+    spawn(async {
+        let receiver = VerifyBackupRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("校验备份消息通道已关闭，退出监听器");
+    });
+
+    // 监听读取备份概要信号
+    spawn(async {
+        let receiver = InspectBackupRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("读取备份概要消息通道已关闭，退出监听器");
+    });
+
+    // 监听列出备份信号
+    spawn(async {
+        let receiver = ListBackupsRequest::get_dart_signal_receiver();
+        while let Some(dart_signal) = receiver.recv().await {
+            let message = dart_signal.message;
+            tokio::spawn(async move {
+                message.handle().await;
+            });
+        }
+        log::info!("列出备份消息通道已关闭，退出监听器");
+    });
 }
 
 // 初始化系统模块
 pub fn init() {
     auto_start::init();
     init_message_listeners();
+    backup::start_auto_backup(signals::send_auto_backup_completed);
 
     #[cfg(target_os = "windows")]
     loopback::init();
@@ -1,9 +1,26 @@
-// 应用更新服务：GitHub Release 检查
-
+// 应用更新服务：GitHub Release 检查与安装包下载
+//
+// 安装包先写入 <目标路径>.part，完成并通过 SHA-256 校验后重命名为目标路径。
+// 中断后再次下载时，服务器支持 Range 请求则从已下载的位置继续，否则重新下载。
+// 校验值来自请求（检查更新时从发布说明中提取）或同名的 .sha256 附件，
+// 校验失败时删除已下载的文件
+
+use super::signals::AppUpdateDownloadErrorCode;
+use crate::clash::subscription::signals::ProxyMode;
+use crate::utils::hash::sha256_file;
+use futures_util::StreamExt;
 use once_cell::sync::Lazy;
 use reqwest;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_RANGE, RANGE};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+
+// 下载进度的上报间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
 
 // ============================================================================
 // 数据结构
@@ -97,19 +114,23 @@ pub async fn check_github_update(
     let arch = get_architecture();
     log::info!("当前平台: {}, 架构: {}", platform, arch);
 
-    let download_url = find_matching_asset(&release.assets, &platform, &arch);
-    match &download_url {
+    let asset = find_matching_asset(&release.assets, &platform, &arch);
+    match asset {
         Some(_) => log::info!("找到匹配的下载链接"),
         None => log::warn!("未找到匹配当前平台的安装包"),
     }
+    let expected_sha256 = asset
+        .zip(release.body.as_deref())
+        .and_then(|(asset, body)| find_checksum_in_notes(body, &asset.name));
 
     Ok(UpdateCheckResult {
         current_version: current_version.to_string(),
         latest_version: latest_version.to_string(),
         has_update,
-        download_url,
+        download_url: asset.map(|asset| asset.browser_download_url.clone()),
         release_notes: release.body,
         html_url: Some(release.html_url),
+        expected_sha256,
     })
 }
 
@@ -129,10 +150,14 @@ fn compare_versions(v1: &str, v2: &str) -> Ordering {
 }
 
 // 查找匹配的安装包
-fn find_matching_asset(assets: &[GitHubAsset], platform: &str, arch: &str) -> Option<String> {
+fn find_matching_asset<'a>(
+    assets: &'a [GitHubAsset],
+    platform: &str,
+    arch: &str,
+) -> Option<&'a GitHubAsset> {
     let rules = get_platform_match_rules(platform, arch)?;
 
-    assets.iter().find_map(|asset| {
+    assets.iter().find(|asset| {
         let name_lower = asset.name.to_lowercase();
 
         // 检查所有匹配条件
@@ -151,13 +176,26 @@ fn find_matching_asset(assets: &[GitHubAsset], platform: &str, arch: &str) -> Op
 
         if matches {
             log::info!("找到匹配的安装包: {}", asset.name);
-            Some(asset.browser_download_url.clone())
-        } else {
-            None
         }
+        matches
     })
 }
 
+// 从发布说明中查找安装包的 SHA-256：提及安装包文件名的行中的 64 位十六进制串
+fn find_checksum_in_notes(notes: &str, asset_name: &str) -> Option<String> {
+    notes
+        .lines()
+        .filter(|line| line.contains(asset_name))
+        .find_map(parse_sha256)
+}
+
+// 提取文本中第一个 SHA-256（如 .sha256 文件的 "<hash>  <文件名>"）
+fn parse_sha256(text: &str) -> Option<String> {
+    text.split(|c: char| !c.is_ascii_hexdigit())
+        .find(|token| token.len() == 64)
+        .map(str::to_ascii_lowercase)
+}
+
 // 获取平台匹配规则
 fn get_platform_match_rules(platform: &str, arch: &str) -> Option<PlatformMatchRules> {
     match platform {
@@ -232,11 +270,272 @@ pub struct UpdateCheckResult {
     pub download_url: Option<String>,
     pub release_notes: Option<String>,
     pub html_url: Option<String>,
+    pub expected_sha256: Option<String>,
+}
+
+// ============================================================================
+// 安装包下载
+// ============================================================================
+
+// 进度回调：(已接收字节数, 总字节数)
+pub type DownloadProgress<'a> = &'a (dyn Fn(u64, Option<u64>) + Send + Sync);
+
+// 校验通过的安装包
+pub struct DownloadedUpdate {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+// 安装包下载失败
+#[derive(Debug)]
+pub struct UpdateDownloadError {
+    pub code: AppUpdateDownloadErrorCode,
+    pub message: String,
+}
+
+impl UpdateDownloadError {
+    fn new(code: AppUpdateDownloadErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn network(error: impl std::fmt::Display) -> Self {
+        Self::new(
+            AppUpdateDownloadErrorCode::Network,
+            format!("下载安装包失败：{}", error),
+        )
+    }
+
+    fn io(error: impl std::fmt::Display) -> Self {
+        Self::new(
+            AppUpdateDownloadErrorCode::Io,
+            format!("写入安装包失败：{}", error),
+        )
+    }
+}
+
+// 下载安装包并校验 SHA-256，返回最终路径
+pub async fn download_update(
+    download_url: &str,
+    target_path: &Path,
+    expected_sha256: Option<&str>,
+    proxy_mode: ProxyMode,
+    mixed_port: u16,
+    on_progress: DownloadProgress<'_>,
+) -> Result<DownloadedUpdate, UpdateDownloadError> {
+    let client = create_download_client(proxy_mode, mixed_port)?;
+
+    // 先确定校验值，没有校验值时不下载
+    let expected = match expected_sha256 {
+        Some(value) => parse_sha256(value).ok_or_else(|| {
+            UpdateDownloadError::new(
+                AppUpdateDownloadErrorCode::InvalidRequest,
+                format!("SHA-256 校验值无效：{}", value),
+            )
+        })?,
+        None => fetch_sidecar_checksum(&client, download_url).await?,
+    };
+
+    let file_name = target_path.file_name().ok_or_else(|| {
+        UpdateDownloadError::new(
+            AppUpdateDownloadErrorCode::InvalidRequest,
+            format!("保存路径无效：{}", target_path.display()),
+        )
+    })?;
+    let mut part_name = file_name.to_os_string();
+    part_name.push(".part");
+    let part_path = target_path.with_file_name(part_name);
+    if let Some(parent) = target_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(UpdateDownloadError::io)?;
+    }
+
+    download_to_part(&client, download_url, &part_path, on_progress).await?;
+
+    let hash_path = part_path.clone();
+    let actual = tokio::task::spawn_blocking(move || sha256_file(&hash_path))
+        .await
+        .map_err(UpdateDownloadError::io)?
+        .map_err(UpdateDownloadError::io)?;
+    if actual != expected {
+        if let Err(e) = tokio::fs::remove_file(&part_path).await {
+            log::warn!("删除校验失败的安装包失败：{} - {}", part_path.display(), e);
+        }
+        return Err(UpdateDownloadError::new(
+            AppUpdateDownloadErrorCode::ChecksumMismatch,
+            format!(
+                "安装包校验失败，已删除下载的文件：期望 {}，实际 {}",
+                expected, actual
+            ),
+        ));
+    }
+
+    tokio::fs::rename(&part_path, target_path)
+        .await
+        .map_err(UpdateDownloadError::io)?;
+    Ok(DownloadedUpdate {
+        path: target_path.to_path_buf(),
+        sha256: actual,
+    })
+}
+
+fn create_download_client(
+    proxy_mode: ProxyMode,
+    mixed_port: u16,
+) -> Result<reqwest::Client, UpdateDownloadError> {
+    // 安装包较大，不设置总超时
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .read_timeout(Duration::from_secs(30))
+        .user_agent("Stelliberty-App");
+
+    match proxy_mode {
+        ProxyMode::Direct => builder = builder.no_proxy(),
+        // reqwest 默认读取系统代理环境变量
+        ProxyMode::System => {}
+        ProxyMode::Core => {
+            let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{}", mixed_port))
+                .map_err(UpdateDownloadError::network)?;
+            builder = builder.proxy(proxy);
+        }
+        ProxyMode::Custom => {
+            return Err(UpdateDownloadError::new(
+                AppUpdateDownloadErrorCode::InvalidRequest,
+                "下载更新不支持自定义代理模式",
+            ));
+        }
+    }
+
+    builder.build().map_err(UpdateDownloadError::network)
+}
+
+// 获取同名 .sha256 附件中的校验值
+async fn fetch_sidecar_checksum(
+    client: &reqwest::Client,
+    download_url: &str,
+) -> Result<String, UpdateDownloadError> {
+    let unavailable = |reason: String| {
+        UpdateDownloadError::new(
+            AppUpdateDownloadErrorCode::ChecksumUnavailable,
+            format!(
+                "未提供 SHA-256 校验值，且无法获取 .sha256 校验文件：{}",
+                reason
+            ),
+        )
+    };
+
+    let checksum_url = format!("{}.sha256", download_url);
+    let response = client
+        .get(&checksum_url)
+        .send()
+        .await
+        .map_err(|e| unavailable(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(unavailable(format!("HTTP {}", response.status())));
+    }
+    let text = response
+        .text()
+        .await
+        .map_err(|e| unavailable(e.to_string()))?;
+    parse_sha256(&text).ok_or_else(|| unavailable("文件中没有 SHA-256".to_string()))
+}
+
+// 下载到临时文件，已有部分内容时尝试断点续传
+async fn download_to_part(
+    client: &reqwest::Client,
+    download_url: &str,
+    part_path: &Path,
+    on_progress: DownloadProgress<'_>,
+) -> Result<(), UpdateDownloadError> {
+    let mut offset = tokio::fs::metadata(part_path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+
+    let response = loop {
+        let mut request = client.get(download_url);
+        if offset > 0 {
+            log::info!("从 {} 字节处继续下载安装包", offset);
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().await.map_err(UpdateDownloadError::network)?;
+
+        // 已下载的部分超出文件大小（服务器上的文件可能已变化），重新下载
+        if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            log::warn!("服务器拒绝续传范围，重新下载安装包");
+            offset = 0;
+            continue;
+        }
+        break response;
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        return Err(UpdateDownloadError::network(format!("HTTP {}", status)));
+    }
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    if resumed && content_range_start(&response) != Some(offset) {
+        return Err(UpdateDownloadError::network(
+            "服务器返回的续传范围与请求不符",
+        ));
+    }
+    if offset > 0 && !resumed {
+        log::info!("服务器不支持断点续传，重新下载安装包");
+        offset = 0;
+    }
+
+    let total = response.content_length().map(|len| len + offset);
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part_path)
+        .await
+        .map_err(UpdateDownloadError::io)?;
+
+    let mut received = offset;
+    on_progress(received, total);
+    let mut last_report = Instant::now();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        // 中断时保留已写入的部分，下次下载时续传
+        let chunk = chunk.map_err(UpdateDownloadError::network)?;
+        file.write_all(&chunk)
+            .await
+            .map_err(UpdateDownloadError::io)?;
+        received += chunk.len() as u64;
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            on_progress(received, total);
+            last_report = Instant::now();
+        }
+    }
+    file.flush().await.map_err(UpdateDownloadError::io)?;
+    file.sync_all().await.map_err(UpdateDownloadError::io)?;
+    on_progress(received, total);
+    Ok(())
+}
+
+// Content-Range 的起始位置（bytes <start>-<end>/<total>）
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    value
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn test_version_comparison() {
@@ -255,4 +554,113 @@ mod tests {
         let arch = get_architecture();
         assert!(arch == "x64" || arch == "arm64");
     }
+
+    #[test]
+    fn test_find_checksum_in_notes() {
+        let hash = "ab".repeat(32);
+        let notes = format!(
+            "## 校验值\n| 文件 | SHA-256 |\n| --- | --- |\n\
+             | app-linux-x64.AppImage | `{}` |\n\
+             | app-windows-x64-setup.exe | sha256:{} |\n",
+            "cd".repeat(32),
+            hash.to_uppercase()
+        );
+        assert_eq!(
+            find_checksum_in_notes(&notes, "app-windows-x64-setup.exe"),
+            Some(hash)
+        );
+        assert_eq!(find_checksum_in_notes(&notes, "app-macos.dmg"), None);
+    }
+
+    // 支持 Range 请求的本地服务器，返回下载链接与收到的 Range 头
+    async fn serve_ranges(
+        content: &'static [u8],
+    ) -> Result<(String, std::sync::Arc<std::sync::Mutex<Vec<String>>>), String> {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| e.to_string())?;
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        let ranges = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = ranges.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_ascii_lowercase();
+                let start = request
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                    .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+                if let (Some(start), Ok(mut seen)) = (start, seen.lock()) {
+                    seen.push(start.to_string());
+                }
+                let head = match start {
+                    Some(start) => format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n",
+                        start,
+                        content.len() - 1,
+                        content.len()
+                    ),
+                    None => "HTTP/1.1 200 OK\r\n".to_string(),
+                };
+                let body = &content[start.unwrap_or(0)..];
+                let head = format!(
+                    "{head}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(body).await;
+            }
+        });
+        Ok((format!("http://{addr}/app-setup.exe"), ranges))
+    }
+
+    #[tokio::test]
+    async fn test_download_update_resumes_and_verifies() -> Result<(), String> {
+        const CONTENT: &[u8] = b"stelliberty installer payload";
+        let (url, ranges) = serve_ranges(CONTENT).await?;
+        let dir =
+            std::env::temp_dir().join(format!("stelliberty-app-update-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let target = dir.join("app-setup.exe");
+        let part = dir.join("app-setup.exe.part");
+        let expected = format!("{:x}", Sha256::digest(CONTENT));
+
+        // 上次下载中断，保留了前 10 个字节
+        std::fs::write(&part, &CONTENT[..10]).map_err(|e| e.to_string())?;
+        let downloaded = download_update(
+            &url,
+            &target,
+            Some(&expected),
+            ProxyMode::Direct,
+            0,
+            &|_, _| {},
+        )
+        .await
+        .map_err(|e| e.message)?;
+        assert_eq!(downloaded.path, target);
+        assert_eq!(std::fs::read(&target).map_err(|e| e.to_string())?, CONTENT);
+        assert!(!part.exists());
+        assert_eq!(*ranges.lock().map_err(|e| e.to_string())?, ["10"]);
+
+        // 校验失败时删除下载的文件
+        let _ = std::fs::remove_file(&target);
+        let error = download_update(
+            &url,
+            &target,
+            Some(&"0".repeat(64)),
+            ProxyMode::Direct,
+            0,
+            &|_, _| {},
+        )
+        .await
+        .err()
+        .ok_or("应校验失败")?;
+        assert_eq!(error.code, AppUpdateDownloadErrorCode::ChecksumMismatch);
+        assert!(!target.exists() && !part.exists());
+        Ok(())
+    }
 }
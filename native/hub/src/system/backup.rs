@@ -1,17 +1,138 @@
 // 备份与还原服务
 //
 // 目的：处理应用数据的备份和还原操作
+//
+// 两种格式：
+// - v1：单个 JSON 文件，数据文件以 Base64 编码内嵌（旧版本创建）
+// - v2：ZIP 归档，manifest.json 记录元数据，数据文件按数据目录中的相对路径原样保存
+//
+// 订阅与覆写目录递归收集，子目录中的文件以相对路径（以 / 分隔）保存，还原时重建目录结构。
+// 文件系统路径一律以 Path::join 拼接，Dart 传入的路径先经过规范化（见 paths 模块）。
+// 回环豁免与设置中列出的附加文件作为可选内容备份（见 extras 模块）。
+// 包含 ..、绝对路径等可能写出数据目录的名称的备份在校验阶段被拒绝
+//
+// 两种格式都可以使用密码加密（见 crypto 模块），v1 可以使用 zstd 压缩（见 compress 模块，
+// v2 的 ZIP 条目本身已压缩），还原时按文件头区分格式。
+// v1 的 checksum 为 data 规范序列化（键排序的紧凑 JSON）的 SHA-256，v2 依赖 ZIP 的 CRC32。
+// 版本兼容规则与旧版结构的迁移见 migrate 模块，目录中已有备份的列表见 listing 模块。
+// 还原前先完整校验备份（与试运行使用同一遍检查，见 report 模块），再创建快照；新内容先写入暂存目录再整体交换（见 staging 模块），
+// 交换失败时恢复还原前的数据（见 snapshot 模块）
+
+mod compress;
+mod crypto;
+mod extras;
+mod inspect;
+mod listing;
+mod merge;
+mod migrate;
+mod paths;
+mod progress;
+mod report;
+mod schedule;
+mod snapshot;
+mod staging;
+
+pub use inspect::inspect_backup;
+pub use listing::{BackupListEntry, list_backups};
+pub use paths::normalize_path;
+pub use progress::{BackupPhase, Progress};
+pub use report::RestoreReport;
+pub use schedule::{AutoBackupPolicy, configure_auto_backup, start_auto_backup};
 
 use base64::{Engine as _, engine::general_purpose};
+use crypto::BackupCryptoError;
+use extras::BackupExtras;
+use rinf::SignalPiece;
 use serde::{Deserialize, Serialize};
 use serde_json;
+use sha2::{Digest, Sha256};
+use snapshot::RestoreSnapshot;
+use staging::StagedRestore;
 use std::collections::HashMap;
-use std::path::Path;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use tokio::fs as async_fs;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
 
 // 备份版本
 const BACKUP_VERSION: &str = "1.0.0";
 
+// ZIP 备份的版本
+const BACKUP_VERSION_V2: &str = "2.0.0";
+
+// ZIP 备份中的元数据文件
+const MANIFEST_NAME: &str = "manifest.json";
+
+// ZIP 文件头
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+
+// 数据目录根下备份的单个文件（配置文件缺失时还原为空配置）
+const PREFERENCE_FILES: [&str; 2] = ["app_preferences.json", "clash_preferences.json"];
+const ROOT_FILES: [&str; 2] = ["dns_config.json", "proxy.pac"];
+
+// 备份格式
+#[derive(Serialize, Deserialize, SignalPiece, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BackupFormat {
+    // 单个 JSON 文件（旧格式，仅为兼容旧版本保留）
+    V1,
+    // ZIP 归档
+    #[default]
+    V2,
+}
+
+// 还原模式
+#[derive(Serialize, Deserialize, SignalPiece, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RestoreMode {
+    // 用备份内容替换现有的订阅配置与覆写文件（备份中没有的部分保持不变）
+    #[default]
+    Replace,
+    // 写入备份中的文件，保留只存在于本地的文件，list.json 按 id 合并
+    Merge,
+}
+
+// ZIP 备份的元数据
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupManifest {
+    pub version: String,
+    pub timestamp: String, // ISO 8601 格式
+    pub app_version: String,
+    pub platform: String,
+}
+
+// 内存中的备份文件：(归档内名称, 文件内容)
+type BackupFiles = Vec<(String, Vec<u8>)>;
+
+// 待还原的备份内容
+enum RestoreSource {
+    ZipFile(PathBuf),
+    ZipBytes(Vec<u8>),
+    // 由 v1 迁移得到的文件（归档内名称 -> 内容）
+    Files(BackupFiles),
+}
+
+// 还原结果
+#[derive(Debug, Default)]
+pub struct RestoreOutcome {
+    // 合并 list.json 时与本地内容不同、使用了备份版本的条目
+    pub conflicts: Vec<String>,
+    // 数据已还原但未能完成的部分（如回环豁免）
+    pub warnings: Vec<String>,
+}
+
+// 备份校验结果
+#[derive(Debug)]
+pub struct BackupInfo {
+    pub version: String,
+    pub timestamp: String,
+    pub platform: String,
+    // 旧版 v1 备份没有校验和，无法确认内容完整
+    pub checksum_missing: bool,
+    // 将要还原的内容与发现的问题
+    pub report: RestoreReport,
+}
+
 // 备份数据结构
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BackupData {
@@ -19,6 +140,9 @@ pub struct BackupData {
     pub timestamp: String, // ISO 8601 格式
     pub app_version: String,
     pub platform: String,
+    // data 的 SHA-256（旧版备份没有此字段）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
     pub data: BackupContent,
 }
 
@@ -31,6 +155,12 @@ pub struct BackupContent {
     pub overrides: OverrideBackup,
     pub dns_config: Option<String>, // Base64 编码
     pub pac_file: Option<String>,   // Base64 编码
+    // 已启用回环豁免的 UWP 应用 SID（仅 Windows 备份）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loopback_sids: Option<Vec<String>>,
+    // 附加文件：数据目录中的相对路径 -> Base64 内容
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra_files: HashMap<String, String>,
 }
 
 // 订阅备份数据
@@ -53,61 +183,157 @@ pub struct OverrideBackup {
 // - target_path: 备份文件保存路径
 // - app_data_path: 应用数据目录
 // - app_version: 应用版本号
+// - format: 备份格式
+// - compress: 是否压缩（v1 整体使用 zstd 压缩，v2 的条目使用 Deflate，否则仅存储）
+// - password: 加密密码，为空时不加密
+// - progress: 进度上报
 //
 // 返回：备份文件路径
 pub async fn create_backup(
-    target_path: &str,
-    app_data_path: &str,
+    target_path: &Path,
+    app_data_path: &Path,
     app_version: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    log::info!("开始创建备份到：{}", target_path);
+    format: BackupFormat,
+    compress: bool,
+    password: Option<&str>,
+    progress: &Progress,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let password = password.filter(|p| !p.is_empty()).map(str::to_string);
+    log::info!(
+        "开始创建备份到：{}（格式：{:?}，压缩：{}，加密：{}）",
+        target_path.display(),
+        format,
+        compress,
+        password.is_some()
+    );
+
+    if let Some(parent) = target_path.parent() {
+        async_fs::create_dir_all(parent).await?;
+    }
+    let extras = tokio::task::spawn_blocking(BackupExtras::capture).await?;
+
+    if format == BackupFormat::V2 {
+        let manifest = BackupManifest {
+            version: BACKUP_VERSION_V2.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            app_version: app_version.to_string(),
+            platform: std::env::consts::OS.to_string(),
+        };
+        let target = target_path.to_path_buf();
+        let data_dir = app_data_path.to_path_buf();
+        let progress = progress.clone();
+        tokio::task::spawn_blocking(move || match password {
+            // 加密需要完整的归档内容，先写入内存
+            Some(password) => {
+                let mut buffer = Cursor::new(Vec::new());
+                write_zip_backup(
+                    &mut buffer,
+                    &data_dir,
+                    &manifest,
+                    &extras,
+                    compress,
+                    &progress,
+                )?;
+                progress.report(BackupPhase::Encrypting, 0, 1, "");
+                write_encrypted(&target, buffer.get_ref(), &password)
+            }
+            None => write_zip_backup(
+                BufWriter::new(File::create(&target)?),
+                &data_dir,
+                &manifest,
+                &extras,
+                compress,
+                &progress,
+            ),
+        })
+        .await??;
+        log::info!("备份创建成功：{}", target_path.display());
+        return Ok(target_path.to_path_buf());
+    }
 
     // 1. 收集应用配置
-    let app_prefs = collect_preferences(&format!("{}/app_preferences.json", app_data_path)).await?;
+    progress.report(
+        BackupPhase::CollectingPreferences,
+        0,
+        2,
+        "app_preferences.json",
+    );
+    let app_prefs = collect_preferences(&app_data_path.join("app_preferences.json")).await?;
 
     // 2. 收集 Clash 配置
-    let clash_prefs =
-        collect_preferences(&format!("{}/clash_preferences.json", app_data_path)).await?;
+    progress.report(
+        BackupPhase::CollectingPreferences,
+        1,
+        2,
+        "clash_preferences.json",
+    );
+    let clash_prefs = collect_preferences(&app_data_path.join("clash_preferences.json")).await?;
 
     // 3. 收集订阅数据
-    let subscriptions = collect_subscriptions(app_data_path).await?;
+    let subscriptions = collect_subscriptions(app_data_path, progress).await?;
 
     // 4. 收集覆写数据
-    let overrides = collect_overrides(app_data_path).await?;
+    let overrides = collect_overrides(app_data_path, progress).await?;
 
     // 5. 收集 DNS 配置
-    let dns_config = collect_file_base64(&format!("{}/dns_config.json", app_data_path)).await;
+    let dns_config = collect_file_base64(&app_data_path.join("dns_config.json")).await;
 
     // 6. 收集 PAC 文件
-    let pac_file = collect_file_base64(&format!("{}/proxy.pac", app_data_path)).await;
+    let pac_file = collect_file_base64(&app_data_path.join("proxy.pac")).await;
 
-    // 7. 构建备份数据
+    // 7. 收集附加文件
+    let mut extra_files = HashMap::new();
+    for (name, path) in extras::collect_extra_files(app_data_path, &extras.allowlist) {
+        let content = async_fs::read(&path).await?;
+        extra_files.insert(name, general_purpose::STANDARD.encode(&content));
+    }
+
+    // 8. 构建备份数据
+    let data = BackupContent {
+        app_preferences: app_prefs,
+        clash_preferences: clash_prefs,
+        subscriptions,
+        overrides,
+        dns_config,
+        pac_file,
+        loopback_sids: extras.loopback_sids,
+        extra_files,
+    };
     let backup_data = BackupData {
         version: BACKUP_VERSION.to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
         app_version: app_version.to_string(),
         platform: std::env::consts::OS.to_string(),
-        data: BackupContent {
-            app_preferences: app_prefs,
-            clash_preferences: clash_prefs,
-            subscriptions,
-            overrides,
-            dns_config,
-            pac_file,
-        },
+        checksum: Some(content_checksum(&data)?),
+        data,
     };
 
-    // 8. 写入文件
-    let output_path = Path::new(target_path);
-    if let Some(parent) = output_path.parent() {
-        async_fs::create_dir_all(parent).await?;
-    }
-
+    // 9. 写入文件（先压缩再加密）
     let json_str = serde_json::to_string_pretty(&backup_data)?;
-    async_fs::write(output_path, json_str).await?;
+    if !compress && password.is_none() {
+        async_fs::write(target_path, json_str).await?;
+    } else {
+        let target = target_path.to_path_buf();
+        let progress = progress.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut payload = json_str.into_bytes();
+            if compress {
+                progress.report(BackupPhase::Compressing, 0, 1, "");
+                payload = compress::compress(&payload)?;
+            }
+            match password {
+                Some(password) => {
+                    progress.report(BackupPhase::Encrypting, 0, 1, "");
+                    write_encrypted(&target, &payload, &password)
+                }
+                None => Ok(std::fs::write(&target, payload)?),
+            }
+        })
+        .await??;
+    }
 
-    log::info!("备份创建成功：{}", target_path);
-    Ok(target_path.to_string())
+    log::info!("备份创建成功：{}", target_path.display());
+    Ok(target_path.to_path_buf())
 }
 
 // 还原备份
@@ -115,73 +341,289 @@ pub async fn create_backup(
 // 参数：
 // - backup_path: 备份文件路径
 // - app_data_path: 应用数据目录
+// - password: 加密备份的密码
+// - mode: 替换或合并现有数据
+// - progress: 进度上报
+//
+// 返回：合并时使用了备份版本的条目与未能完成的部分（见 RestoreOutcome）
 pub async fn restore_backup(
-    backup_path: &str,
-    app_data_path: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    log::info!("开始还原备份：{}", backup_path);
-
-    // 1. 读取并验证备份文件
-    let json_str = async_fs::read_to_string(backup_path).await?;
-    let backup_data: BackupData = serde_json::from_str(&json_str)?;
-
-    // 2. 验证版本兼容性
-    if backup_data.version != BACKUP_VERSION {
-        log::warn!(
-            "备份版本不匹配：{} != {}",
-            backup_data.version,
-            BACKUP_VERSION
-        );
-        if backup_data.version != "1.0.0" {
-            return Err(format!("不支持的备份版本：{}", backup_data.version).into());
-        }
+    backup_path: &Path,
+    app_data_path: &Path,
+    password: Option<&str>,
+    mode: RestoreMode,
+    progress: &Progress,
+) -> Result<RestoreOutcome, Box<dyn std::error::Error + Send + Sync>> {
+    log::info!("开始还原备份：{}", backup_path.display());
+
+    // 1. 读取并校验备份（失败时还没有修改任何数据）
+    let (source, info) = read_restore_source(backup_path, password, progress).await?;
+    info.report.ensure_valid()?;
+    log::info!("备份版本：{}，时间：{}", info.version, info.timestamp);
+    if info.checksum_missing {
+        log::warn!("备份没有校验和，无法确认内容完整");
     }
 
-    log::info!(
-        "备份版本：{}，时间：{}",
-        backup_data.version,
-        backup_data.timestamp
-    );
+    // 2. 处理上次中断的还原并创建还原前快照
+    let data_dir = app_data_path.to_path_buf();
+    progress.report(BackupPhase::CreatingSnapshot, 0, 1, "");
+    let snapshot = {
+        let data_dir = data_dir.clone();
+        let extra_files = info.report.extra_files.clone();
+        tokio::task::spawn_blocking(move || {
+            StagedRestore::recover(&data_dir)?;
+            RestoreSnapshot::create(&data_dir, &extra_files)
+        })
+        .await?
+        .map_err(|e| format!("创建还原前快照失败：{}", e))?
+    };
 
-    // 3. 还原应用配置
-    restore_preferences(
-        &backup_data.data.app_preferences,
-        &format!("{}/app_preferences.json", app_data_path),
-    )
+    // 3. 写入暂存目录，失败时现有数据没有被修改
+    let blocking_progress = progress.clone();
+    let staged = tokio::task::spawn_blocking(move || {
+        let mut staged = StagedRestore::create(&data_dir, mode)?;
+        let result = match source {
+            RestoreSource::ZipFile(backup) => restore_zip_backup(
+                BufReader::new(File::open(&backup)?),
+                &mut staged,
+                &blocking_progress,
+            ),
+            RestoreSource::ZipBytes(payload) => {
+                restore_zip_backup(Cursor::new(payload), &mut staged, &blocking_progress)
+            }
+            RestoreSource::Files(files) => restore_files(files, &mut staged, &blocking_progress),
+        };
+        match result {
+            Ok(()) => Ok(staged),
+            Err(e) => {
+                staged.discard();
+                Err(e)
+            }
+        }
+    })
     .await?;
+    let mut staged = match staged {
+        Ok(staged) => staged,
+        Err(e) => {
+            log::error!("备份还原失败，现有数据未被修改：{}", e);
+            tokio::task::spawn_blocking(move || snapshot.discard()).await?;
+            return Err(format!("{}，现有数据未被修改", e).into());
+        }
+    };
 
-    // 4. 还原 Clash 配置
-    restore_preferences(
-        &backup_data.data.clash_preferences,
-        &format!("{}/clash_preferences.json", app_data_path),
-    )
-    .await?;
+    // 4. 交换目录，失败时恢复快照
+    progress.report(BackupPhase::Committing, 0, 1, "");
+    let mut outcome = RestoreOutcome {
+        conflicts: staged.conflicts().to_vec(),
+        warnings: Vec::new(),
+    };
+    let loopback_sids = staged.take_loopback_sids();
+    let result = tokio::task::spawn_blocking(move || staged.commit()).await?;
+
+    match result {
+        Ok(()) => {
+            tokio::task::spawn_blocking(move || snapshot.discard()).await?;
+            log::info!("备份还原成功");
+            if !outcome.conflicts.is_empty() {
+                log::info!("合并时使用备份版本的条目：{}", outcome.conflicts.join("，"));
+            }
+
+            // 5. 应用回环豁免（不属于数据目录，失败时不回滚已还原的数据）
+            if let Some(sids) = loopback_sids
+                && let Err(e) =
+                    tokio::task::spawn_blocking(move || extras::apply_loopback_sids(&sids)).await?
+            {
+                log::warn!("还原回环豁免失败：{}", e);
+                outcome
+                    .warnings
+                    .push(format!("回环豁免未能完全还原：{}", e));
+            }
+            Ok(outcome)
+        }
+        Err(e) => {
+            log::error!("备份还原失败，正在恢复还原前的数据：{}", e);
+            progress.report(BackupPhase::RollingBack, 0, 1, "");
+            let (rollback, snapshot_dir) = tokio::task::spawn_blocking(move || {
+                (snapshot.rollback(), snapshot.dir().to_path_buf())
+            })
+            .await?;
+            match rollback {
+                Ok(()) => Err(format!("{}，已恢复还原前的数据", e).into()),
+                Err(rollback_error) => Err(format!(
+                    "{}；恢复还原前的数据失败：{}，快照保留在：{}",
+                    e,
+                    rollback_error,
+                    snapshot_dir.display()
+                )
+                .into()),
+            }
+        }
+    }
+}
 
-    // 5. 还原订阅数据
-    restore_subscriptions(&backup_data.data.subscriptions, app_data_path).await?;
+// 按文件头读取备份，加密备份在此解密
+//
+// 同时校验完整性：v1 比对校验和，v2 读取全部条目以检查 CRC32
+async fn read_restore_source(
+    backup_path: &Path,
+    password: Option<&str>,
+    progress: &Progress,
+) -> Result<(RestoreSource, BackupInfo), Box<dyn std::error::Error + Send + Sync>> {
+    let head = read_head(backup_path).await?;
 
-    // 6. 还原覆写数据
-    restore_overrides(&backup_data.data.overrides, app_data_path).await?;
+    if crypto::is_encrypted(&head) || compress::is_compressed(&head) {
+        let password = if crypto::is_encrypted(&head) {
+            Some(
+                password
+                    .filter(|p| !p.is_empty())
+                    .ok_or(BackupCryptoError::PasswordRequired)?
+                    .to_string(),
+            )
+        } else {
+            None
+        };
+        let data = async_fs::read(backup_path).await?;
+        let progress = progress.clone();
+        let (payload, zip_info) = tokio::task::spawn_blocking(move || {
+            let payload = decode_payload(data, password.as_deref(), &progress)?;
+            let zip_info = if is_zip_backup(&payload)? {
+                Some(verify_zip_backup(
+                    Cursor::new(payload.as_slice()),
+                    &progress,
+                )?)
+            } else {
+                None
+            };
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>((payload, zip_info))
+        })
+        .await??;
 
-    // 7. 还原 DNS 配置
-    if let Some(dns_config) = &backup_data.data.dns_config {
-        restore_file_base64(dns_config, &format!("{}/dns_config.json", app_data_path)).await?;
+        return match zip_info {
+            Some(info) => Ok((RestoreSource::ZipBytes(payload), info)),
+            None => {
+                let (files, info) = verify_json_backup(&String::from_utf8(payload)?)?;
+                Ok((RestoreSource::Files(files), info))
+            }
+        };
     }
 
-    // 8. 还原 PAC 文件
-    if let Some(pac_file) = &backup_data.data.pac_file {
-        restore_file_base64(pac_file, &format!("{}/proxy.pac", app_data_path)).await?;
+    if is_zip_backup(&head)? {
+        let backup = backup_path.to_path_buf();
+        let progress = progress.clone();
+        let info = tokio::task::spawn_blocking(move || {
+            verify_zip_backup(BufReader::new(File::open(&backup)?), &progress)
+        })
+        .await??;
+        Ok((RestoreSource::ZipFile(backup_path.to_path_buf()), info))
+    } else {
+        let json_str = async_fs::read_to_string(backup_path).await?;
+        progress.report(BackupPhase::Verifying, 0, 1, "");
+        let (files, info) = verify_json_backup(&json_str)?;
+        Ok((RestoreSource::Files(files), info))
     }
+}
 
-    log::info!("备份还原成功");
-    Ok(())
+// 去掉加密与压缩层，返回 ZIP 或 JSON 备份内容
+//
+// password 为 None 时数据不应是加密的
+fn decode_payload(
+    data: Vec<u8>,
+    password: Option<&str>,
+    progress: &Progress,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut payload = data;
+    if let Some(password) = password {
+        progress.report(BackupPhase::Decrypting, 0, 1, "");
+        payload = crypto::decrypt(&payload, password)?;
+    }
+    if compress::is_compressed(&payload) {
+        progress.report(BackupPhase::Decompressing, 0, 1, "");
+        payload = compress::decompress(&payload)?;
+    }
+    Ok(payload)
+}
+
+// 校验备份但不还原
+//
+// 参数：
+// - backup_path: 备份文件路径
+// - password: 加密备份的密码
+pub async fn verify_backup(
+    backup_path: &Path,
+    password: Option<&str>,
+) -> Result<BackupInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let (_, info) = read_restore_source(backup_path, password, &Progress::none()).await?;
+    info.report.ensure_valid()?;
+    Ok(info)
+}
+
+// 试运行还原：执行还原前的全部检查，不写入任何文件
+//
+// 无法读取或校验失败的备份返回错误；条目的问题记录在报告中
+pub async fn dry_run_restore(
+    backup_path: &Path,
+    password: Option<&str>,
+    progress: &Progress,
+) -> Result<BackupInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let (_, info) = read_restore_source(backup_path, password, progress).await?;
+    Ok(info)
+}
+
+// 备份损坏的错误
+fn corrupted(reason: impl std::fmt::Display) -> Box<dyn std::error::Error + Send + Sync> {
+    format!("备份文件已损坏：{}", reason).into()
+}
+
+// 计算 v1 备份内容的校验和
+//
+// 先转换为 serde_json::Value（对象的键按字典序排列），不受 HashMap 遍历顺序影响
+fn content_checksum(
+    data: &BackupContent,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let canonical = serde_json::to_vec(&serde_json::to_value(data)?)?;
+    Ok(format!("{:x}", Sha256::digest(&canonical)))
+}
+
+// 解析并校验 v1 备份，迁移为当前结构
+fn verify_json_backup(
+    json_str: &str,
+) -> Result<(BackupFiles, BackupInfo), Box<dyn std::error::Error + Send + Sync>> {
+    let backup_data: BackupData = serde_json::from_str(json_str).map_err(corrupted)?;
+    migrate::check_version(&backup_data.version)?;
+
+    if let Some(expected) = &backup_data.checksum
+        && !content_checksum(&backup_data.data)?.eq_ignore_ascii_case(expected)
+    {
+        return Err(corrupted("校验和不匹配"));
+    }
+
+    let mut info = BackupInfo {
+        version: backup_data.version.clone(),
+        timestamp: backup_data.timestamp.clone(),
+        platform: backup_data.platform.clone(),
+        checksum_missing: backup_data.checksum.is_none(),
+        report: RestoreReport::default(),
+    };
+    let files = migrate::migrate_v1(backup_data)?;
+    for (name, content) in &files {
+        let checked = RestoreReport::needs_content(name).then_some(content.as_slice());
+        info.report.check(name, content.len() as u64, checked);
+    }
+    Ok((files, info))
+}
+
+// 为还原步骤的错误加上步骤名称
+fn step<T, E: std::fmt::Display>(
+    name: &str,
+    result: Result<T, E>,
+) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+    result.map_err(|e| format!("{}失败：{}", name, e).into())
 }
 
 // 收集配置文件
 async fn collect_preferences(
-    path: &str,
+    path: &Path,
 ) -> Result<HashMap<String, serde_json::Value>, Box<dyn std::error::Error + Send + Sync>> {
-    if !Path::new(path).exists() {
+    if !path.exists() {
         return Ok(HashMap::new());
     }
 
@@ -192,10 +634,11 @@ async fn collect_preferences(
 
 // 收集订阅数据
 async fn collect_subscriptions(
-    app_data_path: &str,
+    app_data_path: &Path,
+    progress: &Progress,
 ) -> Result<SubscriptionBackup, Box<dyn std::error::Error + Send + Sync>> {
-    let subscriptions_dir = format!("{}/subscriptions", app_data_path);
-    let list_path = format!("{}/list.json", subscriptions_dir);
+    let dir = app_data_path.join("subscriptions");
+    let list_path = dir.join("list.json");
 
     let mut backup = SubscriptionBackup {
         list: None,
@@ -203,25 +646,22 @@ async fn collect_subscriptions(
     };
 
     // 读取订阅列表
-    if Path::new(&list_path).exists() {
+    if list_path.exists() {
         backup.list = Some(async_fs::read_to_string(&list_path).await?);
     }
 
-    // 读取所有订阅配置文件
-    if Path::new(&subscriptions_dir).exists() {
-        let mut entries = async_fs::read_dir(&subscriptions_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("yaml")
-                && let Some(file_name) = path.file_stem().and_then(|s| s.to_str())
-            {
-                let content = async_fs::read(&path).await?;
-                backup.configs.insert(
-                    file_name.to_string(),
-                    general_purpose::STANDARD.encode(&content),
-                );
-            }
-        }
+    // 读取各级目录中的订阅配置文件，键为不含 .yaml 的相对路径
+    let files = tokio::task::spawn_blocking(move || list_files(&dir, is_yaml)).await??;
+    let total = files.len();
+    for (index, (name, path)) in files.into_iter().enumerate() {
+        let Some(key) = name.strip_suffix(".yaml") else {
+            continue;
+        };
+        progress.report(BackupPhase::CollectingSubscriptions, index + 1, total, key);
+        let content = async_fs::read(&path).await?;
+        backup
+            .configs
+            .insert(key.to_string(), general_purpose::STANDARD.encode(&content));
     }
 
     Ok(backup)
@@ -229,10 +669,11 @@ async fn collect_subscriptions(
 
 // 收集覆写数据
 async fn collect_overrides(
-    app_data_path: &str,
+    app_data_path: &Path,
+    progress: &Progress,
 ) -> Result<OverrideBackup, Box<dyn std::error::Error + Send + Sync>> {
-    let overrides_dir = format!("{}/overrides", app_data_path);
-    let list_path = format!("{}/list.json", overrides_dir);
+    let dir = app_data_path.join("overrides");
+    let list_path = dir.join("list.json");
 
     let mut backup = OverrideBackup {
         list: None,
@@ -240,145 +681,1203 @@ async fn collect_overrides(
     };
 
     // 读取覆写列表
-    if Path::new(&list_path).exists() {
+    if list_path.exists() {
         backup.list = Some(async_fs::read_to_string(&list_path).await?);
     }
 
-    // 读取所有覆写文件
-    if Path::new(&overrides_dir).exists() {
-        let mut entries = async_fs::read_dir(&overrides_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file()
-                && let Some(file_name) = path.file_name().and_then(|s| s.to_str())
-            {
-                let content = async_fs::read(&path).await?;
-                backup.files.insert(
-                    file_name.to_string(),
-                    general_purpose::STANDARD.encode(&content),
-                );
-            }
-        }
+    // 读取各级目录中的覆写文件，键为相对路径
+    let files = tokio::task::spawn_blocking(move || list_files(&dir, |_| true)).await??;
+    let total = files.len();
+    for (index, (name, path)) in files.into_iter().enumerate() {
+        progress.report(BackupPhase::CollectingOverrides, index + 1, total, &name);
+        let content = async_fs::read(&path).await?;
+        backup
+            .files
+            .insert(name, general_purpose::STANDARD.encode(&content));
     }
 
     Ok(backup)
 }
 
 // 收集文件并 Base64 编码
-async fn collect_file_base64(path: &str) -> Option<String> {
-    if !Path::new(path).exists() {
+async fn collect_file_base64(path: &Path) -> Option<String> {
+    if !path.exists() {
         return None;
     }
 
     match async_fs::read(path).await {
         Ok(content) => Some(general_purpose::STANDARD.encode(&content)),
         Err(e) => {
-            log::warn!("读取文件失败：{} - {}", path, e);
+            log::warn!("读取文件失败：{} - {}", path.display(), e);
             None
         }
     }
 }
 
-// 还原配置文件
-async fn restore_preferences(
-    prefs: &HashMap<String, serde_json::Value>,
-    path: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let json_str = serde_json::to_string_pretty(prefs)?;
+// 读取备份文件开头用于判断格式
+async fn read_head(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut head = vec![0u8; 4];
+    let mut file = async_fs::File::open(path).await?;
+    let len = tokio::io::AsyncReadExt::read(&mut file, &mut head).await?;
+    head.truncate(len);
+    Ok(head)
+}
 
-    if let Some(parent) = Path::new(path).parent() {
-        async_fs::create_dir_all(parent).await?;
+// 按文件头判断备份格式：ZIP 为 v2，以 '{' 开头为 v1
+fn is_zip_backup(head: &[u8]) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    if head.starts_with(ZIP_MAGIC) {
+        return Ok(true);
+    }
+    if head.trim_ascii_start().starts_with(b"{") {
+        return Ok(false);
     }
+    Err("无法识别的备份文件格式".into())
+}
 
-    async_fs::write(path, json_str).await?;
-    log::info!("配置已还原：{}", path);
+// 加密并写入备份文件
+fn write_encrypted(
+    target: &Path,
+    plain: &[u8],
+    password: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    std::fs::write(target, crypto::encrypt(plain, password)?)?;
     Ok(())
 }
 
-// 还原订阅数据
-async fn restore_subscriptions(
-    backup: &SubscriptionBackup,
-    app_data_path: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let subscriptions_dir = format!("{}/subscriptions", app_data_path);
-    let list_path = format!("{}/list.json", subscriptions_dir);
-
-    // 清空现有订阅配置文件
-    if Path::new(&subscriptions_dir).exists() {
-        let mut entries = async_fs::read_dir(&subscriptions_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("yaml") {
-                async_fs::remove_file(path).await?;
-            }
+// 收集 ZIP 备份的文件：(归档内名称, 源文件路径)
+fn collect_backup_files(
+    app_data_path: &Path,
+) -> Result<Vec<(String, PathBuf)>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut files = Vec::new();
+
+    for name in PREFERENCE_FILES.iter().chain(ROOT_FILES.iter()) {
+        let path = app_data_path.join(name);
+        if path.is_file() {
+            files.push((name.to_string(), path));
         }
     }
 
-    // 还原订阅列表
-    if let Some(list_content) = &backup.list {
-        async_fs::create_dir_all(&subscriptions_dir).await?;
-        async_fs::write(&list_path, list_content).await?;
+    // 订阅：列表与各级目录中的 *.yaml 配置；覆写：各级目录中的所有文件（与 v1 一致）
+    let subscriptions = app_data_path.join("subscriptions");
+    let list_path = subscriptions.join("list.json");
+    if list_path.is_file() {
+        files.push(("subscriptions/list.json".to_string(), list_path));
     }
+    for (name, path) in list_files(&subscriptions, is_yaml)? {
+        files.push((format!("subscriptions/{}", name), path));
+    }
+    for (name, path) in list_files(&app_data_path.join("overrides"), |_| true)? {
+        files.push((format!("overrides/{}", name), path));
+    }
+
+    Ok(files)
+}
 
-    // 还原订阅配置文件
-    for (file_name, base64_content) in &backup.configs {
-        let content = general_purpose::STANDARD.decode(base64_content)?;
-        let file_path = format!("{}/{}.yaml", subscriptions_dir, file_name);
-        async_fs::write(&file_path, content).await?;
+fn is_yaml(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("yaml")
+}
+
+// 递归列出目录中满足条件的文件：(以 / 分隔的相对路径, 文件路径)，按相对路径排序
+//
+// 目录不存在时返回空列表；不进入符号链接指向的目录，跳过非 UTF-8 文件名
+fn list_files(dir: &Path, filter: fn(&Path) -> bool) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        walk_dir(dir, "", filter, &mut files)?;
     }
+    files.sort();
+    Ok(files)
+}
 
-    log::info!("订阅数据已还原");
+fn walk_dir(
+    dir: &Path,
+    prefix: &str,
+    filter: fn(&Path) -> bool,
+    files: &mut Vec<(String, PathBuf)>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let name = format!("{}{}", prefix, file_name);
+        if entry.file_type()?.is_dir() {
+            walk_dir(&path, &format!("{}/", name), filter, files)?;
+        } else if path.is_file() && filter(&path) {
+            files.push((name, path));
+        }
+    }
     Ok(())
 }
 
-// 还原覆写数据
-async fn restore_overrides(
-    backup: &OverrideBackup,
-    app_data_path: &str,
+// 写入 ZIP 备份（逐个文件流式写入，写入文件时不在内存中保留完整内容）
+fn write_zip_backup<W: Write + Seek>(
+    writer: W,
+    app_data_path: &Path,
+    manifest: &BackupManifest,
+    extras: &BackupExtras,
+    compress: bool,
+    progress: &Progress,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let overrides_dir = format!("{}/overrides", app_data_path);
-    let list_path = format!("{}/list.json", overrides_dir);
-
-    // 清空现有覆写文件
-    if Path::new(&overrides_dir).exists() {
-        let mut entries = async_fs::read_dir(&overrides_dir).await?;
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.is_file() {
-                async_fs::remove_file(path).await?;
-            }
+    let mut files = collect_backup_files(app_data_path)?;
+    files.extend(
+        extras::collect_extra_files(app_data_path, &extras.allowlist)
+            .into_iter()
+            .map(|(name, path)| (format!("{}/{}", extras::EXTRA_DIR, name), path)),
+    );
+    let method = if compress {
+        CompressionMethod::Deflated
+    } else {
+        CompressionMethod::Stored
+    };
+    let options = SimpleFileOptions::default().compression_method(method);
+    let mut zip = ZipWriter::new(writer);
+
+    zip.start_file(MANIFEST_NAME, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    if let Some(sids) = &extras.loopback_sids {
+        zip.start_file(extras::LOOPBACK_FILE, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(sids)?)?;
+    }
+
+    for (index, (name, path)) in files.iter().enumerate() {
+        progress.report(BackupPhase::WritingArchive, index + 1, files.len(), name);
+        zip.start_file(name.as_str(), options)?;
+        std::io::copy(&mut BufReader::new(File::open(path)?), &mut zip)?;
+    }
+
+    zip.finish()?.flush()?;
+    log::info!("已写入 {} 个文件到备份", files.len());
+    Ok(())
+}
+
+// 归档内名称对应的还原位置，不属于备份内容的名称返回 None
+fn restore_target(app_data_path: &Path, name: &str) -> Option<PathBuf> {
+    if PREFERENCE_FILES.contains(&name) || ROOT_FILES.contains(&name) {
+        return Some(app_data_path.join(name));
+    }
+
+    if !is_safe_name(name) {
+        return None;
+    }
+    let (dir, relative) = name.split_once('/')?;
+    match dir {
+        "subscriptions" if relative == "list.json" || relative.ends_with(".yaml") => {
+            Some(join_name(app_data_path, name))
         }
+        "overrides" => Some(join_name(app_data_path, name)),
+        extras::EXTRA_DIR if extras::is_extra_name(relative) => {
+            Some(join_name(app_data_path, relative))
+        }
+        _ => None,
     }
+}
+
+// 将以 / 分隔的归档内名称逐个组件接在目录后（Windows 的 \\?\ 路径不把 / 视为分隔符）
+fn join_name(dir: &Path, name: &str) -> PathBuf {
+    name.split('/')
+        .fold(dir.to_path_buf(), |path, component| path.join(component))
+}
+
+// 归档内名称是否为安全的相对路径：不含 .. 或 . 组件、空组件、绝对路径、
+// 反斜杠与盘符（Windows 上 C:foo 或 \\server 会逃出数据目录）
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(['\\', ':'])
+        && name
+            .split('/')
+            .all(|component| !component.is_empty() && component != "." && component != "..")
+}
 
-    // 还原覆写列表
-    if let Some(list_content) = &backup.list {
-        async_fs::create_dir_all(&overrides_dir).await?;
-        async_fs::write(&list_path, list_content).await?;
+// 读取并验证 ZIP 备份的元数据
+fn read_manifest<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<BackupManifest, Box<dyn std::error::Error + Send + Sync>> {
+    let mut content = String::new();
+    archive
+        .by_name(MANIFEST_NAME)
+        .map_err(|_| "备份文件缺少 manifest.json")?
+        .read_to_string(&mut content)
+        .map_err(corrupted)?;
+    let manifest: BackupManifest = serde_json::from_str(&content).map_err(corrupted)?;
+    migrate::check_version(&manifest.version)?;
+    Ok(manifest)
+}
+
+// 校验 ZIP 备份：读取全部条目，截断或 CRC32 不匹配时返回错误，条目的问题记录在报告中
+fn verify_zip_backup<R: Read + Seek>(
+    reader: R,
+    progress: &Progress,
+) -> Result<BackupInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let mut archive = ZipArchive::new(reader).map_err(corrupted)?;
+    let manifest = read_manifest(&mut archive)?;
+
+    let mut report = RestoreReport::default();
+    let total = archive.len();
+    for index in 0..total {
+        let mut entry = archive.by_index(index).map_err(corrupted)?;
+        let name = entry.name().to_string();
+        progress.report(BackupPhase::Verifying, index + 1, total, &name);
+        let read_error = |e: std::io::Error| corrupted(format!("{} - {}", name, e));
+        if entry.is_dir() || name == MANIFEST_NAME {
+            std::io::copy(&mut entry, &mut std::io::sink()).map_err(read_error)?;
+        } else if RestoreReport::needs_content(&name) {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).map_err(read_error)?;
+            report.check(&name, content.len() as u64, Some(&content));
+        } else {
+            let size = std::io::copy(&mut entry, &mut std::io::sink()).map_err(read_error)?;
+            report.check(&name, size, None);
+        }
     }
 
-    // 还原覆写文件
-    for (file_name, base64_content) in &backup.files {
-        let content = general_purpose::STANDARD.decode(base64_content)?;
-        let file_path = format!("{}/{}", overrides_dir, file_name);
-        async_fs::write(&file_path, content).await?;
+    Ok(BackupInfo {
+        version: manifest.version,
+        timestamp: manifest.timestamp,
+        platform: manifest.platform,
+        checksum_missing: false,
+        report,
+    })
+}
+
+// 还原 ZIP 备份
+fn restore_zip_backup<R: Read + Seek>(
+    reader: R,
+    staged: &mut StagedRestore,
+    progress: &Progress,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // 1. 读取元数据（备份已由 verify_zip_backup 校验）
+    let mut archive = ZipArchive::new(reader)?;
+    read_manifest(&mut archive)?;
+
+    // 2. 逐个写出备份中的文件
+    let total = archive.len();
+    for index in 0..total {
+        let mut entry = archive.by_index(index)?;
+        if entry.is_dir() || entry.name() == MANIFEST_NAME {
+            continue;
+        }
+        let name = entry.name().to_string();
+        progress.report(BackupPhase::restoring(&name), index + 1, total, &name);
+        staged.write(&name, &mut entry)?;
     }
 
-    log::info!("覆写数据已还原");
+    // 备份中没有的配置文件保留本地版本（v1 迁移时空配置已显式写为 {}）
+
+    log::info!("已从备份还原 {} 个文件", staged.restored().len());
     Ok(())
 }
 
-// 还原文件（Base64 解码）
-async fn restore_file_base64(
-    base64_content: &str,
-    path: &str,
+// 还原由 v1 迁移得到的文件
+fn restore_files(
+    files: BackupFiles,
+    staged: &mut StagedRestore,
+    progress: &Progress,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let content = general_purpose::STANDARD.decode(base64_content)?;
-
-    if let Some(parent) = Path::new(path).parent() {
-        async_fs::create_dir_all(parent).await?;
+    let total = files.len();
+    for (index, (name, content)) in files.into_iter().enumerate() {
+        progress.report(BackupPhase::restoring(&name), index + 1, total, &name);
+        staged.write(&name, &mut content.as_slice())?;
     }
 
-    async_fs::write(path, content).await?;
-    log::info!("文件已还原：{}", path);
+    log::info!("已从备份还原 {} 个文件", staged.restored().len());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = Result<(), String>;
+
+    // 测试用的独立临时目录
+    fn temp_dir(name: &str) -> Result<PathBuf, String> {
+        let dir = std::env::temp_dir().join(format!(
+            "stelliberty-backup-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        Ok(dir)
+    }
+
+    // 示例数据：包含非 UTF-8 内容、带空格的文件名和子目录
+    fn sample_files() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("app_preferences.json", br#"{"theme":"dark"}"#.to_vec()),
+            ("clash_preferences.json", br#"{"mixed_port":7890}"#.to_vec()),
+            ("proxy.pac", b"function FindProxyForURL() {}".to_vec()),
+            ("subscriptions/list.json", br#"[{"id":"my sub"}]"#.to_vec()),
+            (
+                "subscriptions/my sub.yaml",
+                vec![0xff, 0xfe, 0x00, b'\n', 0x80],
+            ),
+            ("overrides/list.json", b"[]".to_vec()),
+            ("overrides/rule set.js", vec![0xc3, 0x28, 0x00, 0xa0]),
+            (
+                "subscriptions/providers/rules/foo.yaml",
+                b"payload: []".to_vec(),
+            ),
+            ("overrides/rules/nested rule.js", b"// nested".to_vec()),
+        ]
+    }
+
+    fn write_files(dir: &Path, files: &[(&str, Vec<u8>)]) -> TestResult {
+        for (name, content) in files {
+            let path = join_name(dir, name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(path, content).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    async fn round_trip(
+        format: BackupFormat,
+        compress: bool,
+        name: &str,
+        password: Option<&str>,
+    ) -> TestResult {
+        let source = temp_dir(&format!("{}-source", name))?;
+        let target = temp_dir(&format!("{}-target", name))?;
+        let backup = temp_dir(&format!("{}-out", name))?.join("backup file");
+        let files = sample_files();
+        write_files(&source, &files)?;
+        // 还原前已有的订阅配置应被清除
+        write_files(
+            &target,
+            &[
+                ("subscriptions/stale.yaml", b"old".to_vec()),
+                ("subscriptions/providers/stale.yaml", b"old".to_vec()),
+            ],
+        )?;
+
+        create_backup(
+            &backup,
+            &source,
+            "1.0.0",
+            format,
+            compress,
+            password,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        if format == BackupFormat::V1 && password.is_none() {
+            let head = read_head(&backup).await.map_err(|e| e.to_string())?;
+            assert_eq!(compress::is_compressed(&head), compress, "{name}");
+        }
+        restore_backup(
+            &backup,
+            &target,
+            password,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for (name, content) in &files {
+            let restored = std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"))?;
+            if name.ends_with("preferences.json") {
+                // v1 会重新格式化配置文件，比较解析后的内容
+                let parse = |bytes: &[u8]| serde_json::from_slice::<serde_json::Value>(bytes).ok();
+                assert_eq!(parse(&restored), parse(content), "{name}");
+            } else {
+                assert_eq!(&restored, content, "{name}");
+            }
+        }
+        assert!(!target.join("subscriptions/stale.yaml").exists());
+        assert!(!target.join("subscriptions/providers/stale.yaml").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zip_backup_round_trip() -> TestResult {
+        round_trip(BackupFormat::V2, true, "v2", None).await?;
+        round_trip(BackupFormat::V2, false, "v2-stored", None).await
+    }
+
+    #[tokio::test]
+    async fn test_json_backup_still_restores() -> TestResult {
+        round_trip(BackupFormat::V1, false, "v1", None).await
+    }
+
+    #[tokio::test]
+    async fn test_compressed_json_backup_round_trip() -> TestResult {
+        round_trip(BackupFormat::V1, true, "v1-compressed", None).await
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_round_trip() -> TestResult {
+        round_trip(BackupFormat::V2, true, "v2-encrypted", Some("p@ss word")).await?;
+        round_trip(BackupFormat::V1, true, "v1-encrypted", Some("p@ss word")).await
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup_errors() -> TestResult {
+        let source = temp_dir("errors-source")?;
+        let target = temp_dir("errors-target")?;
+        let backup = temp_dir("errors-out")?.join("backup");
+        write_files(&source, &sample_files())?;
+        let backup_path = backup.as_path();
+        let target_path = target.as_path();
+
+        create_backup(
+            backup_path,
+            &source,
+            "1.0.0",
+            BackupFormat::V2,
+            true,
+            Some("secret"),
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let restore_error = async |password| {
+            restore_backup(
+                backup_path,
+                target_path,
+                password,
+                RestoreMode::Replace,
+                &Progress::none(),
+            )
+            .await
+            .err()
+            .map(|e| e.to_string())
+        };
+        let missing = restore_error(None).await;
+        let wrong = restore_error(Some("wrong")).await;
+        assert_eq!(
+            missing,
+            Some(BackupCryptoError::PasswordRequired.to_string())
+        );
+        assert_eq!(wrong, Some(BackupCryptoError::WrongPassword.to_string()));
+
+        let mut data = std::fs::read(&backup).map_err(|e| e.to_string())?;
+        if let Some(last) = data.last_mut() {
+            *last ^= 0x01;
+        }
+        std::fs::write(&backup, data).map_err(|e| e.to_string())?;
+        let tampered = restore_error(Some("secret")).await;
+        assert_eq!(tampered, Some(BackupCryptoError::Tampered.to_string()));
+
+        // 还原失败时不修改数据目录
+        assert!(!target.join("app_preferences.json").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_restore_rolls_back() -> TestResult {
+        let target = temp_dir("rollback-target")?;
+        let backup = temp_dir("rollback-out")?.join("backup.json");
+        let files = sample_files();
+        write_files(&target, &files)?;
+        // 目录交换后，目标位置是目录导致 DNS 配置无法替换
+        std::fs::create_dir_all(target.join("dns_config.json")).map_err(|e| e.to_string())?;
+
+        let backup_data = serde_json::json!({
+            "version": BACKUP_VERSION,
+            "timestamp": "2025-01-01T00:00:00Z",
+            "app_version": "1.0.0",
+            "platform": "linux",
+            "data": {
+                "app_preferences": {},
+                "clash_preferences": {},
+                "subscriptions": { "list": "[]", "configs": { "new": "bmV3" } },
+                "overrides": { "list": "[]", "files": { "new.js": "bmV3" } },
+                "dns_config": "e30=",
+                "pac_file": null
+            }
+        });
+        std::fs::write(&backup, backup_data.to_string()).map_err(|e| e.to_string())?;
+
+        let error = restore_backup(
+            &backup,
+            &target,
+            None,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert!(error.contains("替换 dns_config.json失败"), "{error}");
+        assert!(error.contains("已恢复还原前的数据"), "{error}");
+
+        for (name, content) in &files {
+            let restored = std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"))?;
+            assert_eq!(&restored, content, "{name}");
+        }
+        assert!(!target.join("subscriptions/new.yaml").exists());
+        assert!(!target.join("overrides/new.js").exists());
+        assert!(!target.join(".restore-snapshot").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_staging_leaves_data_untouched() -> TestResult {
+        let target = temp_dir("staging-target")?;
+        let backup = temp_dir("staging-out")?.join("backup.json");
+        let files = sample_files();
+        write_files(&target, &files)?;
+
+        // overrides/a 是文件，无法再写入 overrides/a/b.js
+        let backup_data = serde_json::json!({
+            "version": BACKUP_VERSION,
+            "timestamp": "2025-01-01T00:00:00Z",
+            "app_version": "1.0.0",
+            "platform": "linux",
+            "data": {
+                "app_preferences": {},
+                "clash_preferences": {},
+                "subscriptions": { "list": "[]", "configs": { "new": "bmV3" } },
+                "overrides": { "list": "[]", "files": { "a": "bmV3", "a/b.js": "bmV3" } },
+                "dns_config": null,
+                "pac_file": null
+            }
+        });
+        std::fs::write(&backup, backup_data.to_string()).map_err(|e| e.to_string())?;
+
+        let error = restore_backup(
+            &backup,
+            &target,
+            None,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert!(error.contains("还原 overrides/a/b.js失败"), "{error}");
+        assert!(error.contains("现有数据未被修改"), "{error}");
+
+        for (name, content) in &files {
+            let current = std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"))?;
+            assert_eq!(&current, content, "{name}");
+        }
+        for leftover in [
+            "subscriptions.staging",
+            "overrides.staging",
+            "app_preferences.json.staging",
+            ".restore-snapshot",
+        ] {
+            assert!(!target.join(leftover).exists(), "{leftover}");
+        }
+        Ok(())
+    }
+
+    // Windows：非 ASCII 目录名、结尾分隔符与超过 260 字符的路径（规范化为 \\?\ 前缀）
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_windows_long_and_unicode_paths() -> TestResult {
+        let mut base = temp_dir("windows-paths")?
+            .join("Users")
+            .join("名前")
+            .join("AppData");
+        while base.as_os_str().len() <= 260 {
+            base.push("a-long-directory-name");
+        }
+        std::fs::create_dir_all(normalize_path(&base.to_string_lossy()))
+            .map_err(|e| e.to_string())?;
+
+        let raw = base.to_string_lossy();
+        let source = normalize_path(&format!("{raw}\\source\\"));
+        let target = normalize_path(&format!("{raw}/target/"));
+        let backup = normalize_path(&format!("{raw}\\out\\backup.zip"));
+        assert!(source.to_string_lossy().starts_with(r"\\?\"));
+        assert!(source.ends_with("source"));
+        assert!(source.components().any(|c| c.as_os_str() == "名前"));
+
+        let files = sample_files();
+        write_files(&source, &files)?;
+        create_backup(
+            &backup,
+            &source,
+            "1.0.0",
+            BackupFormat::V2,
+            true,
+            None,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        restore_backup(
+            &backup,
+            &target,
+            None,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for (name, content) in &files {
+            let restored =
+                std::fs::read(join_name(&target, name)).map_err(|e| format!("{name}: {e}"))?;
+            assert_eq!(&restored, content, "{name}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extra_files_and_loopback_restored() -> TestResult {
+        let source = temp_dir("extras-source")?;
+        let target = temp_dir("extras-target")?;
+        let out = temp_dir("extras-out")?;
+        write_files(&source, &[("geo/custom.dat", b"new geo".to_vec())])?;
+        let extras = BackupExtras {
+            loopback_sids: Some(vec!["S-1-15-2-1".to_string()]),
+            allowlist: vec!["geo/custom.dat".to_string(), "missing.txt".to_string()],
+        };
+
+        // v2：附加文件保存在 extra/ 下
+        let v2 = out.join("backup.zip");
+        let manifest = BackupManifest {
+            version: BACKUP_VERSION_V2.to_string(),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            app_version: "1.0.0".to_string(),
+            platform: "windows".to_string(),
+        };
+        write_zip_backup(
+            BufWriter::new(File::create(&v2).map_err(|e| e.to_string())?),
+            &source,
+            &manifest,
+            &extras,
+            true,
+            &Progress::none(),
+        )
+        .map_err(|e| e.to_string())?;
+
+        // v1：附加文件与回环豁免保存在 data 中
+        let v1 = out.join("backup.json");
+        let mut json: serde_json::Value =
+            serde_json::from_str(FIXTURE_V1).map_err(|e| e.to_string())?;
+        json["data"]["loopback_sids"] = serde_json::json!(["S-1-15-2-1"]);
+        json["data"]["extra_files"] =
+            serde_json::json!({ "geo/custom.dat": general_purpose::STANDARD.encode("new geo") });
+        std::fs::write(&v1, json.to_string()).map_err(|e| e.to_string())?;
+
+        for backup in [&v2, &v1] {
+            write_files(&target, &[("geo/custom.dat", b"old geo".to_vec())])?;
+            let info = dry_run_restore(backup, None, &Progress::none())
+                .await
+                .map_err(|e| e.to_string())?;
+            assert_eq!(info.report.extra_files, ["geo/custom.dat"]);
+            assert!(
+                info.report.problems.is_empty(),
+                "{:?}",
+                info.report.problems
+            );
+
+            let outcome = restore_backup(
+                backup,
+                &target,
+                None,
+                RestoreMode::Replace,
+                &Progress::none(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            // 非 Windows 平台忽略回环豁免
+            #[cfg(not(target_os = "windows"))]
+            assert!(outcome.warnings.is_empty(), "{:?}", outcome.warnings);
+            #[cfg(target_os = "windows")]
+            let _ = outcome;
+
+            let restored =
+                std::fs::read(target.join("geo").join("custom.dat")).map_err(|e| e.to_string())?;
+            assert_eq!(restored, b"new geo");
+            assert!(!target.join("loopback_sids.json").exists());
+            assert!(!target.join("extra").exists());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_sections_keep_existing_data() -> TestResult {
+        let source = temp_dir("empty-source")?;
+        let target = temp_dir("empty-target")?;
+        let backup = temp_dir("empty-out")?.join("backup.zip");
+        let files = sample_files();
+        write_files(&target, &files)?;
+        // 新安装：只有配置文件，没有订阅与覆写
+        write_files(
+            &source,
+            &[("app_preferences.json", br#"{"theme":"light"}"#.to_vec())],
+        )?;
+
+        for format in [BackupFormat::V1, BackupFormat::V2] {
+            create_backup(
+                &backup,
+                &source,
+                "1.0.0",
+                format,
+                false,
+                None,
+                &Progress::none(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            restore_backup(
+                &backup,
+                &target,
+                None,
+                RestoreMode::Replace,
+                &Progress::none(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+            for (name, content) in &files {
+                if name.starts_with("subscriptions/") || name.starts_with("overrides/") {
+                    let current =
+                        std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"))?;
+                    assert_eq!(&current, content, "{format:?} {name}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_restore_keeps_local_entries() -> TestResult {
+        let source = temp_dir("merge-source")?;
+        let target = temp_dir("merge-target")?;
+        let backup = temp_dir("merge-out")?.join("backup.zip");
+        write_files(
+            &source,
+            &[
+                (
+                    "subscriptions/list.json",
+                    br#"[{"id":"a","url":"backup"},{"id":"b","url":"same"}]"#.to_vec(),
+                ),
+                ("subscriptions/a.yaml", b"backup a".to_vec()),
+                ("subscriptions/b.yaml", b"backup b".to_vec()),
+            ],
+        )?;
+        write_files(
+            &target,
+            &[
+                (
+                    "subscriptions/list.json",
+                    br#"[{"id":"a","url":"local"},{"id":"b","url":"same"},{"id":"c"}]"#.to_vec(),
+                ),
+                ("subscriptions/a.yaml", b"local a".to_vec()),
+                ("subscriptions/c.yaml", b"local c".to_vec()),
+                ("overrides/local.js", b"// local".to_vec()),
+            ],
+        )?;
+
+        create_backup(
+            &backup,
+            &source,
+            "1.0.0",
+            BackupFormat::V2,
+            true,
+            None,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let outcome = restore_backup(
+            &backup,
+            &target,
+            None,
+            RestoreMode::Merge,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        assert_eq!(outcome.conflicts, ["subscriptions/list.json 中的 a"]);
+
+        let read =
+            |name: &str| std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"));
+        assert_eq!(read("subscriptions/a.yaml")?, b"backup a");
+        assert_eq!(read("subscriptions/b.yaml")?, b"backup b");
+        assert_eq!(read("subscriptions/c.yaml")?, b"local c");
+        assert_eq!(read("overrides/local.js")?, b"// local");
+
+        let list: serde_json::Value =
+            serde_json::from_slice(&read("subscriptions/list.json")?).map_err(|e| e.to_string())?;
+        let entries: Vec<_> = list
+            .as_array()
+            .ok_or("list.json 不是数组")?
+            .iter()
+            .map(|entry| (entry["id"].as_str(), entry["url"].as_str()))
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (Some("a"), Some("backup")),
+                (Some("b"), Some("same")),
+                (Some("c"), None)
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_keeps_local_preferences_missing_from_backup() -> TestResult {
+        let source = temp_dir("prefs-source")?;
+        let target = temp_dir("prefs-target")?;
+        let backup = temp_dir("prefs-out")?.join("backup.zip");
+        let preferences = [
+            ("app_preferences.json", br#"{"theme":"dark"}"#.to_vec()),
+            ("clash_preferences.json", br#"{"mixed_port":7890}"#.to_vec()),
+        ];
+        // 新安装时的备份：没有配置文件
+        write_files(
+            &source,
+            &[("subscriptions/list.json", br#"[{"id":"a"}]"#.to_vec())],
+        )?;
+        create_backup(
+            &backup,
+            &source,
+            "1.0.0",
+            BackupFormat::V2,
+            false,
+            None,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for mode in [RestoreMode::Merge, RestoreMode::Replace] {
+            write_files(&target, &preferences)?;
+            restore_backup(&backup, &target, None, mode, &Progress::none())
+                .await
+                .map_err(|e| e.to_string())?;
+
+            for (name, content) in &preferences {
+                let current =
+                    std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"))?;
+                assert_eq!(&current, content, "{mode:?} {name}");
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_backup_detected_before_restore() -> TestResult {
+        let source = temp_dir("corrupt-source")?;
+        let target = temp_dir("corrupt-target")?;
+        let out = temp_dir("corrupt-out")?;
+        write_files(&source, &sample_files())?;
+        write_files(&target, &[("proxy.pac", b"keep".to_vec())])?;
+
+        for (format, name) in [(BackupFormat::V1, "v1"), (BackupFormat::V2, "v2")] {
+            let backup = out.join(name);
+            let backup_path = backup.as_path();
+            create_backup(
+                backup_path,
+                &source,
+                "1.0.0",
+                format,
+                false,
+                None,
+                &Progress::none(),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            let info = verify_backup(backup_path, None)
+                .await
+                .map_err(|e| e.to_string())?;
+            assert!(!info.checksum_missing, "{name}");
+
+            // 修改内容中的一个字节（v1 改动 Base64 字符，v2 改动压缩数据）
+            let mut data = std::fs::read(&backup).map_err(|e| e.to_string())?;
+            let position = match format {
+                BackupFormat::V1 => data
+                    .windows(6)
+                    .position(|w| w == b"\"list\"")
+                    .map(|p| p + 10),
+                BackupFormat::V2 => {
+                    let mut archive =
+                        ZipArchive::new(Cursor::new(data.as_slice())).map_err(|e| e.to_string())?;
+                    let entry = archive
+                        .by_name("overrides/rule set.js")
+                        .map_err(|e| e.to_string())?;
+                    Some(entry.data_start() as usize)
+                }
+            }
+            .ok_or("找不到修改位置")?;
+            data[position] ^= 0x01;
+            std::fs::write(&backup, data).map_err(|e| e.to_string())?;
+
+            let error = restore_backup(
+                backup_path,
+                &target,
+                None,
+                RestoreMode::Replace,
+                &Progress::none(),
+            )
+            .await
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+            assert!(error.contains("已损坏"), "{name}: {error}");
+            assert_eq!(
+                std::fs::read(target.join("proxy.pac")).map_err(|e| e.to_string())?,
+                b"keep"
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backup_without_checksum_accepted() -> TestResult {
+        let backup = temp_dir("legacy-out")?.join("backup.json");
+        let backup_data = serde_json::json!({
+            "version": BACKUP_VERSION,
+            "timestamp": "2025-01-01T00:00:00Z",
+            "app_version": "1.0.0",
+            "platform": "windows",
+            "data": {
+                "app_preferences": {},
+                "clash_preferences": {},
+                "subscriptions": { "list": null, "configs": {} },
+                "overrides": { "list": null, "files": {} },
+                "dns_config": null,
+                "pac_file": null
+            }
+        });
+        std::fs::write(&backup, backup_data.to_string()).map_err(|e| e.to_string())?;
+
+        let info = verify_backup(&backup, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert!(info.checksum_missing);
+        assert_eq!(info.platform, "windows");
+        Ok(())
+    }
+
+    // 1.0.0 版本创建的备份（没有校验和）
+    const FIXTURE_V1: &str = include_str!("backup/fixtures/backup-1.0.0.json");
+
+    #[tokio::test]
+    async fn test_restore_1_0_0_fixture() -> TestResult {
+        let out = temp_dir("fixture-out")?;
+        let first = temp_dir("fixture-first")?;
+        let second = temp_dir("fixture-second")?;
+        let fixture = out.join("backup-1.0.0.json");
+        std::fs::write(&fixture, FIXTURE_V1).map_err(|e| e.to_string())?;
+
+        let info = verify_backup(&fixture, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        assert_eq!(info.version, "1.0.0");
+        assert!(info.checksum_missing);
+
+        restore_backup(
+            &fixture,
+            &first,
+            None,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let read = |dir: &Path, name: &str| {
+            std::fs::read_to_string(dir.join(name)).map_err(|e| format!("{name}: {e}"))
+        };
+        assert!(read(&first, "subscriptions/sub-1.yaml")?.contains("香港 01"));
+        assert!(read(&first, "subscriptions/list.json")?.contains("机场 A"));
+        assert!(read(&first, "overrides/ovr-1.js")?.starts_with("function main"));
+        assert!(read(&first, "dns_config.json")?.contains("223.5.5.5"));
+        assert!(read(&first, "proxy.pac")?.contains("PROXY 127.0.0.1:7890"));
+        let prefs: serde_json::Value =
+            serde_json::from_str(&read(&first, "clash_preferences.json")?)
+                .map_err(|e| e.to_string())?;
+        assert_eq!(prefs["flutter.mixed_port"], 7890);
+
+        // 迁移后的数据以当前格式重新备份并还原，内容保持一致
+        let current = out.join("backup-current");
+        create_backup(
+            &current,
+            &first,
+            "1.0.0",
+            BackupFormat::default(),
+            true,
+            None,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        restore_backup(
+            &current,
+            &second,
+            None,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let files = collect_backup_files(&first).map_err(|e| e.to_string())?;
+        assert_eq!(files.len(), 8);
+        for (name, _) in &files {
+            assert_eq!(read(&first, name)?, read(&second, name)?, "{name}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_newer_major_version_rejected() -> TestResult {
+        let backup = temp_dir("future-out")?.join("backup.json");
+        let future = FIXTURE_V1.replacen("\"1.0.0\"", "\"3.0.0\"", 1);
+        std::fs::write(&backup, future).map_err(|e| e.to_string())?;
+
+        let error = verify_backup(&backup, None)
+            .await
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+        assert!(error.contains("3.0.0"), "{error}");
+        Ok(())
+    }
+
+    // 构造包含 overrides/../../evil.js 的备份，还原时会写到数据目录之外
+    #[tokio::test]
+    async fn test_path_traversal_backup_rejected() -> TestResult {
+        let source = temp_dir("traversal-source")?;
+        let root = temp_dir("traversal-target")?;
+        let target = root.join("data");
+        let out = temp_dir("traversal-out")?;
+        let files = sample_files();
+        write_files(&source, &files)?;
+        write_files(&target, &files)?;
+
+        let v2 = out.join("backup.zip");
+        create_backup(
+            &v2,
+            &source,
+            "1.0.0",
+            BackupFormat::V2,
+            true,
+            None,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let mut archive = ZipArchive::new(File::open(&v2).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        let malicious_v2 = out.join("malicious.zip");
+        let mut zip = ZipWriter::new(File::create(&malicious_v2).map_err(|e| e.to_string())?);
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index).map_err(|e| e.to_string())?;
+            zip.raw_copy_file(entry).map_err(|e| e.to_string())?;
+        }
+        zip.start_file("overrides/../../evil.js", SimpleFileOptions::default())
+            .map_err(|e| e.to_string())?;
+        zip.write_all(b"evil").map_err(|e| e.to_string())?;
+        zip.finish().map_err(|e| e.to_string())?;
+
+        let mut json: serde_json::Value =
+            serde_json::from_str(FIXTURE_V1).map_err(|e| e.to_string())?;
+        json["data"]["overrides"]["files"]["../../evil.js"] =
+            serde_json::Value::String(general_purpose::STANDARD.encode("evil"));
+        let malicious_v1 = out.join("malicious.json");
+        std::fs::write(&malicious_v1, json.to_string()).map_err(|e| e.to_string())?;
+
+        for backup in [&malicious_v2, &malicious_v1] {
+            let error = restore_backup(
+                backup,
+                &target,
+                None,
+                RestoreMode::Replace,
+                &Progress::none(),
+            )
+            .await
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+            assert!(error.contains("非法的文件路径"), "{error}");
+        }
+
+        assert!(!root.join("evil.js").exists());
+        for (name, content) in &files {
+            let current = std::fs::read(target.join(name)).map_err(|e| format!("{name}: {e}"))?;
+            assert_eq!(&current, content, "{name}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_reports_problems_restore_refuses() -> TestResult {
+        let source = temp_dir("dry-run-source")?;
+        let target = temp_dir("dry-run-target")?;
+        let backup = temp_dir("dry-run-out")?.join("backup.zip");
+        write_files(&source, &sample_files())?;
+        write_files(&source, &[("overrides/list.json", b"not json".to_vec())])?;
+        create_backup(
+            &backup,
+            &source,
+            "1.0.0",
+            BackupFormat::V2,
+            true,
+            None,
+            &Progress::none(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let info = dry_run_restore(&backup, None, &Progress::none())
+            .await
+            .map_err(|e| e.to_string())?;
+        let report = &info.report;
+        assert_eq!(report.preferences.file_count, 2);
+        assert_eq!(report.subscriptions.file_count, 3);
+        assert_eq!(report.overrides.file_count, 3);
+        assert_eq!(report.other.file_count, 1);
+        assert_eq!(report.problems.len(), 1, "{:?}", report.problems);
+        assert!(report.problems[0].contains("overrides/list.json"));
+
+        let error = restore_backup(
+            &backup,
+            &target,
+            None,
+            RestoreMode::Replace,
+            &Progress::none(),
+        )
+        .await
+        .err()
+        .map(|e| e.to_string())
+        .unwrap_or_default();
+        assert_eq!(error, report.problems[0]);
+        let written = std::fs::read_dir(&target)
+            .map_err(|e| e.to_string())?
+            .count();
+        assert_eq!(written, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_target_rejects_escaping_names() {
+        let root = Path::new("/data");
+        assert_eq!(
+            restore_target(root, "overrides/a b.js"),
+            Some(root.join("overrides").join("a b.js"))
+        );
+        assert_eq!(
+            restore_target(root, "subscriptions/providers/rules/foo.yaml"),
+            Some(root.join("subscriptions/providers/rules/foo.yaml"))
+        );
+        assert_eq!(restore_target(root, "overrides/../secret"), None);
+        assert_eq!(restore_target(root, "overrides/rules/../../secret"), None);
+        assert_eq!(restore_target(root, "overrides//etc/passwd"), None);
+        assert_eq!(restore_target(root, "overrides/./a.js"), None);
+        assert_eq!(restore_target(root, "overrides/C:/a.js"), None);
+        assert_eq!(restore_target(root, "overrides/..\\secret"), None);
+        assert_eq!(restore_target(root, "/etc/passwd"), None);
+        assert_eq!(restore_target(root, "subscriptions/a.txt"), None);
+        assert_eq!(restore_target(root, "../app_preferences.json"), None);
+    }
+}
@@ -0,0 +1,117 @@
+// 备份压缩
+//
+// v1 JSON 备份以 Base64 内嵌 YAML，体积大且重复度高。压缩后的文件格式：
+// 4 字节魔数 "STBZ" + 8 字节小端原始大小 + zstd 数据。
+// 原始大小用于预分配缓冲区，并在解压前拒绝超过上限的数据（防止解压炸弹）；
+// 解压时最多读取声明的大小，实际大小不一致时视为损坏。
+// 加密备份先压缩再加密，还原时先解密再解压
+
+use super::corrupted;
+use std::io::Read;
+
+// 压缩备份的文件头
+pub const COMPRESSED_MAGIC: &[u8; 4] = b"STBZ";
+
+const HEADER_LEN: usize = COMPRESSED_MAGIC.len() + 8;
+
+// 解压后大小的上限
+const MAX_UNCOMPRESSED_SIZE: u64 = 512 * 1024 * 1024;
+
+// 数据是否为压缩备份
+pub fn is_compressed(head: &[u8]) -> bool {
+    head.starts_with(COMPRESSED_MAGIC)
+}
+
+pub fn compress(plain: &[u8]) -> std::io::Result<Vec<u8>> {
+    let encoded = zstd::encode_all(plain, zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    let mut output = Vec::with_capacity(HEADER_LEN + encoded.len());
+    output.extend_from_slice(COMPRESSED_MAGIC);
+    output.extend_from_slice(&(plain.len() as u64).to_le_bytes());
+    output.extend_from_slice(&encoded);
+    Ok(output)
+}
+
+// 解压压缩备份，返回原始备份内容
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let rest = data
+        .strip_prefix(COMPRESSED_MAGIC.as_slice())
+        .ok_or_else(|| corrupted("缺少压缩文件头"))?;
+    let (size_bytes, encoded) = rest
+        .split_first_chunk::<8>()
+        .ok_or_else(|| corrupted("压缩文件头过短"))?;
+    let size = checked_size(*size_bytes)?;
+
+    let mut plain = Vec::with_capacity(size as usize);
+    // 多读取 1 字节，用于发现比声明更长的数据
+    zstd::Decoder::new(encoded)?
+        .take(size + 1)
+        .read_to_end(&mut plain)
+        .map_err(|e| corrupted(format!("解压失败 - {}", e)))?;
+    if plain.len() as u64 != size {
+        return Err(corrupted(format!(
+            "解压后大小不一致（声明 {} 字节，实际至少 {} 字节）",
+            size,
+            plain.len()
+        )));
+    }
+    Ok(plain)
+}
+
+// 流式解压，只需要读取开头部分时使用（最多读取声明的大小）
+pub fn decoder(
+    mut reader: impl Read,
+) -> Result<impl Read, Box<dyn std::error::Error + Send + Sync>> {
+    let mut header = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| corrupted("压缩文件头过短"))?;
+    let (magic, size_bytes) = header.split_at(COMPRESSED_MAGIC.len());
+    if magic != COMPRESSED_MAGIC {
+        return Err(corrupted("缺少压缩文件头"));
+    }
+    let size_bytes: [u8; 8] = size_bytes.try_into()?;
+    let size = checked_size(size_bytes)?;
+    Ok(zstd::Decoder::new(reader)?.take(size))
+}
+
+// 读取声明的原始大小，超过上限时返回错误
+fn checked_size(size_bytes: [u8; 8]) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    let size = u64::from_le_bytes(size_bytes);
+    if size > MAX_UNCOMPRESSED_SIZE {
+        return Err(format!(
+            "备份解压后过大（{} 字节，上限 {} 字节）",
+            size, MAX_UNCOMPRESSED_SIZE
+        )
+        .into());
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_mismatch_and_bomb_rejected() -> Result<(), String> {
+        let plain = b"subscriptions: aGVsbG8=".repeat(1000);
+        let compressed = compress(&plain).map_err(|e| e.to_string())?;
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < plain.len() / 10);
+        assert_eq!(decompress(&compressed).map_err(|e| e.to_string())?, plain);
+
+        // 声明的大小小于实际大小
+        let mut understated = compressed.clone();
+        understated[4..12].copy_from_slice(&10u64.to_le_bytes());
+        assert!(decompress(&understated).is_err());
+
+        // 声明的大小超过上限，不分配缓冲区
+        let mut bomb = compressed;
+        bomb[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+        let error = decompress(&bomb)
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+        assert!(error.contains("过大"), "{error}");
+        Ok(())
+    }
+}
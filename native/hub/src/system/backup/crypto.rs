@@ -0,0 +1,286 @@
+// 备份加密
+//
+// 加密备份的文件格式：4 字节魔数 "STBE" + 2 字节小端头部长度 + JSON 头部（明文）+ 密文
+//
+// 头部记录 KDF 与加密参数，以后调整参数时旧备份仍可解密；头部同时作为附加认证数据，
+// 被修改时解密失败。Argon2id 派生 64 字节：前 32 字节为 AES-256-GCM 密钥，
+// 其余部分的前 16 字节作为密码校验值，用于区分密码错误与数据损坏
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// 加密备份的文件头
+pub const ENCRYPTED_MAGIC: &[u8; 4] = b"STBE";
+
+const HEADER_VERSION: u32 = 1;
+const KDF_ARGON2ID: &str = "argon2id";
+const CIPHER_AES_256_GCM: &str = "aes-256-gcm";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const KEY_CHECK_LEN: usize = 16;
+
+// 解密时接受的参数上限，防止构造的头部耗尽内存或 CPU
+const MAX_M_COST_KIB: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 16;
+
+// 备份加密错误
+#[derive(Debug, thiserror::Error)]
+pub enum BackupCryptoError {
+    #[error("备份文件已加密，请输入密码")]
+    PasswordRequired,
+    #[error("备份密码错误")]
+    WrongPassword,
+    #[error("备份文件已损坏或被篡改")]
+    Tampered,
+    #[error("加密备份的文件头无效：{0}")]
+    InvalidHeader(String),
+    #[error("不支持的备份加密参数：{0}")]
+    Unsupported(String),
+    #[error("加密备份失败：{0}")]
+    Encrypt(String),
+}
+
+// Argon2id 参数
+#[derive(Debug, Clone, Copy)]
+pub struct KdfParams {
+    // 内存开销（KiB）
+    pub m_cost: u32,
+    // 迭代次数
+    pub t_cost: u32,
+    // 并行度
+    pub p_cost: u32,
+}
+
+// OWASP 推荐的 Argon2id 最低参数
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+// 明文头部
+#[derive(Serialize, Deserialize)]
+struct EncryptionHeader {
+    version: u32,
+    kdf: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    cipher: String,
+    nonce: String,
+    key_check: String,
+}
+
+// 数据是否为加密备份
+pub fn is_encrypted(head: &[u8]) -> bool {
+    head.starts_with(ENCRYPTED_MAGIC)
+}
+
+// 使用默认参数加密
+pub fn encrypt(plain: &[u8], password: &str) -> Result<Vec<u8>, BackupCryptoError> {
+    encrypt_with(plain, password, KdfParams::default())
+}
+
+pub fn encrypt_with(
+    plain: &[u8],
+    password: &str,
+    params: KdfParams,
+) -> Result<Vec<u8>, BackupCryptoError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rng().fill(&mut salt);
+    rand::rng().fill(&mut nonce);
+
+    let derived = derive(password, &salt, params)?;
+    let (key, key_check) = split_derived(&derived);
+
+    let header = EncryptionHeader {
+        version: HEADER_VERSION,
+        kdf: KDF_ARGON2ID.to_string(),
+        m_cost: params.m_cost,
+        t_cost: params.t_cost,
+        p_cost: params.p_cost,
+        salt: BASE64.encode(salt),
+        cipher: CIPHER_AES_256_GCM.to_string(),
+        nonce: BASE64.encode(nonce),
+        key_check: BASE64.encode(key_check),
+    };
+    let header =
+        serde_json::to_vec(&header).map_err(|e| BackupCryptoError::Encrypt(e.to_string()))?;
+    let header_len = u16::try_from(header.len())
+        .map_err(|_| BackupCryptoError::Encrypt("文件头过长".to_string()))?;
+
+    let cipher =
+        Aes256Gcm::new_from_slice(key).map_err(|e| BackupCryptoError::Encrypt(e.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plain,
+                aad: &header,
+            },
+        )
+        .map_err(|e| BackupCryptoError::Encrypt(e.to_string()))?;
+
+    let mut output = Vec::with_capacity(6 + header.len() + ciphertext.len());
+    output.extend_from_slice(ENCRYPTED_MAGIC);
+    output.extend_from_slice(&header_len.to_le_bytes());
+    output.extend_from_slice(&header);
+    output.extend_from_slice(&ciphertext);
+    Ok(output)
+}
+
+// 解密加密备份，返回原始备份内容
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, BackupCryptoError> {
+    let rest = data
+        .strip_prefix(ENCRYPTED_MAGIC.as_slice())
+        .ok_or_else(|| BackupCryptoError::InvalidHeader("缺少文件头".to_string()))?;
+    let (len_bytes, rest) = rest
+        .split_first_chunk::<2>()
+        .ok_or_else(|| BackupCryptoError::InvalidHeader("文件过短".to_string()))?;
+    let header_len = u16::from_le_bytes(*len_bytes) as usize;
+    if rest.len() < header_len {
+        return Err(BackupCryptoError::InvalidHeader("文件过短".to_string()));
+    }
+    let (header_bytes, ciphertext) = rest.split_at(header_len);
+
+    let header: EncryptionHeader = serde_json::from_slice(header_bytes)
+        .map_err(|e| BackupCryptoError::InvalidHeader(e.to_string()))?;
+    if header.version != HEADER_VERSION {
+        return Err(BackupCryptoError::Unsupported(format!(
+            "文件头版本 {}",
+            header.version
+        )));
+    }
+    if header.kdf != KDF_ARGON2ID {
+        return Err(BackupCryptoError::Unsupported(header.kdf));
+    }
+    if header.cipher != CIPHER_AES_256_GCM {
+        return Err(BackupCryptoError::Unsupported(header.cipher));
+    }
+    if header.m_cost > MAX_M_COST_KIB || header.t_cost > MAX_T_COST || header.p_cost > MAX_P_COST {
+        return Err(BackupCryptoError::Unsupported(format!(
+            "m={}, t={}, p={}",
+            header.m_cost, header.t_cost, header.p_cost
+        )));
+    }
+
+    let salt = decode_field(&header.salt, "salt")?;
+    let nonce = decode_field(&header.nonce, "nonce")?;
+    let expected_check = decode_field(&header.key_check, "key_check")?;
+    if nonce.len() != NONCE_LEN {
+        return Err(BackupCryptoError::InvalidHeader(
+            "nonce 长度错误".to_string(),
+        ));
+    }
+
+    let params = KdfParams {
+        m_cost: header.m_cost,
+        t_cost: header.t_cost,
+        p_cost: header.p_cost,
+    };
+    let derived = derive(password, &salt, params)?;
+    let (key, key_check) = split_derived(&derived);
+    if key_check != expected_check.as_slice() {
+        return Err(BackupCryptoError::WrongPassword);
+    }
+
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| BackupCryptoError::Tampered)?;
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header_bytes,
+            },
+        )
+        .map_err(|_| BackupCryptoError::Tampered)
+}
+
+fn derive(
+    password: &str,
+    salt: &[u8],
+    params: KdfParams,
+) -> Result<[u8; KEY_LEN * 2], BackupCryptoError> {
+    let params = Params::new(
+        params.m_cost,
+        params.t_cost,
+        params.p_cost,
+        Some(KEY_LEN * 2),
+    )
+    .map_err(|e| BackupCryptoError::Unsupported(e.to_string()))?;
+
+    let mut output = [0u8; KEY_LEN * 2];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut output)
+        .map_err(|e| BackupCryptoError::Unsupported(e.to_string()))?;
+    Ok(output)
+}
+
+fn split_derived(derived: &[u8; KEY_LEN * 2]) -> (&[u8], &[u8]) {
+    (
+        &derived[..KEY_LEN],
+        &derived[KEY_LEN..KEY_LEN + KEY_CHECK_LEN],
+    )
+}
+
+fn decode_field(value: &str, name: &str) -> Result<Vec<u8>, BackupCryptoError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| BackupCryptoError::InvalidHeader(format!("{}：{}", name, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试使用较低的参数，避免调试构建中耗时过长
+    const TEST_PARAMS: KdfParams = KdfParams {
+        m_cost: 256,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn test_wrong_password_and_tampering() -> Result<(), String> {
+        let plain = b"subscription: https://example.com/?token=secret";
+        let encrypted =
+            encrypt_with(plain, "correct horse", TEST_PARAMS).map_err(|e| e.to_string())?;
+        assert!(is_encrypted(&encrypted));
+
+        let decrypted = decrypt(&encrypted, "correct horse").map_err(|e| e.to_string())?;
+        assert_eq!(decrypted, plain);
+
+        assert!(matches!(
+            decrypt(&encrypted, "wrong"),
+            Err(BackupCryptoError::WrongPassword)
+        ));
+
+        let mut tampered = encrypted.clone();
+        if let Some(last) = tampered.last_mut() {
+            *last ^= 0x01;
+        }
+        assert!(matches!(
+            decrypt(&tampered, "correct horse"),
+            Err(BackupCryptoError::Tampered)
+        ));
+
+        assert!(matches!(
+            decrypt(&encrypted[..8], "correct horse"),
+            Err(BackupCryptoError::InvalidHeader(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+// 附加备份内容
+//
+// - 回环豁免：Windows 上已启用回环豁免的 UWP 应用 SID，保存为 loopback_sids.json。
+//   还原时使用与 SaveLoopbackConfiguration 相同的逻辑应用，其他平台上忽略
+// - 附加文件：设置项 backup_extra_files 列出的数据目录文件（如自定义 hosts、geo 配置），
+//   归档内名称为 extra/<相对路径>，还原到数据目录中的原位置。
+//   名称不能与其余备份内容、Hub 设置以及还原使用的快照和暂存目录重叠
+
+use super::{PREFERENCE_FILES, ROOT_FILES, is_safe_name, join_name};
+use crate::settings::{self, keys};
+use std::path::{Path, PathBuf};
+
+// 回环豁免在备份中的名称
+pub const LOOPBACK_FILE: &str = "loopback_sids.json";
+
+// 附加文件在归档内的目录
+pub const EXTRA_DIR: &str = "extra";
+
+// 不能作为附加文件的顶层名称
+const RESERVED_NAMES: [&str; 4] = ["subscriptions", "overrides", EXTRA_DIR, "hub_settings.json"];
+
+// 创建备份时收集的附加内容
+pub struct BackupExtras {
+    pub loopback_sids: Option<Vec<String>>,
+    pub allowlist: Vec<String>,
+}
+
+impl BackupExtras {
+    // 读取回环豁免与附加文件列表（会调用系统 API，需在阻塞线程中执行）
+    pub fn capture() -> Self {
+        Self {
+            loopback_sids: capture_loopback_sids(),
+            allowlist: extra_allowlist(),
+        }
+    }
+}
+
+// 是否可以作为附加文件（数据目录中以 / 分隔的相对路径）
+pub fn is_extra_name(name: &str) -> bool {
+    if !is_safe_name(name) || PREFERENCE_FILES.contains(&name) || ROOT_FILES.contains(&name) {
+        return false;
+    }
+    let top = name.split('/').next().unwrap_or_default();
+    // 快照目录以 . 开头，暂存与交换目录以 .staging、.old 结尾
+    !RESERVED_NAMES.contains(&top)
+        && !name.split('/').any(|component| component.starts_with('.'))
+        && !top.ends_with(".staging")
+        && !top.ends_with(".old")
+}
+
+// 设置中的附加文件列表，忽略无效的名称
+fn extra_allowlist() -> Vec<String> {
+    settings::get_string_list(keys::BACKUP_EXTRA_FILES)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| {
+            let valid = is_extra_name(name);
+            if !valid {
+                log::warn!("忽略无效的附加备份文件：{}", name);
+            }
+            valid
+        })
+        .collect()
+}
+
+// 收集存在的附加文件：(数据目录中的相对路径, 文件路径)
+pub fn collect_extra_files(app_data_path: &Path, allowlist: &[String]) -> Vec<(String, PathBuf)> {
+    allowlist
+        .iter()
+        .filter(|name| is_extra_name(name))
+        .filter_map(|name| {
+            let path = join_name(app_data_path, name);
+            path.is_file().then(|| (name.clone(), path))
+        })
+        .collect()
+}
+
+// 已启用回环豁免的应用 SID，不支持的平台返回 None
+fn capture_loopback_sids() -> Option<Vec<String>> {
+    #[cfg(target_os = "windows")]
+    {
+        match crate::system::loopback::enumerate_app_containers() {
+            Ok(containers) => Some(
+                containers
+                    .into_iter()
+                    .filter(|container| container.is_loopback_enabled)
+                    .map(|container| container.sid_string)
+                    .collect(),
+            ),
+            Err(e) => {
+                log::warn!("读取回环豁免失败，备份中不包含回环豁免：{}", e);
+                None
+            }
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    None
+}
+
+// 应用备份中的回环豁免，失败时返回无法设置的应用
+pub fn apply_loopback_sids(sids: &[String]) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let summary = crate::system::loopback::save_loopback_configuration(sids)?;
+        if summary.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(summary.errors.join("；"))
+        }
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        log::debug!("当前平台不支持回环豁免，跳过 {} 个条目", sids.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_names_cannot_overlap_backup_content() -> Result<(), String> {
+        for name in ["hosts.yaml", "geo/custom.dat", "loopback_sids.json"] {
+            assert!(is_extra_name(name), "{name}");
+        }
+        for name in [
+            "app_preferences.json",
+            "dns_config.json",
+            "hub_settings.json",
+            "subscriptions/a.yaml",
+            "extra/a",
+            ".restore-snapshot/a",
+            "geo/.hidden",
+            "overrides.staging/a.js",
+            "../hosts",
+            "C:/hosts",
+        ] {
+            assert!(!is_extra_name(name), "{name}");
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("stelliberty-backup-extras-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("geo")).map_err(|e| e.to_string())?;
+        std::fs::write(dir.join("geo/custom.dat"), "geo").map_err(|e| e.to_string())?;
+        std::fs::write(dir.join("hub_settings.json"), "{}").map_err(|e| e.to_string())?;
+
+        let allowlist = ["geo/custom.dat", "missing.txt", "hub_settings.json"].map(String::from);
+        let names: Vec<_> = collect_extra_files(&dir, &allowlist)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["geo/custom.dat"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+// 备份概要
+//
+// 还原前在界面上展示备份的元数据与内容统计。只读取元数据并统计条目：
+// v2 读取 ZIP 中央目录中的大小，v1 借用解析 JSON（不复制、不解码 Base64 内容），
+// 按 Base64 长度计算解码后的大小。压缩的 v1 备份需要先解压
+
+use super::{BackupFormat, is_zip_backup, read_head, read_manifest};
+use super::{compress, crypto};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::Path;
+use tokio::fs as async_fs;
+use zip::ZipArchive;
+
+// 备份概要
+#[derive(Debug, Default)]
+pub struct BackupSummary {
+    pub encrypted: bool,
+    pub compressed: bool,
+    // 加密备份在解密前无法得知以下内容
+    pub format: Option<BackupFormat>,
+    pub version: Option<String>,
+    pub timestamp: Option<String>,
+    pub app_version: Option<String>,
+    pub platform: Option<String>,
+    // 订阅配置与覆写文件的数量（不含 list.json）和总大小（字节）
+    pub subscription_count: u32,
+    pub subscription_size: u64,
+    pub override_count: u32,
+    pub override_size: u64,
+}
+
+// v1 备份中需要统计的部分，其余字段忽略
+#[derive(Deserialize)]
+struct JsonSummary<'a> {
+    version: String,
+    timestamp: String,
+    app_version: String,
+    platform: String,
+    #[serde(borrow)]
+    data: JsonContentSummary<'a>,
+}
+
+#[derive(Deserialize)]
+struct JsonContentSummary<'a> {
+    #[serde(borrow)]
+    subscriptions: JsonEntries<'a>,
+    #[serde(borrow)]
+    overrides: JsonEntries<'a>,
+}
+
+// 订阅的 configs 与覆写的 files：文件名 -> Base64 内容
+#[derive(Deserialize)]
+struct JsonEntries<'a> {
+    #[serde(borrow, alias = "files")]
+    configs: HashMap<Cow<'a, str>, Cow<'a, str>>,
+}
+
+impl JsonEntries<'_> {
+    fn count_and_size(&self) -> (u32, u64) {
+        let size = self.configs.values().map(|v| decoded_len(v)).sum();
+        (self.configs.len() as u32, size)
+    }
+}
+
+// 读取备份概要
+pub async fn inspect_backup(
+    backup_path: &Path,
+) -> Result<BackupSummary, Box<dyn std::error::Error + Send + Sync>> {
+    let head = read_head(backup_path).await?;
+
+    if crypto::is_encrypted(&head) {
+        return Ok(BackupSummary {
+            encrypted: true,
+            ..Default::default()
+        });
+    }
+
+    if compress::is_compressed(&head) {
+        let data = async_fs::read(backup_path).await?;
+        return tokio::task::spawn_blocking(move || {
+            let payload = compress::decompress(&data)?;
+            let summary = if is_zip_backup(&payload)? {
+                inspect_zip(Cursor::new(payload))?
+            } else {
+                inspect_json(&payload)?
+            };
+            Ok(BackupSummary {
+                compressed: true,
+                ..summary
+            })
+        })
+        .await?;
+    }
+
+    if is_zip_backup(&head)? {
+        let path = backup_path.to_path_buf();
+        return tokio::task::spawn_blocking(move || inspect_zip(BufReader::new(File::open(path)?)))
+            .await?;
+    }
+
+    let content = async_fs::read(backup_path).await?;
+    tokio::task::spawn_blocking(move || inspect_json(&content)).await?
+}
+
+fn inspect_zip(
+    reader: impl Read + Seek,
+) -> Result<BackupSummary, Box<dyn std::error::Error + Send + Sync>> {
+    let mut archive = ZipArchive::new(reader)?;
+    let manifest = read_manifest(&mut archive)?;
+
+    let mut summary = BackupSummary {
+        format: Some(BackupFormat::V2),
+        version: Some(manifest.version),
+        timestamp: Some(manifest.timestamp),
+        app_version: Some(manifest.app_version),
+        platform: Some(manifest.platform),
+        ..Default::default()
+    };
+
+    for index in 0..archive.len() {
+        // 不解压，只读取中央目录中的信息
+        let entry = archive.by_index_raw(index)?;
+        let Some((dir, file_name)) = entry.name().split_once('/') else {
+            continue;
+        };
+        if file_name == "list.json" || entry.is_dir() {
+            continue;
+        }
+        match dir {
+            "subscriptions" => {
+                summary.subscription_count += 1;
+                summary.subscription_size += entry.size();
+            }
+            "overrides" => {
+                summary.override_count += 1;
+                summary.override_size += entry.size();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(summary)
+}
+
+fn inspect_json(content: &[u8]) -> Result<BackupSummary, Box<dyn std::error::Error + Send + Sync>> {
+    let backup: JsonSummary = serde_json::from_slice(content)?;
+    let (subscription_count, subscription_size) = backup.data.subscriptions.count_and_size();
+    let (override_count, override_size) = backup.data.overrides.count_and_size();
+
+    Ok(BackupSummary {
+        encrypted: false,
+        compressed: false,
+        format: Some(BackupFormat::V1),
+        version: Some(backup.version),
+        timestamp: Some(backup.timestamp),
+        app_version: Some(backup.app_version),
+        platform: Some(backup.platform),
+        subscription_count,
+        subscription_size,
+        override_count,
+        override_size,
+    })
+}
+
+// Base64 解码后的长度
+fn decoded_len(encoded: &str) -> u64 {
+    let encoded = encoded.trim_end();
+    let padding = encoded.bytes().rev().take_while(|&b| b == b'=').count();
+    ((encoded.len() / 4 * 3) as u64).saturating_sub(padding as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_summary_counts_without_decoding() -> Result<(), String> {
+        let backup = serde_json::json!({
+            "version": "1.0.0",
+            "timestamp": "2025-01-01T00:00:00Z",
+            "app_version": "1.2.0",
+            "platform": "macos",
+            "data": {
+                "app_preferences": {},
+                "clash_preferences": {},
+                "subscriptions": { "list": "[]", "configs": { "a": "aGVsbG8=", "b": "aGk=" } },
+                "overrides": { "list": null, "files": { "c.js": "YWJj" } },
+                "dns_config": null,
+                "pac_file": null
+            }
+        });
+        let summary = inspect_json(backup.to_string().as_bytes()).map_err(|e| e.to_string())?;
+
+        assert_eq!(summary.format, Some(BackupFormat::V1));
+        assert_eq!(summary.platform.as_deref(), Some("macos"));
+        assert_eq!(summary.subscription_count, 2);
+        assert_eq!(summary.subscription_size, 5 + 2);
+        assert_eq!(summary.override_count, 1);
+        assert_eq!(summary.override_size, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+// 备份列表
+//
+// 还原界面列出目录中已有的备份。按文件头识别备份（加密、压缩、ZIP、JSON），
+// 每个文件只读取元数据：v2 只读取 manifest.json，v1 流式解析 JSON 并跳过 data 的内容，
+// 压缩的 v1 边解压边解析。加密备份在解密前只能得知已加密。
+// 看起来是备份但无法读取的文件带错误信息返回，不会被忽略
+
+use super::{BackupFormat, ZIP_MAGIC, compress, crypto, read_manifest};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+// 列表中的一个备份
+#[derive(Debug, Default)]
+pub struct BackupListEntry {
+    pub path: String,
+    pub file_name: String,
+    pub size: u64,
+    // 文件修改时间（RFC 3339），加密备份没有 timestamp 时用于显示与排序
+    pub modified: Option<String>,
+    pub encrypted: bool,
+    pub compressed: bool,
+    pub format: Option<BackupFormat>,
+    pub version: Option<String>,
+    pub timestamp: Option<String>,
+    pub app_version: Option<String>,
+    pub platform: Option<String>,
+    // 读取失败的原因
+    pub error: Option<String>,
+}
+
+impl BackupListEntry {
+    // 排序用的时间：优先使用备份时间，没有时使用文件修改时间
+    fn sort_time(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        self.timestamp
+            .as_deref()
+            .or(self.modified.as_deref())
+            .and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok())
+    }
+}
+
+// v1 备份的元数据，data 等其余字段跳过
+#[derive(Deserialize)]
+struct JsonMetadata {
+    version: String,
+    timestamp: String,
+    app_version: String,
+    platform: String,
+}
+
+// 列出目录中的备份，按时间从新到旧排序
+pub async fn list_backups(
+    directory: &Path,
+) -> Result<Vec<BackupListEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    let directory = directory.to_path_buf();
+    tokio::task::spawn_blocking(move || scan_directory(&directory)).await?
+}
+
+fn scan_directory(
+    dir: &Path,
+) -> Result<Vec<BackupListEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        // 跟随符号链接，只列出文件
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let mut backup = BackupListEntry {
+            path: path.to_string_lossy().into_owned(),
+            file_name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()),
+            ..Default::default()
+        };
+        match read_metadata(&path, &mut backup) {
+            Ok(true) => backups.push(backup),
+            // 不是备份文件
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!("读取备份元数据失败：{} - {}", backup.path, e);
+                backup.error = Some(e.to_string());
+                backups.push(backup);
+            }
+        }
+    }
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.sort_time()));
+    Ok(backups)
+}
+
+// 读取文件开头的若干字节
+fn read_prefix(reader: &mut impl Read, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    reader.take(len).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+// 按文件头识别备份并读取元数据，不是备份时返回 false
+fn read_metadata(
+    path: &Path,
+    backup: &mut BackupListEntry,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let head = read_prefix(&mut file, 4)?;
+    file.rewind()?;
+
+    if crypto::is_encrypted(&head) {
+        backup.encrypted = true;
+        return Ok(true);
+    }
+
+    if compress::is_compressed(&head) {
+        backup.compressed = true;
+        let mut decoder = compress::decoder(file)?;
+        let mut plain = read_prefix(&mut decoder, 4)?;
+        if plain.starts_with(ZIP_MAGIC) {
+            // ZIP 需要随机访问，只能完整解压
+            decoder.read_to_end(&mut plain)?;
+            read_zip(Cursor::new(plain), backup)?;
+        } else {
+            read_json(Cursor::new(plain).chain(decoder), backup)?;
+        }
+        return Ok(true);
+    }
+
+    if head.starts_with(ZIP_MAGIC) {
+        read_zip(file, backup)?;
+        return Ok(true);
+    }
+
+    if head.trim_ascii_start().starts_with(b"{") {
+        read_json(file, backup)?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+fn read_zip(
+    reader: impl Read + Seek,
+    backup: &mut BackupListEntry,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut archive = ZipArchive::new(reader)?;
+    let manifest = read_manifest(&mut archive)?;
+    backup.format = Some(BackupFormat::V2);
+    backup.version = Some(manifest.version);
+    backup.timestamp = Some(manifest.timestamp);
+    backup.app_version = Some(manifest.app_version);
+    backup.platform = Some(manifest.platform);
+    Ok(())
+}
+
+fn read_json(
+    reader: impl Read,
+    backup: &mut BackupListEntry,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let metadata: JsonMetadata = serde_json::from_reader(BufReader::new(reader))?;
+    backup.format = Some(BackupFormat::V1);
+    backup.version = Some(metadata.version);
+    backup.timestamp = Some(metadata.timestamp);
+    backup.app_version = Some(metadata.app_version);
+    backup.platform = Some(metadata.platform);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_backup(timestamp: &str) -> Vec<u8> {
+        serde_json::json!({
+            "version": "1.0.0",
+            "timestamp": timestamp,
+            "app_version": "1.2.0",
+            "platform": "linux",
+            "data": { "subscriptions": { "list": null, "configs": { "a": "aGVsbG8=" } } }
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_list_backups_sorted_with_errors() -> Result<(), String> {
+        let dir =
+            std::env::temp_dir().join(format!("stelliberty-backup-listing-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let compressed =
+            compress::compress(&json_backup("2025-06-01T00:00:00Z")).map_err(|e| e.to_string())?;
+        let encrypted = crypto::encrypt(&json_backup("2025-01-01T00:00:00Z"), "secret")
+            .map_err(|e| e.to_string())?;
+        for (name, content) in [
+            ("old.json", json_backup("2024-01-01T00:00:00+08:00")),
+            ("compressed.json", compressed),
+            ("encrypted.bak", encrypted),
+            ("broken.zip", b"PK\x03\x04truncated".to_vec()),
+            ("notes.txt", b"not a backup".to_vec()),
+        ] {
+            std::fs::write(dir.join(name), content).map_err(|e| e.to_string())?;
+        }
+        std::fs::create_dir_all(dir.join("nested.json")).map_err(|e| e.to_string())?;
+
+        let backups = scan_directory(&dir).map_err(|e| e.to_string())?;
+        let names: Vec<_> = backups.iter().map(|b| b.file_name.as_str()).collect();
+        assert_eq!(names.len(), 4, "{names:?}");
+        // 没有备份时间的文件按修改时间（刚刚）排在前面
+        assert_eq!(names[2..], ["compressed.json", "old.json"]);
+
+        let find = |name: &str| backups.iter().find(|b| b.file_name == name);
+        let compressed = find("compressed.json").ok_or("缺少压缩备份")?;
+        assert!(compressed.compressed);
+        assert_eq!(compressed.format, Some(BackupFormat::V1));
+        assert_eq!(compressed.app_version.as_deref(), Some("1.2.0"));
+        let encrypted = find("encrypted.bak").ok_or("缺少加密备份")?;
+        assert!(encrypted.encrypted && encrypted.timestamp.is_none());
+        let broken = find("broken.zip").ok_or("缺少损坏的备份")?;
+        assert!(broken.error.is_some());
+        assert!(find("old.json").is_some_and(|b| b.error.is_none() && b.size > 0));
+        Ok(())
+    }
+}
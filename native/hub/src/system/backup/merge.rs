@@ -0,0 +1,99 @@
+// 合并模式下的列表合并
+//
+// 订阅与覆写的 list.json 为条目数组（或包含条目数组的对象，如 {"subscriptions": [...]}），
+// 条目以 id 区分。合并时保留备份中的全部条目，追加只存在于本地的条目；
+// 同一 id 内容不同时使用备份中的版本并记录冲突。无法识别的结构直接使用备份内容
+
+use serde_json::{Map, Value};
+
+// 合并本地与备份中的列表，返回合并后的内容与冲突的 id
+pub fn merge_list(local: &[u8], backup: &[u8]) -> Option<(Vec<u8>, Vec<String>)> {
+    let local: Value = serde_json::from_slice(local).ok()?;
+    let backup: Value = serde_json::from_slice(backup).ok()?;
+
+    let mut conflicts = Vec::new();
+    let merged = match (local, backup) {
+        (Value::Array(local), Value::Array(backup)) => {
+            Value::Array(merge_entries(local, backup, &mut conflicts))
+        }
+        (Value::Object(mut local), Value::Object(backup)) => {
+            let mut merged = Map::new();
+            for (key, value) in backup {
+                let value = match (local.remove(&key), value) {
+                    (Some(Value::Array(local)), Value::Array(backup)) => {
+                        Value::Array(merge_entries(local, backup, &mut conflicts))
+                    }
+                    (_, value) => value,
+                };
+                merged.insert(key, value);
+            }
+            // 只存在于本地的字段保留
+            merged.extend(local);
+            Value::Object(merged)
+        }
+        _ => return None,
+    };
+
+    let content = serde_json::to_vec_pretty(&merged).ok()?;
+    Some((content, conflicts))
+}
+
+fn entry_id(entry: &Value) -> Option<&str> {
+    entry.get("id")?.as_str()
+}
+
+fn merge_entries(local: Vec<Value>, backup: Vec<Value>, conflicts: &mut Vec<String>) -> Vec<Value> {
+    let mut merged = backup;
+    for entry in local {
+        let existing = match entry_id(&entry) {
+            Some(id) => merged.iter().find(|e| entry_id(e) == Some(id)),
+            // 没有 id 的条目只在内容完全相同时视为同一条目
+            None => merged.iter().find(|e| **e == entry),
+        };
+        match existing {
+            Some(existing) => {
+                if *existing != entry
+                    && let Some(id) = entry_id(&entry)
+                {
+                    conflicts.push(id.to_string());
+                }
+            }
+            None => merged.push(entry),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_list_unions_by_id() -> Result<(), String> {
+        let local = br#"{"subscriptions": [
+            {"id": "a", "name": "local a"},
+            {"id": "b", "name": "only local"},
+            {"id": "c", "name": "same"}
+        ], "version": 1}"#;
+        let backup = br#"{"subscriptions": [
+            {"id": "a", "name": "backup a"},
+            {"id": "c", "name": "same"},
+            {"id": "d", "name": "only backup"}
+        ]}"#;
+
+        let (merged, conflicts) = merge_list(local, backup).ok_or("合并失败")?;
+        let merged: Value = serde_json::from_slice(&merged).map_err(|e| e.to_string())?;
+        let names: Vec<_> = merged["subscriptions"]
+            .as_array()
+            .ok_or("不是数组")?
+            .iter()
+            .filter_map(|e| e["name"].as_str())
+            .collect();
+        assert_eq!(names, ["backup a", "same", "only backup", "only local"]);
+        assert_eq!(merged["version"], 1);
+        assert_eq!(conflicts, ["a"]);
+
+        assert!(merge_list(b"[]", b"{}").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+// 备份版本兼容与迁移
+//
+// 版本号为语义化版本：只拒绝主版本号比当前支持的更新的备份，同一主版本新增的字段
+// 由 serde 忽略或取默认值。旧版结构在内存中升级为当前结构（归档内名称 -> 文件内容，
+// 与 v2 ZIP 相同），之后与 ZIP 备份使用同一还原流程
+
+use super::{BackupData, BackupFiles, corrupted, extras};
+use base64::{Engine as _, engine::general_purpose};
+
+// 当前可以理解的最高主版本
+const SUPPORTED_MAJOR: u64 = 2;
+
+// 检查备份版本是否可以还原
+pub fn check_version(version: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let major = version
+        .split('.')
+        .next()
+        .and_then(|major| major.trim().parse::<u64>().ok())
+        .ok_or_else(|| format!("无效的备份版本：{}", version))?;
+
+    if major > SUPPORTED_MAJOR {
+        return Err(format!(
+            "备份版本 {} 比当前应用支持的版本新，请更新应用后再还原",
+            version
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// 1.x JSON 备份升级为归档内的文件列表，Base64 内容在此解码
+pub fn migrate_v1(
+    backup: BackupData,
+) -> Result<BackupFiles, Box<dyn std::error::Error + Send + Sync>> {
+    let data = backup.data;
+    let mut files = vec![
+        (
+            "app_preferences.json".to_string(),
+            serde_json::to_vec_pretty(&data.app_preferences)?,
+        ),
+        (
+            "clash_preferences.json".to_string(),
+            serde_json::to_vec_pretty(&data.clash_preferences)?,
+        ),
+    ];
+
+    if let Some(list) = data.subscriptions.list {
+        files.push(("subscriptions/list.json".to_string(), list.into_bytes()));
+    }
+    let mut configs: Vec<_> = data.subscriptions.configs.into_iter().collect();
+    configs.sort();
+    for (name, content) in configs {
+        let name = format!("subscriptions/{}.yaml", name);
+        let content = decode(&name, &content)?;
+        files.push((name, content));
+    }
+
+    if let Some(list) = data.overrides.list {
+        files.push(("overrides/list.json".to_string(), list.into_bytes()));
+    }
+    let mut overrides: Vec<_> = data.overrides.files.into_iter().collect();
+    overrides.sort();
+    for (name, content) in overrides {
+        let name = format!("overrides/{}", name);
+        let content = decode(&name, &content)?;
+        files.push((name, content));
+    }
+
+    if let Some(dns_config) = data.dns_config {
+        files.push((
+            "dns_config.json".to_string(),
+            decode("dns_config.json", &dns_config)?,
+        ));
+    }
+    if let Some(pac_file) = data.pac_file {
+        files.push(("proxy.pac".to_string(), decode("proxy.pac", &pac_file)?));
+    }
+
+    if let Some(sids) = data.loopback_sids {
+        files.push((
+            extras::LOOPBACK_FILE.to_string(),
+            serde_json::to_vec_pretty(&sids)?,
+        ));
+    }
+    let mut extra_files: Vec<_> = data.extra_files.into_iter().collect();
+    extra_files.sort();
+    for (name, content) in extra_files {
+        let name = format!("{}/{}", extras::EXTRA_DIR, name);
+        let content = decode(&name, &content)?;
+        files.push((name, content));
+    }
+
+    Ok(files)
+}
+
+fn decode(name: &str, content: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    general_purpose::STANDARD
+        .decode(content)
+        .map_err(|e| corrupted(format!("{} - {}", name, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_version() {
+        assert!(check_version("1.0.0").is_ok());
+        assert!(check_version("1.3.0").is_ok());
+        assert!(check_version("2.0.0").is_ok());
+        assert!(check_version("2.7.1").is_ok());
+        assert!(check_version("3.0.0").is_err());
+        assert!(check_version("").is_err());
+    }
+}
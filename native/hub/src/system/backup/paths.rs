@@ -0,0 +1,66 @@
+// Dart 传入路径的规范化
+//
+// Dart 传来的路径可能带有结尾分隔符、混用 / 与 \，或是 UNC 路径。按组件重建路径
+// （去掉结尾分隔符与 . 组件，统一为平台分隔符），并对已存在的最长前缀调用 canonicalize：
+// 解析符号链接与 ..，Windows 上得到 \\?\ 前缀的路径，不受 260 字符的长度限制。
+// 尚不存在的部分（如待创建的备份文件）原样接在后面
+
+use std::path::{Path, PathBuf};
+
+// 规范化 Dart 传入的路径
+pub fn normalize_path(raw: &str) -> PathBuf {
+    let path: PathBuf = Path::new(raw).components().collect();
+    for ancestor in path.ancestors() {
+        if ancestor.as_os_str().is_empty() {
+            break;
+        }
+        let Ok(canonical) = std::fs::canonicalize(ancestor) else {
+            continue;
+        };
+        return match path.strip_prefix(ancestor) {
+            Ok(rest) if !rest.as_os_str().is_empty() => canonical.join(rest),
+            _ => canonical,
+        };
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_trailing_separator_and_missing_parts() -> Result<(), String> {
+        let dir =
+            std::env::temp_dir().join(format!("stelliberty-backup-paths-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let canonical = std::fs::canonicalize(&dir).map_err(|e| e.to_string())?;
+        let raw = dir.to_string_lossy();
+
+        assert_eq!(normalize_path(&format!("{raw}/")), canonical);
+        assert_eq!(normalize_path(&format!("{raw}/./")), canonical);
+        assert_eq!(
+            normalize_path(&format!("{raw}/missing/backup.zip")),
+            canonical.join("missing").join("backup.zip")
+        );
+        assert_eq!(normalize_path("relative/dir/"), Path::new("relative/dir"));
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_normalize_windows_paths() {
+        // 混用分隔符、结尾分隔符与非 ASCII 目录名
+        let path = normalize_path(r"C:/Users\名前\AppData\");
+        assert!(path.ends_with(r"Users\名前\AppData"), "{}", path.display());
+        assert!(!path.to_string_lossy().contains('/'));
+
+        // 已存在的部分规范化为 \\?\ 前缀的路径
+        let path = normalize_path(r"C:\Windows\");
+        assert!(
+            path.to_string_lossy().starts_with(r"\\?\"),
+            "{}",
+            path.display()
+        );
+    }
+}
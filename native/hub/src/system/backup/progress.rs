@@ -0,0 +1,171 @@
+// 备份与还原进度
+//
+// 进度通过回调上报（信号处理函数把事件转换为 BackupProgress 信号，测试中直接收集）。
+// 同一阶段内按时间节流，阶段切换与阶段的最后一项总是上报。回调不会失败，
+// 上报进度不影响任何步骤的结果
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 同一阶段内两次上报的最小间隔（约每秒 10 次）
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+// 进度阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupPhase {
+    CollectingPreferences,
+    CollectingSubscriptions,
+    CollectingOverrides,
+    WritingArchive,
+    Compressing,
+    Encrypting,
+    Decrypting,
+    Decompressing,
+    Verifying,
+    CreatingSnapshot,
+    RestoringPreferences,
+    RestoringSubscriptions,
+    RestoringOverrides,
+    Committing,
+    RollingBack,
+}
+
+impl BackupPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::CollectingPreferences => "collecting_preferences",
+            Self::CollectingSubscriptions => "collecting_subscriptions",
+            Self::CollectingOverrides => "collecting_overrides",
+            Self::WritingArchive => "writing_archive",
+            Self::Compressing => "compressing",
+            Self::Encrypting => "encrypting",
+            Self::Decrypting => "decrypting",
+            Self::Decompressing => "decompressing",
+            Self::Verifying => "verifying",
+            Self::CreatingSnapshot => "creating_snapshot",
+            Self::RestoringPreferences => "restoring_preferences",
+            Self::RestoringSubscriptions => "restoring_subscriptions",
+            Self::RestoringOverrides => "restoring_overrides",
+            Self::Committing => "committing",
+            Self::RollingBack => "rolling_back",
+        }
+    }
+
+    // 归档内名称对应的还原阶段（写入暂存目录）
+    pub fn restoring(name: &str) -> Self {
+        if name.starts_with("subscriptions/") {
+            Self::RestoringSubscriptions
+        } else if name.starts_with("overrides/") {
+            Self::RestoringOverrides
+        } else {
+            Self::RestoringPreferences
+        }
+    }
+}
+
+// 进度事件（total 为 0 表示总数未知）
+#[derive(Debug, Clone)]
+pub struct ProgressEvent {
+    pub phase: BackupPhase,
+    pub current: u32,
+    pub total: u32,
+    pub detail: String,
+}
+
+type ProgressSink = dyn Fn(ProgressEvent) + Send + Sync;
+
+// 已上报的阶段与时间
+#[derive(Default)]
+struct ThrottleState {
+    phase: Option<BackupPhase>,
+    sent_at: Option<Instant>,
+}
+
+// 进度上报（可克隆，克隆共享节流状态，可以传入 spawn_blocking）
+#[derive(Clone)]
+pub struct Progress {
+    sink: Option<Arc<ProgressSink>>,
+    state: Arc<Mutex<ThrottleState>>,
+}
+
+impl Progress {
+    pub fn new(sink: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self {
+            sink: Some(Arc::new(sink)),
+            state: Arc::default(),
+        }
+    }
+
+    // 不上报进度
+    pub fn none() -> Self {
+        Self {
+            sink: None,
+            state: Arc::default(),
+        }
+    }
+
+    pub fn report(&self, phase: BackupPhase, current: usize, total: usize, detail: &str) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+
+        {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+            let now = Instant::now();
+            let throttled = state.phase == Some(phase)
+                && (total == 0 || current < total)
+                && state
+                    .sent_at
+                    .is_some_and(|sent_at| now.duration_since(sent_at) < MIN_INTERVAL);
+            if throttled {
+                return;
+            }
+            state.phase = Some(phase);
+            state.sent_at = Some(now);
+        }
+
+        sink(ProgressEvent {
+            phase,
+            current: u32::try_from(current).unwrap_or(u32::MAX),
+            total: u32::try_from(total).unwrap_or(u32::MAX),
+            detail: detail.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_throttled_within_phase() -> Result<(), String> {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let events = events.clone();
+            Progress::new(move |event| {
+                if let Ok(mut events) = events.lock() {
+                    events.push(event);
+                }
+            })
+        };
+
+        for index in 1..=1000 {
+            progress.report(BackupPhase::WritingArchive, index, 1000, "file");
+        }
+        progress.report(BackupPhase::Encrypting, 0, 1, "");
+
+        let events = events.lock().map_err(|e| e.to_string())?;
+        // 第一项与最后一项总是上报，中间的被节流
+        assert!(events.len() < 10, "{}", events.len());
+        assert_eq!(events[0].current, 1);
+        let last_written = &events[events.len() - 2];
+        assert_eq!(
+            (last_written.phase, last_written.current),
+            (BackupPhase::WritingArchive, 1000)
+        );
+        assert_eq!(events[events.len() - 1].phase, BackupPhase::Encrypting);
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+// 还原前的内容检查
+//
+// 校验备份时对每个条目调用一次 check：检查路径是否安全、JSON 文件能否解析，并按分类统计。
+// 试运行把报告原样返回给界面；实际还原与校验请求在报告有问题时拒绝还原，
+// 两者使用同一遍检查，结果不会不一致
+
+use super::{PREFERENCE_FILES, extras, is_safe_name, restore_target};
+use std::path::Path;
+
+// 需要解析检查的 JSON 文件
+const JSON_FILES: [&str; 4] = [
+    "dns_config.json",
+    "subscriptions/list.json",
+    "overrides/list.json",
+    extras::LOOPBACK_FILE,
+];
+
+// 一个分类的文件统计
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SectionReport {
+    pub file_count: u32,
+    pub total_size: u64,
+}
+
+// 还原内容报告
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub preferences: SectionReport,
+    pub subscriptions: SectionReport,
+    pub overrides: SectionReport,
+    // dns_config.json、proxy.pac、回环豁免与附加文件
+    pub other: SectionReport,
+    // 附加文件在数据目录中的相对路径（还原前快照需要包含这些文件）
+    pub extra_files: Vec<String>,
+    // 不属于备份内容、还原时跳过的文件
+    pub skipped: Vec<String>,
+    // 发现的问题，不为空时不能还原
+    pub problems: Vec<String>,
+}
+
+impl RestoreReport {
+    // 是否需要读取条目内容进行检查
+    pub fn needs_content(name: &str) -> bool {
+        PREFERENCE_FILES.contains(&name) || JSON_FILES.contains(&name)
+    }
+
+    // 检查一个条目（needs_content 为 true 时 content 为条目内容）
+    pub fn check(&mut self, name: &str, size: u64, content: Option<&[u8]>) {
+        if !is_safe_name(name) {
+            self.problems
+                .push(format!("备份包含非法的文件路径：{}", name));
+            return;
+        }
+        if name == extras::LOOPBACK_FILE {
+            if let Some(content) = content
+                && let Err(e) = serde_json::from_slice::<Vec<String>>(content)
+            {
+                self.problems
+                    .push(format!("{} 不是有效的 SID 列表：{}", name, e));
+            }
+            self.other.file_count += 1;
+            self.other.total_size += size;
+            return;
+        }
+        if restore_target(Path::new(""), name).is_none() {
+            self.skipped.push(name.to_string());
+            return;
+        }
+
+        if let Some(content) = content
+            && let Err(e) = serde_json::from_slice::<serde_json::Value>(content)
+        {
+            self.problems
+                .push(format!("{} 不是有效的 JSON：{}", name, e));
+        }
+        if let Some(relative) = name
+            .strip_prefix(extras::EXTRA_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+        {
+            self.extra_files.push(relative.to_string());
+        }
+
+        let section = if PREFERENCE_FILES.contains(&name) {
+            &mut self.preferences
+        } else if name.starts_with("subscriptions/") {
+            &mut self.subscriptions
+        } else if name.starts_with("overrides/") {
+            &mut self.overrides
+        } else {
+            &mut self.other
+        };
+        section.file_count += 1;
+        section.total_size += size;
+    }
+
+    // 有问题时返回包含所有问题的错误
+    pub fn ensure_valid(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(self.problems.join("；").into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_sections_and_problems() {
+        let mut report = RestoreReport::default();
+        report.check("app_preferences.json", 2, Some(b"{}"));
+        report.check("subscriptions/list.json", 7, Some(b"[{\"id\""));
+        report.check("subscriptions/a.yaml", 10, None);
+        report.check("overrides/rules/b.js", 5, None);
+        report.check("proxy.pac", 3, None);
+        report.check("extra/geo/custom.dat", 4, None);
+        report.check("loopback_sids.json", 2, Some(b"[]"));
+        report.check("notes.txt", 1, None);
+        report.check("overrides/../../evil.js", 4, None);
+
+        assert_eq!(report.preferences.file_count, 1);
+        assert_eq!(report.subscriptions.file_count, 2);
+        assert_eq!(report.subscriptions.total_size, 17);
+        assert_eq!(report.overrides.file_count, 1);
+        assert_eq!(report.other.file_count, 3);
+        assert_eq!(report.extra_files, ["geo/custom.dat"]);
+        assert_eq!(report.skipped, ["notes.txt"]);
+        assert_eq!(report.problems.len(), 2);
+        assert!(report.problems[0].contains("subscriptions/list.json"));
+        assert!(report.problems[1].contains("非法的文件路径"));
+        assert!(report.ensure_valid().is_err());
+    }
+}
@@ -0,0 +1,288 @@
+// 定时自动备份
+//
+// 策略保存在 Hub 设置中，启动时恢复。后台任务每次等待前重新读取策略：
+// 距目标目录中最新的自动备份超过间隔时立即备份（应用关闭期间错过的备份在启动时补上），
+// 否则等待到期或策略变更。备份文件名包含 UTC 时间，按文件名排序即为时间顺序，
+// 超过保留数量的旧备份被删除
+
+use super::{BackupFormat, Progress, create_backup};
+use crate::settings::{self, keys};
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+
+// 自动备份文件名前缀
+const FILE_PREFIX: &str = "stelliberty-backup-";
+
+// 默认保留数量
+const DEFAULT_MAX_KEEP: u64 = 10;
+
+// 备份失败后的重试间隔（不超过备份间隔）
+const RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+// 策略变更通知
+static POLICY_CHANGED: Lazy<Notify> = Lazy::new(Notify::new);
+
+// 自动备份策略
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoBackupPolicy {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub target_dir: PathBuf,
+    // 0 表示不删除旧备份
+    pub max_keep: u32,
+    // create_backup 需要的数据目录与应用版本
+    pub app_data_path: PathBuf,
+    pub app_version: String,
+}
+
+impl AutoBackupPolicy {
+    fn load() -> Self {
+        Self {
+            enabled: settings::get_bool(keys::AUTO_BACKUP_ENABLED).unwrap_or(false),
+            interval_hours: settings::get_u64(keys::AUTO_BACKUP_INTERVAL_HOURS)
+                .and_then(|value| u32::try_from(value).ok())
+                .unwrap_or(24),
+            target_dir: settings::get_string(keys::AUTO_BACKUP_TARGET_DIR)
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+            max_keep: settings::get_u64(keys::AUTO_BACKUP_MAX_KEEP)
+                .unwrap_or(DEFAULT_MAX_KEEP)
+                .try_into()
+                .unwrap_or(u32::MAX),
+            app_data_path: settings::get_string(keys::AUTO_BACKUP_APP_DATA_PATH)
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+            app_version: settings::get_string(keys::AUTO_BACKUP_APP_VERSION).unwrap_or_default(),
+        }
+    }
+
+    // 策略是否可以执行（未启用也视为无效）
+    fn is_active(&self) -> bool {
+        self.enabled
+            && self.interval_hours > 0
+            && !self.target_dir.as_os_str().is_empty()
+            && !self.app_data_path.as_os_str().is_empty()
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(u64::from(self.interval_hours) * 3600)
+    }
+}
+
+// 自动备份结果回调（成功时为备份路径）
+pub type AutoBackupSink = fn(Result<String, String>);
+
+// 保存策略并唤醒后台任务
+pub fn configure_auto_backup(policy: &AutoBackupPolicy) -> Result<(), String> {
+    settings::set_many([
+        (keys::AUTO_BACKUP_ENABLED, Some(policy.enabled.into())),
+        (
+            keys::AUTO_BACKUP_INTERVAL_HOURS,
+            Some(policy.interval_hours.into()),
+        ),
+        (
+            keys::AUTO_BACKUP_TARGET_DIR,
+            Some(policy.target_dir.to_string_lossy().into_owned().into()),
+        ),
+        (keys::AUTO_BACKUP_MAX_KEEP, Some(policy.max_keep.into())),
+        (
+            keys::AUTO_BACKUP_APP_DATA_PATH,
+            Some(policy.app_data_path.to_string_lossy().into_owned().into()),
+        ),
+        (
+            keys::AUTO_BACKUP_APP_VERSION,
+            Some(policy.app_version.clone().into()),
+        ),
+    ])?;
+    POLICY_CHANGED.notify_one();
+    Ok(())
+}
+
+// 启动后台任务
+pub fn start_auto_backup(sink: AutoBackupSink) {
+    tokio::spawn(async move {
+        loop {
+            let policy = AutoBackupPolicy::load();
+            if !policy.is_active() {
+                POLICY_CHANGED.notified().await;
+                continue;
+            }
+
+            let last = latest_backup_time(&policy.target_dir);
+            let wait = time_until_due(last, SystemTime::now(), policy.interval());
+            if !wait.is_zero() {
+                log::info!("下一次自动备份在 {} 分钟后", wait.as_secs() / 60);
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    // 策略变更后重新计算
+                    _ = POLICY_CHANGED.notified() => continue,
+                }
+            }
+
+            // 等待期间策略可能已被外部修改
+            if AutoBackupPolicy::load() != policy {
+                continue;
+            }
+            let result = run_auto_backup(&policy).await;
+            let failed = result.is_err();
+            sink(result);
+
+            // 失败时最新备份的时间不变，等待一段时间再重试，避免连续失败
+            if failed {
+                tokio::select! {
+                    _ = tokio::time::sleep(RETRY_DELAY.min(policy.interval())) => {}
+                    _ = POLICY_CHANGED.notified() => {}
+                }
+            }
+        }
+    });
+}
+
+// 执行一次自动备份并删除多余的旧备份
+async fn run_auto_backup(policy: &AutoBackupPolicy) -> Result<String, String> {
+    let format = BackupFormat::default();
+    let target_dir = policy.target_dir.clone();
+    let target = target_dir.join(backup_file_name(chrono::Utc::now(), format));
+    log::info!("开始自动备份：{}", target.display());
+
+    let path = create_backup(
+        &target,
+        &policy.app_data_path,
+        &policy.app_version,
+        format,
+        true,
+        None,
+        &Progress::none(),
+    )
+    .await
+    .map_err(|e| {
+        log::error!("自动备份失败：{}", e);
+        e.to_string()
+    })?;
+
+    if policy.max_keep > 0 {
+        let max_keep = policy.max_keep as usize;
+        tokio::task::spawn_blocking(move || prune_backups(&target_dir, max_keep))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(path.to_string_lossy().into_owned())
+}
+
+// 自动备份文件名，如 stelliberty-backup-2025-01-01T12-00.zip
+fn backup_file_name(time: chrono::DateTime<chrono::Utc>, format: BackupFormat) -> String {
+    let extension = match format {
+        BackupFormat::V1 => "json",
+        BackupFormat::V2 => "zip",
+    };
+    format!(
+        "{}{}.{}",
+        FILE_PREFIX,
+        time.format("%Y-%m-%dT%H-%M"),
+        extension
+    )
+}
+
+fn is_auto_backup(name: &str) -> bool {
+    name.starts_with(FILE_PREFIX) && (name.ends_with(".json") || name.ends_with(".zip"))
+}
+
+// 目录中的自动备份文件名，按时间从旧到新排序
+fn list_auto_backups(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| is_auto_backup(name))
+        .collect();
+    names.sort();
+    names
+}
+
+// 最新自动备份的修改时间
+fn latest_backup_time(dir: &Path) -> Option<SystemTime> {
+    let latest = list_auto_backups(dir).pop()?;
+    std::fs::metadata(dir.join(latest)).ok()?.modified().ok()
+}
+
+// 距下一次备份的时间，从未备份或已超过间隔时为 0
+fn time_until_due(last: Option<SystemTime>, now: SystemTime, interval: Duration) -> Duration {
+    let Some(last) = last else {
+        return Duration::ZERO;
+    };
+    // 最新备份的时间在未来（时钟被调整）时按刚刚备份处理
+    let elapsed = now.duration_since(last).unwrap_or_default();
+    interval.saturating_sub(elapsed)
+}
+
+// 删除超过保留数量的旧自动备份
+fn prune_backups(dir: &Path, max_keep: usize) {
+    let names = list_auto_backups(dir);
+    let excess = names.len().saturating_sub(max_keep);
+    for name in &names[..excess] {
+        match std::fs::remove_file(dir.join(name)) {
+            Ok(()) => log::info!("已删除旧的自动备份：{}", name),
+            Err(e) => log::warn!("删除旧的自动备份失败：{} - {}", name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_time_and_pruning() -> Result<(), String> {
+        let hour = Duration::from_secs(3600);
+        let now = SystemTime::now();
+        assert_eq!(time_until_due(None, now, hour * 24), Duration::ZERO);
+        assert_eq!(
+            time_until_due(Some(now - hour * 30), now, hour * 24),
+            Duration::ZERO
+        );
+        assert_eq!(
+            time_until_due(Some(now - hour * 20), now, hour * 24),
+            hour * 4
+        );
+        assert_eq!(time_until_due(Some(now + hour), now, hour * 24), hour * 24);
+
+        let time = chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:30Z")
+            .map_err(|e| e.to_string())?
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            backup_file_name(time, BackupFormat::V1),
+            "stelliberty-backup-2025-01-01T12-00.json"
+        );
+
+        let dir = std::env::temp_dir().join(format!(
+            "stelliberty-backup-schedule-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        for name in [
+            "stelliberty-backup-2025-01-03T00-00.zip",
+            "stelliberty-backup-2025-01-01T00-00.json",
+            "stelliberty-backup-2025-01-02T00-00.zip",
+            "manual-backup.zip",
+        ] {
+            std::fs::write(dir.join(name), "").map_err(|e| e.to_string())?;
+        }
+        prune_backups(&dir, 2);
+
+        assert_eq!(
+            list_auto_backups(&dir),
+            [
+                "stelliberty-backup-2025-01-02T00-00.zip",
+                "stelliberty-backup-2025-01-03T00-00.zip"
+            ]
+        );
+        assert!(dir.join("manual-backup.zip").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+// 还原前的数据快照
+//
+// 新内容在暂存目录中写好后才交换（见 staging 模块），但交换多个目录与根目录文件不是一步完成的，
+// 中途失败时数据目录可能新旧混合。修改前把备份范围内的文件复制到数据目录下的快照目录
+// （同一文件系统，可以原子重命名），失败时删除备份范围内的所有文件再从快照复制回来。
+// 备份中的附加文件也在快照范围内
+
+use super::{collect_backup_files, join_name, list_files};
+use std::path::{Path, PathBuf};
+
+// 快照目录名称
+const SNAPSHOT_DIR: &str = ".restore-snapshot";
+
+// 快照完整的标记文件
+const COMPLETE_MARKER: &str = ".complete";
+
+pub struct RestoreSnapshot {
+    app_data_path: PathBuf,
+    dir: PathBuf,
+    // 快照中的文件（数据目录中的相对路径）
+    files: Vec<String>,
+    // 将要还原的附加文件，恢复快照时删除（不在快照中的是新增的文件）
+    extra_files: Vec<String>,
+}
+
+impl RestoreSnapshot {
+    // 复制备份范围内的现有文件
+    //
+    // 残留的完整快照说明上次还原被中断，此时先用它恢复数据，避免把写了一半的文件当作快照；
+    // 不完整的快照在修改数据前就已中断，直接删除
+    pub fn create(
+        app_data_path: &Path,
+        extra_files: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let dir = app_data_path.join(SNAPSHOT_DIR);
+        if dir.join(COMPLETE_MARKER).is_file() {
+            log::warn!("发现上次未完成的还原，先恢复快照：{}", dir.display());
+            Self::load(app_data_path, dir.clone())?.rollback()?;
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+
+        let mut sources = collect_backup_files(app_data_path)?;
+        for name in extra_files {
+            let path = join_name(app_data_path, name);
+            if path.is_file() {
+                sources.push((name.clone(), path));
+            }
+        }
+
+        let mut files = Vec::new();
+        for (name, source) in sources {
+            let target = join_name(&dir, &name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(&source, &target)?;
+            files.push(name);
+        }
+        // 复制完成后才写入标记，未完成的快照不会被用于恢复
+        std::fs::write(dir.join(COMPLETE_MARKER), "")?;
+
+        log::info!("已创建还原前快照（{} 个文件）", files.len());
+        Ok(Self {
+            app_data_path: app_data_path.to_path_buf(),
+            dir,
+            files,
+            extra_files: extra_files.to_vec(),
+        })
+    }
+
+    // 读取已有的快照目录（快照中的附加文件照常恢复，新增的附加文件无从得知，保留）
+    fn load(
+        app_data_path: &Path,
+        dir: PathBuf,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let files = list_files(&dir, |path| !path.ends_with(COMPLETE_MARKER))?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        Ok(Self {
+            app_data_path: app_data_path.to_path_buf(),
+            dir,
+            files,
+            extra_files: Vec::new(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // 恢复快照：删除备份范围内的当前文件，再复制快照中的文件
+    //
+    // 单个文件失败时继续处理其余文件，最后返回失败数；成功后删除快照
+    pub fn rollback(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut failed = 0;
+
+        match collect_backup_files(&self.app_data_path) {
+            Ok(current) => {
+                for (name, path) in current {
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        log::warn!("删除文件失败：{} - {}", name, e);
+                        failed += 1;
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!("读取数据目录失败：{}", e);
+                failed += 1;
+            }
+        }
+        for name in &self.extra_files {
+            let path = join_name(&self.app_data_path, name);
+            if path.is_file()
+                && let Err(e) = std::fs::remove_file(&path)
+            {
+                log::warn!("删除文件失败：{} - {}", name, e);
+                failed += 1;
+            }
+        }
+
+        for name in &self.files {
+            if let Err(e) = self.restore_file(name) {
+                log::warn!("恢复文件失败：{} - {}", name, e);
+                failed += 1;
+            }
+        }
+
+        if failed > 0 {
+            return Err(format!("{} 个文件恢复失败", failed).into());
+        }
+        log::info!("已恢复还原前的数据（{} 个文件）", self.files.len());
+        self.discard();
+        Ok(())
+    }
+
+    // 先复制到临时文件再重命名，中断时目标文件不会只写了一半
+    fn restore_file(&self, name: &str) -> std::io::Result<()> {
+        let target = join_name(&self.app_data_path, name);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = target.with_file_name(format!(
+            "{}.restoring",
+            target
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default()
+        ));
+        std::fs::copy(join_name(&self.dir, name), &temp)?;
+        std::fs::rename(&temp, &target)
+    }
+
+    // 删除快照
+    pub fn discard(&self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("删除还原前快照失败：{} - {}", self.dir.display(), e);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+// 还原暂存与目录交换
+//
+// 应用与核心可能正在读取订阅与覆写目录，直接写入会让核心读到写了一半的 YAML。
+// 还原时先把新内容完整写入 subscriptions.staging/ 与 overrides.staging/（逐个 fsync），
+// 再交换目录：现有目录重命名为 .old，暂存目录重命名为现有目录，最后删除 .old。
+// 根目录下的单个文件与附加文件先写入同目录的 .staging 临时文件，在交换时重命名。
+// 回环豁免不是数据目录中的文件，暂存时只记录内容，由调用方在交换成功后应用。
+//
+// 备份中没有任何条目的目录不交换，保留现有文件（在新安装上创建的备份不会清空已有订阅）。
+// 合并模式下暂存目录先复制全部现有文件，list.json 按 id 合并（见 merge 模块）。
+//
+// 暂存失败时现有目录没有被修改；交换失败时把已交换的目录换回。
+// 跨目录重命名失败的文件系统上改为复制后删除
+
+use super::{RestoreMode, extras, join_name, list_files, merge, restore_target, step};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+// 整体交换的目录
+const STAGED_DIRS: [&str; 2] = ["subscriptions", "overrides"];
+
+// 交换目录时保留的现有文件：替换模式下为订阅目录中的非配置文件
+// （包括 list.json，备份中有时被覆盖），合并模式下为全部文件
+fn kept_in_live(mode: RestoreMode, dir: &str, path: &Path) -> bool {
+    mode == RestoreMode::Merge || (dir == "subscriptions" && !super::is_yaml(path))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub struct StagedRestore {
+    app_data_path: PathBuf,
+    mode: RestoreMode,
+    // 备份中有条目、需要交换的目录
+    touched: Vec<&'static str>,
+    // 合并 list.json 时与本地内容不同的条目
+    conflicts: Vec<String>,
+    // 备份中的回环豁免
+    loopback_sids: Option<Vec<String>>,
+    // 已写入的归档内名称
+    restored: Vec<String>,
+    // 根目录文件与附加文件：(归档内名称, 临时文件, 目标文件)
+    files: Vec<(String, PathBuf, PathBuf)>,
+}
+
+impl StagedRestore {
+    // 处理上次被中断的交换（需要在创建快照前调用）
+    //
+    // .old 存在而现有目录不存在时说明现有目录已移走，将其移回；
+    // 两者都存在时说明交换已完成，删除 .old
+    pub fn recover(app_data_path: &Path) -> std::io::Result<()> {
+        for dir in STAGED_DIRS {
+            let live = app_data_path.join(dir);
+            let old = with_suffix(&live, ".old");
+            if !old.is_dir() {
+                continue;
+            }
+            if live.exists() {
+                log::warn!("删除上次还原残留的目录：{}", old.display());
+                std::fs::remove_dir_all(&old)?;
+            } else {
+                log::warn!("恢复上次还原中断时移走的目录：{}", live.display());
+                move_dir(&old, &live)?;
+            }
+        }
+        Ok(())
+    }
+
+    // 创建暂存目录，复制交换时需要保留的现有文件
+    pub fn create(
+        app_data_path: &Path,
+        mode: RestoreMode,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        for dir in STAGED_DIRS {
+            let live = app_data_path.join(dir);
+            let staging = with_suffix(&live, ".staging");
+            if staging.exists() {
+                std::fs::remove_dir_all(&staging)?;
+            }
+            std::fs::create_dir_all(&staging)?;
+            for (name, path) in list_files(&live, |_| true)? {
+                if kept_in_live(mode, dir, &path) {
+                    let target = join_name(&staging, &name);
+                    if let Some(parent) = target.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::copy(&path, &target)?;
+                }
+            }
+        }
+
+        Ok(Self {
+            app_data_path: app_data_path.to_path_buf(),
+            mode,
+            touched: Vec::new(),
+            conflicts: Vec::new(),
+            loopback_sids: None,
+            restored: Vec::new(),
+            files: Vec::new(),
+        })
+    }
+
+    pub fn restored(&self) -> &[String] {
+        &self.restored
+    }
+
+    pub fn conflicts(&self) -> &[String] {
+        &self.conflicts
+    }
+
+    pub fn take_loopback_sids(&mut self) -> Option<Vec<String>> {
+        self.loopback_sids.take()
+    }
+
+    // 暂存备份中的一个文件，不属于备份内容的名称跳过并返回 false
+    pub fn write(
+        &mut self,
+        name: &str,
+        content: &mut impl Read,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        if name == extras::LOOPBACK_FILE {
+            let mut sids = Vec::new();
+            content.read_to_end(&mut sids)?;
+            self.loopback_sids = Some(step(
+                &format!("读取 {}", name),
+                serde_json::from_slice(&sids),
+            )?);
+            return Ok(true);
+        }
+        let Some(target) = restore_target(&self.app_data_path, name) else {
+            log::warn!("跳过备份中的未知文件：{}", name);
+            return Ok(false);
+        };
+
+        let staged_dir = name
+            .split_once('/')
+            .and_then(|(dir, _)| STAGED_DIRS.into_iter().find(|d| *d == dir));
+        let staged = match staged_dir {
+            Some(dir) => {
+                if !self.touched.contains(&dir) {
+                    self.touched.push(dir);
+                }
+                let live = self.app_data_path.join(dir);
+                with_suffix(&live, ".staging").join(target.strip_prefix(&live)?)
+            }
+            // 根目录文件与附加文件
+            None => with_suffix(&target, ".staging"),
+        };
+
+        let result = if self.mode == RestoreMode::Merge
+            && name.ends_with("/list.json")
+            && target.is_file()
+        {
+            self.merge_list(name, content, &target)
+                .and_then(|merged| write_synced(&mut merged.as_slice(), &staged))
+        } else {
+            write_synced(content, &staged)
+        };
+        step(&format!("还原 {}", name), result)?;
+
+        if staged_dir.is_none() {
+            self.files.push((name.to_string(), staged, target));
+        }
+        self.restored.push(name.to_string());
+        Ok(true)
+    }
+
+    // 合并本地与备份中的 list.json，无法合并时使用备份中的内容
+    fn merge_list(
+        &mut self,
+        name: &str,
+        content: &mut impl Read,
+        live_list: &Path,
+    ) -> std::io::Result<Vec<u8>> {
+        let mut backup = Vec::new();
+        content.read_to_end(&mut backup)?;
+        let local = std::fs::read(live_list)?;
+
+        match merge::merge_list(&local, &backup) {
+            Some((merged, conflicts)) => {
+                self.conflicts.extend(
+                    conflicts
+                        .into_iter()
+                        .map(|id| format!("{} 中的 {}", name, id)),
+                );
+                Ok(merged)
+            }
+            None => {
+                log::warn!("无法合并 {}，使用备份中的内容", name);
+                Ok(backup)
+            }
+        }
+    }
+
+    // 交换目录并重命名根目录文件，失败时把已交换的目录换回
+    pub fn commit(self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for dir in STAGED_DIRS {
+            let staging = with_suffix(&self.app_data_path.join(dir), ".staging");
+            if self.touched.contains(&dir) {
+                sync_dir(&staging);
+            } else {
+                log::info!("备份中没有 {}，保留现有文件", dir);
+                let _ = std::fs::remove_dir_all(&staging);
+            }
+        }
+
+        let mut swapped = Vec::new();
+        if let Err(e) = self.swap(&mut swapped) {
+            for (dir, existed) in swapped.into_iter().rev() {
+                if let Err(revert_error) = self.revert(dir, existed) {
+                    log::error!("换回目录失败：{} - {}", dir, revert_error);
+                }
+            }
+            self.discard();
+            return Err(e);
+        }
+
+        sync_dir(&self.app_data_path);
+        for dir in STAGED_DIRS {
+            let old = with_suffix(&self.app_data_path.join(dir), ".old");
+            if old.exists()
+                && let Err(e) = std::fs::remove_dir_all(&old)
+            {
+                log::warn!("删除旧目录失败：{} - {}", old.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    // 逐个交换目录，再替换根目录文件；swapped 记录已移走或放入的目录与交换前是否存在
+    fn swap(
+        &self,
+        swapped: &mut Vec<(&'static str, bool)>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for dir in STAGED_DIRS.into_iter().filter(|d| self.touched.contains(d)) {
+            let live = self.app_data_path.join(dir);
+            let existed = live.exists();
+            if existed {
+                step(
+                    &format!("移走 {}", dir),
+                    move_dir(&live, &with_suffix(&live, ".old")),
+                )?;
+            }
+            swapped.push((dir, existed));
+            step(
+                &format!("替换 {}", dir),
+                move_dir(&with_suffix(&live, ".staging"), &live),
+            )?;
+        }
+        for (name, staged, target) in &self.files {
+            step(&format!("替换 {}", name), std::fs::rename(staged, target))?;
+        }
+        Ok(())
+    }
+
+    // 换回一个目录：删除已放入的新目录，把 .old 移回
+    fn revert(&self, dir: &str, existed: bool) -> std::io::Result<()> {
+        let live = self.app_data_path.join(dir);
+        let old = with_suffix(&live, ".old");
+        if live.exists() {
+            std::fs::remove_dir_all(&live)?;
+        }
+        if existed {
+            move_dir(&old, &live)?;
+        }
+        Ok(())
+    }
+
+    // 删除暂存目录与临时文件
+    pub fn discard(&self) {
+        for dir in STAGED_DIRS {
+            let staging = with_suffix(&self.app_data_path.join(dir), ".staging");
+            if staging.exists()
+                && let Err(e) = std::fs::remove_dir_all(&staging)
+            {
+                log::warn!("删除暂存目录失败：{} - {}", staging.display(), e);
+            }
+        }
+        for (_, staged, _) in &self.files {
+            let _ = std::fs::remove_file(staged);
+        }
+    }
+}
+
+// 写入文件并同步到磁盘
+fn write_synced(content: &mut impl Read, target: &Path) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(target)?);
+    std::io::copy(content, &mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+// 同步目录项（Windows 上无法打开目录，重命名由文件系统日志保证）
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Err(e) = File::open(path).and_then(|dir| dir.sync_all()) {
+        log::warn!("同步目录失败：{} - {}", path.display(), e);
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+// 移动目录：优先重命名，失败时复制后删除源目录
+//
+// 目标已存在时直接失败，复制失败时删除复制了一半的目标，源目录保持不变
+fn move_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("目标已存在：{}", to.display()),
+        ));
+    }
+    let Err(e) = std::fs::rename(from, to) else {
+        return Ok(());
+    };
+    log::warn!(
+        "重命名目录失败，改为复制：{} -> {} - {}",
+        from.display(),
+        to.display(),
+        e
+    );
+    if let Err(e) = copy_dir(from, to) {
+        let _ = std::fs::remove_dir_all(to);
+        return Err(e);
+    }
+    std::fs::remove_dir_all(from)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failed_swap_reverted() -> Result<(), String> {
+        let root = std::env::temp_dir().join(format!(
+            "stelliberty-backup-staging-revert-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("subscriptions")).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(root.join("overrides")).map_err(|e| e.to_string())?;
+        std::fs::write(root.join("subscriptions/a.yaml"), "old").map_err(|e| e.to_string())?;
+        std::fs::write(root.join("subscriptions/cache.db"), "keep").map_err(|e| e.to_string())?;
+        std::fs::write(root.join("overrides/a.js"), "old").map_err(|e| e.to_string())?;
+        // 目标位置是目录，根目录文件无法替换
+        std::fs::create_dir_all(root.join("proxy.pac")).map_err(|e| e.to_string())?;
+
+        StagedRestore::recover(&root).map_err(|e| e.to_string())?;
+        let mut staged =
+            StagedRestore::create(&root, RestoreMode::Replace).map_err(|e| e.to_string())?;
+        for (name, content) in [
+            ("subscriptions/b.yaml", "new"),
+            ("overrides/b.js", "new"),
+            ("proxy.pac", "new"),
+        ] {
+            staged
+                .write(name, &mut content.as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        assert!(staged.commit().is_err());
+
+        let read = |name: &str| std::fs::read_to_string(root.join(name)).ok();
+        assert_eq!(read("subscriptions/a.yaml").as_deref(), Some("old"));
+        assert_eq!(read("subscriptions/cache.db").as_deref(), Some("keep"));
+        assert_eq!(read("overrides/a.js").as_deref(), Some("old"));
+        assert!(!root.join("subscriptions/b.yaml").exists());
+        assert!(!root.join("overrides/b.js").exists());
+        for leftover in [
+            "subscriptions.staging",
+            "subscriptions.old",
+            "overrides.staging",
+            "overrides.old",
+            "proxy.pac.staging",
+        ] {
+            assert!(!root.join(leftover).exists(), "{leftover}");
+        }
+        Ok(())
+    }
+}
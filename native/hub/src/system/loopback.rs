@@ -398,6 +398,69 @@ pub fn set_loopback_exemption(package_family_name: &str, enabled: bool) -> Resul
     }
 }
 
+// 批量保存的结果
+#[derive(Debug, Default)]
+pub struct LoopbackSaveSummary {
+    pub success_count: usize,
+    // 系统保护、无法修改的应用名称
+    pub skipped: Vec<String>,
+    // 设置失败的应用与原因
+    pub errors: Vec<String>,
+}
+
+// 批量保存回环豁免配置
+//
+// 目的：只为 sid_strings 中的容器启用回环豁免，其余容器禁用；状态不变的容器不修改
+#[cfg(windows)]
+pub fn save_loopback_configuration(sid_strings: &[String]) -> Result<LoopbackSaveSummary, String> {
+    let containers = enumerate_app_containers().map_err(|e| format!("无法枚举容器：{}", e))?;
+
+    // 性能优化：使用 HashSet 进行 O(1) 查找，避免 O(n²) 复杂度
+    let enabled_sids: HashSet<&str> = sid_strings.iter().map(|s| s.as_str()).collect();
+    let mut summary = LoopbackSaveSummary::default();
+
+    // 对每个容器，检查是否应该启用（现在是 O(1) 查找）
+    for container in containers {
+        let should_enable = enabled_sids.contains(container.sid_string.as_str());
+
+        if container.is_loopback_enabled != should_enable {
+            log::info!(
+                "修改容器：{}(SID：{}) | {} -> {}",
+                container.display_name,
+                container.sid_string,
+                container.is_loopback_enabled,
+                should_enable
+            );
+
+            if let Err(e) = set_loopback_exemption_by_sid(&container.sid, should_enable) {
+                // 检查是否是系统保护的应用（ERROR_ACCESS_DENIED）
+                if e.contains("0x80070005")
+                    || e.contains("0x00000005")
+                    || e.contains("ERROR_ACCESS_DENIED")
+                {
+                    log::info!("跳过系统保护的应用：{}", container.display_name);
+                    summary.skipped.push(container.display_name.clone());
+                } else {
+                    log::error!("设置容器失败：{} - {}", container.display_name, e);
+                    summary
+                        .errors
+                        .push(format!("{}：{}", container.display_name, e));
+                }
+            } else {
+                summary.success_count += 1;
+            }
+        }
+    }
+
+    log::info!(
+        "配置保存完成，成功：{}，跳过：{}，错误：{}",
+        summary.success_count,
+        summary.skipped.len(),
+        summary.errors.len()
+    );
+    Ok(summary)
+}
+
 // ============================================================================
 // 消息监听初始化
 // ============================================================================
@@ -2,8 +2,13 @@
 //
 // 目的：定义开机自启动、URL 启动、UWP 回环豁免等系统配置的通信接口
 
+use crate::clash::subscription::signals::ProxyMode;
 use crate::system::auto_start;
-use rinf::{DartSignal, RustSignal};
+use crate::system::backup::{
+    AutoBackupPolicy, BackupFormat, BackupListEntry, Progress, RestoreMode, RestoreReport,
+    normalize_path,
+};
+use rinf::{DartSignal, RustSignal, SignalPiece};
 use serde::{Deserialize, Serialize};
 
 // ============================================================================
@@ -257,70 +262,25 @@ pub mod loopback_messages {
         pub fn handle(self) {
             log::info!("处理保存配置请求，期望启用{}个容器", self.sid_strings.len());
 
-            // 获取所有容器
-            let containers = match crate::system::loopback::enumerate_app_containers() {
-                Ok(c) => c,
-                Err(e) => {
-                    log::error!("枚举容器失败：{}", e);
-                    SaveLoopbackConfigurationResult {
-                        success: false,
-                        error_message: Some(format!("无法枚举容器：{}", e)),
-                    }
-                    .send_signal_to_dart();
-                    return;
-                }
-            };
-
-            // 性能优化：使用 HashSet 进行 O(1) 查找，避免 O(n²) 复杂度
-            use std::collections::HashSet;
-            let enabled_sids: HashSet<&str> = self.sid_strings.iter().map(|s| s.as_str()).collect();
-
-            let mut errors = Vec::new();
-            let mut skipped = Vec::new();
-            let mut success_count = 0;
-            let mut skipped_count = 0;
-
-            // 对每个容器，检查是否应该启用（现在是 O(1) 查找）
-            for container in containers {
-                let should_enable = enabled_sids.contains(container.sid_string.as_str());
-
-                if container.is_loopback_enabled != should_enable {
-                    log::info!(
-                        "修改容器：{}(SID：{}) | {} -> {}",
-                        container.display_name,
-                        container.sid_string,
-                        container.is_loopback_enabled,
-                        should_enable
-                    );
-
-                    if let Err(e) = crate::system::loopback::set_loopback_exemption_by_sid(
-                        &container.sid,
-                        should_enable,
-                    ) {
-                        // 检查是否是系统保护的应用（ERROR_ACCESS_DENIED）
-                        if e.contains("0x80070005")
-                            || e.contains("0x00000005")
-                            || e.contains("ERROR_ACCESS_DENIED")
-                        {
-                            log::info!("跳过系统保护的应用：{}", container.display_name);
-                            skipped.push(container.display_name.clone());
-                            skipped_count += 1;
-                        } else {
-                            log::error!("设置容器失败：{} - {}", container.display_name, e);
-                            errors.push(format!("{}：{}", container.display_name, e));
+            let summary =
+                match crate::system::loopback::save_loopback_configuration(&self.sid_strings) {
+                    Ok(summary) => summary,
+                    Err(e) => {
+                        log::error!("保存配置失败：{}", e);
+                        SaveLoopbackConfigurationResult {
+                            success: false,
+                            error_message: Some(e),
                         }
-                    } else {
-                        success_count += 1;
+                        .send_signal_to_dart();
+                        return;
                     }
-                }
-            }
-
-            log::info!(
-                "配置保存完成，成功：{}，跳过：{}，错误：{}",
+                };
+            let crate::system::loopback::LoopbackSaveSummary {
                 success_count,
-                skipped_count,
-                errors.len()
-            );
+                skipped,
+                errors,
+            } = summary;
+            let skipped_count = skipped.len();
 
             // 构建结果消息
             let mut message_parts = Vec::new();
@@ -386,6 +346,46 @@ pub struct AppUpdateResult {
     pub download_url: String,
     pub release_notes: String,
     pub html_url: String,
+    pub expected_sha256: Option<String>, // 发布说明中列出的安装包 SHA-256
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：下载应用更新安装包
+//
+// 未提供 expected_sha256 时从同名的 .sha256 附件获取，下载中断后再次请求时断点续传
+#[derive(Debug, Clone, Deserialize, DartSignal)]
+pub struct DownloadAppUpdateRequest {
+    pub download_url: String,
+    pub target_path: String,
+    pub expected_sha256: Option<String>,
+    pub proxy_mode: ProxyMode,
+    pub mixed_port: u16, // Clash 混合端口（用于 Core 代理模式）
+}
+
+// Rust → Dart：安装包下载进度
+#[derive(Debug, Clone, Serialize, RustSignal)]
+pub struct AppUpdateDownloadProgress {
+    pub received: u64,
+    pub total: Option<u64>, // 来自 Content-Length，未知时为空
+}
+
+// 安装包下载失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, SignalPiece)]
+pub enum AppUpdateDownloadErrorCode {
+    InvalidRequest = 0,      // 参数无效或不支持的代理模式
+    Network = 1,             // 请求失败或服务器返回错误
+    ChecksumUnavailable = 2, // 没有可用的 SHA-256 校验值
+    ChecksumMismatch = 3,    // 安装包的 SHA-256 与预期不符（文件已删除）
+    Io = 4,                  // 写入文件失败
+}
+
+// Rust → Dart：安装包下载结果
+#[derive(Debug, Clone, Serialize, RustSignal)]
+pub struct AppUpdateDownloadResult {
+    pub success: bool,
+    pub path: Option<String>,   // 校验通过的安装包路径
+    pub sha256: Option<String>, // 校验通过的 SHA-256
+    pub error_code: Option<AppUpdateDownloadErrorCode>,
     pub error_message: Option<String>,
 }
 
@@ -414,6 +414,7 @@ impl CheckAppUpdateRequest {
                         download_url: update_result.download_url.unwrap_or_default(),
                         release_notes: update_result.release_notes.unwrap_or_default(),
                         html_url: update_result.html_url.unwrap_or_default(),
+                        expected_sha256: update_result.expected_sha256,
                         error_message: None,
                     }
                     .send_signal_to_dart();
@@ -428,6 +429,7 @@ impl CheckAppUpdateRequest {
                         download_url: String::new(),
                         release_notes: String::new(),
                         html_url: String::new(),
+                        expected_sha256: None,
                         error_message: Some(e),
                     }
                     .send_signal_to_dart();
@@ -437,6 +439,54 @@ impl CheckAppUpdateRequest {
     }
 }
 
+impl DownloadAppUpdateRequest {
+    pub async fn handle(self) {
+        log::info!(
+            "下载应用更新：{} -> {}",
+            self.download_url,
+            self.target_path
+        );
+
+        let on_progress = |received, total| {
+            AppUpdateDownloadProgress { received, total }.send_signal_to_dart();
+        };
+        let result = crate::system::app_update::download_update(
+            &self.download_url,
+            &normalize_path(&self.target_path),
+            self.expected_sha256.as_deref(),
+            self.proxy_mode,
+            self.mixed_port,
+            &on_progress,
+        )
+        .await;
+
+        match result {
+            Ok(downloaded) => {
+                log::info!("应用更新下载完成：{}", downloaded.path.display());
+                AppUpdateDownloadResult {
+                    success: true,
+                    path: Some(downloaded.path.to_string_lossy().into_owned()),
+                    sha256: Some(downloaded.sha256),
+                    error_code: None,
+                    error_message: None,
+                }
+                .send_signal_to_dart();
+            }
+            Err(e) => {
+                log::error!("应用更新下载失败（{:?}）：{}", e.code, e.message);
+                AppUpdateDownloadResult {
+                    success: false,
+                    path: None,
+                    sha256: None,
+                    error_code: Some(e.code),
+                    error_message: Some(e.message),
+                }
+                .send_signal_to_dart();
+            }
+        }
+    }
+}
+
 // ============================================================================
 // 备份与还原消息协议
 // ============================================================================
@@ -447,6 +497,15 @@ pub struct CreateBackupRequest {
     pub target_path: String,
     pub app_data_path: String,
     pub app_version: String,
+    // 未指定时使用 ZIP 格式
+    #[serde(default)]
+    pub format: BackupFormat,
+    // 未指定时压缩
+    #[serde(default)]
+    pub compress: Option<bool>,
+    // 为空时不加密
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
 // Dart → Rust：还原备份请求
@@ -454,6 +513,201 @@ pub struct CreateBackupRequest {
 pub struct RestoreBackupRequest {
     pub backup_path: String,
     pub app_data_path: String,
+    // 加密备份的密码
+    #[serde(default)]
+    pub password: Option<String>,
+    // 只检查备份，不写入任何文件，结果以 RestoreDryRunResult 返回
+    #[serde(default)]
+    pub dry_run: bool,
+    // 未指定时替换现有数据
+    #[serde(default)]
+    pub mode: RestoreMode,
+}
+
+// Dart → Rust：校验备份请求（不还原）
+#[derive(Deserialize, DartSignal)]
+pub struct VerifyBackupRequest {
+    pub path: String,
+    // 加密备份的密码
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+// Rust → Dart：备份校验结果
+#[derive(Serialize, RustSignal)]
+pub struct VerifyBackupResult {
+    pub valid: bool,
+    pub version: Option<String>,
+    pub timestamp: Option<String>,
+    pub platform: Option<String>,
+    // 旧版备份没有校验和，无法确认内容完整
+    pub checksum_missing: bool,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：读取备份概要请求
+#[derive(Deserialize, DartSignal)]
+pub struct InspectBackupRequest {
+    pub backup_path: String,
+}
+
+// Rust → Dart：备份概要
+//
+// 加密备份只返回 encrypted，其余内容需要解密后才能得知
+#[derive(Serialize, RustSignal)]
+pub struct BackupMetadataResponse {
+    pub success: bool,
+    pub encrypted: bool,
+    pub compressed: bool,
+    pub format: Option<BackupFormat>,
+    pub version: Option<String>,
+    pub timestamp: Option<String>,
+    pub app_version: Option<String>,
+    pub platform: Option<String>,
+    pub subscription_count: u32,
+    pub subscription_size: u64,
+    pub override_count: u32,
+    pub override_size: u64,
+    pub error_message: Option<String>,
+}
+
+// 试运行报告中一个分类的统计
+#[derive(Serialize, SignalPiece)]
+pub struct RestoreDryRunSection {
+    // preferences、subscriptions、overrides、other
+    pub name: String,
+    pub file_count: u32,
+    pub total_size: u64,
+}
+
+// Rust → Dart：试运行还原结果
+#[derive(Serialize, RustSignal)]
+pub struct RestoreDryRunResult {
+    // 没有发现任何问题，可以还原
+    pub success: bool,
+    pub version: Option<String>,
+    pub timestamp: Option<String>,
+    pub platform: Option<String>,
+    pub checksum_missing: bool,
+    pub sections: Vec<RestoreDryRunSection>,
+    // 不属于备份内容、还原时会跳过的文件
+    pub skipped: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl RestoreDryRunResult {
+    fn sections(report: &RestoreReport) -> Vec<RestoreDryRunSection> {
+        [
+            ("preferences", report.preferences),
+            ("subscriptions", report.subscriptions),
+            ("overrides", report.overrides),
+            ("other", report.other),
+        ]
+        .into_iter()
+        .map(|(name, section)| RestoreDryRunSection {
+            name: name.to_string(),
+            file_count: section.file_count,
+            total_size: section.total_size,
+        })
+        .collect()
+    }
+}
+
+// Dart → Rust：列出目录中的备份
+#[derive(Deserialize, DartSignal)]
+pub struct ListBackupsRequest {
+    pub directory: String,
+}
+
+// 备份列表中的一项
+//
+// 加密备份只有 encrypted 与文件信息；读取失败时 error 为失败原因
+#[derive(Serialize, SignalPiece)]
+pub struct BackupListItem {
+    pub path: String,
+    pub file_name: String,
+    pub size: u64,
+    pub modified: Option<String>,
+    pub encrypted: bool,
+    pub compressed: bool,
+    pub format: Option<BackupFormat>,
+    pub version: Option<String>,
+    pub timestamp: Option<String>,
+    pub app_version: Option<String>,
+    pub platform: Option<String>,
+    pub error: Option<String>,
+}
+
+impl From<BackupListEntry> for BackupListItem {
+    fn from(entry: BackupListEntry) -> Self {
+        Self {
+            path: entry.path,
+            file_name: entry.file_name,
+            size: entry.size,
+            modified: entry.modified,
+            encrypted: entry.encrypted,
+            compressed: entry.compressed,
+            format: entry.format,
+            version: entry.version,
+            timestamp: entry.timestamp,
+            app_version: entry.app_version,
+            platform: entry.platform,
+            error: entry.error,
+        }
+    }
+}
+
+// Rust → Dart：备份列表（按时间从新到旧排序）
+#[derive(Serialize, RustSignal)]
+pub struct BackupListResponse {
+    pub success: bool,
+    pub backups: Vec<BackupListItem>,
+    pub error_message: Option<String>,
+}
+
+// Dart → Rust：配置定时自动备份（持久化，应用启动时恢复）
+#[derive(Deserialize, DartSignal)]
+pub struct ConfigureAutoBackup {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub target_dir: String,
+    // 保留的自动备份数量，0 表示不删除
+    pub max_keep: u32,
+    pub app_data_path: String,
+    pub app_version: String,
+}
+
+// Rust → Dart：一次自动备份完成
+#[derive(Serialize, RustSignal)]
+pub struct AutoBackupCompleted {
+    pub success: bool,
+    pub path: Option<String>,
+    pub error_message: Option<String>,
+}
+
+// Rust → Dart：备份或还原进度（最终结果仍以 BackupOperationResult 通知）
+#[derive(Serialize, RustSignal)]
+pub struct BackupProgress {
+    // collecting_preferences、writing_archive、restoring_subscriptions 等
+    pub phase: String,
+    pub current: u32,
+    // 为 0 时总数未知
+    pub total: u32,
+    // 当前处理的文件
+    pub detail: String,
+}
+
+// 以 BackupProgress 信号上报进度
+fn backup_progress() -> Progress {
+    Progress::new(|event| {
+        BackupProgress {
+            phase: event.phase.as_str().to_string(),
+            current: event.current,
+            total: event.total,
+            detail: event.detail,
+        }
+        .send_signal_to_dart();
+    })
 }
 
 // Rust → Dart：备份操作响应
@@ -470,18 +724,22 @@ impl CreateBackupRequest {
         log::info!("收到创建备份请求：{}", self.target_path);
 
         let result = crate::system::backup::create_backup(
-            &self.target_path,
-            &self.app_data_path,
+            &normalize_path(&self.target_path),
+            &normalize_path(&self.app_data_path),
             &self.app_version,
+            self.format,
+            self.compress.unwrap_or(true),
+            self.password.as_deref(),
+            &backup_progress(),
         )
         .await;
 
         let response = match result {
             Ok(path) => {
-                log::info!("备份创建成功：{}", path);
+                log::info!("备份创建成功：{}", path.display());
                 BackupOperationResult {
                     success: true,
-                    message: path,
+                    message: path.to_string_lossy().into_owned(),
                     error_message: None,
                 }
             }
@@ -504,15 +762,36 @@ impl RestoreBackupRequest {
     pub async fn handle(self) {
         log::info!("收到还原备份请求：{}", self.backup_path);
 
-        let result =
-            crate::system::backup::restore_backup(&self.backup_path, &self.app_data_path).await;
+        if self.dry_run {
+            self.handle_dry_run().await;
+            return;
+        }
+
+        let result = crate::system::backup::restore_backup(
+            &normalize_path(&self.backup_path),
+            &normalize_path(&self.app_data_path),
+            self.password.as_deref(),
+            self.mode,
+            &backup_progress(),
+        )
+        .await;
 
         let response = match result {
-            Ok(()) => {
+            Ok(outcome) => {
                 log::info!("备份还原成功");
+                let mut message = "备份还原成功".to_string();
+                if !outcome.conflicts.is_empty() {
+                    message.push_str(&format!(
+                        "，以下条目与本地不同，已使用备份中的版本：{}",
+                        outcome.conflicts.join("，")
+                    ));
+                }
+                for warning in &outcome.warnings {
+                    message.push_str(&format!("；{}", warning));
+                }
                 BackupOperationResult {
                     success: true,
-                    message: "备份还原成功".to_string(),
+                    message,
                     error_message: None,
                 }
             }
@@ -529,3 +808,220 @@ impl RestoreBackupRequest {
         response.send_signal_to_dart();
     }
 }
+
+impl RestoreBackupRequest {
+    // 试运行还原
+    async fn handle_dry_run(self) {
+        let result = crate::system::backup::dry_run_restore(
+            &normalize_path(&self.backup_path),
+            self.password.as_deref(),
+            &backup_progress(),
+        )
+        .await;
+
+        let response = match result {
+            Ok(info) => {
+                log::info!(
+                    "试运行还原完成：{} 个问题，{} 个文件将被跳过",
+                    info.report.problems.len(),
+                    info.report.skipped.len()
+                );
+                RestoreDryRunResult {
+                    success: info.report.problems.is_empty(),
+                    sections: RestoreDryRunResult::sections(&info.report),
+                    version: Some(info.version),
+                    timestamp: Some(info.timestamp),
+                    platform: Some(info.platform),
+                    checksum_missing: info.checksum_missing,
+                    skipped: info.report.skipped,
+                    errors: info.report.problems,
+                }
+            }
+            Err(e) => {
+                log::warn!("试运行还原失败：{}", e);
+                RestoreDryRunResult {
+                    success: false,
+                    version: None,
+                    timestamp: None,
+                    platform: None,
+                    checksum_missing: false,
+                    sections: Vec::new(),
+                    skipped: Vec::new(),
+                    errors: vec![e.to_string()],
+                }
+            }
+        };
+
+        response.send_signal_to_dart();
+    }
+}
+
+impl ConfigureAutoBackup {
+    // 处理配置自动备份请求
+    pub fn handle(self) {
+        if self.enabled && (self.interval_hours == 0 || self.target_dir.is_empty()) {
+            log::warn!(
+                "忽略无效的自动备份策略：间隔 {} 小时，目录 {}",
+                self.interval_hours,
+                self.target_dir
+            );
+            return;
+        }
+
+        log::info!(
+            "更新自动备份策略：启用 {}，间隔 {} 小时，目录 {}，保留 {} 个",
+            self.enabled,
+            self.interval_hours,
+            self.target_dir,
+            self.max_keep
+        );
+        let policy = AutoBackupPolicy {
+            enabled: self.enabled,
+            interval_hours: self.interval_hours,
+            target_dir: normalize_path(&self.target_dir),
+            max_keep: self.max_keep,
+            app_data_path: normalize_path(&self.app_data_path),
+            app_version: self.app_version,
+        };
+        if let Err(e) = crate::system::backup::configure_auto_backup(&policy) {
+            log::warn!("保存自动备份策略失败：{}", e);
+        }
+    }
+}
+
+// 以 AutoBackupCompleted 信号通知自动备份结果
+pub fn send_auto_backup_completed(result: Result<String, String>) {
+    let response = match result {
+        Ok(path) => AutoBackupCompleted {
+            success: true,
+            path: Some(path),
+            error_message: None,
+        },
+        Err(e) => AutoBackupCompleted {
+            success: false,
+            path: None,
+            error_message: Some(e),
+        },
+    };
+    response.send_signal_to_dart();
+}
+
+impl VerifyBackupRequest {
+    // 处理校验备份请求
+    pub async fn handle(self) {
+        log::info!("收到校验备份请求：{}", self.path);
+
+        let result = crate::system::backup::verify_backup(
+            &normalize_path(&self.path),
+            self.password.as_deref(),
+        )
+        .await;
+
+        let response = match result {
+            Ok(info) => {
+                log::info!(
+                    "备份校验通过：版本 {}，时间 {}，平台 {}",
+                    info.version,
+                    info.timestamp,
+                    info.platform
+                );
+                VerifyBackupResult {
+                    valid: true,
+                    version: Some(info.version),
+                    timestamp: Some(info.timestamp),
+                    platform: Some(info.platform),
+                    checksum_missing: info.checksum_missing,
+                    error_message: None,
+                }
+            }
+            Err(e) => {
+                log::warn!("备份校验失败：{}", e);
+                VerifyBackupResult {
+                    valid: false,
+                    version: None,
+                    timestamp: None,
+                    platform: None,
+                    checksum_missing: false,
+                    error_message: Some(e.to_string()),
+                }
+            }
+        };
+
+        response.send_signal_to_dart();
+    }
+}
+
+impl InspectBackupRequest {
+    // 处理读取备份概要请求
+    pub async fn handle(self) {
+        log::info!("收到读取备份概要请求：{}", self.backup_path);
+
+        let response =
+            match crate::system::backup::inspect_backup(&normalize_path(&self.backup_path)).await {
+                Ok(summary) => BackupMetadataResponse {
+                    success: true,
+                    encrypted: summary.encrypted,
+                    compressed: summary.compressed,
+                    format: summary.format,
+                    version: summary.version,
+                    timestamp: summary.timestamp,
+                    app_version: summary.app_version,
+                    platform: summary.platform,
+                    subscription_count: summary.subscription_count,
+                    subscription_size: summary.subscription_size,
+                    override_count: summary.override_count,
+                    override_size: summary.override_size,
+                    error_message: None,
+                },
+                Err(e) => {
+                    log::warn!("读取备份概要失败：{}", e);
+                    BackupMetadataResponse {
+                        success: false,
+                        encrypted: false,
+                        compressed: false,
+                        format: None,
+                        version: None,
+                        timestamp: None,
+                        app_version: None,
+                        platform: None,
+                        subscription_count: 0,
+                        subscription_size: 0,
+                        override_count: 0,
+                        override_size: 0,
+                        error_message: Some(e.to_string()),
+                    }
+                }
+            };
+
+        response.send_signal_to_dart();
+    }
+}
+
+impl ListBackupsRequest {
+    // 处理列出备份请求
+    pub async fn handle(self) {
+        log::info!("收到列出备份请求：{}", self.directory);
+
+        let response =
+            match crate::system::backup::list_backups(&normalize_path(&self.directory)).await {
+                Ok(backups) => {
+                    log::info!("找到 {} 个备份", backups.len());
+                    BackupListResponse {
+                        success: true,
+                        backups: backups.into_iter().map(BackupListItem::from).collect(),
+                        error_message: None,
+                    }
+                }
+                Err(e) => {
+                    log::warn!("列出备份失败：{}", e);
+                    BackupListResponse {
+                        success: false,
+                        backups: Vec::new(),
+                        error_message: Some(e.to_string()),
+                    }
+                }
+            };
+
+        response.send_signal_to_dart();
+    }
+}
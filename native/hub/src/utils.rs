@@ -1,3 +1,4 @@
+pub mod hash;
 pub mod init_logger;
 mod signals;
 
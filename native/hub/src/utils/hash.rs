@@ -0,0 +1,20 @@
+// 文件哈希
+
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+
+// 分块计算文件的 SHA-256（小写十六进制），不把整个文件读入内存
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
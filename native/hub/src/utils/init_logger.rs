@@ -152,7 +152,7 @@ fn check_and_rotate_log(path: &PathBuf) -> std::io::Result<()> {
 }
 
 // 获取应用数据目录（便携模式：可执行文件同级 data/ 目录）
-fn get_app_data_dir() -> Result<PathBuf, String> {
+pub fn get_app_data_dir() -> Result<PathBuf, String> {
     use std::env;
 
     let binary_path = env::current_exe().map_err(|e| format!("无法获取可执行文件路径：{}", e))?;
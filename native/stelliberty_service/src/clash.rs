@@ -1,6 +1,10 @@
 // Clash 核心管理模块
 
+pub mod controller;
 pub mod manager;
+pub mod output;
+pub mod stats;
+pub mod tun;
 
 // Re-export
 pub use manager::*;
@@ -0,0 +1,296 @@
+// 核心控制接口访问
+//
+// 服务模式下主程序可能还没有控制器密钥，或数据目录属于 root/SYSTEM，
+// 由服务通过核心的本地控制接口（配置中的 external-controller-pipe/unix，不需要密钥）
+// 代为发送请求
+
+use super::RunningParams;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+// 响应最大长度（连接很多时 /connections 可能达到数 MB）
+const MAX_RESPONSE_LEN: u64 = 32 * 1024 * 1024;
+
+// 流式响应中单行的最大长度
+const MAX_STREAM_LINE_LEN: u64 = 64 * 1024;
+
+// 启动检测时单次连接的超时时间
+const PROBE_TIMEOUT: Duration = Duration::from_millis(200);
+
+// 控制接口地址
+pub(super) enum ControllerEndpoint {
+    // Named Pipe 或 Unix Socket
+    Local(String),
+    // external-controller（HTTP API，配置了密钥时会被拒绝）
+    Tcp(String),
+}
+
+// 控制接口请求失败
+#[derive(Debug, Error)]
+pub enum ControllerError {
+    // 无法连接或响应无效
+    #[error("{0}")]
+    Unavailable(String),
+    // 核心返回了错误状态码，body 为核心的响应内容
+    #[error("核心返回 HTTP {status}: {body}")]
+    Rejected { status: u16, body: String },
+}
+
+trait ControllerStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> ControllerStream for S {}
+
+// 向正在运行的核心发送请求，返回响应体
+pub async fn request(
+    params: &RunningParams,
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+) -> Result<String, ControllerError> {
+    let endpoint = running_endpoint(params)?;
+    let mut stream = connect(&endpoint).await?;
+    send_request(&mut stream, method, path, body).await?;
+
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE_LEN)
+        .read_to_end(&mut response)
+        .await
+        .map_err(|e| ControllerError::Unavailable(format!("读取响应失败: {}", e)))?;
+    let response = String::from_utf8_lossy(&response);
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| ControllerError::Unavailable("核心返回了无效的 HTTP 响应".to_string()))?;
+    check_status(head, body.trim())?;
+    Ok(body.to_string())
+}
+
+// 以 GET 订阅核心的流式接口（如 /logs），每行响应体发送到 lines
+//
+// 核心关闭连接或 lines 的接收端被释放时返回，调用方负责在不再需要时中断
+pub async fn stream_lines(
+    params: &RunningParams,
+    path: &str,
+    lines: mpsc::Sender<String>,
+) -> Result<(), ControllerError> {
+    let endpoint = running_endpoint(params)?;
+    let mut stream = connect(&endpoint).await?;
+    send_request(&mut stream, "GET", path, None).await?;
+
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        let read = (&mut reader)
+            .take(MAX_STREAM_LINE_LEN)
+            .read_line(&mut line)
+            .await
+            .map_err(|e| ControllerError::Unavailable(format!("读取响应失败: {}", e)))?;
+        if read == 0 || line.trim().is_empty() {
+            break;
+        }
+        head.push_str(&line);
+    }
+    check_status(&head, "")?;
+
+    loop {
+        let mut line = String::new();
+        match (&mut reader)
+            .take(MAX_STREAM_LINE_LEN)
+            .read_line(&mut line)
+            .await
+        {
+            Ok(0) | Err(_) => return Ok(()),
+            Ok(_) => {}
+        }
+        let line = line.trim();
+        if !line.is_empty() && lines.send(line.to_string()).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// 优先使用配置中的本地控制接口，其次使用 external-controller
+pub(super) fn controller_endpoint(
+    config_path: &str,
+    external_controller: &str,
+) -> Option<ControllerEndpoint> {
+    #[cfg(windows)]
+    const LOCAL_KEY: &str = "external-controller-pipe";
+    #[cfg(not(windows))]
+    const LOCAL_KEY: &str = "external-controller-unix";
+
+    let config = std::fs::read_to_string(config_path).ok();
+    if let Some(path) = config
+        .as_deref()
+        .and_then(|c| top_level_value(c, LOCAL_KEY))
+    {
+        return Some(ControllerEndpoint::Local(path));
+    }
+
+    (!external_controller.is_empty())
+        .then(|| ControllerEndpoint::Tcp(external_controller.to_string()))
+}
+
+impl ControllerEndpoint {
+    // 控制接口是否已可以连接（同步，用于启动检测）
+    pub(super) fn is_reachable(&self) -> bool {
+        match self {
+            #[cfg(windows)]
+            Self::Local(path) => std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .is_ok(),
+            #[cfg(not(windows))]
+            Self::Local(path) => std::os::unix::net::UnixStream::connect(path).is_ok(),
+            Self::Tcp(address) => {
+                use std::net::ToSocketAddrs;
+                connect_address(address)
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addrs| addrs.next())
+                    .is_some_and(|addr| {
+                        std::net::TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok()
+                    })
+            }
+        }
+    }
+}
+
+fn running_endpoint(params: &RunningParams) -> Result<ControllerEndpoint, ControllerError> {
+    controller_endpoint(&params.config_path, &params.external_controller)
+        .ok_or_else(|| ControllerError::Unavailable("核心未启用任何控制接口".to_string()))
+}
+
+async fn connect(
+    endpoint: &ControllerEndpoint,
+) -> Result<Box<dyn ControllerStream>, ControllerError> {
+    let unavailable =
+        |e: std::io::Error| ControllerError::Unavailable(format!("连接核心控制接口失败: {}", e));
+    match endpoint {
+        #[cfg(windows)]
+        ControllerEndpoint::Local(path) => {
+            let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+                .open(path)
+                .map_err(unavailable)?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(not(windows))]
+        ControllerEndpoint::Local(path) => {
+            let stream = tokio::net::UnixStream::connect(path)
+                .await
+                .map_err(unavailable)?;
+            Ok(Box::new(stream))
+        }
+        ControllerEndpoint::Tcp(address) => {
+            let stream = tokio::net::TcpStream::connect(connect_address(address))
+                .await
+                .map_err(unavailable)?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+// 监听地址转换为可连接的地址（0.0.0.0、[::] 或省略主机时连接本机）
+fn connect_address(address: &str) -> String {
+    match address.rsplit_once(':') {
+        Some(("" | "0.0.0.0", port)) => format!("127.0.0.1:{}", port),
+        Some(("[::]", port)) => format!("[::1]:{}", port),
+        _ => address.to_string(),
+    }
+}
+
+// 读取 YAML 顶层的标量值（配置由主程序生成，顶层键不缩进）
+fn top_level_value(config: &str, key: &str) -> Option<String> {
+    config.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix(':')?.trim();
+        let value = value
+            .strip_prefix('\'')
+            .and_then(|v| v.strip_suffix('\''))
+            .or_else(|| value.strip_prefix('"').and_then(|v| v.strip_suffix('"')))
+            .unwrap_or(value);
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+// 发送 HTTP/1.0 请求
+//
+// 使用 HTTP/1.0：核心不会使用分块编码，响应在连接关闭时结束
+async fn send_request<S>(
+    stream: &mut S,
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+) -> Result<(), ControllerError>
+where
+    S: AsyncWrite + Unpin + ?Sized,
+{
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: localhost\r\n", method, path);
+    if let Some(body) = body {
+        request.push_str(&format!(
+            "Content-Type: application/json\r\nContent-Length: {}\r\n",
+            body.len()
+        ));
+    }
+    request.push_str("\r\n");
+    request.push_str(body.unwrap_or_default());
+
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|e| ControllerError::Unavailable(format!("发送请求失败: {}", e)))
+}
+
+// 检查状态行，非 2xx 时返回核心的响应内容
+fn check_status(head: &str, body: &str) -> Result<(), ControllerError> {
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| ControllerError::Unavailable("核心返回了无效的 HTTP 状态行".to_string()))?;
+
+    match status {
+        200..=299 => Ok(()),
+        401 => Err(ControllerError::Unavailable(
+            "核心控制接口需要密钥".to_string(),
+        )),
+        _ => Err(ControllerError::Rejected {
+            status,
+            body: body.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_and_status() {
+        let config = "mixed-port: 7890\nexternal-controller-unix: '/tmp/stelliberty.sock'\n\
+                      dns:\n  external-controller-unix: nested\n";
+        assert_eq!(
+            top_level_value(config, "external-controller-unix").as_deref(),
+            Some("/tmp/stelliberty.sock")
+        );
+        assert_eq!(top_level_value(config, "external-controller"), None);
+        assert_eq!(connect_address("0.0.0.0:9090"), "127.0.0.1:9090");
+        assert_eq!(connect_address(":9090"), "127.0.0.1:9090");
+        assert_eq!(connect_address("[::]:9090"), "[::1]:9090");
+        assert_eq!(connect_address("127.0.0.1:9090"), "127.0.0.1:9090");
+
+        assert!(check_status("HTTP/1.0 204 No Content", "").is_ok());
+        assert!(matches!(
+            check_status("HTTP/1.0 400 Bad Request", r#"{"message":"Body invalid"}"#),
+            Err(ControllerError::Rejected { status: 400, body }) if body.contains("Body invalid")
+        ));
+        assert!(matches!(
+            check_status("HTTP/1.0 401 Unauthorized", ""),
+            Err(ControllerError::Unavailable(_))
+        ));
+    }
+}
@@ -1,7 +1,22 @@
 // Clash 核心进程管理器
 
+use super::controller;
+use super::output::{CAPTURE_WINDOW, StartupOutput};
 use std::process::{Child, Command, Stdio};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 默认的优雅停止等待时间，超过后强制终止
+pub const DEFAULT_FORCE_AFTER: Duration = Duration::from_secs(3);
+
+// 等待进程退出时的轮询间隔
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// 核心退出后等待读取剩余输出的最长时间（派生的子进程可能继续持有管道）
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+// 未启用控制接口时，启动后检查核心是否立即退出的等待时间
+const STARTUP_EXIT_CHECK_DELAY: Duration = Duration::from_millis(500);
 
 // Clash 进程状态
 #[derive(Debug, Clone)]
@@ -10,8 +25,27 @@ pub struct ClashStatus {
     pub running: bool,
     // 进程 PID
     pub pid: Option<u32>,
-    // 运行时长（秒）
-    pub uptime: u64,
+    // 运行时长（秒），未记录启动时间时为 None
+    pub uptime: Option<u64>,
+}
+
+// 停止核心的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopOutcome {
+    // 核心未在等待时间内退出，已被强制终止
+    pub forced: bool,
+    // 进程退出码，核心未运行或被信号终止时为 None
+    pub exit_code: Option<i32>,
+}
+
+// 核心启动参数（用于主程序重启后接管已运行的核心）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningParams {
+    pub core_path: String,
+    pub config_path: String,
+    pub data_dir: String,
+    pub external_controller: String,
+    pub tun_device: Option<String>,
 }
 
 // Clash 管理器
@@ -26,6 +60,10 @@ pub struct ClashManager {
     api_host: Option<String>,
     // API 端口
     api_port: Option<u16>,
+    // 外部控制器地址（空字符串表示禁用）
+    external_controller: Option<String>,
+    // TUN 设备名（启用 TUN 时记录，停止后用于清理）
+    tun_device: Option<String>,
     // 子进程句柄（使用 Mutex 实现内部可变性）
     child: Mutex<Option<Child>>,
     // 启动时间
@@ -40,6 +78,8 @@ impl Default for ClashManager {
             data_dir: None,
             api_host: None,
             api_port: None,
+            external_controller: None,
+            tun_device: None,
             child: Mutex::new(None),
             start_time: Mutex::new(None),
         }
@@ -59,6 +99,7 @@ impl ClashManager {
         config_path: String,
         data_dir: String,
         external_controller: String,
+        tun_device: Option<String>,
     ) -> Result<(), String> {
         // 如果已经在运行，先停止
         if self.is_running() {
@@ -66,6 +107,11 @@ impl ClashManager {
             self.stop()?;
         }
 
+        // 上次 TUN 核心崩溃退出时可能残留网卡和路由
+        if let Some(device) = self.tun_device.take() {
+            super::tun::cleanup(&device);
+        }
+
         log::info!("启动 Clash 核心");
         log::info!("核心路径: {}", core_path);
         log::info!("配置文件: {}", config_path);
@@ -115,11 +161,11 @@ impl ClashManager {
 
         log::debug!("Clash 启动参数: {:?}", args);
 
-        // 启动进程，重定向输出防止缓冲区阻塞
-        let child = Command::new(&core_path)
+        // 启动进程，输出由读取线程持续消费，防止缓冲区填满导致进程阻塞
+        let mut child = Command::new(&core_path)
             .args(&args)
-            .stdout(Stdio::null()) // 防止输出缓冲区填满导致进程阻塞
-            .stderr(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
             .map_err(|e| {
                 let error_msg = format!(
@@ -136,12 +182,16 @@ impl ClashManager {
             })?;
 
         let pid = child.id();
+        let output = StartupOutput::capture(&mut child);
+        Self::wait_startup(&mut child, &output, &config_path, &external_controller)?;
 
         self.core_path = Some(core_path);
         self.config_path = Some(config_path);
         self.data_dir = Some(data_dir);
         self.api_host = None;
         self.api_port = None;
+        self.external_controller = Some(external_controller);
+        self.tun_device = tun_device;
 
         *self.child.lock().unwrap_or_else(|e| {
             log::warn!("Child 锁中毒，正在恢复");
@@ -156,6 +206,75 @@ impl ClashManager {
         Ok(())
     }
 
+    // 等待核心完成启动
+    //
+    // 捕获窗口内核心退出，或控制接口始终无法连接时，返回带有核心输出的错误
+    fn wait_startup(
+        child: &mut Child,
+        output: &StartupOutput,
+        config_path: &str,
+        external_controller: &str,
+    ) -> Result<(), String> {
+        let Some(endpoint) = controller::controller_endpoint(config_path, external_controller)
+        else {
+            // 未启用控制接口时只能通过进程是否退出判断
+            std::thread::sleep(STARTUP_EXIT_CHECK_DELAY);
+            return match child.try_wait() {
+                Ok(Some(status)) => Err(Self::startup_error(&status, output)),
+                _ => Ok(()),
+            };
+        };
+
+        let deadline = Instant::now() + CAPTURE_WINDOW;
+        loop {
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(Self::startup_error(&status, output));
+            }
+            if endpoint.is_reachable() {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(STOP_POLL_INTERVAL);
+        }
+
+        let _ = child.kill();
+        let _ = child.wait();
+        let error_msg = format!(
+            "Clash 核心在 {} 秒内未开放控制接口，已终止{}",
+            CAPTURE_WINDOW.as_secs(),
+            Self::format_core_output(output)
+        );
+        log::error!("{}", error_msg);
+        Err(error_msg)
+    }
+
+    fn startup_error(status: &std::process::ExitStatus, output: &StartupOutput) -> String {
+        output.wait_drained(OUTPUT_DRAIN_TIMEOUT);
+        let exit_info = match status.code() {
+            Some(code) => format!("退出码: {}", code),
+            None => "被信号终止".to_string(),
+        };
+        let error_msg = format!(
+            "Clash 核心启动后立即退出 ({}){}",
+            exit_info,
+            Self::format_core_output(output)
+        );
+        log::error!("{}", error_msg);
+        error_msg
+    }
+
+    // 附加到错误信息中的核心输出
+    fn format_core_output(output: &StartupOutput) -> String {
+        let text = output.text();
+        if text.is_empty() {
+            "\n核心没有输出任何内容".to_string()
+        } else {
+            format!("\n核心输出:\n{}", text)
+        }
+    }
+
     // 强制停止 Clash（Windows 使用 taskkill）
     #[cfg(windows)]
     fn force_kill_windows(pid: u32) -> Result<(), String> {
@@ -176,85 +295,134 @@ impl ClashManager {
         }
     }
 
-    // 停止 Clash 核心（改进版：带强制清理）
+    // 停止 Clash 核心（使用默认的优雅停止等待时间）
     pub fn stop(&mut self) -> Result<(), String> {
+        self.stop_with_timeout(DEFAULT_FORCE_AFTER).map(|_| ())
+    }
+
+    // 停止 Clash 核心：先发送优雅停止信号，超过 force_after 仍未退出时强制终止
+    pub fn stop_with_timeout(&mut self, force_after: Duration) -> Result<StopOutcome, String> {
         let mut child_guard = self.child.lock().unwrap_or_else(|e| {
             log::warn!("Child 锁中毒，正在恢复");
             e.into_inner()
         });
 
-        if let Some(mut child) = child_guard.take() {
-            let pid = child.id();
-            log::info!("停止 Clash 核心 (PID: {})", pid);
+        let outcome = if let Some(child) = child_guard.take() {
+            let outcome = Self::terminate(child, force_after)?;
 
-            // 先尝试优雅停止
-            match child.kill() {
-                Ok(_) => {
-                    log::debug!("已发送 kill 信号到 PID={}", pid);
+            // 清空状态
+            *self.start_time.lock().unwrap_or_else(|e| {
+                log::warn!("StartTime 锁中毒，正在恢复");
+                e.into_inner()
+            }) = None;
+            outcome
+        } else {
+            log::debug!("Clash 未运行，无需停止");
+            StopOutcome {
+                forced: false,
+                exit_code: None,
+            }
+        };
+        drop(child_guard);
 
-                    // 在子线程中等待，避免阻塞
-                    let wait_handle = std::thread::spawn(move || child.wait());
+        // 无论核心是否已崩溃退出，都清理 TUN 网卡和路由
+        if let Some(device) = self.tun_device.take() {
+            super::tun::cleanup(&device);
+        }
 
-                    // 等待最多 3 秒
-                    let wait_result = wait_handle.join_timeout(std::time::Duration::from_secs(3));
+        Ok(outcome)
+    }
 
-                    match wait_result {
-                        Ok(Ok(Ok(_))) => {
+    // 终止核心进程并回收退出状态
+    fn terminate(mut child: Child, force_after: Duration) -> Result<StopOutcome, String> {
+        let pid = child.id();
+        log::info!("停止 Clash 核心 (PID: {})", pid);
+
+        match Self::send_stop_signal(pid) {
+            Ok(()) => {
+                log::debug!("已发送停止信号到 PID={}", pid);
+                let deadline = Instant::now() + force_after;
+                loop {
+                    match child.try_wait() {
+                        Ok(Some(status)) => {
                             log::info!("Clash 核心已正常停止 (PID: {})", pid);
+                            return Ok(StopOutcome {
+                                forced: false,
+                                exit_code: status.code(),
+                            });
                         }
-                        Ok(Ok(Err(e))) => {
-                            log::warn!("等待进程退出失败: {}, 尝试强制清理", e);
-                            #[cfg(windows)]
-                            {
-                                let _ = Self::force_kill_windows(pid);
-                            }
+                        Ok(None) if Instant::now() < deadline => {
+                            std::thread::sleep(STOP_POLL_INTERVAL);
                         }
-                        Ok(Err(_)) => {
-                            log::error!("等待进程超时 (3秒)，强制清理 PID={}", pid);
-                            #[cfg(windows)]
-                            {
-                                let _ = Self::force_kill_windows(pid);
-                            }
+                        Ok(None) => {
+                            log::error!(
+                                "等待进程超时 ({} 毫秒)，强制清理 PID={}",
+                                force_after.as_millis(),
+                                pid
+                            );
+                            break;
                         }
-                        Err(_) => {
-                            log::error!("等待进程线程 panic，尝试强制清理 PID={}", pid);
-                            #[cfg(windows)]
-                            {
-                                let _ = Self::force_kill_windows(pid);
-                            }
+                        Err(e) => {
+                            log::warn!("等待进程退出失败: {}, 尝试强制清理", e);
+                            break;
                         }
                     }
                 }
-                Err(e) => {
-                    log::error!("发送 kill 信号失败: {}, 尝试强制清理", e);
-                    #[cfg(windows)]
-                    {
-                        let _ = Self::force_kill_windows(pid);
-                    }
-                    #[cfg(not(windows))]
-                    {
-                        let error_msg = format!(
-                            "停止 Clash 失败 (PID: {}): {}\n{}",
-                            pid,
-                            e,
-                            Self::format_io_error_hint(&e)
-                        );
-                        log::error!("{}", error_msg);
-                        return Err(error_msg);
-                    }
-                }
             }
+            Err(e) => {
+                log::warn!("发送停止信号失败: {}, 尝试强制清理", e);
+            }
+        }
 
-            // 清空状态
-            *self.start_time.lock().unwrap_or_else(|e| {
-                log::warn!("StartTime 锁中毒，正在恢复");
-                e.into_inner()
-            }) = None;
+        #[cfg(windows)]
+        if Self::force_kill_windows(pid).is_err() {
+            let _ = child.kill();
+        }
+
+        #[cfg(not(windows))]
+        if let Err(e) = child.kill() {
+            let error_msg = format!(
+                "停止 Clash 失败 (PID: {}): {}\n{}",
+                pid,
+                e,
+                Self::format_io_error_hint(&e)
+            );
+            log::error!("{}", error_msg);
+            return Err(error_msg);
+        }
+
+        // 已强制终止，等待回收不会长时间阻塞
+        let exit_code = child.wait().ok().and_then(|status| status.code());
+        Ok(StopOutcome {
+            forced: true,
+            exit_code,
+        })
+    }
+
+    // 发送优雅停止信号（Unix 为 SIGTERM，Windows 为不带 /F 的 taskkill）
+    #[cfg(not(windows))]
+    fn send_stop_signal(pid: u32) -> Result<(), String> {
+        let pid = libc::pid_t::try_from(pid).map_err(|e| e.to_string())?;
+        if unsafe { libc::kill(pid, libc::SIGTERM) } == 0 {
+            Ok(())
         } else {
-            log::debug!("Clash 未运行，无需停止");
+            Err(std::io::Error::last_os_error().to_string())
         }
+    }
 
-        Ok(())
+    // 没有窗口的控制台程序不响应 taskkill，此时会立即失败并转为强制终止
+    #[cfg(windows)]
+    fn send_stop_signal(pid: u32) -> Result<(), String> {
+        let output = Command::new("taskkill")
+            .args(["/T", "/PID", &pid.to_string()])
+            .output()
+            .map_err(|e| format!("执行 taskkill 失败: {}", e))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
     }
 
     // 检查 Clash 是否正在运行（不需要可变引用，支持并发读）
@@ -306,6 +474,21 @@ impl ClashManager {
         }
     }
 
+    // 获取正在运行的核心的启动参数，核心未运行时返回 None
+    pub fn running_params(&self) -> Option<RunningParams> {
+        if !self.is_running() {
+            return None;
+        }
+
+        Some(RunningParams {
+            core_path: self.core_path.clone()?,
+            config_path: self.config_path.clone()?,
+            data_dir: self.data_dir.clone()?,
+            external_controller: self.external_controller.clone()?,
+            tun_device: self.tun_device.clone(),
+        })
+    }
+
     // 获取 Clash 状态（不需要可变引用，支持并发读）
     pub fn get_status(&self) -> ClashStatus {
         let running = self.is_running();
@@ -331,9 +514,8 @@ impl ClashManager {
                     e.into_inner()
                 })
                 .map(|t| t.elapsed().as_secs())
-                .unwrap_or(0)
         } else {
-            0
+            None
         };
 
         ClashStatus {
@@ -383,32 +565,3 @@ impl Drop for ClashManager {
         }
     }
 }
-
-// 扩展 JoinHandle 以支持超时
-trait JoinHandleExt<T> {
-    fn join_timeout(
-        self,
-        duration: std::time::Duration,
-    ) -> Result<Result<T, Box<dyn std::any::Any + Send>>, ()>;
-}
-
-impl<T> JoinHandleExt<T> for std::thread::JoinHandle<T> {
-    fn join_timeout(
-        self,
-        duration: std::time::Duration,
-    ) -> Result<Result<T, Box<dyn std::any::Any + Send>>, ()> {
-        let start = std::time::Instant::now();
-
-        loop {
-            if self.is_finished() {
-                return Ok(self.join());
-            }
-
-            if start.elapsed() >= duration {
-                return Err(());
-            }
-
-            std::thread::sleep(std::time::Duration::from_millis(100));
-        }
-    }
-}
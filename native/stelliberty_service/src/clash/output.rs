@@ -0,0 +1,77 @@
+// 核心启动输出捕获
+//
+// 核心启动失败的原因（配置语法错误、缺少 geoip.dat 等）只会打印到它自己的
+// stdout/stderr。启动后的一小段时间内保存输出的前几 KB，启动失败时返回给主程序；
+// 之后继续读取并丢弃，避免管道写满阻塞核心
+
+use std::io::Read;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// 捕获输出的时间窗口
+pub const CAPTURE_WINDOW: Duration = Duration::from_secs(2);
+
+// 最多保存的字节数
+const CAPTURE_MAX_BYTES: usize = 8 * 1024;
+
+// 核心启动阶段的输出
+pub struct StartupOutput {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl StartupOutput {
+    // 接管子进程的 stdout/stderr 并开始读取（两者写入同一缓冲区）
+    pub fn capture(child: &mut Child) -> Self {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let deadline = Instant::now() + CAPTURE_WINDOW;
+
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(spawn_reader(stdout, buffer.clone(), deadline));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(spawn_reader(stderr, buffer.clone(), deadline));
+        }
+
+        Self { buffer, readers }
+    }
+
+    // 核心退出后等待读取线程读完管道中剩余的输出
+    pub fn wait_drained(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && !self.readers.iter().all(JoinHandle::is_finished) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // 已捕获的输出（去除首尾空白，非 UTF-8 字节按替换字符处理）
+    pub fn text(&self) -> String {
+        let buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&buffer).trim().to_string()
+    }
+}
+
+fn spawn_reader<R>(mut reader: R, buffer: Arc<Mutex<Vec<u8>>>, deadline: Instant) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if Instant::now() >= deadline {
+                continue;
+            }
+
+            let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+            let remaining = CAPTURE_MAX_BYTES.saturating_sub(buffer.len());
+            buffer.extend_from_slice(&chunk[..n.min(remaining)]);
+        }
+    })
+}
@@ -0,0 +1,74 @@
+// 核心流量与连接统计
+//
+// 通过核心的控制接口查询 /connections 摘要（见 controller 模块）
+
+use super::RunningParams;
+use super::controller;
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+use std::time::Duration;
+
+// 查询超时时间
+const STATS_TIMEOUT: Duration = Duration::from_secs(3);
+
+// 核心统计摘要
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClashStats {
+    pub connections: u32,
+    pub upload_total: u64,
+    pub download_total: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawConnections {
+    #[serde(default)]
+    upload_total: u64,
+    #[serde(default)]
+    download_total: u64,
+    // 没有连接时为 null
+    #[serde(default)]
+    connections: Option<Vec<IgnoredAny>>,
+}
+
+// 查询正在运行的核心的统计摘要
+pub async fn query(params: &RunningParams) -> Result<ClashStats, String> {
+    let body = tokio::time::timeout(
+        STATS_TIMEOUT,
+        controller::request(params, "GET", "/connections", None),
+    )
+    .await
+    .map_err(|_| "查询核心统计超时".to_string())?
+    .map_err(|e| e.to_string())?;
+    parse_connections(&body)
+}
+
+fn parse_connections(body: &str) -> Result<ClashStats, String> {
+    let raw: RawConnections =
+        serde_json::from_str(body).map_err(|e| format!("解析连接信息失败: {}", e))?;
+    Ok(ClashStats {
+        connections: raw.connections.map_or(0, |c| c.len() as u32),
+        upload_total: raw.upload_total,
+        download_total: raw.download_total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary() -> Result<(), String> {
+        let stats = parse_connections(
+            r#"{"downloadTotal":2048,"uploadTotal":1024,"connections":[{"id":"a"},{"id":"b"}]}"#,
+        )?;
+        assert_eq!(stats.connections, 2);
+        assert_eq!(stats.upload_total, 1024);
+        assert_eq!(
+            parse_connections(r#"{"downloadTotal":0,"uploadTotal":0,"connections":null}"#)?
+                .connections,
+            0
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,503 @@
+// TUN 模式支持
+//
+// 启动前检查 TUN 前置条件，停止后检测并清理残留的网卡和路由
+
+#[cfg(not(target_os = "macos"))]
+use std::path::Path;
+#[cfg(not(windows))]
+use std::process::Command;
+
+// TUN 残留检测与清理结果
+#[derive(Debug, Clone, Default)]
+pub struct TunCleanupOutcome {
+    // 检测到的残留网卡
+    pub interfaces: Vec<String>,
+    // 检测到的指向残留网卡的路由
+    pub routes: Vec<String>,
+    // 已成功清理的条目
+    pub removed: Vec<String>,
+    // 清理失败的条目（附原因）
+    pub failed: Vec<String>,
+}
+
+impl TunCleanupOutcome {
+    // 是否未检测到任何残留
+    pub fn is_clean(&self) -> bool {
+        self.interfaces.is_empty() && self.routes.is_empty()
+    }
+}
+
+// 默认 TUN 设备名（与 mihomo 默认值保持一致）
+//
+// macOS 上未指定设备名时由系统分配 utunN，无法得知核心创建的是哪个网卡，
+// 此时不做残留检测与清理，避免误删系统或其他 VPN 的 utun 路由
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_TUN_DEVICE: Option<&str> = Some("Meta");
+#[cfg(target_os = "macos")]
+pub const DEFAULT_TUN_DEVICE: Option<&str> = None;
+
+// 检查 TUN 前置条件
+//
+// Windows: 需要 wintun.dll（核心目录或 System32）
+// Linux: 需要 /dev/net/tun 且具备 CAP_NET_ADMIN
+// macOS: 需要 root 权限创建 utun
+pub fn check_prerequisites(core_path: &str) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        let core_dir_dll = Path::new(core_path)
+            .parent()
+            .map(|dir| dir.join("wintun.dll"));
+        let system_dll = std::env::var("SystemRoot")
+            .map(|root| Path::new(&root).join("System32").join("wintun.dll"))
+            .ok();
+
+        let found = core_dir_dll
+            .iter()
+            .chain(system_dll.iter())
+            .any(|path| path.exists());
+
+        if !found {
+            return Err(format!(
+                "未找到 wintun.dll\n提示: 请将 wintun.dll 放置到核心目录（{}）",
+                Path::new(core_path)
+                    .parent()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_default()
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = core_path;
+        if !Path::new("/dev/net/tun").exists() {
+            return Err(
+                "未找到 /dev/net/tun\n提示: 请加载 tun 内核模块（modprobe tun）".to_string(),
+            );
+        }
+        if !has_net_admin_capability() {
+            return Err(
+                "缺少 CAP_NET_ADMIN 权限\n提示: 请以 root 身份运行服务或授予 cap_net_admin"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let _ = core_path;
+        // SAFETY: geteuid 无副作用
+        if unsafe { libc::geteuid() } != 0 {
+            return Err("创建 utun 需要 root 权限\n提示: 请通过服务模式运行".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 检查当前进程是否具备 CAP_NET_ADMIN
+#[cfg(target_os = "linux")]
+fn has_net_admin_capability() -> bool {
+    const CAP_NET_ADMIN: u32 = 12;
+
+    // SAFETY: geteuid 无副作用
+    if unsafe { libc::geteuid() } == 0 {
+        return true;
+    }
+
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("CapEff:"))
+                .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+        })
+        .map(|caps| caps & (1 << CAP_NET_ADMIN) != 0)
+        .unwrap_or(false)
+}
+
+// 检查网卡名是否为指定 TUN 设备（只做精确匹配，不匹配编号前缀）
+fn matches_device(name: &str, device: &str) -> bool {
+    !device.is_empty() && name == device
+}
+
+// 检测 TUN 残留（remove 为 true 时尝试清理）
+//
+// preflight 场景传入 remove = false，仅用于提示其他客户端遗留的状态
+pub fn verify_cleanup(device: &str, remove: bool) -> TunCleanupOutcome {
+    if device.is_empty() {
+        log::debug!("TUN 设备名未知，跳过残留检测");
+        return TunCleanupOutcome::default();
+    }
+
+    let mut outcome = TunCleanupOutcome {
+        interfaces: list_interfaces(device),
+        routes: list_routes(device)
+            .into_iter()
+            .map(|route| route.description)
+            .collect(),
+        ..Default::default()
+    };
+
+    if remove && !outcome.is_clean() {
+        remove_residue(device, &mut outcome);
+    }
+
+    outcome
+}
+
+// 清理 TUN 网卡和路由（核心异常退出时也会调用）
+pub fn cleanup(device: &str) {
+    log::info!("清理 TUN 设备: {}", device);
+
+    let outcome = verify_cleanup(device, true);
+    if outcome.is_clean() {
+        log::debug!("未检测到 TUN 残留: {}", device);
+        return;
+    }
+
+    for item in &outcome.removed {
+        log::info!("已清理 TUN 残留: {}", item);
+    }
+    for item in &outcome.failed {
+        log::warn!("清理 TUN 残留失败: {}", item);
+    }
+}
+
+// 路由条目
+struct RouteEntry {
+    description: String,
+    #[cfg(windows)]
+    row: windows::Win32::NetworkManagement::IpHelper::MIB_IPFORWARD_ROW2,
+    #[cfg(target_os = "macos")]
+    destination: String,
+    #[cfg(target_os = "macos")]
+    interface: String,
+}
+
+// Linux: 通过 /sys/class/net 与 /proc/net/*route 检测
+
+#[cfg(target_os = "linux")]
+fn list_interfaces(device: &str) -> Vec<String> {
+    std::fs::read_dir("/sys/class/net")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| matches_device(name, device))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "linux")]
+fn list_routes(device: &str) -> Vec<RouteEntry> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut routes = Vec::new();
+
+    // IPv4：Iface Destination Gateway Flags RefCnt Use Metric Mask ...（小端十六进制）
+    if let Ok(content) = std::fs::read_to_string("/proc/net/route") {
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || !matches_device(fields[0], device) {
+                continue;
+            }
+            let (Ok(dest), Ok(mask)) = (
+                u32::from_str_radix(fields[1], 16),
+                u32::from_str_radix(fields[7], 16),
+            ) else {
+                continue;
+            };
+            routes.push(RouteEntry {
+                description: format!(
+                    "{}/{} dev {}",
+                    Ipv4Addr::from(dest.to_le_bytes()),
+                    mask.count_ones(),
+                    fields[0]
+                ),
+            });
+        }
+    }
+
+    // IPv6：dest prefix_len src src_len next_hop metric ref use flags iface
+    if let Ok(content) = std::fs::read_to_string("/proc/net/ipv6_route") {
+        for line in content.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 || !matches_device(fields[9], device) {
+                continue;
+            }
+            let (Ok(dest), Ok(prefix_len)) = (
+                u128::from_str_radix(fields[0], 16),
+                u8::from_str_radix(fields[1], 16),
+            ) else {
+                continue;
+            };
+            routes.push(RouteEntry {
+                description: format!("{}/{} dev {}", Ipv6Addr::from(dest), prefix_len, fields[9]),
+            });
+        }
+    }
+
+    routes
+}
+
+#[cfg(target_os = "linux")]
+fn remove_residue(device: &str, outcome: &mut TunCleanupOutcome) {
+    // 删除网卡时内核会一并移除指向它的路由
+    for interface in &outcome.interfaces {
+        match Command::new("ip")
+            .args(["link", "delete", interface])
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                outcome.removed.push(format!("interface {}", interface));
+            }
+            Ok(output) => outcome.failed.push(format!(
+                "interface {}: {}",
+                interface,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) => outcome.failed.push(format!(
+                "interface {}: 执行 ip link delete 失败: {}",
+                interface, e
+            )),
+        }
+    }
+
+    // 网卡删除后仍存在的路由视为清理失败
+    let remaining: Vec<String> = list_routes(device)
+        .into_iter()
+        .map(|route| route.description)
+        .collect();
+    for route in &outcome.routes {
+        if remaining.contains(route) {
+            outcome.failed.push(format!("route {}", route));
+        } else {
+            outcome.removed.push(format!("route {}", route));
+        }
+    }
+}
+
+// Windows: 通过 GetIfTable2 / GetIpForwardTable2 检测
+
+#[cfg(windows)]
+fn wide_to_string(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+#[cfg(windows)]
+fn list_interfaces(device: &str) -> Vec<String> {
+    use windows::Win32::Foundation::NO_ERROR;
+    use windows::Win32::NetworkManagement::IpHelper::{FreeMibTable, GetIfTable2, MIB_IF_TABLE2};
+
+    let mut table: *mut MIB_IF_TABLE2 = std::ptr::null_mut();
+
+    // SAFETY: GetIfTable2 成功时分配表内存，读取完成后由 FreeMibTable 释放
+    unsafe {
+        if GetIfTable2(&mut table) != NO_ERROR || table.is_null() {
+            log::warn!("GetIfTable2 调用失败");
+            return Vec::new();
+        }
+
+        let rows =
+            std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize);
+        let names = rows
+            .iter()
+            .map(|row| wide_to_string(&row.Alias))
+            .filter(|name| matches_device(name, device))
+            .collect();
+
+        FreeMibTable(table as *const _);
+        names
+    }
+}
+
+#[cfg(windows)]
+fn list_routes(device: &str) -> Vec<RouteEntry> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use windows::Win32::Foundation::NO_ERROR;
+    use windows::Win32::NetworkManagement::IpHelper::{
+        ConvertInterfaceLuidToAlias, FreeMibTable, GetIpForwardTable2, MIB_IPFORWARD_TABLE2,
+    };
+    use windows::Win32::Networking::WinSock::{AF_INET, AF_INET6, AF_UNSPEC};
+
+    let mut table: *mut MIB_IPFORWARD_TABLE2 = std::ptr::null_mut();
+    let mut routes = Vec::new();
+
+    // SAFETY: GetIpForwardTable2 成功时分配表内存，读取完成后由 FreeMibTable 释放；
+    // SOCKADDR_INET 按 si_family 读取对应的联合体成员
+    unsafe {
+        if GetIpForwardTable2(AF_UNSPEC, &mut table) != NO_ERROR || table.is_null() {
+            log::warn!("GetIpForwardTable2 调用失败");
+            return routes;
+        }
+
+        let rows =
+            std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize);
+        for row in rows {
+            let mut alias = [0u16; 257];
+            if ConvertInterfaceLuidToAlias(&row.InterfaceLuid, &mut alias) != NO_ERROR {
+                continue;
+            }
+            let alias = wide_to_string(&alias);
+            if !matches_device(&alias, device) {
+                continue;
+            }
+
+            let prefix = &row.DestinationPrefix;
+            let destination = match prefix.Prefix.si_family {
+                AF_INET => Ipv4Addr::from(prefix.Prefix.Ipv4.sin_addr.S_un.S_addr.to_ne_bytes())
+                    .to_string(),
+                AF_INET6 => Ipv6Addr::from(prefix.Prefix.Ipv6.sin6_addr.u.Byte).to_string(),
+                _ => continue,
+            };
+
+            routes.push(RouteEntry {
+                description: format!("{}/{} dev {}", destination, prefix.PrefixLength, alias),
+                row: *row,
+            });
+        }
+
+        FreeMibTable(table as *const _);
+    }
+
+    routes
+}
+
+#[cfg(windows)]
+fn remove_residue(device: &str, outcome: &mut TunCleanupOutcome) {
+    use windows::Win32::Foundation::NO_ERROR;
+    use windows::Win32::NetworkManagement::IpHelper::DeleteIpForwardEntry2;
+
+    for route in list_routes(device) {
+        // SAFETY: row 来自 GetIpForwardTable2 返回的完整路由行
+        let result = unsafe { DeleteIpForwardEntry2(&route.row) };
+        if result == NO_ERROR {
+            outcome.removed.push(format!("route {}", route.description));
+        } else {
+            outcome.failed.push(format!(
+                "route {}: 错误代码 {}",
+                route.description, result.0
+            ));
+        }
+    }
+
+    // wintun 网卡由驱动在持有进程退出后回收，无法直接删除
+    for interface in &outcome.interfaces {
+        outcome.failed.push(format!(
+            "interface {}: wintun 网卡仍被占用，请确认没有其他进程在使用",
+            interface
+        ));
+    }
+}
+
+// macOS: 通过 ifconfig / netstat 检测，route 删除
+
+#[cfg(target_os = "macos")]
+fn list_interfaces(device: &str) -> Vec<String> {
+    Command::new("ifconfig")
+        .arg("-l")
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .filter(|name| matches_device(name, device))
+                .map(|name| name.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+fn list_routes(device: &str) -> Vec<RouteEntry> {
+    let Ok(output) = Command::new("netstat").args(["-rn"]).output() else {
+        return Vec::new();
+    };
+    let content = String::from_utf8_lossy(&output.stdout);
+
+    let mut routes = Vec::new();
+    let mut netif_index = None;
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        // 每个地址族都有独立表头
+        if fields.first() == Some(&"Destination") {
+            netif_index = fields.iter().position(|f| *f == "Netif");
+            continue;
+        }
+
+        let Some(index) = netif_index else {
+            continue;
+        };
+        let Some(interface) = fields.get(index) else {
+            continue;
+        };
+        if !matches_device(interface, device) {
+            continue;
+        }
+
+        routes.push(RouteEntry {
+            description: format!("{} dev {}", fields[0], interface),
+            destination: fields[0].to_string(),
+            interface: interface.to_string(),
+        });
+    }
+
+    routes
+}
+
+#[cfg(target_os = "macos")]
+fn remove_residue(device: &str, outcome: &mut TunCleanupOutcome) {
+    for route in list_routes(device) {
+        let mut command = Command::new("route");
+        command.arg("-n").arg("delete");
+        if route.destination.contains(':') {
+            command.arg("-inet6");
+        }
+        command
+            .arg(&route.destination)
+            .arg("-interface")
+            .arg(&route.interface);
+
+        match command.output() {
+            Ok(output) if output.status.success() => {
+                outcome.removed.push(format!("route {}", route.description));
+            }
+            Ok(output) => outcome.failed.push(format!(
+                "route {}: {}",
+                route.description,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )),
+            Err(e) => outcome.failed.push(format!(
+                "route {}: 执行 route delete 失败: {}",
+                route.description, e
+            )),
+        }
+    }
+
+    // utun 由内核在持有进程退出时回收，仍存在说明被其他进程占用
+    for interface in &outcome.interfaces {
+        outcome
+            .failed
+            .push(format!("interface {}: utun 仍被其他进程占用", interface));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_device_is_exact() {
+        assert!(matches_device("Meta", "Meta"));
+        assert!(matches_device("utun7", "utun7"));
+        assert!(!matches_device("utun3", "utun"));
+        assert!(!matches_device("Meta2", "Meta"));
+        assert!(!matches_device("utun17", "utun1"));
+        assert!(!matches_device("", ""));
+    }
+}
@@ -2,8 +2,11 @@
 //
 // 提供客户端和服务端的 IPC 通信能力
 
+pub mod auth;
 pub mod client;
 pub mod error;
+pub mod frame;
+pub mod multiplex;
 pub mod protocol;
 pub mod server;
 
@@ -0,0 +1,113 @@
+// IPC 客户端认证
+//
+// 服务安装时生成随机令牌，保存到服务可执行文件所在的私有目录（仅安装用户与
+// 管理员/root 可读）。每个连接的第一帧必须携带该令牌，否则服务返回错误并断开连接
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// 令牌文件名（位于服务可执行文件所在目录）
+pub const TOKEN_FILE_NAME: &str = "service.token";
+
+// 令牌长度（字节，写入文件时编码为十六进制）
+const TOKEN_BYTES: usize = 32;
+
+// 连接建立后客户端发送的第一帧
+#[derive(Serialize, Deserialize)]
+pub struct AuthFrame {
+    pub token: String,
+}
+
+// 当前程序所在目录下的令牌文件路径
+pub fn default_token_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(TOKEN_FILE_NAME))
+}
+
+// 读取令牌，文件不存在或为空时返回 None
+pub fn read_token(path: &Path) -> Option<String> {
+    let token = std::fs::read_to_string(path).ok()?;
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+// 生成新令牌并写入默认路径（安装服务时调用，已有令牌会被替换）
+pub fn generate_token() -> std::io::Result<PathBuf> {
+    let path =
+        default_token_path().ok_or_else(|| std::io::Error::other("无法获取服务程序所在目录"))?;
+
+    let mut bytes = [0u8; TOKEN_BYTES];
+    rand::rng().fill(&mut bytes[..]);
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+    // 先删除旧文件，确保新文件以受限权限创建
+    let _ = std::fs::remove_file(&path);
+    write_private(&path, &token)?;
+    #[cfg(unix)]
+    restrict_to_owner(&path)?;
+    Ok(path)
+}
+
+// 令牌文件不存在时生成（服务启动时调用）
+pub fn ensure_token() -> std::io::Result<()> {
+    let exists = default_token_path()
+        .and_then(|path| read_token(&path))
+        .is_some();
+    if !exists {
+        let path = generate_token()?;
+        log::info!("已生成 IPC 认证令牌: {}", path.display());
+    }
+    Ok(())
+}
+
+// 校验客户端令牌（恒定时间比较，避免按耗时逐字节猜测）
+pub fn verify(expected: &str, provided: &str) -> bool {
+    let (expected, provided) = (expected.as_bytes(), provided.as_bytes());
+    expected.len() == provided.len()
+        && expected
+            .iter()
+            .zip(provided)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(content.as_bytes())
+}
+
+// Windows：私有目录位于用户的 %APPDATA% 下，继承的 ACL 仅允许该用户、管理员和 SYSTEM 访问
+#[cfg(windows)]
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    std::fs::write(path, content)
+}
+
+// 将文件权限设为 0600，并把所有者改为所在目录的所有者
+//
+// 服务以 root 身份安装和运行，私有目录属于安装服务的用户。改为该用户所有后，
+// 主程序（普通用户）可以读取令牌和连接 Socket，其他用户不能
+#[cfg(unix)]
+pub fn restrict_to_owner(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+    let Some(dir) = default_token_path().and_then(|p| p.parent().map(Path::to_path_buf)) else {
+        return Ok(());
+    };
+    let dir_meta = std::fs::metadata(dir)?;
+    let file_meta = std::fs::metadata(path)?;
+    if (file_meta.uid(), file_meta.gid()) != (dir_meta.uid(), dir_meta.gid()) {
+        std::os::unix::fs::chown(path, Some(dir_meta.uid()), Some(dir_meta.gid()))?;
+    }
+    Ok(())
+}
@@ -1,19 +1,72 @@
 // IPC 客户端实现
 //
-// 预留给 Flutter 端使用
-
-use super::error::{IpcError, Result};
-use super::protocol::{IPC_PATH, IpcCommand, IpcResponse};
-use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+// 命令通过一条多路复用的长连接发送；旧版服务不支持时退回每条命令一个连接
+
+use super::auth::{self, AuthFrame};
+use super::error::{ErrorCode, IpcError, Result};
+use super::frame::{DEFAULT_MAX_FRAME_LEN, FrameFormat, read_frame, write_frame};
+use super::multiplex::MultiplexedConnection;
+use super::protocol::{IPC_PATH, IpcCommand, IpcResponse, MAX_COMMAND_FRAME_LEN, PROTOCOL_VERSION};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
 use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+
+// 单条日志的最大长度
+const MAX_LOG_LINE_LEN: usize = 1024 * 1024;
+
+// 服务存活检测结果的默认缓存时间
+const DEFAULT_LIVENESS_TTL: Duration = Duration::from_secs(2);
+
+// 管道繁忙时的重试间隔
+#[cfg(windows)]
+const PIPE_BUSY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// 握手结果（每个进程协商一次，重新安装服务后需调用 reset_protocol_version）
+static SERVICE_PROTOCOL: Mutex<Option<ServiceProtocol>> = Mutex::new(None);
 
-// IPC 客户端
+// 服务不支持多路复用时为 true（与协议版本一同在重新安装服务后重置）
+static SEQUENTIAL_MODE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+struct ServiceProtocol {
+    version: u32,
+    // 服务接受的最大命令帧长度
+    max_frame_len: usize,
+}
+
+// 当前使用的帧格式与服务接受的最大命令长度
+//
+// 握手完成且版本一致后使用带校验的帧；握手本身使用旧格式，旧版服务也能解析
+fn frame_params() -> (FrameFormat, usize) {
+    match *SERVICE_PROTOCOL.lock().unwrap_or_else(|e| e.into_inner()) {
+        Some(protocol) if protocol.version == PROTOCOL_VERSION => {
+            (FrameFormat::Checked, protocol.max_frame_len)
+        }
+        _ => (FrameFormat::Legacy, MAX_COMMAND_FRAME_LEN),
+    }
+}
+
+// IPC 客户端（克隆的客户端共享同一条长连接）
+#[derive(Clone)]
 pub struct IpcClient {
     // 超时时间
     timeout: Duration,
     // 最大重试次数
     max_retries: usize,
+    // 认证令牌文件路径
+    token_path: Option<PathBuf>,
+    // 多路复用长连接，建立连接时持锁，避免并发请求重复连接
+    connection: Arc<tokio::sync::Mutex<Option<MultiplexedConnection>>>,
+    // 存活检测结果的缓存时间
+    liveness_ttl: Duration,
+    // 最近一次心跳成功的时间，任意命令因连接失败出错时清除
+    last_alive: Arc<Mutex<Option<Instant>>>,
 }
 
 impl Default for IpcClient {
@@ -21,6 +74,10 @@ impl Default for IpcClient {
         Self {
             timeout: Duration::from_secs(5),
             max_retries: 3,
+            token_path: auth::default_token_path(),
+            connection: Arc::default(),
+            liveness_ttl: DEFAULT_LIVENESS_TTL,
+            last_alive: Arc::default(),
         }
     }
 }
@@ -37,14 +94,136 @@ impl IpcClient {
         self
     }
 
+    // 设置存活检测结果的缓存时间（为零时每次检测都发送心跳）
+    pub fn with_liveness_ttl(mut self, ttl: Duration) -> Self {
+        self.liveness_ttl = ttl;
+        self
+    }
+
     // 设置最大重试次数
     pub fn with_max_retries(mut self, max_retries: usize) -> Self {
         self.max_retries = max_retries;
         self
     }
 
-    // 发送命令并等待响应
+    // 设置认证令牌文件路径（默认为当前程序所在目录，主程序需指向服务的私有目录）
+    pub fn with_token_path(mut self, token_path: PathBuf) -> Self {
+        self.token_path = Some(token_path);
+        self
+    }
+
+    // 发送命令并等待响应（首次发送前执行协议握手）
     pub async fn send_command(&self, command: IpcCommand) -> Result<IpcResponse> {
+        let theirs = self
+            .protocol_version()
+            .await
+            .inspect_err(|e| self.note_error(e))?;
+        if !Self::is_command_compatible(&command, theirs) {
+            return Err(IpcError::IncompatibleProtocol {
+                ours: PROTOCOL_VERSION,
+                theirs,
+            });
+        }
+        self.send_with_retry(&command).await
+    }
+
+    // 获取服务的协议版本（缓存握手结果）
+    pub async fn protocol_version(&self) -> Result<u32> {
+        if let Some(protocol) = *SERVICE_PROTOCOL.lock().unwrap_or_else(|e| e.into_inner()) {
+            return Ok(protocol.version);
+        }
+
+        let hello = IpcCommand::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            max_frame_len: Some(DEFAULT_MAX_FRAME_LEN),
+        };
+        let mut attempt = 0;
+        let (version, max_frame_len) = loop {
+            match self.try_send_command(&hello).await {
+                Ok(IpcResponse::Hello {
+                    protocol_version,
+                    max_frame_len,
+                }) => {
+                    break (
+                        protocol_version,
+                        max_frame_len.unwrap_or(MAX_COMMAND_FRAME_LEN),
+                    );
+                }
+                // 认证失败不代表服务是旧版本，不缓存
+                Ok(IpcResponse::Error { code, message }) if code == ErrorCode::Unauthorized => {
+                    return Err(IpcError::ServiceError(code, message));
+                }
+                // 旧版服务无法解析 Hello 会直接断开连接，视为版本 0
+                Err(IpcError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                    break (0, MAX_COMMAND_FRAME_LEN);
+                }
+                // 连接失败或帧损坏时重试，仍失败则不缓存，下次发送命令重新握手
+                Err(
+                    e @ (IpcError::ConnectionFailed(_)
+                    | IpcError::Timeout
+                    | IpcError::IoError(_)
+                    | IpcError::FrameCorrupted(_)),
+                ) => {
+                    if attempt >= self.max_retries {
+                        return Err(e);
+                    }
+                    attempt += 1;
+                    log::debug!("重试协议握手，尝试 {}/{}", attempt, self.max_retries);
+                    tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+                }
+                // 返回错误响应或无法识别的响应，同样视为旧版服务
+                Ok(_) | Err(_) => break (0, MAX_COMMAND_FRAME_LEN),
+            }
+        };
+
+        if version != PROTOCOL_VERSION {
+            log::warn!(
+                "服务协议版本不一致 (客户端: {}, 服务: {})",
+                PROTOCOL_VERSION,
+                version
+            );
+        }
+        *SERVICE_PROTOCOL.lock().unwrap_or_else(|e| e.into_inner()) = Some(ServiceProtocol {
+            version,
+            max_frame_len,
+        });
+        Ok(version)
+    }
+
+    // 检查服务协议版本是否与客户端一致
+    pub async fn ensure_compatible(&self) -> Result<()> {
+        match self.protocol_version().await? {
+            PROTOCOL_VERSION => Ok(()),
+            theirs => Err(IpcError::IncompatibleProtocol {
+                ours: PROTOCOL_VERSION,
+                theirs,
+            }),
+        }
+    }
+
+    // 清除缓存的协议版本（安装或卸载服务后调用）
+    pub fn reset_protocol_version() {
+        SERVICE_PROTOCOL
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        SEQUENTIAL_MODE.store(false, Ordering::Relaxed);
+    }
+
+    // 版本一致时所有命令可用；旧版服务（版本 0）仅允许状态检测类命令
+    fn is_command_compatible(command: &IpcCommand, theirs: u32) -> bool {
+        match theirs {
+            PROTOCOL_VERSION => true,
+            0 => matches!(
+                command,
+                IpcCommand::Heartbeat | IpcCommand::GetStatus | IpcCommand::GetVersion
+            ),
+            _ => false,
+        }
+    }
+
+    // 发送命令（失败时重试）
+    async fn send_with_retry(&self, command: &IpcCommand) -> Result<IpcResponse> {
         let mut last_error: Option<IpcError> = None;
 
         for attempt in 0..=self.max_retries {
@@ -53,15 +232,19 @@ impl IpcClient {
                 tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
             }
 
-            match self.try_send_command(&command).await {
+            match self.try_send_command(command).await {
                 Ok(response) => {
                     // 检查是否是错误响应
                     if let IpcResponse::Error { code, message } = response {
                         return Err(IpcError::ServiceError(code, message));
                     }
+                    if matches!(response, IpcResponse::HeartbeatAck) {
+                        self.mark_alive();
+                    }
                     return Ok(response);
                 }
                 Err(e) => {
+                    self.note_error(&e);
                     log::debug!(
                         "IPC 通信失败（尝试 {}/{}）: {}",
                         attempt + 1,
@@ -79,53 +262,104 @@ impl IpcClient {
 
     // 尝试发送命令（单次）
     async fn try_send_command(&self, command: &IpcCommand) -> Result<IpcResponse> {
-        // 序列化命令
-        let command_json = serde_json::to_string(command)?;
-        let command_bytes = command_json.as_bytes();
+        if SEQUENTIAL_MODE.load(Ordering::Relaxed) {
+            return self.send_sequential(command).await;
+        }
 
-        // 连接到服务
-        let mut stream = timeout(self.timeout, self.connect())
-            .await
-            .map_err(|_| IpcError::Timeout)??;
+        let connection = self.multiplexed_connection().await?;
+        let (format, max_len) = frame_params();
+        timeout(
+            self.timeout,
+            connection.send(command.clone(), format, max_len),
+        )
+        .await
+        .map_err(|_| IpcError::Timeout)?
+    }
 
-        // 发送命令长度（4 字节）+ 命令数据
-        let len = command_bytes.len() as u32;
-        stream.write_all(&len.to_le_bytes()).await?;
-        stream.write_all(command_bytes).await?;
-        stream.flush().await?;
+    // 获取长连接，尚未建立或已断开时重新连接
+    async fn multiplexed_connection(&self) -> Result<MultiplexedConnection> {
+        let mut cached = self.connection.lock().await;
+        if let Some(connection) = cached.as_ref()
+            && !connection.is_closed()
+        {
+            return Ok(connection.clone());
+        }
 
-        // 读取响应长度
-        let mut len_buf = [0u8; 4];
-        timeout(self.timeout, stream.read_exact(&mut len_buf))
+        let mut stream = timeout(self.timeout, self.connect())
             .await
             .map_err(|_| IpcError::Timeout)??;
-        let response_len = u32::from_le_bytes(len_buf) as usize;
+        self.authenticate(&mut stream, frame_params()).await?;
+        log::debug!("已建立 IPC 长连接");
 
-        // 防止恶意响应占用过多内存
-        if response_len > 10 * 1024 * 1024 {
-            // 最大 10MB
-            return Err(IpcError::Other("响应数据过大".to_string()));
-        }
+        let connection = MultiplexedConnection::start(stream, &SEQUENTIAL_MODE);
+        *cached = Some(connection.clone());
+        Ok(connection)
+    }
 
-        // 读取响应数据
-        let mut response_buf = vec![0u8; response_len];
-        timeout(self.timeout, stream.read_exact(&mut response_buf))
+    // 每条命令使用一个新连接（旧版服务）
+    async fn send_sequential(&self, command: &IpcCommand) -> Result<IpcResponse> {
+        let (format, max_len) = frame_params();
+
+        // 连接到服务
+        let mut stream = timeout(self.timeout, self.connect())
             .await
             .map_err(|_| IpcError::Timeout)??;
+        self.authenticate(&mut stream, (format, max_len)).await?;
+
+        write_frame(&mut stream, command, format, max_len).await?;
+        let strict = format == FrameFormat::Checked;
+        let (_, response) = timeout(
+            self.timeout,
+            read_frame(&mut stream, DEFAULT_MAX_FRAME_LEN, strict),
+        )
+        .await
+        .map_err(|_| IpcError::Timeout)??;
 
         // 反序列化响应
-        let response: IpcResponse = serde_json::from_slice(&response_buf)?;
+        let response: IpcResponse = serde_json::from_slice(&response)?;
         Ok(response)
     }
 
+    // 发送认证帧
+    //
+    // 没有令牌文件时不发送：旧版服务不要求认证，安装新版服务时总会生成令牌
+    async fn authenticate<S>(
+        &self,
+        stream: &mut S,
+        (format, max_len): (FrameFormat, usize),
+    ) -> Result<()>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let Some(token) = self.token_path.as_deref().and_then(auth::read_token) else {
+            log::debug!("未找到 IPC 认证令牌，以无认证方式连接");
+            return Ok(());
+        };
+
+        write_frame(stream, &AuthFrame { token }, format, max_len).await
+    }
+
     // 连接到服务
+    //
+    // 所有管道实例都在服务其他客户端时返回 ERROR_PIPE_BUSY，实例通常很快释放，
+    // 短间隔轮询直到连接成功（总时长由调用方的超时限制）
     #[cfg(windows)]
     async fn connect(&self) -> Result<tokio::net::windows::named_pipe::NamedPipeClient> {
         use tokio::net::windows::named_pipe::ClientOptions;
+        use windows::Win32::Foundation::ERROR_PIPE_BUSY;
 
-        ClientOptions::new()
-            .open(IPC_PATH)
-            .map_err(|e| IpcError::ConnectionFailed(format!("无法连接到服务: {e}")))
+        loop {
+            match ClientOptions::new().open(IPC_PATH) {
+                Ok(client) => return Ok(client),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY.0 as i32) => {
+                    log::trace!("服务管道繁忙，稍后重试");
+                    tokio::time::sleep(PIPE_BUSY_POLL_INTERVAL).await;
+                }
+                Err(e) => {
+                    return Err(IpcError::ConnectionFailed(format!("无法连接到服务: {e}")));
+                }
+            }
+        }
     }
 
     // 连接到服务
@@ -139,7 +373,12 @@ impl IpcClient {
     }
 
     // 检查服务是否在运行（快速检测）
-    pub async fn is_service_running(&self) -> bool {
+    //
+    // 缓存时间内心跳成功过时直接返回 true，force 为 true 时总是发送心跳
+    pub async fn is_service_running(&self, force: bool) -> bool {
+        if !force && self.recently_alive() {
+            return true;
+        }
         matches!(
             timeout(
                 Duration::from_millis(500),
@@ -150,104 +389,342 @@ impl IpcClient {
         )
     }
 
-    // 订阅日志流（持续接收日志，直到连接断开或返回错误）
-    // 参数 callback: 每收到一行日志时调用，返回 false 表示停止接收
-    pub async fn stream_logs<F>(&self, mut callback: F) -> Result<()>
-    where
-        F: FnMut(String) -> bool,
-    {
-        // 序列化 StreamLogs 命令
-        let command = IpcCommand::StreamLogs;
-        let command_json = serde_json::to_string(&command)?;
-        let command_bytes = command_json.as_bytes();
+    fn recently_alive(&self) -> bool {
+        self.last_alive
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some_and(|at| at.elapsed() < self.liveness_ttl)
+    }
 
-        // 连接到服务
-        let mut stream = timeout(self.timeout, self.connect())
-            .await
-            .map_err(|_| IpcError::Timeout)??;
+    // 记录心跳成功
+    fn mark_alive(&self) {
+        *self.last_alive.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
 
-        // 发送命令长度 + 命令数据
-        let len = command_bytes.len() as u32;
-        stream.write_all(&len.to_le_bytes()).await?;
-        stream.write_all(command_bytes).await?;
-        stream.flush().await?;
+    // 连接失败时清除存活缓存
+    fn note_error(&self, e: &IpcError) {
+        if matches!(e, IpcError::ConnectionFailed(_) | IpcError::IoError(_)) {
+            self.last_alive
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+        }
+    }
 
-        // 读取初始响应（应该是 Success）
-        let mut len_buf = [0u8; 4];
-        timeout(self.timeout, stream.read_exact(&mut len_buf))
-            .await
-            .map_err(|_| IpcError::Timeout)??;
-        let response_len = u32::from_le_bytes(len_buf) as usize;
+    // 订阅日志流，收到的日志行发送到 lines（使用独立连接）
+    //
+    // 正常结束（cancel 被取消、服务关闭连接或 lines 的接收端被释放）时返回 Ok，
+    // 取消会立即中断正在等待的读取。调用方通常在单独的任务中运行
+    pub async fn stream_logs(
+        &self,
+        cancel: CancellationToken,
+        lines: mpsc::Sender<String>,
+    ) -> Result<()> {
+        let mut stream = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            stream = timeout(self.timeout, self.connect()) => stream
+                .map_err(|_| IpcError::Timeout)?
+                .inspect_err(|e| self.note_error(e))?,
+        };
+        let (format, max_len) = frame_params();
+        self.authenticate(&mut stream, (format, max_len)).await?;
+        receive_log_stream(stream, self.timeout, format, cancel, lines).await
+    }
 
-        if response_len > 10 * 1024 * 1024 {
-            return Err(IpcError::Other("响应数据过大".to_string()));
+    // 在独立连接上执行请求进度的命令（如 UpdateGeoDatabases），进度行发送到 progress
+    //
+    // 每帧的等待时间不超过 timeout；progress 的接收端被释放时仍等待最终响应
+    pub async fn send_with_progress(
+        &self,
+        command: IpcCommand,
+        progress: mpsc::Sender<String>,
+    ) -> Result<IpcResponse> {
+        let theirs = self
+            .protocol_version()
+            .await
+            .inspect_err(|e| self.note_error(e))?;
+        if !Self::is_command_compatible(&command, theirs) {
+            return Err(IpcError::IncompatibleProtocol {
+                ours: PROTOCOL_VERSION,
+                theirs,
+            });
         }
 
-        let mut response_buf = vec![0u8; response_len];
-        timeout(self.timeout, stream.read_exact(&mut response_buf))
+        let mut stream = timeout(self.timeout, self.connect())
             .await
-            .map_err(|_| IpcError::Timeout)??;
+            .map_err(|_| IpcError::Timeout)?
+            .inspect_err(|e| self.note_error(e))?;
+        let (format, max_len) = frame_params();
+        self.authenticate(&mut stream, (format, max_len)).await?;
+        write_frame(&mut stream, &command, format, max_len).await?;
+        receive_with_progress(stream, self.timeout, format, progress).await
+    }
+}
 
-        let initial_response: IpcResponse = serde_json::from_slice(&response_buf)?;
+// 接收进度直到最终响应，错误响应转换为 ServiceError
+async fn receive_with_progress<S>(
+    mut stream: S,
+    frame_timeout: Duration,
+    format: FrameFormat,
+    progress: mpsc::Sender<String>,
+) -> Result<IpcResponse>
+where
+    S: AsyncRead + Unpin,
+{
+    let strict = format == FrameFormat::Checked;
+    loop {
+        let (_, data) = timeout(
+            frame_timeout,
+            read_frame(&mut stream, DEFAULT_MAX_FRAME_LEN, strict),
+        )
+        .await
+        .map_err(|_| IpcError::Timeout)??;
 
-        // 确认初始响应是成功
-        match initial_response {
-            IpcResponse::Success { .. } => {
-                // 继续接收日志流
+        match serde_json::from_slice::<IpcResponse>(&data)? {
+            IpcResponse::Progress { line } => {
+                let _ = progress.send(line).await;
             }
             IpcResponse::Error { code, message } => {
                 return Err(IpcError::ServiceError(code, message));
             }
-            _ => {
-                return Err(IpcError::Other("意外的初始响应类型".to_string()));
-            }
+            response => return Ok(response),
         }
+    }
+}
 
-        // 持续接收日志流
-        loop {
-            // 读取日志响应长度
-            let mut len_buf = [0u8; 4];
-            match stream.read_exact(&mut len_buf).await {
-                Ok(_) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    // 连接关闭，正常退出
+// 在已认证的连接上订阅并接收日志流
+async fn receive_log_stream<S>(
+    mut stream: S,
+    response_timeout: Duration,
+    format: FrameFormat,
+    cancel: CancellationToken,
+    lines: mpsc::Sender<String>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // 不带请求 ID，服务按旧方式在此连接上持续推送
+    write_frame(
+        &mut stream,
+        &IpcCommand::StreamLogs,
+        format,
+        MAX_COMMAND_FRAME_LEN,
+    )
+    .await?;
+
+    // 服务按命令的帧格式推送
+    let strict = format == FrameFormat::Checked;
+
+    // 读取初始响应（应该是 Success）
+    let (_, initial) = tokio::select! {
+        _ = cancel.cancelled() => return Ok(()),
+        frame = timeout(response_timeout, read_frame(&mut stream, DEFAULT_MAX_FRAME_LEN, strict)) => {
+            frame.map_err(|_| IpcError::Timeout)??
+        }
+    };
+    match serde_json::from_slice::<IpcResponse>(&initial)? {
+        IpcResponse::Success { .. } => {}
+        IpcResponse::Error { code, message } => {
+            return Err(IpcError::ServiceError(code, message));
+        }
+        _ => {
+            return Err(IpcError::Other("意外的初始响应类型".to_string()));
+        }
+    }
+
+    // 持续接收日志流
+    loop {
+        let frame = tokio::select! {
+            _ = cancel.cancelled() => break,
+            frame = read_frame(&mut stream, MAX_LOG_LINE_LEN, strict) => frame,
+        };
+        let data = match frame {
+            Ok((_, data)) => data,
+            // 服务关闭连接，正常退出
+            Err(IpcError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+
+        match serde_json::from_slice::<IpcResponse>(&data)? {
+            IpcResponse::LogStream { line } => {
+                // 接收端已释放，停止接收
+                if lines.send(line).await.is_err() {
                     break;
                 }
-                Err(e) => {
-                    return Err(e.into());
-                }
             }
+            IpcResponse::Error { code, message } => {
+                return Err(IpcError::ServiceError(code, message));
+            }
+            _ => {
+                return Err(IpcError::Other("意外的日志流响应类型".to_string()));
+            }
+        }
+    }
 
-            let log_len = u32::from_le_bytes(len_buf) as usize;
+    Ok(())
+}
 
-            // 防止恶意响应
-            if log_len > 1024 * 1024 {
-                return Err(IpcError::Other("单条日志数据过大".to_string()));
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{DuplexStream, duplex};
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+    // 模拟服务：确认订阅并推送给定的日志行，随后保持连接但不再发送
+    async fn silent_server(mut server: DuplexStream, log_lines: &[&str]) -> Result<DuplexStream> {
+        let (format, command) = read_frame(&mut server, MAX_COMMAND_FRAME_LEN, true).await?;
+        assert_eq!(format, FrameFormat::Checked);
+        assert!(matches!(
+            serde_json::from_slice(&command)?,
+            IpcCommand::StreamLogs
+        ));
+        let success = IpcResponse::Success { message: None };
+        write_frame(&mut server, &success, format, DEFAULT_MAX_FRAME_LEN).await?;
+        for line in log_lines {
+            let response = IpcResponse::LogStream {
+                line: line.to_string(),
+            };
+            write_frame(&mut server, &response, format, DEFAULT_MAX_FRAME_LEN).await?;
+        }
+        Ok(server)
+    }
+
+    #[tokio::test]
+    async fn test_cancel_interrupts_silent_stream() -> Result<()> {
+        let (client, server) = duplex(4096);
+        let cancel = CancellationToken::new();
+        let (lines_tx, mut lines) = mpsc::channel(8);
+        let stream = tokio::spawn(receive_log_stream(
+            client,
+            TEST_TIMEOUT,
+            FrameFormat::Checked,
+            cancel.clone(),
+            lines_tx,
+        ));
+
+        let _server = silent_server(server, &["first"]).await?;
+        assert_eq!(lines.recv().await.as_deref(), Some("first"));
+
+        // 服务不再发送数据，取消后读取立即结束并返回 Ok
+        cancel.cancel();
+        let result = timeout(TEST_TIMEOUT, stream)
+            .await
+            .map_err(|_| IpcError::Timeout)?
+            .map_err(|e| IpcError::Other(e.to_string()))?;
+        assert!(result.is_ok());
+        assert_eq!(lines.recv().await, None);
+        Ok(())
+    }
 
-            // 读取日志数据
-            let mut log_buf = vec![0u8; log_len];
-            stream.read_exact(&mut log_buf).await?;
+    #[tokio::test]
+    async fn test_cancel_before_initial_response() -> Result<()> {
+        let (client, _server) = duplex(4096);
+        let cancel = CancellationToken::new();
+        let (lines_tx, _lines) = mpsc::channel(8);
+        let stream = tokio::spawn(receive_log_stream(
+            client,
+            Duration::from_secs(60),
+            FrameFormat::Checked,
+            cancel.clone(),
+            lines_tx,
+        ));
+
+        cancel.cancel();
+        let result = timeout(TEST_TIMEOUT, stream)
+            .await
+            .map_err(|_| IpcError::Timeout)?
+            .map_err(|e| IpcError::Other(e.to_string()))?;
+        assert!(result.is_ok());
+        Ok(())
+    }
 
-            // 反序列化日志响应
-            let log_response: IpcResponse = serde_json::from_slice(&log_buf)?;
+    #[tokio::test]
+    async fn test_stream_ends_when_service_closes() -> Result<()> {
+        let (client, server) = duplex(4096);
+        let (lines_tx, mut lines) = mpsc::channel(8);
+        let stream = tokio::spawn(receive_log_stream(
+            client,
+            TEST_TIMEOUT,
+            FrameFormat::Checked,
+            CancellationToken::new(),
+            lines_tx,
+        ));
+
+        drop(silent_server(server, &["a", "b"]).await?);
+        let result = timeout(TEST_TIMEOUT, stream)
+            .await
+            .map_err(|_| IpcError::Timeout)?
+            .map_err(|e| IpcError::Other(e.to_string()))?;
+        assert!(result.is_ok());
+        assert_eq!(lines.recv().await.as_deref(), Some("a"));
+        assert_eq!(lines.recv().await.as_deref(), Some("b"));
+        Ok(())
+    }
 
-            match log_response {
-                IpcResponse::LogStream { line } => {
-                    // 调用回调函数，如果返回 false 则停止接收
-                    if !callback(line) {
-                        break;
-                    }
-                }
-                IpcResponse::Error { code, message } => {
-                    return Err(IpcError::ServiceError(code, message));
-                }
-                _ => {
-                    return Err(IpcError::Other("意外的日志流响应类型".to_string()));
-                }
+    #[tokio::test]
+    async fn test_progress_before_final_response() -> Result<()> {
+        let (client, mut server) = duplex(4096);
+        let (progress_tx, mut progress) = mpsc::channel(8);
+
+        let service = async {
+            for line in ["[GEO] Updating GeoIP", "[GEO] Updating GeoSite"] {
+                let response = IpcResponse::Progress {
+                    line: line.to_string(),
+                };
+                write_frame(
+                    &mut server,
+                    &response,
+                    FrameFormat::Checked,
+                    DEFAULT_MAX_FRAME_LEN,
+                )
+                .await?;
             }
-        }
-
+            let done = IpcResponse::Success { message: None };
+            write_frame(
+                &mut server,
+                &done,
+                FrameFormat::Checked,
+                DEFAULT_MAX_FRAME_LEN,
+            )
+            .await
+        };
+        let (sent, response) = tokio::join!(
+            service,
+            receive_with_progress(client, TEST_TIMEOUT, FrameFormat::Checked, progress_tx)
+        );
+        sent?;
+
+        assert!(matches!(response?, IpcResponse::Success { .. }));
+        assert_eq!(
+            progress.recv().await.as_deref(),
+            Some("[GEO] Updating GeoIP")
+        );
+        assert_eq!(
+            progress.recv().await.as_deref(),
+            Some("[GEO] Updating GeoSite")
+        );
         Ok(())
     }
+
+    #[test]
+    fn test_liveness_cache() {
+        let client = IpcClient::new();
+        assert!(!client.recently_alive());
+
+        client.mark_alive();
+        assert!(client.clone().recently_alive(), "克隆的客户端共享缓存");
+        assert!(
+            !client
+                .clone()
+                .with_liveness_ttl(Duration::ZERO)
+                .recently_alive()
+        );
+
+        // 连接失败后缓存失效，其他错误不影响
+        client.note_error(&IpcError::Timeout);
+        assert!(client.recently_alive());
+        client.note_error(&IpcError::ConnectionFailed("拒绝连接".to_string()));
+        assert!(!client.recently_alive());
+    }
 }
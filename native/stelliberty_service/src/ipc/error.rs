@@ -1,7 +1,139 @@
 // IPC 错误类型定义
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+// 稳定的错误码（编码规则见 protocol 模块）
+//
+// 传输时使用数值，传给主程序 UI 时使用字符串形式，两种形式都不会改变含义
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "i32", into = "i32")]
+pub enum ErrorCode {
+    // 启动 Clash 失败
+    StartFailed,
+    // 停止 Clash 失败
+    StopFailed,
+    // TUN 前置条件缺失（wintun.dll、/dev/net/tun 或 CAP_NET_ADMIN）
+    TunPrerequisite,
+    // Clash 核心未运行
+    CoreNotRunning,
+    // 客户端未携带有效的认证令牌
+    Unauthorized,
+    // 命令不能在多路复用连接中执行（StreamLogs）
+    StreamUnsupported,
+    // 核心控制接口不可用（未启用或查询失败）
+    ControllerUnavailable,
+    // 帧超过对方接受的最大长度
+    FrameTooLarge,
+    // 核心拒绝了控制接口请求（消息为核心的响应内容）
+    ControllerRejected,
+
+    // 无法连接服务（服务未安装或未启动）
+    ServiceUnavailable,
+    // IPC 操作超时
+    Timeout,
+    // 消息无法解析
+    InvalidMessage,
+    // IO 错误
+    Io,
+    // 服务未运行
+    ServiceNotRunning,
+    // 协议版本不兼容（需要重新安装服务）
+    IncompatibleProtocol,
+    // 帧头或校验和不正确（数据流错位或损坏）
+    FrameCorrupted,
+    // 其他错误
+    Other,
+
+    // 本客户端不认识的服务端错误码（来自更新的服务）
+    Unknown(i32),
+}
+
+impl ErrorCode {
+    pub fn as_i32(self) -> i32 {
+        match self {
+            Self::StartFailed => 1001,
+            Self::StopFailed => 1002,
+            Self::TunPrerequisite => 1003,
+            Self::CoreNotRunning => 1004,
+            Self::Unauthorized => 1005,
+            Self::StreamUnsupported => 1006,
+            Self::ControllerUnavailable => 1007,
+            Self::FrameTooLarge => 1008,
+            Self::ControllerRejected => 1009,
+            Self::Other => 2000,
+            Self::ServiceUnavailable => 2001,
+            Self::Timeout => 2002,
+            Self::InvalidMessage => 2003,
+            Self::Io => 2004,
+            Self::ServiceNotRunning => 2005,
+            Self::IncompatibleProtocol => 2006,
+            Self::FrameCorrupted => 2007,
+            Self::Unknown(code) => code,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::StartFailed => "start_failed",
+            Self::StopFailed => "stop_failed",
+            Self::TunPrerequisite => "tun_prerequisite",
+            Self::CoreNotRunning => "core_not_running",
+            Self::Unauthorized => "unauthorized",
+            Self::StreamUnsupported => "stream_unsupported",
+            Self::ControllerUnavailable => "controller_unavailable",
+            Self::FrameTooLarge => "frame_too_large",
+            Self::ControllerRejected => "controller_rejected",
+            Self::Other => "other",
+            Self::ServiceUnavailable => "service_unavailable",
+            Self::Timeout => "timeout",
+            Self::InvalidMessage => "invalid_message",
+            Self::Io => "io",
+            Self::ServiceNotRunning => "service_not_running",
+            Self::IncompatibleProtocol => "incompatible_protocol",
+            Self::FrameCorrupted => "frame_corrupted",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<i32> for ErrorCode {
+    fn from(code: i32) -> Self {
+        match code {
+            1001 => Self::StartFailed,
+            1002 => Self::StopFailed,
+            1003 => Self::TunPrerequisite,
+            1004 => Self::CoreNotRunning,
+            1005 => Self::Unauthorized,
+            1006 => Self::StreamUnsupported,
+            1007 => Self::ControllerUnavailable,
+            1008 => Self::FrameTooLarge,
+            1009 => Self::ControllerRejected,
+            2000 => Self::Other,
+            2001 => Self::ServiceUnavailable,
+            2002 => Self::Timeout,
+            2003 => Self::InvalidMessage,
+            2004 => Self::Io,
+            2005 => Self::ServiceNotRunning,
+            2006 => Self::IncompatibleProtocol,
+            2007 => Self::FrameCorrupted,
+            code => Self::Unknown(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for i32 {
+    fn from(code: ErrorCode) -> Self {
+        code.as_i32()
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_i32())
+    }
+}
+
 // IPC 错误类型
 #[derive(Error, Debug)]
 #[allow(dead_code)]
@@ -32,12 +164,101 @@ pub enum IpcError {
 
     // 服务返回错误
     #[error("服务返回错误 (错误代码: {0}): {1}")]
-    ServiceError(i32, String),
+    ServiceError(ErrorCode, String),
+
+    // 协议版本不兼容
+    #[error("IPC 协议版本不兼容（客户端: {ours}, 服务: {theirs}）\n提示: 请重新安装服务以升级")]
+    IncompatibleProtocol { ours: u32, theirs: u32 },
+
+    // 帧头或校验和不正确，连接已不可用
+    #[error("IPC 数据帧损坏: {0}\n提示: 连接已关闭，请重试")]
+    FrameCorrupted(String),
+
+    // 帧超过对方接受的最大长度
+    #[error("IPC 数据帧过大: {len} 字节（上限 {max} 字节）")]
+    FrameTooLarge { len: usize, max: usize },
 
     // 其他错误
     #[error("{0}")]
     Other(String),
 }
 
+impl IpcError {
+    // 错误对应的稳定错误码
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::ConnectionFailed(_) => ErrorCode::ServiceUnavailable,
+            Self::Timeout => ErrorCode::Timeout,
+            Self::SerializationError(_) => ErrorCode::InvalidMessage,
+            Self::IoError(_) => ErrorCode::Io,
+            Self::ServiceNotRunning => ErrorCode::ServiceNotRunning,
+            Self::ServiceError(code, _) => *code,
+            Self::IncompatibleProtocol { .. } => ErrorCode::IncompatibleProtocol,
+            Self::FrameCorrupted(_) => ErrorCode::FrameCorrupted,
+            Self::FrameTooLarge { .. } => ErrorCode::FrameTooLarge,
+            Self::Other(_) => ErrorCode::Other,
+        }
+    }
+}
+
 // IPC Result 类型
 pub type Result<T> = std::result::Result<T, IpcError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_variant_maps_to_code() -> std::result::Result<(), String> {
+        let serde_error = serde_json::from_str::<u32>("invalid")
+            .err()
+            .ok_or("解析应当失败")?;
+        let errors = [
+            IpcError::ConnectionFailed("拒绝连接".to_string()),
+            IpcError::Timeout,
+            IpcError::SerializationError(serde_error),
+            IpcError::IoError(std::io::Error::other("写入失败")),
+            IpcError::ServiceNotRunning,
+            IpcError::ServiceError(ErrorCode::StartFailed, "配置错误".to_string()),
+            IpcError::IncompatibleProtocol { ours: 2, theirs: 1 },
+            IpcError::FrameCorrupted("CRC32 校验失败".to_string()),
+            IpcError::FrameTooLarge {
+                len: 2048,
+                max: 1024,
+            },
+            IpcError::Other("其他".to_string()),
+        ];
+
+        let mut codes = Vec::new();
+        for error in &errors {
+            // 新增变体时必须在这里补充，否则无法编译
+            match error {
+                IpcError::ConnectionFailed(_)
+                | IpcError::Timeout
+                | IpcError::SerializationError(_)
+                | IpcError::IoError(_)
+                | IpcError::ServiceNotRunning
+                | IpcError::ServiceError(..)
+                | IpcError::IncompatibleProtocol { .. }
+                | IpcError::FrameCorrupted(_)
+                | IpcError::FrameTooLarge { .. }
+                | IpcError::Other(_) => {}
+            }
+
+            let code = error.code();
+            assert!(!matches!(code, ErrorCode::Unknown(_)), "{error:?}");
+            assert_eq!(ErrorCode::from(code.as_i32()), code);
+            assert!(!codes.contains(&code), "重复的错误码: {code:?}");
+            codes.push(code);
+        }
+
+        // 错误码经过 JSON 传输后保持不变，未知的数值不会丢失
+        for code in codes.into_iter().chain([ErrorCode::Unknown(4242)]) {
+            let json = serde_json::to_string(&code).map_err(|e| e.to_string())?;
+            assert_eq!(json, code.as_i32().to_string());
+            let decoded: ErrorCode = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+            assert_eq!(decoded, code);
+        }
+        Ok(())
+    }
+}
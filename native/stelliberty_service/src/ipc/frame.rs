@@ -0,0 +1,169 @@
+// IPC 帧读写
+//
+// 帧格式：4 字节魔数 + 1 字节帧版本 + 4 字节小端载荷长度 + 4 字节小端 CRC32 + JSON 载荷。
+// 长度与校验和可以发现流错位或数据损坏，出错时应关闭连接
+//
+// 兼容旧格式（4 字节小端长度 + JSON）：旧格式的长度不可能等于魔数（远超帧大小上限），
+// 读取时按前 4 字节区分。连接上收到过新格式的帧后不再接受旧格式。
+// 旧格式仅为兼容上一版本的主程序和服务保留
+
+use super::error::{IpcError, Result};
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// 帧头魔数
+const FRAME_MAGIC: [u8; 4] = *b"STIP";
+
+// 帧格式版本
+const FRAME_VERSION: u8 = 1;
+
+// 未协商时双方接受的最大帧载荷长度
+pub const DEFAULT_MAX_FRAME_LEN: usize = 10 * 1024 * 1024;
+
+// 帧格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    // 旧格式：仅有长度前缀
+    Legacy,
+    // 带魔数、版本与 CRC32 的帧头
+    Checked,
+}
+
+// 读取一帧，返回帧格式与载荷
+//
+// 载荷超过 max_len 时返回 FrameTooLarge；魔数、版本或校验和不正确时返回 FrameCorrupted，
+// strict 为 true 时旧格式的帧同样视为损坏
+pub async fn read_frame<R>(
+    reader: &mut R,
+    max_len: usize,
+    strict: bool,
+) -> Result<(FrameFormat, Vec<u8>)>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut prefix = [0u8; 4];
+    reader.read_exact(&mut prefix).await?;
+
+    if prefix != FRAME_MAGIC {
+        if strict {
+            return Err(IpcError::FrameCorrupted("帧头魔数不匹配".to_string()));
+        }
+        let len = u32::from_le_bytes(prefix) as usize;
+        check_len(len, max_len)?;
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload).await?;
+        return Ok((FrameFormat::Legacy, payload));
+    }
+
+    let mut header = [0u8; 9];
+    reader.read_exact(&mut header).await?;
+    if header[0] != FRAME_VERSION {
+        return Err(IpcError::FrameCorrupted(format!(
+            "不支持的帧版本: {}",
+            header[0]
+        )));
+    }
+    let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    let checksum = u32::from_le_bytes([header[5], header[6], header[7], header[8]]);
+    check_len(len, max_len)?;
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    if crc32fast::hash(&payload) != checksum {
+        return Err(IpcError::FrameCorrupted("CRC32 校验失败".to_string()));
+    }
+    Ok((FrameFormat::Checked, payload))
+}
+
+// 序列化并写入一帧，载荷超过 max_len 时返回 FrameTooLarge 且不写入任何数据
+pub async fn write_frame<W, T>(
+    writer: &mut W,
+    value: &T,
+    format: FrameFormat,
+    max_len: usize,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let payload = serde_json::to_vec(value)?;
+    check_len(payload.len(), max_len)?;
+    let len = (payload.len() as u32).to_le_bytes();
+
+    let mut frame = Vec::with_capacity(payload.len() + 13);
+    match format {
+        FrameFormat::Legacy => frame.extend_from_slice(&len),
+        FrameFormat::Checked => {
+            frame.extend_from_slice(&FRAME_MAGIC);
+            frame.push(FRAME_VERSION);
+            frame.extend_from_slice(&len);
+            frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        }
+    }
+    frame.extend_from_slice(&payload);
+
+    writer.write_all(&frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn check_len(len: usize, max_len: usize) -> Result<()> {
+    if len > max_len {
+        return Err(IpcError::FrameTooLarge { len, max: max_len });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checked_frame_integrity() -> Result<()> {
+        let mut buf = Vec::new();
+        write_frame(
+            &mut buf,
+            &"hello",
+            FrameFormat::Checked,
+            DEFAULT_MAX_FRAME_LEN,
+        )
+        .await?;
+        let (format, payload) =
+            read_frame(&mut buf.as_slice(), DEFAULT_MAX_FRAME_LEN, true).await?;
+        assert_eq!(format, FrameFormat::Checked);
+        assert_eq!(payload, b"\"hello\"");
+
+        // 载荷被篡改
+        let last = buf.len() - 2;
+        buf[last] ^= 0x01;
+        assert!(matches!(
+            read_frame(&mut buf.as_slice(), DEFAULT_MAX_FRAME_LEN, false).await,
+            Err(IpcError::FrameCorrupted(_))
+        ));
+
+        // 旧格式仅在非严格模式下接受
+        let mut legacy = Vec::new();
+        write_frame(
+            &mut legacy,
+            &"hello",
+            FrameFormat::Legacy,
+            DEFAULT_MAX_FRAME_LEN,
+        )
+        .await?;
+        let (format, _) = read_frame(&mut legacy.as_slice(), DEFAULT_MAX_FRAME_LEN, false).await?;
+        assert_eq!(format, FrameFormat::Legacy);
+        assert!(matches!(
+            read_frame(&mut legacy.as_slice(), DEFAULT_MAX_FRAME_LEN, true).await,
+            Err(IpcError::FrameCorrupted(_))
+        ));
+
+        // 超过上限时不写入
+        let mut empty = Vec::new();
+        assert!(matches!(
+            write_frame(&mut empty, &"hello", FrameFormat::Checked, 4).await,
+            Err(IpcError::FrameTooLarge { len: 7, max: 4 })
+        ));
+        assert!(empty.is_empty());
+        Ok(())
+    }
+}
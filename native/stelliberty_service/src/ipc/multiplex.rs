@@ -0,0 +1,183 @@
+// 多路复用连接
+//
+// 在一条长连接上并发发送多条命令，响应按请求 ID 匹配。连接由单独的任务持有：
+// 写入与待响应表只在该任务内访问，不需要互斥锁；读取任务把收到的响应交回该任务分发
+
+use super::error::{IpcError, Result};
+use super::frame::{DEFAULT_MAX_FRAME_LEN, FrameFormat, read_frame, write_frame};
+use super::protocol::{CommandFrame, IpcCommand, IpcResponse, ResponseFrame};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::io::{AsyncRead, AsyncWrite, ReadHalf};
+use tokio::sync::{mpsc, oneshot};
+
+// 排队等待写入的请求数
+const REQUEST_QUEUE_SIZE: usize = 32;
+
+type Reply = oneshot::Sender<Result<IpcResponse>>;
+
+struct PendingRequest {
+    command: IpcCommand,
+    // 帧格式与服务接受的最大命令长度（握手完成前使用旧格式）
+    format: FrameFormat,
+    max_len: usize,
+    reply: Reply,
+}
+
+// 多路复用连接句柄（可克隆，连接断开后 is_closed 返回 true，需要重新建立）
+#[derive(Clone)]
+pub struct MultiplexedConnection {
+    requests: mpsc::Sender<PendingRequest>,
+}
+
+impl MultiplexedConnection {
+    // 接管已认证的连接并启动连接任务
+    //
+    // 收到不带 ID 的响应说明服务不支持多路复用，此时设置 sequential 并关闭连接
+    pub fn start<S>(stream: S, sequential: &'static AtomicBool) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (requests, receiver) = mpsc::channel(REQUEST_QUEUE_SIZE);
+        tokio::spawn(run(stream, receiver, sequential));
+        Self { requests }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.requests.is_closed()
+    }
+
+    // 发送命令并等待对应的响应
+    pub async fn send(
+        &self,
+        command: IpcCommand,
+        format: FrameFormat,
+        max_len: usize,
+    ) -> Result<IpcResponse> {
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(PendingRequest {
+                command,
+                format,
+                max_len,
+                reply,
+            })
+            .await
+            .map_err(|_| connection_closed())?;
+        response.await.map_err(|_| connection_closed())?
+    }
+}
+
+fn connection_closed() -> IpcError {
+    IpcError::ConnectionFailed("IPC 连接已断开".to_string())
+}
+
+// 连接任务：写入请求、分发响应，连接出错时以相同错误结束所有等待中的请求
+async fn run<S>(
+    stream: S,
+    mut requests: mpsc::Receiver<PendingRequest>,
+    sequential: &'static AtomicBool,
+) where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let (frame_tx, mut frames) = mpsc::channel(REQUEST_QUEUE_SIZE);
+    let reader_task = tokio::spawn(read_responses(reader, frame_tx));
+
+    let mut pending: HashMap<u64, Reply> = HashMap::new();
+    let mut next_id: u64 = 0;
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                // 所有客户端句柄已释放
+                let Some(request) = request else { break };
+
+                next_id += 1;
+                let frame = CommandFrame {
+                    id: Some(next_id),
+                    command: request.command,
+                };
+                match write_frame(&mut writer, &frame, request.format, request.max_len).await {
+                    Ok(()) => {}
+                    // 写入前检查长度，连接仍然可用
+                    Err(e @ IpcError::FrameTooLarge { .. }) => {
+                        let _ = request.reply.send(Err(e));
+                        continue;
+                    }
+                    Err(e) => {
+                        let _ = request.reply.send(Err(e));
+                        break;
+                    }
+                }
+                pending.insert(next_id, request.reply);
+            }
+
+            frame = frames.recv() => match frame {
+                Some(Ok(ResponseFrame { id: Some(id), response })) => {
+                    if let Some(reply) = pending.remove(&id) {
+                        let _ = reply.send(Ok(response));
+                    }
+                }
+                // 旧版服务忽略 ID：只处理了第一条命令，随后会关闭连接
+                Some(Ok(ResponseFrame { id: None, response })) => {
+                    log::debug!("服务不支持多路复用，改用逐条连接模式");
+                    sequential.store(true, Ordering::Relaxed);
+                    if let Some(reply) = pending.remove(&1) {
+                        let _ = reply.send(Ok(response));
+                    }
+                    break;
+                }
+                Some(Err(e)) => {
+                    log::debug!("IPC 连接已断开: {}", e);
+                    for (_, reply) in pending.drain() {
+                        let _ = reply.send(Err(same_error(&e)));
+                    }
+                    break;
+                }
+                None => break,
+            },
+        }
+    }
+
+    reader_task.abort();
+}
+
+// 复制连接错误，分发给每个等待中的请求（保留帧损坏与 IO 错误的类型）
+fn same_error(e: &IpcError) -> IpcError {
+    match e {
+        IpcError::FrameCorrupted(message) => IpcError::FrameCorrupted(message.clone()),
+        IpcError::FrameTooLarge { len, max } => IpcError::FrameTooLarge {
+            len: *len,
+            max: *max,
+        },
+        IpcError::IoError(io) => IpcError::IoError(std::io::Error::new(io.kind(), e.to_string())),
+        _ => IpcError::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            e.to_string(),
+        )),
+    }
+}
+
+// 读取任务：持续读取响应帧，出错后结束
+//
+// 收到带校验的帧后不再接受旧格式的帧
+async fn read_responses<S>(mut reader: ReadHalf<S>, frames: mpsc::Sender<Result<ResponseFrame>>)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let mut strict = false;
+    loop {
+        let frame = match read_frame(&mut reader, DEFAULT_MAX_FRAME_LEN, strict).await {
+            Ok((format, data)) => {
+                strict |= format == FrameFormat::Checked;
+                serde_json::from_slice::<ResponseFrame>(&data).map_err(IpcError::from)
+            }
+            Err(e) => Err(e),
+        };
+        let failed = frame.is_err();
+        if frames.send(frame).await.is_err() || failed {
+            break;
+        }
+    }
+}
@@ -2,6 +2,7 @@
 //
 // 定义客户端和服务端之间的通信协议
 
+use super::error::ErrorCode;
 use serde::{Deserialize, Serialize};
 
 // IPC 通信路径
@@ -11,6 +12,49 @@ pub const IPC_PATH: &str = r"\\.\pipe\stelliberty_service";
 #[cfg(not(windows))]
 pub const IPC_PATH: &str = "/tmp/stelliberty_service.sock";
 
+// IPC 协议版本，命令或响应格式不兼容地变化时递增
+//
+// 不认识 Hello 的旧版服务视为版本 0
+//
+// 版本 2：StopClash 携带强制终止等待时间并返回 Stopped
+// 版本 3：帧头带 CRC32 校验，Hello 协商最大帧长度
+// 版本 4：SetClashLogLevel、UpdateGeoDatabases 与 Progress 响应
+// 版本 5：Shutdown
+pub const PROTOCOL_VERSION: u32 = 5;
+
+// 服务接受的最大命令帧长度（旧版服务同样为 1MB）
+pub const MAX_COMMAND_FRAME_LEN: usize = 1024 * 1024;
+
+// 错误码（IpcResponse::Error 携带，定义见 error::ErrorCode）
+//
+// 1xxx 由服务返回：
+//   1001 start_failed            启动 Clash 失败（消息中包含核心启动时的输出）
+//   1002 stop_failed             停止 Clash 失败
+//   1003 tun_prerequisite        TUN 前置条件缺失
+//   1004 core_not_running        Clash 核心未运行
+//   1005 unauthorized            未携带有效的认证令牌
+//   1006 stream_unsupported      命令不能在多路复用连接中执行
+//   1007 controller_unavailable  核心控制接口不可用
+//   1008 frame_too_large         帧超过对方接受的最大长度（客户端写入超限时也使用）
+//   1009 controller_rejected     核心拒绝了控制接口请求（消息为核心的响应内容）
+// 2xxx 由客户端产生，不会出现在响应中：
+//   2000 other                   其他错误
+//   2001 service_unavailable     无法连接服务（未安装或未启动）
+//   2002 timeout                 IPC 操作超时
+//   2003 invalid_message         消息无法解析
+//   2004 io                      IO 错误
+//   2005 service_not_running     服务未运行
+//   2006 incompatible_protocol   协议版本不兼容
+//   2007 frame_corrupted         帧头或校验和不正确
+// 已分配的错误码不会改变含义，不认识的错误码按 unknown 处理
+
+// GetLogsTail 的行数与字节上限
+pub const LOGS_TAIL_MAX_LINES: usize = 500;
+pub const LOGS_TAIL_MAX_BYTES: usize = 256 * 1024;
+
+// StopClash 强制终止前的最长等待时间（毫秒）
+pub const STOP_FORCE_AFTER_MAX_MS: u64 = 30_000;
+
 // 客户端发送给服务的命令
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
@@ -25,27 +69,84 @@ pub enum IpcCommand {
         data_dir: String,
         // 外部控制器地址（HTTP API），空字符串表示禁用
         external_controller: String,
+        // 是否启用 TUN 模式（启动前检查前置条件，停止后清理网卡和路由）
+        #[serde(default)]
+        tun: bool,
+        // TUN 设备名，None 表示使用核心默认值
+        #[serde(default)]
+        tun_device: Option<String>,
     },
 
-    // 停止 Clash 核心
-    StopClash,
+    // 停止 Clash 核心：先优雅停止，等待 force_after_ms 后仍未退出则强制终止
+    //
+    // 旧版客户端发送不带 data 的 StopClash，按 None 处理
+    StopClash {
+        // None 表示使用服务默认的等待时间，超过 STOP_FORCE_AFTER_MAX_MS 时按上限处理
+        #[serde(default)]
+        force_after_ms: Option<u64>,
+    },
 
     // 获取服务状态
     GetStatus,
 
+    // 获取正在运行的核心的启动参数（主程序重启后接管核心）
+    GetRunningParams,
+
     // 获取 Clash 日志（最近 N 行）
     GetLogs {
         lines: usize,
     },
 
+    // 获取服务自身日志尾部（最多 500 行 / 256 KB），核心未运行时同样可用
+    GetLogsTail {
+        lines: u32,
+    },
+
     // 流式获取日志（实时监听）
     StreamLogs,
 
     // 获取服务版本
     GetVersion,
 
+    // 获取核心的连接数与累计流量（核心未运行时返回 core_not_running）
+    GetClashStats,
+
+    // 修改正在运行的核心的日志级别（转发为 PATCH /configs）
+    SetClashLogLevel {
+        // silent、error、warning、info 或 debug，由核心校验
+        level: String,
+    },
+
+    // 让核心更新 GeoIP/GeoSite 等数据库（转发为 POST /configs/geo）
+    //
+    // 数据目录属于 root/SYSTEM 时主程序无法自行替换数据库文件
+    UpdateGeoDatabases {
+        // 在最终响应前推送 Progress（仅在独立连接上生效，多路复用连接中忽略）
+        #[serde(default)]
+        stream_progress: bool,
+    },
+
     // Heartbeat（心跳检测），由主程序定期发送
     Heartbeat,
+
+    // 协议版本握手，客户端在每个进程首次发送命令前发送
+    Hello {
+        protocol_version: u32,
+        // 客户端可接收的最大响应帧长度（旧版客户端不发送）
+        #[serde(default)]
+        max_frame_len: Option<usize>,
+    },
+
+    // 检测并清理 TUN 残留网卡和路由（remove 为 false 时仅检测）
+    VerifyTunCleanup {
+        device: String,
+        remove: bool,
+    },
+
+    // 停止核心并退出服务进程（卸载前调用）
+    //
+    // 服务返回 Success 后关闭 IPC 监听并退出，连接不再响应时程序文件即可删除
+    Shutdown,
 }
 
 // 服务返回给客户端的响应
@@ -59,7 +160,7 @@ pub enum IpcResponse {
 
     // 操作失败
     Error {
-        code: i32,
+        code: ErrorCode,
         message: String,
     },
 
@@ -69,8 +170,21 @@ pub enum IpcResponse {
         clash_running: bool,
         // Clash 进程 PID
         clash_pid: Option<u32>,
-        // 服务启动时间（Unix 时间戳）
+        // 服务运行时长（秒）
         service_uptime: u64,
+        // Clash 核心运行时长（秒），从最近一次成功启动核心开始计算
+        // 核心未运行或非由服务启动（接管已有进程）时为 None
+        #[serde(default)]
+        clash_uptime: Option<u64>,
+    },
+
+    // 核心启动参数
+    RunningParams {
+        core_path: String,
+        config_path: String,
+        data_dir: String,
+        external_controller: String,
+        tun_device: Option<String>,
     },
 
     // 日志内容
@@ -83,11 +197,137 @@ pub enum IpcResponse {
         line: String,
     },
 
+    // 命令执行进度（单行，最终响应之前发送）
+    Progress {
+        line: String,
+    },
+
     // 版本信息
     Version {
         version: String,
     },
 
+    // 核心已停止
+    Stopped {
+        // 核心未在等待时间内退出，已被强制终止
+        forced: bool,
+        // 进程退出码，核心未运行或被信号终止时为 None
+        exit_code: Option<i32>,
+    },
+
+    // 核心统计摘要
+    Stats {
+        // 当前活动连接数
+        connections: u32,
+        // 核心启动以来的累计上传/下载字节数
+        upload_total: u64,
+        download_total: u64,
+    },
+
     // HeartbeatAck（心跳响应）
     HeartbeatAck,
+
+    // 握手响应，返回服务端的协议版本
+    Hello {
+        protocol_version: u32,
+        // 服务接受的最大命令帧长度（旧版服务不返回）
+        #[serde(default)]
+        max_frame_len: Option<usize>,
+    },
+
+    // TUN 残留检测与清理结果
+    TunCleanup {
+        interfaces: Vec<String>,
+        routes: Vec<String>,
+        removed: Vec<String>,
+        failed: Vec<String>,
+    },
+}
+
+impl IpcCommand {
+    // 是否请求在最终响应前推送进度
+    pub fn wants_progress(&self) -> bool {
+        matches!(
+            self,
+            Self::UpdateGeoDatabases {
+                stream_progress: true
+            }
+        )
+    }
+}
+
+// 带请求 ID 的命令帧
+//
+// ID 为空时服务按旧方式处理一条命令后关闭连接；带 ID 时连接保持打开，
+// 响应携带相同 ID。旧版服务忽略未知的 id 字段
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Value")]
+pub struct CommandFrame {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub command: IpcCommand,
+}
+
+impl TryFrom<serde_json::Value> for CommandFrame {
+    type Error = serde_json::Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        let serde_json::Value::Object(mut fields) = value else {
+            return Err(serde::de::Error::custom("命令帧必须是 JSON 对象"));
+        };
+
+        let id = match fields.remove("id") {
+            Some(id) => serde_json::from_value(id)?,
+            None => None,
+        };
+
+        // 旧版的 StopClash 是单元变体，补上空的 data 以便按结构体变体解析
+        if fields.get("type").and_then(|t| t.as_str()) == Some("StopClash") {
+            let data = fields.entry("data").or_insert(serde_json::Value::Null);
+            if data.is_null() {
+                *data = serde_json::Value::Object(Default::default());
+            }
+        }
+
+        let command = serde_json::from_value(serde_json::Value::Object(fields))?;
+        Ok(Self { id, command })
+    }
+}
+
+// 带请求 ID 的响应帧（旧版服务的响应没有 ID）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseFrame {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub response: IpcResponse,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_stop_clash_frame() -> serde_json::Result<()> {
+        let frame: CommandFrame = serde_json::from_str(r#"{"type":"StopClash"}"#)?;
+        assert!(frame.id.is_none());
+        assert!(matches!(
+            frame.command,
+            IpcCommand::StopClash {
+                force_after_ms: None
+            }
+        ));
+
+        let frame: CommandFrame =
+            serde_json::from_str(r#"{"id":7,"type":"StopClash","data":{"force_after_ms":500}}"#)?;
+        assert_eq!(frame.id, Some(7));
+        assert!(matches!(
+            frame.command,
+            IpcCommand::StopClash {
+                force_after_ms: Some(500)
+            }
+        ));
+        Ok(())
+    }
 }
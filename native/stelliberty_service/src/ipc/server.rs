@@ -1,11 +1,16 @@
 // IPC 服务端实现
 
-use super::error::{IpcError, Result};
-use super::protocol::{IPC_PATH, IpcCommand, IpcResponse};
+use super::auth::{self, AuthFrame};
+use super::error::{ErrorCode, IpcError, Result};
+use super::frame::{DEFAULT_MAX_FRAME_LEN, FrameFormat, read_frame, write_frame};
+use super::protocol::{
+    CommandFrame, IPC_PATH, IpcCommand, IpcResponse, MAX_COMMAND_FRAME_LEN, ResponseFrame,
+};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::sync::mpsc;
 
 #[cfg(windows)]
@@ -16,6 +21,24 @@ use windows::Win32::{
     },
 };
 
+// 认证帧的最大长度
+const AUTH_FRAME_MAX_LEN: usize = 1024;
+
+// 排队等待发送的进度行数
+const PROGRESS_QUEUE_SIZE: usize = 32;
+
+tokio::task_local! {
+    // 当前命令的进度通道（仅在独立连接上请求了进度的命令执行期间设置）
+    static PROGRESS: mpsc::Sender<String>;
+}
+
+// 当前命令的进度通道，客户端未请求进度时返回 None
+//
+// 必须在命令处理器的 Future 中调用，spawn 出的任务需要先取得通道再移入
+pub fn progress_sender() -> Option<mpsc::Sender<String>> {
+    PROGRESS.try_with(Clone::clone).ok()
+}
+
 // 命令处理器类型（异步）
 pub type CommandHandler =
     Arc<dyn Fn(IpcCommand) -> Pin<Box<dyn Future<Output = IpcResponse> + Send>> + Send + Sync>;
@@ -44,10 +67,10 @@ impl IpcServer {
     // 启动服务端（阻塞直到关闭）
     pub async fn run(&mut self) -> Result<()> {
         // 删除旧的 IPC 文件
-        #[cfg(not(windows))]
-        {
-            let _ = std::fs::remove_file(IPC_PATH);
-        }
+        Self::cleanup();
+
+        // 确保认证令牌存在（旧版本安装的服务没有令牌）
+        auth::ensure_token().map_err(|e| IpcError::Other(format!("生成认证令牌失败: {e}")))?;
 
         // 创建关闭通道
         let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>(1);
@@ -66,13 +89,16 @@ impl IpcServer {
             self.run_unix(shutdown_rx).await?;
         }
 
-        // 清理
+        Self::cleanup();
+        Ok(())
+    }
+
+    // 删除 Unix Socket 文件（服务端任务被中止时由调用方执行）
+    pub fn cleanup() {
         #[cfg(not(windows))]
         {
             let _ = std::fs::remove_file(IPC_PATH);
         }
-
-        Ok(())
     }
 
     // Windows 平台运行
@@ -80,8 +106,8 @@ impl IpcServer {
     async fn run_windows(&self, mut shutdown_rx: mpsc::Receiver<()>) -> Result<()> {
         log::info!("准备创建 Named Pipe: {IPC_PATH}");
 
-        // 创建允许已认证用户访问的安全描述符
-        let security_descriptor = create_permissive_security_attributes()
+        // 创建仅允许本机交互用户访问的安全描述符
+        let security_descriptor = create_restricted_security_attributes()
             .map_err(|e| IpcError::Other(format!("创建安全描述符失败: {e}")))?;
 
         // 第一次循环创建第一个实例
@@ -90,7 +116,7 @@ impl IpcServer {
         loop {
             // 为每个连接创建新的 Named Pipe 实例
             let server = if is_first_instance {
-                log::info!("创建第一个 Named Pipe 实例（允许已认证用户访问）");
+                log::info!("创建第一个 Named Pipe 实例（仅允许本机交互用户访问）");
 
                 // 使用 Windows API 创建带权限的 Named Pipe
                 let pipe = create_named_pipe_with_security(IPC_PATH, true, &security_descriptor)
@@ -149,6 +175,10 @@ impl IpcServer {
         let listener = UnixListener::bind(IPC_PATH)
             .map_err(|e| IpcError::Other(format!("创建 Unix Socket 失败: {}", e)))?;
 
+        // 仅允许安装服务的用户（及 root）连接
+        auth::restrict_to_owner(std::path::Path::new(IPC_PATH))
+            .map_err(|e| IpcError::Other(format!("设置 Unix Socket 权限失败: {}", e)))?;
+
         loop {
             tokio::select! {
                 // 接受新连接
@@ -182,37 +212,78 @@ impl IpcServer {
     // 处理客户端连接
     async fn handle_client<S>(mut stream: S, handler: CommandHandler) -> Result<()>
     where
-        S: AsyncReadExt + AsyncWriteExt + Unpin,
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
     {
-        // 读取命令长度
-        let mut len_buf = [0u8; 4];
-        stream.read_exact(&mut len_buf).await?;
-        let command_len = u32::from_le_bytes(len_buf) as usize;
-
-        // 防止恶意请求
-        if command_len > 1024 * 1024 {
-            // 最大 1MB
-            return Err(IpcError::Other("命令数据过大".to_string()));
+        let state = Arc::new(FrameState::new());
+
+        // 第一帧必须是认证令牌
+        let (format, auth_buf) = read_frame(&mut stream, AUTH_FRAME_MAX_LEN, false).await?;
+        state.observe_format(format);
+        let authorized = match (
+            auth::default_token_path().and_then(|path| auth::read_token(&path)),
+            serde_json::from_slice::<AuthFrame>(&auth_buf),
+        ) {
+            (Some(expected), Ok(frame)) => auth::verify(&expected, &frame.token),
+            _ => false,
+        };
+        if !authorized {
+            log::warn!("拒绝未认证的 IPC 连接");
+            let response = IpcResponse::Error {
+                code: ErrorCode::Unauthorized,
+                message: "IPC 认证失败，请重新安装服务".to_string(),
+            };
+            return write_response(&mut stream, &state, None, response).await;
         }
 
-        // 读取命令数据
-        let mut command_buf = vec![0u8; command_len];
-        stream.read_exact(&mut command_buf).await?;
+        let frame = Self::read_command(&mut stream, &state).await?;
 
-        // 反序列化命令
-        let command: IpcCommand = serde_json::from_slice(&command_buf)?;
-        log::trace!("收到命令: {command:?}");
+        // 带请求 ID 的命令：保持连接，并发处理后续命令
+        if let Some(id) = frame.id {
+            return Self::handle_multiplexed(stream, handler, state, id, frame.command).await;
+        }
 
         // 处理 StreamLogs 特殊命令（流式推送）
-        if matches!(command, IpcCommand::StreamLogs) {
+        if matches!(frame.command, IpcCommand::StreamLogs) {
             log::info!("启动日志流订阅");
-            return Self::handle_log_stream(stream).await;
+            return Self::handle_log_stream(stream, &state).await;
         }
 
-        // 处理普通命令（请求-响应）
-        let response = handler(command).await;
+        // 请求了进度的命令：执行期间推送 Progress，随后发送最终响应
+        if frame.command.wants_progress() {
+            return Self::handle_with_progress(stream, &handler, &state, frame.command).await;
+        }
+
+        // 处理普通命令（请求-响应，随后关闭连接）
+        let response = Self::execute(&handler, frame.command).await;
+        write_response(&mut stream, &state, None, response).await
+    }
+
+    // 读取并反序列化一条命令（超过 MAX_COMMAND_FRAME_LEN 时拒绝，防止恶意请求）
+    async fn read_command<S>(stream: &mut S, state: &FrameState) -> Result<CommandFrame>
+    where
+        S: AsyncRead + Unpin,
+    {
+        let (format, command_buf) =
+            read_frame(stream, MAX_COMMAND_FRAME_LEN, state.is_checked()).await?;
+        state.observe_format(format);
+        let frame: CommandFrame = serde_json::from_slice(&command_buf)?;
+        log::trace!("收到命令: {frame:?}");
+
+        if let IpcCommand::Hello {
+            max_frame_len: Some(max_frame_len),
+            ..
+        } = frame.command
+        {
+            state
+                .max_response_len
+                .store(max_frame_len, Ordering::Relaxed);
+        }
+        Ok(frame)
+    }
 
-        // 记录响应（避免日志递归：GetLogs 响应不打印完整内容）
+    // 执行命令并记录响应（避免日志递归：GetLogs 响应不打印完整内容）
+    async fn execute(handler: &CommandHandler, command: IpcCommand) -> IpcResponse {
+        let response = handler(command).await;
         match &response {
             IpcResponse::Logs { lines } => {
                 log::trace!("返回响应: Logs (共 {} 行)", lines.len());
@@ -221,24 +292,116 @@ impl IpcServer {
                 log::trace!("返回响应: {response:?}");
             }
         }
+        response
+    }
 
-        // 序列化响应
-        let response_json = serde_json::to_string(&response)?;
-        let response_bytes = response_json.as_bytes();
+    // 执行命令并在最终响应前推送进度（客户端断开时停止执行）
+    async fn handle_with_progress<S>(
+        mut stream: S,
+        handler: &CommandHandler,
+        state: &FrameState,
+        command: IpcCommand,
+    ) -> Result<()>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let (progress_tx, mut progress) = mpsc::channel(PROGRESS_QUEUE_SIZE);
+        let execution = PROGRESS.scope(progress_tx, Self::execute(handler, command));
+        tokio::pin!(execution);
 
-        // 发送响应长度 + 响应数据
-        let len = response_bytes.len() as u32;
-        stream.write_all(&len.to_le_bytes()).await?;
-        stream.write_all(response_bytes).await?;
-        stream.flush().await?;
+        let response = loop {
+            tokio::select! {
+                response = &mut execution => break response,
+                Some(line) = progress.recv() => {
+                    write_response(&mut stream, state, None, IpcResponse::Progress { line }).await?;
+                }
+            }
+        };
 
-        Ok(())
+        // 命令结束前已排队的进度
+        while let Ok(line) = progress.try_recv() {
+            write_response(&mut stream, state, None, IpcResponse::Progress { line }).await?;
+        }
+        write_response(&mut stream, state, None, response).await
+    }
+
+    // 多路复用连接：每条命令在独立任务中执行，响应由写入任务按完成顺序发送
+    async fn handle_multiplexed<S>(
+        stream: S,
+        handler: CommandHandler,
+        state: Arc<FrameState>,
+        first_id: u64,
+        first_command: IpcCommand,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let (response_tx, mut responses) = mpsc::channel::<ResponseFrame>(32);
+
+        let writer_state = state.clone();
+        let writer_task = tokio::spawn(async move {
+            while let Some(frame) = responses.recv().await {
+                write_response(&mut writer, &writer_state, frame.id, frame.response).await?;
+            }
+            Ok::<(), IpcError>(())
+        });
+
+        let mut next = Some((first_id, first_command));
+        loop {
+            let (id, command) = match next.take() {
+                Some(command) => command,
+                None => match Self::read_command(&mut reader, &state).await {
+                    Ok(CommandFrame {
+                        id: Some(id),
+                        command,
+                    }) => (id, command),
+                    Ok(CommandFrame { id: None, .. }) => {
+                        return Err(IpcError::Other(
+                            "多路复用连接中收到不带 ID 的命令".to_string(),
+                        ));
+                    }
+                    // 客户端关闭连接
+                    Err(IpcError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                        break;
+                    }
+                    // 帧损坏后数据流无法再同步，直接关闭连接
+                    Err(e) => return Err(e),
+                },
+            };
+
+            let handler = handler.clone();
+            let response_tx = response_tx.clone();
+            tokio::spawn(async move {
+                // 日志流会持续推送，不能与其他命令共用连接
+                let response = if matches!(command, IpcCommand::StreamLogs) {
+                    IpcResponse::Error {
+                        code: ErrorCode::StreamUnsupported,
+                        message: "日志流需要使用独立连接".to_string(),
+                    }
+                } else {
+                    Self::execute(&handler, command).await
+                };
+                let _ = response_tx
+                    .send(ResponseFrame {
+                        id: Some(id),
+                        response,
+                    })
+                    .await;
+            });
+        }
+
+        // 等待进行中的命令发送完响应
+        drop(response_tx);
+        writer_task
+            .await
+            .map_err(|e| IpcError::Other(format!("响应写入任务异常: {e}")))?
     }
 
     // 处理日志流订阅（持续推送）
-    async fn handle_log_stream<S>(mut stream: S) -> Result<()>
+    async fn handle_log_stream<S>(mut stream: S, state: &FrameState) -> Result<()>
     where
-        S: AsyncReadExt + AsyncWriteExt + Unpin,
+        S: AsyncWrite + Unpin,
     {
         use crate::logger;
 
@@ -249,12 +412,7 @@ impl IpcServer {
         let initial_response = IpcResponse::Success {
             message: Some("日志流已启用".to_string()),
         };
-        let response_json = serde_json::to_string(&initial_response)?;
-        let response_bytes = response_json.as_bytes();
-        let len = response_bytes.len() as u32;
-        stream.write_all(&len.to_le_bytes()).await?;
-        stream.write_all(response_bytes).await?;
-        stream.flush().await?;
+        write_response(&mut stream, state, None, initial_response).await?;
 
         log::debug!("日志流订阅已激活，开始推送日志");
 
@@ -264,22 +422,23 @@ impl IpcServer {
                 Ok(log_line) => {
                     // 构造日志流响应
                     let log_response = IpcResponse::LogStream { line: log_line };
-                    let response_json = serde_json::to_string(&log_response)?;
-                    let response_bytes = response_json.as_bytes();
-                    let len = response_bytes.len() as u32;
-
-                    // 发送日志行
-                    if let Err(e) = stream.write_all(&len.to_le_bytes()).await {
-                        log::debug!("日志流客户端断开连接: {}", e);
-                        break;
-                    }
-                    if let Err(e) = stream.write_all(response_bytes).await {
-                        log::debug!("日志流客户端断开连接: {}", e);
-                        break;
-                    }
-                    if let Err(e) = stream.flush().await {
-                        log::debug!("日志流客户端断开连接: {}", e);
-                        break;
+                    match write_frame(
+                        &mut stream,
+                        &log_response,
+                        state.format(),
+                        state.max_response_len(),
+                    )
+                    .await
+                    {
+                        Ok(()) => {}
+                        // 超长的日志行直接跳过
+                        Err(IpcError::FrameTooLarge { len, .. }) => {
+                            log::debug!("日志行过长 ({} 字节)，已跳过", len);
+                        }
+                        Err(e) => {
+                            log::debug!("日志流客户端断开连接: {}", e);
+                            break;
+                        }
                     }
                 }
                 Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
@@ -298,6 +457,74 @@ impl IpcServer {
     }
 }
 
+// 连接的帧格式状态
+//
+// 客户端发送过带校验的帧后，服务只接受并发送带校验的帧；
+// 响应长度上限来自客户端的 Hello，旧版客户端不发送时使用默认值
+struct FrameState {
+    checked: AtomicBool,
+    max_response_len: AtomicUsize,
+}
+
+impl FrameState {
+    fn new() -> Self {
+        Self {
+            checked: AtomicBool::new(false),
+            max_response_len: AtomicUsize::new(DEFAULT_MAX_FRAME_LEN),
+        }
+    }
+
+    fn observe_format(&self, format: FrameFormat) {
+        if format == FrameFormat::Checked {
+            self.checked.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn is_checked(&self) -> bool {
+        self.checked.load(Ordering::Relaxed)
+    }
+
+    fn format(&self) -> FrameFormat {
+        if self.is_checked() {
+            FrameFormat::Checked
+        } else {
+            FrameFormat::Legacy
+        }
+    }
+
+    fn max_response_len(&self) -> usize {
+        self.max_response_len.load(Ordering::Relaxed)
+    }
+}
+
+// 写入响应，超过客户端的接收上限时改为发送错误响应
+async fn write_response<W>(
+    writer: &mut W,
+    state: &FrameState,
+    id: Option<u64>,
+    response: IpcResponse,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let (format, max_len) = (state.format(), state.max_response_len());
+    match write_frame(writer, &ResponseFrame { id, response }, format, max_len).await {
+        Err(IpcError::FrameTooLarge { len, max }) => {
+            log::warn!(
+                "响应过大 ({} 字节，客户端上限 {} 字节)，改为返回错误",
+                len,
+                max
+            );
+            let response = IpcResponse::Error {
+                code: ErrorCode::FrameTooLarge,
+                message: format!("响应过大: {} 字节（上限 {} 字节）", len, max),
+            };
+            write_frame(writer, &ResponseFrame { id, response }, format, max_len).await
+        }
+        result => result,
+    }
+}
+
 // ============================================================================
 // Windows 安全描述符辅助函数
 // ============================================================================
@@ -321,21 +548,21 @@ impl Drop for SecurityDescriptorWrapper {
 }
 
 #[cfg(windows)]
-// 创建允许已认证用户访问的安全描述符
+// 创建仅允许本机交互用户访问的安全描述符
 //
 // SDDL 字符串说明：
-// - D: = DACL（访问控制列表）
-// - (A;;GA;;;AU) = 允许 (A)，通用访问 (GA)，已认证用户 (AU)
+// - D:P = DACL（访问控制列表），P 表示不继承父对象的权限
+// - (A;;GA;;;IU) = 允许 (A)，通用访问 (GA)，交互式登录用户 (IU)
 // - (A;;GA;;;BA) = 允许 (A)，通用访问 (GA)，管理员组 (BA)
 // - (A;;GA;;;SY) = 允许 (A)，通用访问 (GA)，系统 (SY)
 //
-// 这比允许 Everyone (WD) 更安全，因为排除了匿名用户
-fn create_permissive_security_attributes() -> std::result::Result<SecurityDescriptorWrapper, String>
+// 相比已认证用户 (AU)，排除了服务账户和网络登录；连接后仍需通过令牌认证
+fn create_restricted_security_attributes() -> std::result::Result<SecurityDescriptorWrapper, String>
 {
     use windows::core::PCWSTR;
 
-    // SDDL 字符串：允许已认证用户、管理员和系统访问
-    let sddl = "D:(A;;GA;;;AU)(A;;GA;;;BA)(A;;GA;;;SY)";
+    // SDDL 字符串：允许交互式登录用户、管理员和系统访问
+    let sddl = "D:P(A;;GA;;;IU)(A;;GA;;;BA)(A;;GA;;;SY)";
 
     let sddl_wide: Vec<u16> = sddl.encode_utf16().chain(std::iter::once(0)).collect();
 
@@ -351,7 +578,7 @@ fn create_permissive_security_attributes() -> std::result::Result<SecurityDescri
         .map_err(|e| format!("创建安全描述符失败: {e}"))?;
     }
 
-    log::info!("创建安全描述符成功（允许已认证用户访问）");
+    log::info!("创建安全描述符成功（仅允许本机交互用户访问）");
     Ok(SecurityDescriptorWrapper(security_descriptor))
 }
 
@@ -368,7 +595,8 @@ fn create_named_pipe_with_security(
         FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX,
     };
     use windows::Win32::System::Pipes::{
-        CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
     };
     use windows::core::PCWSTR;
 
@@ -391,7 +619,8 @@ fn create_named_pipe_with_security(
         let handle = CreateNamedPipeW(
             PCWSTR(path_wide.as_ptr()),
             open_mode,
-            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+            // 拒绝来自其他计算机的连接
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
             PIPE_UNLIMITED_INSTANCES,
             65536, // 输出缓冲区大小
             65536, // 输入缓冲区大小
@@ -131,7 +131,11 @@ pub async fn run_console_mode() -> Result<()> {
     let last_heartbeat = Arc::new(RwLock::new(Instant::now()));
 
     // 创建 IPC 服务端和处理器
-    let handler = service::handler::create_handler(clash_manager.clone(), last_heartbeat.clone());
+    let handler = service::handler::create_handler(
+        clash_manager.clone(),
+        last_heartbeat.clone(),
+        shutdown_tx.clone(),
+    );
     let mut ipc_server = ipc::IpcServer::new(handler);
 
     // 启动心跳监控器（HeartbeatMonitor）任务
@@ -190,7 +194,9 @@ pub async fn run_console_mode() -> Result<()> {
     }
 
     ipc_handle.abort();
-    log::info!("服务已停止");
+    ipc::IpcServer::cleanup();
+    log::info!("服务已停止，程序文件可以删除");
+    log::logger().flush();
     Ok(())
 }
 
@@ -260,13 +266,24 @@ async fn follow_logs() -> Result<()> {
         }
     }
 
-    // 接收实时日志流
-    let _ = client
-        .stream_logs(|line| {
-            println!("{}", line);
-            true
-        })
-        .await;
+    // 接收实时日志流，Ctrl+C 结束
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let (lines_tx, mut lines) = tokio::sync::mpsc::channel(64);
+    let stream = tokio::spawn({
+        let cancel = cancel.clone();
+        async move { client.stream_logs(cancel, lines_tx).await }
+    });
+    tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            let _ = tokio::signal::ctrl_c().await;
+            cancel.cancel();
+        }
+    });
+    while let Some(line) = lines.recv().await {
+        println!("{}", line);
+    }
+    let _ = stream.await;
 
     println!("\n日志流已断开");
     Ok(())
@@ -44,6 +44,33 @@ pub fn get_recent_logs(lines: usize) -> Vec<String> {
     buffer.iter().skip(start).cloned().collect()
 }
 
+// 获取最近的日志尾部（同时限制行数和总字节数）
+//
+// 从最新一行向前收集，超出字节上限时丢弃更早的行
+pub fn get_logs_tail(max_lines: usize, max_bytes: usize) -> Vec<String> {
+    let buffer = match LOG_BUFFER.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => {
+            log::warn!("日志缓冲区锁被污染，正在恢复");
+            poisoned.into_inner()
+        }
+    };
+
+    let mut total_bytes = 0;
+    let mut tail: Vec<String> = buffer
+        .iter()
+        .rev()
+        .take(max_lines)
+        .take_while(|line| {
+            total_bytes += line.len();
+            total_bytes <= max_bytes
+        })
+        .cloned()
+        .collect();
+    tail.reverse();
+    tail
+}
+
 // 订阅日志流
 pub fn subscribe_logs() -> broadcast::Receiver<String> {
     LOG_BROADCASTER.subscribe()
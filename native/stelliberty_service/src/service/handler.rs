@@ -1,21 +1,42 @@
 // IPC 命令处理器
 
-use crate::clash::ClashManager;
+use crate::clash::controller::{self, ControllerError};
+use crate::clash::{ClashManager, DEFAULT_FORCE_AFTER, RunningParams, stats, tun};
+use crate::ipc::error::ErrorCode;
+use crate::ipc::protocol::{
+    LOGS_TAIL_MAX_BYTES, LOGS_TAIL_MAX_LINES, MAX_COMMAND_FRAME_LEN, PROTOCOL_VERSION,
+    STOP_FORCE_AFTER_MAX_MS,
+};
+use crate::ipc::server::progress_sender;
 use crate::ipc::{IpcCommand, IpcResponse};
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, mpsc};
 
-// 创建命令处理器（异步）
+// 修改日志级别的超时时间
+const LOG_LEVEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 更新 Geo 数据库的超时时间（核心依次下载所有数据库后才返回）
+const GEO_UPDATE_TIMEOUT: Duration = Duration::from_secs(300);
+
+// 收到 Shutdown 后延迟发出关闭信号，留出时间写回响应
+const SHUTDOWN_NOTIFY_DELAY: Duration = Duration::from_millis(100);
+
+// 创建命令处理器（异步），收到 Shutdown 时通过 shutdown 通知服务退出
 pub fn create_handler(
     clash_manager: Arc<RwLock<ClashManager>>,
     last_heartbeat: Arc<RwLock<Instant>>,
+    shutdown: mpsc::Sender<()>,
 ) -> impl Fn(IpcCommand) -> std::pin::Pin<Box<dyn std::future::Future<Output = IpcResponse> + Send>>
 + Send
 + Sync {
+    // 服务启动时间（处理器随服务启动创建）
+    let service_start = Instant::now();
+
     move |command: IpcCommand| {
         let clash_manager = clash_manager.clone();
         let last_heartbeat = last_heartbeat.clone();
+        let shutdown = shutdown.clone();
 
         Box::pin(async move {
             match command {
@@ -24,10 +45,55 @@ pub fn create_handler(
                     config_path,
                     data_dir,
                     external_controller,
+                    tun,
+                    tun_device,
                 } => {
-                    log::info!("收到启动 Clash 命令");
+                    log::info!("收到启动 Clash 命令 (TUN: {})", tun);
+
+                    // 设备名未知时不记录，停止后不做清理
+                    let tun_device = tun
+                        .then(|| {
+                            tun_device
+                                .filter(|device| !device.is_empty())
+                                .or_else(|| tun::DEFAULT_TUN_DEVICE.map(str::to_string))
+                        })
+                        .flatten();
+                    let requested = RunningParams {
+                        core_path,
+                        config_path,
+                        data_dir,
+                        external_controller,
+                        tun_device,
+                    };
+
+                    // 主程序重启后以相同参数再次启动时直接接管，不重启核心
+                    if clash_manager.read().await.running_params().as_ref() == Some(&requested) {
+                        log::info!("Clash 已以相同参数运行，跳过启动");
+                        return IpcResponse::Success {
+                            message: Some("Clash 已在运行（参数相同）".to_string()),
+                        };
+                    }
+
+                    if tun && let Err(e) = tun::check_prerequisites(&requested.core_path) {
+                        log::error!("TUN 前置条件检查失败: {}", e);
+                        return IpcResponse::Error {
+                            code: ErrorCode::TunPrerequisite,
+                            message: e,
+                        };
+                    }
+
                     let mut manager = clash_manager.write().await;
-                    match manager.start(core_path, config_path, data_dir, external_controller) {
+                    // 启动后会等待核心开放控制接口，避免占用异步工作线程
+                    let result = tokio::task::block_in_place(|| {
+                        manager.start(
+                            requested.core_path,
+                            requested.config_path,
+                            requested.data_dir,
+                            requested.external_controller,
+                            requested.tun_device,
+                        )
+                    });
+                    match result {
                         Ok(()) => {
                             log::info!("Clash 启动成功");
                             IpcResponse::Success {
@@ -37,27 +103,36 @@ pub fn create_handler(
                         Err(e) => {
                             log::error!("Clash 启动失败: {}", e);
                             IpcResponse::Error {
-                                code: 1001,
+                                code: ErrorCode::StartFailed,
                                 message: format!("Clash 启动失败: {}", e),
                             }
                         }
                     }
                 }
 
-                IpcCommand::StopClash => {
+                IpcCommand::StopClash { force_after_ms } => {
                     log::info!("收到停止 Clash 命令");
+                    let force_after = force_after_ms.map_or(DEFAULT_FORCE_AFTER, |ms| {
+                        Duration::from_millis(ms.min(STOP_FORCE_AFTER_MAX_MS))
+                    });
                     let mut manager = clash_manager.write().await;
-                    match manager.stop() {
-                        Ok(()) => {
-                            log::info!("Clash 停止成功");
-                            IpcResponse::Success {
-                                message: Some("Clash 停止成功".to_string()),
+                    // 等待核心退出可能持续数秒，避免占用异步工作线程
+                    match tokio::task::block_in_place(|| manager.stop_with_timeout(force_after)) {
+                        Ok(outcome) => {
+                            log::info!(
+                                "Clash 停止成功 (强制终止: {}, 退出码: {:?})",
+                                outcome.forced,
+                                outcome.exit_code
+                            );
+                            IpcResponse::Stopped {
+                                forced: outcome.forced,
+                                exit_code: outcome.exit_code,
                             }
                         }
                         Err(e) => {
                             log::error!("Clash 停止失败: {}", e);
                             IpcResponse::Error {
-                                code: 1002,
+                                code: ErrorCode::StopFailed,
                                 message: format!("Clash 停止失败: {}", e),
                             }
                         }
@@ -70,7 +145,7 @@ pub fn create_handler(
                     let manager = clash_manager.read().await;
                     let status = manager.get_status();
                     log::debug!(
-                        "Clash 状态: running={}, pid={:?}, uptime={}s",
+                        "Clash 状态: running={}, pid={:?}, uptime={:?}s",
                         status.running,
                         status.pid,
                         status.uptime
@@ -78,7 +153,22 @@ pub fn create_handler(
                     IpcResponse::Status {
                         clash_running: status.running,
                         clash_pid: status.pid,
-                        service_uptime: status.uptime,
+                        service_uptime: service_start.elapsed().as_secs(),
+                        clash_uptime: status.uptime,
+                    }
+                }
+
+                IpcCommand::GetRunningParams => {
+                    log::debug!("收到获取核心启动参数命令");
+                    match clash_manager.read().await.running_params() {
+                        Some(params) => IpcResponse::RunningParams {
+                            core_path: params.core_path,
+                            config_path: params.config_path,
+                            data_dir: params.data_dir,
+                            external_controller: params.external_controller,
+                            tun_device: params.tun_device,
+                        },
+                        None => core_not_running(),
                     }
                 }
 
@@ -88,6 +178,95 @@ pub fn create_handler(
                     IpcResponse::Logs { lines: log_lines }
                 }
 
+                IpcCommand::GetLogsTail { lines } => {
+                    let lines = (lines as usize).min(LOGS_TAIL_MAX_LINES);
+                    log::trace!("收到获取日志尾部命令 (请求 {} 行)", lines);
+                    // 读取放到阻塞线程，避免占用 IPC 主循环
+                    let log_lines = tokio::task::spawn_blocking(move || {
+                        crate::logger::get_logs_tail(lines, LOGS_TAIL_MAX_BYTES)
+                    })
+                    .await
+                    .unwrap_or_default();
+                    IpcResponse::Logs { lines: log_lines }
+                }
+
+                IpcCommand::GetClashStats => {
+                    log::trace!("收到获取核心统计命令");
+                    let Some(params) = clash_manager.read().await.running_params() else {
+                        return core_not_running();
+                    };
+                    match stats::query(&params).await {
+                        Ok(stats) => IpcResponse::Stats {
+                            connections: stats.connections,
+                            upload_total: stats.upload_total,
+                            download_total: stats.download_total,
+                        },
+                        Err(e) => {
+                            log::debug!("查询核心统计失败: {}", e);
+                            IpcResponse::Error {
+                                code: ErrorCode::ControllerUnavailable,
+                                message: e,
+                            }
+                        }
+                    }
+                }
+
+                IpcCommand::SetClashLogLevel { level } => {
+                    log::info!("收到修改核心日志级别命令: {}", level);
+                    let Some(params) = clash_manager.read().await.running_params() else {
+                        return core_not_running();
+                    };
+                    let body = serde_json::json!({ "log-level": level }).to_string();
+                    let result = tokio::time::timeout(
+                        LOG_LEVEL_TIMEOUT,
+                        controller::request(&params, "PATCH", "/configs", Some(&body)),
+                    )
+                    .await;
+                    match result {
+                        Ok(Ok(_)) => IpcResponse::Success {
+                            message: Some(format!("核心日志级别已设为 {}", level)),
+                        },
+                        Ok(Err(e)) => controller_error("修改核心日志级别失败", e),
+                        Err(_) => controller_timeout("修改核心日志级别超时"),
+                    }
+                }
+
+                IpcCommand::UpdateGeoDatabases { .. } => {
+                    log::info!("收到更新 Geo 数据库命令");
+                    let Some(params) = clash_manager.read().await.running_params() else {
+                        return core_not_running();
+                    };
+
+                    // 客户端请求了进度时转发核心日志中的 Geo 更新记录
+                    let progress = progress_sender();
+                    let forwarder = progress
+                        .clone()
+                        .map(|progress| tokio::spawn(forward_geo_logs(params.clone(), progress)));
+                    if let Some(progress) = &progress {
+                        let _ = progress.send("开始更新 Geo 数据库".to_string()).await;
+                    }
+
+                    let result = tokio::time::timeout(
+                        GEO_UPDATE_TIMEOUT,
+                        controller::request(&params, "POST", "/configs/geo", Some("{}")),
+                    )
+                    .await;
+                    if let Some(forwarder) = forwarder {
+                        forwarder.abort();
+                    }
+
+                    match result {
+                        Ok(Ok(_)) => {
+                            log::info!("Geo 数据库更新完成");
+                            IpcResponse::Success {
+                                message: Some("Geo 数据库更新完成".to_string()),
+                            }
+                        }
+                        Ok(Err(e)) => controller_error("更新 Geo 数据库失败", e),
+                        Err(_) => controller_timeout("更新 Geo 数据库超时"),
+                    }
+                }
+
                 IpcCommand::GetVersion => {
                     let version = env!("CARGO_PKG_VERSION");
                     log::debug!("收到获取版本命令, 版本: {}", version);
@@ -109,7 +288,111 @@ pub fn create_handler(
                     *last_heartbeat.write().await = Instant::now();
                     IpcResponse::HeartbeatAck
                 }
+
+                // 客户端的最大响应长度由连接处理时记录（见 IpcServer）
+                IpcCommand::Hello {
+                    protocol_version, ..
+                } => {
+                    log::debug!(
+                        "收到协议握手 (客户端版本: {}, 服务版本: {})",
+                        protocol_version,
+                        PROTOCOL_VERSION
+                    );
+                    IpcResponse::Hello {
+                        protocol_version: PROTOCOL_VERSION,
+                        max_frame_len: Some(MAX_COMMAND_FRAME_LEN),
+                    }
+                }
+
+                IpcCommand::Shutdown => {
+                    log::info!("收到关闭服务命令");
+                    // 先停止核心，响应返回时核心已退出
+                    let mut manager = clash_manager.write().await;
+                    if let Err(e) = tokio::task::block_in_place(|| manager.stop()) {
+                        log::error!("关闭服务前停止 Clash 失败: {}", e);
+                    }
+                    drop(manager);
+
+                    tokio::spawn(async move {
+                        tokio::time::sleep(SHUTDOWN_NOTIFY_DELAY).await;
+                        let _ = shutdown.send(()).await;
+                    });
+                    IpcResponse::Success {
+                        message: Some("服务正在退出".to_string()),
+                    }
+                }
+
+                IpcCommand::VerifyTunCleanup { device, remove } => {
+                    log::info!("收到 TUN 残留检测命令: {} (清理: {})", device, remove);
+                    let outcome =
+                        tokio::task::spawn_blocking(move || tun::verify_cleanup(&device, remove))
+                            .await
+                            .unwrap_or_default();
+                    IpcResponse::TunCleanup {
+                        interfaces: outcome.interfaces,
+                        routes: outcome.routes,
+                        removed: outcome.removed,
+                        failed: outcome.failed,
+                    }
+                }
             }
         })
     }
 }
+
+fn core_not_running() -> IpcResponse {
+    IpcResponse::Error {
+        code: ErrorCode::CoreNotRunning,
+        message: "Clash 核心未运行".to_string(),
+    }
+}
+
+// 核心拒绝请求时消息为核心的响应内容，便于主程序原样展示
+fn controller_error(action: &str, e: ControllerError) -> IpcResponse {
+    log::error!("{}: {}", action, e);
+    match e {
+        ControllerError::Rejected { body, .. } => IpcResponse::Error {
+            code: ErrorCode::ControllerRejected,
+            message: body,
+        },
+        ControllerError::Unavailable(message) => IpcResponse::Error {
+            code: ErrorCode::ControllerUnavailable,
+            message,
+        },
+    }
+}
+
+fn controller_timeout(message: &str) -> IpcResponse {
+    log::error!("{}", message);
+    IpcResponse::Error {
+        code: ErrorCode::ControllerUnavailable,
+        message: message.to_string(),
+    }
+}
+
+// 订阅核心日志，把 Geo 更新相关的行作为进度发送（直到任务被中止或核心断开）
+async fn forward_geo_logs(params: RunningParams, progress: mpsc::Sender<String>) {
+    let (lines_tx, mut lines) = mpsc::channel(32);
+    // 两部分在同一任务中运行，中止任务时一并关闭与核心的连接
+    let subscribe = async {
+        if let Err(e) = controller::stream_lines(&params, "/logs?level=info", lines_tx).await {
+            log::debug!("订阅核心日志失败: {}", e);
+        }
+    };
+    let forward = async move {
+        while let Some(line) = lines.recv().await {
+            // 核心每行推送一个 {"type":"info","payload":"..."}
+            let Some(payload) = serde_json::from_str::<serde_json::Value>(&line)
+                .ok()
+                .and_then(|entry| entry["payload"].as_str().map(str::to_string))
+            else {
+                continue;
+            };
+            if payload.to_ascii_lowercase().contains("geo") && progress.send(payload).await.is_err()
+            {
+                break;
+            }
+        }
+    };
+    tokio::join!(subscribe, forward);
+}
@@ -8,6 +8,13 @@ use anyhow::Context;
 #[cfg(any(windows, target_os = "linux"))]
 const SERVICE_NAME: &str = "StellibertyService";
 
+// 每次安装都生成新的认证令牌（运行中的服务每个连接都会重新读取）
+#[cfg(any(windows, target_os = "linux", target_os = "macos"))]
+fn regenerate_auth_token() -> Result<()> {
+    crate::ipc::auth::generate_token().context("生成 IPC 认证令牌失败")?;
+    Ok(())
+}
+
 // ============ Windows Service 实现 ============
 
 #[cfg(windows)]
@@ -35,6 +42,8 @@ pub fn install_service() -> Result<()> {
     let service_binary = std::env::current_exe().context("无法获取当前程序路径")?;
     println!("服务程序: {}", service_binary.display());
 
+    regenerate_auth_token()?;
+
     let manager = ServiceManager::local_computer(
         None::<&str>,
         ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
@@ -310,6 +319,8 @@ pub fn install_service() -> Result<()> {
     let service_binary = std::env::current_exe().context("无法获取当前程序路径")?;
     println!("服务程序: {}", service_binary.display());
 
+    regenerate_auth_token()?;
+
     if Path::new(SERVICE_FILE).exists() {
         println!("服务文件已存在，正在检查状态...");
 
@@ -580,6 +591,8 @@ pub fn install_service() -> Result<()> {
     let service_binary = std::env::current_exe().context("无法获取当前程序路径")?;
     println!("服务程序: {}", service_binary.display());
 
+    regenerate_auth_token()?;
+
     // 检查服务是否已安装
     if Path::new(SERVICE_PLIST_PATH).exists() {
         println!("服务文件已存在，正在检查状态...");
@@ -93,7 +93,11 @@ fn run_service_windows() -> Result<(), Box<dyn std::error::Error>> {
     runtime.block_on(async move {
         let clash_manager = Arc::new(RwLock::new(ClashManager::new()));
         let last_heartbeat = Arc::new(RwLock::new(Instant::now()));
-        let handler = handler::create_handler(clash_manager.clone(), last_heartbeat.clone());
+        let handler = handler::create_handler(
+            clash_manager.clone(),
+            last_heartbeat.clone(),
+            shutdown_tx.clone(),
+        );
         let mut ipc_server = IpcServer::new(handler);
 
         let ipc_handle = tokio::spawn(async move {
@@ -210,7 +214,8 @@ fn run_service_windows() -> Result<(), Box<dyn std::error::Error>> {
 
         heartbeat_handle.abort();
         ipc_handle.abort();
-        log::info!("服务已停止");
+        log::info!("服务已停止，程序文件可以删除");
+        log::logger().flush();
     });
 
     status_handle.set_service_status(ServiceStatus {
@@ -257,7 +262,11 @@ pub async fn run_service() -> Result<()> {
 
     let clash_manager = Arc::new(RwLock::new(ClashManager::new()));
     let last_heartbeat = Arc::new(RwLock::new(Instant::now()));
-    let handler = handler::create_handler(clash_manager.clone(), last_heartbeat.clone());
+    let handler = handler::create_handler(
+        clash_manager.clone(),
+        last_heartbeat.clone(),
+        shutdown_tx.clone(),
+    );
     let mut ipc_server = IpcServer::new(handler);
 
     let ipc_handle = tokio::spawn(async move {
@@ -350,6 +359,8 @@ pub async fn run_service() -> Result<()> {
 
     heartbeat_handle.abort();
     ipc_handle.abort();
-    log::info!("服务已停止");
+    IpcServer::cleanup();
+    log::info!("服务已停止，程序文件可以删除");
+    log::logger().flush();
     Ok(())
 }